@@ -0,0 +1,420 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, parse_quote, Data, DeriveInput, Fields, FieldsNamed, GenericParam, Generics, Ident, LitStr};
+
+#[derive(Default)]
+struct FieldOptions {
+    rename: Option<String>,
+    default: bool,
+    skip: bool,
+    flatten: bool,
+}
+
+fn field_options(attrs: &[syn::Attribute]) -> syn::Result<FieldOptions> {
+    let mut options = FieldOptions::default();
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("json")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename") {
+                options.rename = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("default") {
+                options.default = true;
+            } else if meta.path.is_ident("skip") {
+                options.skip = true;
+            } else if meta.path.is_ident("flatten") {
+                options.flatten = true;
+            } else {
+                return Err(meta.error("unsupported json attribute"));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(options)
+}
+
+#[derive(Default)]
+struct ContainerOptions {
+    tag: Option<String>,
+    content: Option<String>,
+}
+
+fn container_options(attrs: &[syn::Attribute]) -> syn::Result<ContainerOptions> {
+    let mut options = ContainerOptions::default();
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("json")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("tag") {
+                options.tag = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("content") {
+                options.content = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else {
+                return Err(meta.error("unsupported json attribute"));
+            }
+            Ok(())
+        })?;
+    }
+    if options.content.is_some() && options.tag.is_none() {
+        return Err(syn::Error::new(proc_macro2::Span::call_site(), "json(content) requires json(tag)"));
+    }
+    Ok(options)
+}
+
+fn add_bound(mut generics: Generics, bound: syn::TypeParamBound) -> Generics {
+    for param in &mut generics.params {
+        if let GenericParam::Type(param) = param {
+            param.bounds.push(bound.clone());
+        }
+    }
+    generics
+}
+
+#[proc_macro_derive(FromJson, attributes(json))]
+pub fn derive_from_json(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match from_json_body(&input) {
+        Ok(body) => {
+            let name = &input.ident;
+            let generics = add_bound(input.generics.clone(), parse_quote!(::cc_json_parser::from_json::FromJson));
+            let (impl_generics, type_generics, where_clause) = generics.split_for_impl();
+            quote! {
+                impl #impl_generics ::cc_json_parser::from_json::FromJson for #name #type_generics #where_clause {
+                    fn from_json(value: &::cc_json_parser::data::JsonValue) -> Result<Self, ::cc_json_parser::from_json::FromJsonError> {
+                        #body
+                    }
+                }
+            }
+            .into()
+        }
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn from_json_body(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => {
+                let mut initializers = vec![];
+                let mut uses_object = false;
+                for field in &fields.named {
+                    let options = field_options(&field.attrs)?;
+                    let ident = field.ident.as_ref().unwrap();
+                    let key = options.rename.unwrap_or_else(|| ident.to_string());
+                    let initializer = if options.skip {
+                        quote! { ::std::default::Default::default() }
+                    } else if options.flatten {
+                        quote! { ::cc_json_parser::from_json::FromJson::from_json(value)? }
+                    } else if options.default {
+                        uses_object = true;
+                        quote! { ::cc_json_parser::from_json::field_or_default(object, #key)? }
+                    } else {
+                        uses_object = true;
+                        quote! { ::cc_json_parser::from_json::field(object, #key)? }
+                    };
+                    initializers.push(quote! { #ident: #initializer });
+                }
+                let object = if uses_object {
+                    quote! { let object = ::cc_json_parser::from_json::expect_object(value)?; }
+                } else {
+                    quote! { ::cc_json_parser::from_json::expect_object(value)?; }
+                };
+                Ok(quote! {
+                    #object
+                    Ok(Self { #(#initializers),* })
+                })
+            }
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => Ok(quote! {
+                Ok(Self(::cc_json_parser::from_json::FromJson::from_json(value)?))
+            }),
+            Fields::Unnamed(fields) => {
+                let types = fields.unnamed.iter().map(|field| &field.ty);
+                let indices = (0..fields.unnamed.len()).map(syn::Index::from);
+                Ok(quote! {
+                    let tuple: (#(#types,)*) = ::cc_json_parser::from_json::FromJson::from_json(value)?;
+                    Ok(Self(#(tuple.#indices),*))
+                })
+            }
+            Fields::Unit => Ok(quote! {
+                match value {
+                    ::cc_json_parser::data::JsonValue::Null => Ok(Self),
+                    _ => Err(::cc_json_parser::from_json::unexpected("null", value)),
+                }
+            }),
+        },
+        Data::Enum(data) => {
+            let container = container_options(&input.attrs)?;
+            let mut arms = vec![];
+            for variant in &data.variants {
+                if !matches!(variant.fields, Fields::Unit) {
+                    return Err(syn::Error::new_spanned(variant, "FromJson can only be derived for enums with unit variants"));
+                }
+                let options = field_options(&variant.attrs)?;
+                let ident = &variant.ident;
+                let key = options.rename.unwrap_or_else(|| ident.to_string());
+                arms.push(quote! { #key => Ok(Self::#ident) });
+            }
+            // Unit variants of internally and adjacently tagged enums are both
+            // written as an object with just the tag.
+            let (string, within) = match &container.tag {
+                Some(tag) => (
+                    quote! {
+                        let object = ::cc_json_parser::from_json::expect_object(value)?;
+                        let string: String = ::cc_json_parser::from_json::field(object, #tag)?;
+                    },
+                    quote! { .within(#tag) },
+                ),
+                None => (
+                    quote! {
+                        let string = <String as ::cc_json_parser::from_json::FromJson>::from_json(value)?;
+                    },
+                    quote! {},
+                ),
+            };
+            Ok(quote! {
+                #string
+                match string.as_str() {
+                    #(#arms,)*
+                    _ => Err(::cc_json_parser::from_json::FromJsonError::new(format!("Unknown variant {:?}", string))#within),
+                }
+            })
+        }
+        Data::Union(_) => Err(syn::Error::new_spanned(&input.ident, "FromJson cannot be derived for unions")),
+    }
+}
+
+#[proc_macro_derive(ToJson, attributes(json))]
+pub fn derive_to_json(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match to_json_body(&input) {
+        Ok(body) => {
+            let name = &input.ident;
+            let generics = add_bound(input.generics.clone(), parse_quote!(::cc_json_parser::to_json::ToJson));
+            let (impl_generics, type_generics, where_clause) = generics.split_for_impl();
+            // Structs with named fields are always objects, so they can be
+            // the content of an internally tagged newtype variant.
+            let object = match &input.data {
+                Data::Struct(syn::DataStruct { fields: Fields::Named(fields), .. }) => match named_object(fields, |ident| quote! { &self.#ident }) {
+                    Ok(object) => quote! {
+                        impl #impl_generics ::cc_json_parser::to_json::ToJsonObject for #name #type_generics #where_clause {
+                            fn to_json_object(&self) -> ::cc_json_parser::data::JsonObject {
+                                #object
+                            }
+                        }
+                    },
+                    Err(err) => return err.to_compile_error().into(),
+                },
+                _ => quote! {},
+            };
+            quote! {
+                impl #impl_generics ::cc_json_parser::to_json::ToJson for #name #type_generics #where_clause {
+                    fn to_json(&self) -> ::cc_json_parser::data::JsonValue {
+                        #body
+                    }
+                }
+                #object
+            }
+            .into()
+        }
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn to_json_body(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(_) => Ok(quote! {
+                ::cc_json_parser::data::JsonValue::Object(::cc_json_parser::to_json::ToJsonObject::to_json_object(self))
+            }),
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => Ok(quote! {
+                ::cc_json_parser::to_json::ToJson::to_json(&self.0)
+            }),
+            Fields::Unnamed(fields) => {
+                let indices: Vec<syn::Index> = (0..fields.unnamed.len()).map(syn::Index::from).collect();
+                Ok(array(indices.iter().map(|index| quote! { &self.#index }).collect()))
+            }
+            Fields::Unit => Ok(quote! { ::cc_json_parser::data::JsonValue::Null }),
+        },
+        Data::Enum(data) => {
+            let container = container_options(&input.attrs)?;
+            let mut arms = vec![];
+            for variant in &data.variants {
+                let options = field_options(&variant.attrs)?;
+                let ident = &variant.ident;
+                let name = options.rename.unwrap_or_else(|| ident.to_string());
+                // Internally tagged content is built as an object, since the
+                // tag goes among its members.
+                let internal = container.tag.is_some() && container.content.is_none();
+                let (pattern, content) = match &variant.fields {
+                    Fields::Unit => (quote! { Self::#ident }, None),
+                    Fields::Unnamed(fields) => {
+                        let bindings: Vec<Ident> = (0..fields.unnamed.len())
+                            .map(|index| Ident::new(&format!("field_{}", index), proc_macro2::Span::call_site()))
+                            .collect();
+                        let content = if bindings.len() == 1 {
+                            let binding = &bindings[0];
+                            if internal {
+                                quote! { ::cc_json_parser::to_json::ToJsonObject::to_json_object(#binding) }
+                            } else {
+                                quote! { ::cc_json_parser::to_json::ToJson::to_json(#binding) }
+                            }
+                        } else {
+                            if internal {
+                                return Err(syn::Error::new_spanned(variant, "internally tagged enums cannot contain tuple variants"));
+                            }
+                            array(bindings.iter().map(|binding| quote! { #binding }).collect())
+                        };
+                        (quote! { Self::#ident(#(#bindings),*) }, Some(content))
+                    }
+                    Fields::Named(fields) => {
+                        let mut bindings = vec![];
+                        for field in &fields.named {
+                            if !field_options(&field.attrs)?.skip {
+                                bindings.push(field.ident.clone().unwrap());
+                            }
+                        }
+                        let object = named_object(fields, |ident| quote! { #ident })?;
+                        let content = if internal {
+                            object
+                        } else {
+                            quote! { ::cc_json_parser::data::JsonValue::Object(#object) }
+                        };
+                        (quote! { Self::#ident { #(#bindings,)* .. } }, Some(content))
+                    }
+                };
+
+                let value = match (&container.tag, &container.content, content) {
+                    (None, _, None) => quote! { ::cc_json_parser::to_json::string(#name) },
+                    (None, _, Some(content)) => quote! {
+                        ::cc_json_parser::to_json::object(vec![(String::from(#name), #content)])
+                    },
+                    (Some(tag), None, None) => quote! {
+                        ::cc_json_parser::to_json::internally_tagged(#tag, #name, ::cc_json_parser::data::JsonObject { members: Vec::new() })
+                    },
+                    (Some(tag), None, Some(content)) => quote! {
+                        ::cc_json_parser::to_json::internally_tagged(#tag, #name, #content)
+                    },
+                    (Some(tag), Some(_), None) => quote! {
+                        ::cc_json_parser::to_json::object(vec![(String::from(#tag), ::cc_json_parser::to_json::string(#name))])
+                    },
+                    (Some(tag), Some(content_key), Some(content)) => quote! {
+                        ::cc_json_parser::to_json::object(vec![
+                            (String::from(#tag), ::cc_json_parser::to_json::string(#name)),
+                            (String::from(#content_key), #content),
+                        ])
+                    },
+                };
+                arms.push(quote! { #pattern => #value });
+            }
+            Ok(quote! {
+                match self {
+                    #(#arms,)*
+                }
+            })
+        }
+        Data::Union(_) => Err(syn::Error::new_spanned(&input.ident, "ToJson cannot be derived for unions")),
+    }
+}
+
+// Builds a `JsonObject` from the fields that are not skipped.
+fn named_object(fields: &FieldsNamed, access: impl Fn(&Ident) -> TokenStream2) -> syn::Result<TokenStream2> {
+    let mut statements = vec![];
+    for field in &fields.named {
+        let options = field_options(&field.attrs)?;
+        if options.skip {
+            continue;
+        }
+        let ident = field.ident.as_ref().unwrap();
+        let key = options.rename.unwrap_or_else(|| ident.to_string());
+        let value = access(ident);
+        if options.flatten {
+            statements.push(quote! {
+                ::cc_json_parser::to_json::flatten_into(&mut members, #key, ::cc_json_parser::to_json::ToJson::to_json(#value));
+            });
+        } else {
+            statements.push(quote! {
+                members.push(::cc_json_parser::to_json::member(String::from(#key), ::cc_json_parser::to_json::ToJson::to_json(#value)));
+            });
+        }
+    }
+    Ok(quote! {
+        {
+            let mut members: Vec<::cc_json_parser::data::JsonMember> = Vec::new();
+            #(#statements)*
+            ::cc_json_parser::data::JsonObject { members }
+        }
+    })
+}
+
+fn array(values: Vec<TokenStream2>) -> TokenStream2 {
+    quote! {
+        ::cc_json_parser::data::JsonValue::Array(::cc_json_parser::data::JsonArray {
+            elements: vec![#(::cc_json_parser::data::JsonElement { value: ::cc_json_parser::to_json::ToJson::to_json(#values) }),*],
+        })
+    }
+}
+
+// Expands a literal inside `json!`. The macro passes its `$crate` first, so the
+// expansion names the library's types the same way from inside it and from
+// other crates. Numbers become JSON text for the library's parser, so they
+// match what a document with the same number would produce.
+#[doc(hidden)]
+#[proc_macro]
+pub fn json_literal(input: TokenStream) -> TokenStream {
+    let parser = |input: syn::parse::ParseStream| {
+        let krate: proc_macro2::TokenTree = input.parse()?;
+        let negative = input.parse::<Option<syn::Token![-]>>()?.is_some();
+        let literal: syn::Lit = input.parse()?;
+        Ok((krate, negative, literal))
+    };
+    let (krate, negative, literal) = match syn::parse::Parser::parse(parser, input) {
+        Ok(parsed) => parsed,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    let string = |value: String| quote! {
+        #krate::data::JsonValue::String(#krate::data::JsonString { string: ::std::string::String::from(#value) })
+    };
+    let number = |digits: &str, float: bool| {
+        let text = json_number(negative, digits, float);
+        quote! { #krate::json_macro::literal(#text) }
+    };
+    let expanded = match (&literal, negative) {
+        (syn::Lit::Bool(boolean), false) if boolean.value => quote! { #krate::data::JsonValue::True },
+        (syn::Lit::Bool(_), false) => quote! { #krate::data::JsonValue::False },
+        (syn::Lit::Str(literal), false) => string(literal.value()),
+        (syn::Lit::Char(literal), false) => string(literal.value().to_string()),
+        // `1f64` is an integer to syn but a float to Rust.
+        (syn::Lit::Int(literal), _) => number(literal.base10_digits(), matches!(literal.suffix(), "f32" | "f64")),
+        (syn::Lit::Float(literal), _) => number(literal.base10_digits(), true),
+        _ => return syn::Error::new_spanned(&literal, "json! cannot represent this literal").to_compile_error().into(),
+    };
+    expanded.into()
+}
+
+// Writes the base 10 digits of a Rust number literal as JSON: leading zeros
+// go, and floats such as `1.` or `1f64` keep a point so they stay floats.
+fn json_number(negative: bool, digits: &str, float: bool) -> String {
+    let (mantissa, exponent) = match digits.find(['e', 'E']) {
+        Some(index) => digits.split_at(index),
+        None => (digits, ""),
+    };
+    let (integral, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let integral = match integral.trim_start_matches('0') {
+        "" => "0",
+        integral => integral,
+    };
+    let sign = if negative { "-" } else { "" };
+    let (point, fraction) = match fraction {
+        "" if float && exponent.is_empty() => (".", "0"),
+        "" => ("", ""),
+        fraction => (".", fraction),
+    };
+    format!("{}{}{}{}{}", sign, integral, point, fraction, exponent)
+}
+
+// Expands an object key inside `json!`, which must be a string literal.
+#[doc(hidden)]
+#[proc_macro]
+pub fn json_key(input: TokenStream) -> TokenStream {
+    let key = parse_macro_input!(input as LitStr);
+    let value = key.value();
+    quote! { ::std::string::String::from(#value) }.into()
+}
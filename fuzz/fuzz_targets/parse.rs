@@ -0,0 +1,29 @@
+#![no_main]
+
+use cc_json_parser::{borrowed, lex, parse, write};
+use libfuzzer_sys::fuzz_target;
+use std::io::ErrorKind;
+
+fuzz_target!(|bytes: &[u8]| {
+    let owned = lex::lex_bytes(bytes).and_then(|mut tokens| parse::parse(&mut tokens));
+    if let Err(err) = &owned {
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    // The indexed and scalar lexers must agree on every input.
+    let scalar = lex::lex_scalar(bytes).map_err(|err| err.to_string());
+    let indexed = lex::lex_indexed(bytes).map_err(|err| err.to_string());
+    assert_eq!(scalar, indexed);
+
+    if let Ok(string) = std::str::from_utf8(bytes) {
+        let borrowed = borrowed::parse(string).map(borrowed::JsonData::into_owned);
+        assert_eq!(borrowed.map_err(|err| err.to_string()), owned.as_ref().map(Clone::clone).map_err(|err| err.to_string()));
+    }
+
+    // Whatever parses must survive being written out and parsed again.
+    if let Ok(data) = owned {
+        let written = write::value_to_string(&data.element.value);
+        let mut tokens = lex::lex_bytes(written.as_bytes()).unwrap();
+        assert_eq!(parse::parse(&mut tokens).unwrap(), data);
+    }
+});
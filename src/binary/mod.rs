@@ -0,0 +1,104 @@
+use crate::data::*;
+use std::io::{Error, ErrorKind, Read};
+
+// What the binary formats share. The CBOR and MessagePack writers make a
+// separate write for every head or marker, and their readers read a byte at a
+// time, so wrap files and sockets in a `BufWriter` or `BufReader`.
+
+// `Iterator` for a format's `Reader`, whose `read` returns `None` when the
+// input ends between values.
+macro_rules! reader_iterator {
+    ($reader:ident) => {
+        impl<R: std::io::Read> Iterator for $reader<R> {
+            type Item = Result<JsonData, Error>;
+
+            fn next(&mut self) -> Option<Result<JsonData, Error>> {
+                self.read().transpose()
+            }
+        }
+    };
+}
+
+// Appends up to `length` bytes and returns how many there were. Reads through
+// `take` rather than allocating the length up front, so a bogus length fails
+// at the end of the input instead of exhausting memory.
+pub fn read_up_to<R: Read>(reader: &mut R, length: u64, bytes: &mut Vec<u8>) -> Result<usize, Error> {
+    reader.take(length).read_to_end(bytes)
+}
+
+// The members of an object in the order a deterministic encoding writes
+// them: sorted by their encoded keys, which come with them, so shorter keys
+// go first and keys of the same length are ordered by their bytes. An object
+// that repeats a key has no one encoding and is an error.
+pub fn sorted_members(object: &JsonObject, write_key: impl Fn(&mut Vec<u8>, &str) -> Result<(), Error>) -> Result<Vec<(Vec<u8>, &JsonMember)>, Error> {
+    let mut members = vec![];
+    for member in &object.members {
+        let mut key = vec![];
+        write_key(&mut key, &member.string.string)?;
+        members.push((key, member));
+    }
+    members.sort_by(|left, right| left.0.cmp(&right.0));
+    if let Some(pair) = members.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+        let message = format!("Duplicate key {:?} cannot be encoded deterministically", pair[0].1.string.string);
+        return Err(Error::new(ErrorKind::InvalidData, message));
+    }
+    Ok(members)
+}
+
+// The byte stream under the CBOR and MessagePack readers. It counts the bytes
+// read so far, and reports errors through the format's own `error`, which
+// puts that count in the message.
+pub struct Input<R> {
+    reader: R,
+    offset: usize,
+    error: fn(usize, &str) -> Error,
+}
+
+impl<R: Read> Input<R> {
+    pub fn new(reader: R, error: fn(usize, &str) -> Error) -> Input<R> {
+        Input { reader, offset: 0, error }
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    // The next byte, or `None` at the end of the input.
+    pub fn next_byte(&mut self) -> Result<Option<u8>, Error> {
+        let mut byte = [0];
+        loop {
+            match self.reader.read(&mut byte) {
+                Ok(0) => return Ok(None),
+                Ok(_) => {
+                    self.offset += 1;
+                    return Ok(Some(byte[0]));
+                }
+                Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    pub fn byte(&mut self) -> Result<u8, Error> {
+        self.next_byte()?.ok_or_else(|| (self.error)(self.offset, "Unexpected end of input"))
+    }
+
+    pub fn exact<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        let mut bytes = [0; N];
+        self.reader.read_exact(&mut bytes).map_err(|err| match err.kind() {
+            ErrorKind::UnexpectedEof => (self.error)(self.offset, "Unexpected end of input"),
+            _ => err,
+        })?;
+        self.offset += N;
+        Ok(bytes)
+    }
+
+    pub fn payload(&mut self, length: u64) -> Result<Vec<u8>, Error> {
+        let mut bytes = vec![];
+        self.offset += read_up_to(&mut self.reader, length, &mut bytes)?;
+        if (bytes.len() as u64) < length {
+            return Err((self.error)(self.offset, "Unexpected end of input"));
+        }
+        Ok(bytes)
+    }
+}
@@ -0,0 +1,143 @@
+use crate::data::{self, JsonNumber, NumberMode, Span};
+use crate::parse::{number_from_lexeme, Builder, ParseOptions};
+use std::borrow::Cow;
+use std::io::Error;
+
+// A tree that borrows its strings from the input text. Keys and values only
+// own their contents when the source spelled them with escapes.
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct JsonData<'a> {
+    pub element: JsonElement<'a>,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct JsonElement<'a> {
+    pub value: JsonValue<'a>,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct JsonObject<'a> {
+    pub members: Vec<JsonMember<'a>>,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct JsonMember<'a> {
+    pub string: JsonString<'a>,
+    pub element: JsonElement<'a>,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct JsonArray<'a> {
+    pub elements: Vec<JsonElement<'a>>,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct JsonString<'a> {
+    pub string: Cow<'a, str>,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum JsonValue<'a> {
+    Object(JsonObject<'a>),
+    Array(JsonArray<'a>),
+    String(JsonString<'a>),
+    Number(JsonNumber),
+    True,
+    False,
+    Null
+}
+
+pub fn parse(string: &str) -> Result<JsonData<'_>, Error> {
+    let mut input = string;
+    let tokens = crate::lex::lex(&mut input)?;
+    // This tree reports no positions, so the tokens need none.
+    let tokens = tokens.into_iter().map(|token| (Ok(token), Span { start: 0, end: 0 }));
+    let value = crate::parse::parse_tokens(tokens, &mut Tree, &ParseOptions::default())?;
+    Ok(JsonData { element: JsonElement { value } })
+}
+
+struct Tree;
+
+impl<'a> Builder<'a> for Tree {
+    type Value = JsonValue<'a>;
+    type Key = JsonString<'a>;
+    type Member = JsonMember<'a>;
+    type Element = JsonElement<'a>;
+    type Token = ();
+
+    fn token(&mut self, _span: Span) {}
+
+    fn string(&mut self, string: Cow<'a, str>, _span: Span) -> JsonValue<'a> {
+        JsonValue::String(JsonString { string })
+    }
+
+    fn number(&mut self, lexeme: &'a str, span: Span, mode: NumberMode) -> Result<JsonValue<'a>, Error> {
+        Ok(JsonValue::Number(number_from_lexeme(lexeme, span, mode)?))
+    }
+
+    fn boolean(&mut self, value: bool, _span: Span) -> JsonValue<'a> {
+        if value { JsonValue::True } else { JsonValue::False }
+    }
+
+    fn null(&mut self, _span: Span) -> JsonValue<'a> {
+        JsonValue::Null
+    }
+
+    fn key(&mut self, string: Cow<'a, str>, _span: Span) -> JsonString<'a> {
+        JsonString { string }
+    }
+
+    fn member(&mut self, string: JsonString<'a>, _colon: (), value: JsonValue<'a>, _comma: Option<()>) -> JsonMember<'a> {
+        JsonMember { string, element: JsonElement { value } }
+    }
+
+    fn element(&mut self, value: JsonValue<'a>, _comma: Option<()>) -> JsonElement<'a> {
+        JsonElement { value }
+    }
+
+    fn object(&mut self, _open: (), members: Vec<JsonMember<'a>>, _close: ()) -> JsonValue<'a> {
+        JsonValue::Object(JsonObject { members })
+    }
+
+    fn array(&mut self, _open: (), elements: Vec<JsonElement<'a>>, _close: ()) -> JsonValue<'a> {
+        JsonValue::Array(JsonArray { elements })
+    }
+}
+
+impl JsonData<'_> {
+    pub fn into_owned(self) -> data::JsonData {
+        data::JsonData {
+            element: self.element.into_owned(),
+        }
+    }
+}
+
+impl JsonElement<'_> {
+    pub fn into_owned(self) -> data::JsonElement {
+        data::JsonElement {
+            value: self.value.into_owned(),
+        }
+    }
+}
+
+impl JsonValue<'_> {
+    pub fn into_owned(self) -> data::JsonValue {
+        match self {
+            JsonValue::Object(object) => data::JsonValue::Object(data::JsonObject {
+                members: object.members.into_iter().map(|member| data::JsonMember {
+                    string: data::JsonString { string: member.string.string.into_owned() },
+                    element: member.element.into_owned(),
+                }).collect(),
+            }),
+            JsonValue::Array(array) => data::JsonValue::Array(data::JsonArray {
+                elements: array.elements.into_iter().map(JsonElement::into_owned).collect(),
+            }),
+            JsonValue::String(string) => data::JsonValue::String(data::JsonString { string: string.string.into_owned() }),
+            JsonValue::Number(number) => data::JsonValue::Number(number),
+            JsonValue::True => data::JsonValue::True,
+            JsonValue::False => data::JsonValue::False,
+            JsonValue::Null => data::JsonValue::Null,
+        }
+    }
+}
@@ -0,0 +1,109 @@
+// IEEE 754-2008 decimal128 in the binary integer decimal encoding BSON uses,
+// converted to and from the strings of `$numberDecimal`. A value is a sign,
+// a coefficient of up to 34 digits and a power of ten between -6176 and 6111.
+
+const BIAS: i64 = 6176;
+const MAX_EXPONENT: i64 = 6111;
+const MIN_EXPONENT: i64 = -6176;
+const MAX_DIGITS: usize = 34;
+const INFINITY: u128 = 0x7800 << 112;
+const NAN: u128 = 0x7c00 << 112;
+
+// Writes the value the way the General Decimal Arithmetic specification's
+// to-scientific-string does, which is what the BSON specification requires.
+pub fn to_string(bits: u128) -> String {
+    let sign = if bits >> 127 == 1 { "-" } else { "" };
+    // The two bits after the sign select the layout of the rest.
+    let (exponent, coefficient) = if (bits >> 125) & 0b11 == 0b11 {
+        match (bits >> 122) & 0b11111 {
+            0b11111 => return String::from("NaN"),
+            0b11110 => return format!("{}Infinity", sign),
+            // This layout only holds coefficients past 34 digits, which
+            // count as zero.
+            _ => ((bits >> 111) & 0x3fff, 0),
+        }
+    } else {
+        ((bits >> 113) & 0x3fff, bits & ((1 << 113) - 1))
+    };
+    let exponent = exponent as i64 - BIAS;
+    let coefficient = if coefficient >= 10u128.pow(MAX_DIGITS as u32) { 0 } else { coefficient };
+
+    let digits = coefficient.to_string();
+    let adjusted = exponent + digits.len() as i64 - 1;
+    if exponent <= 0 && adjusted >= -6 {
+        let point = digits.len() as i64 + exponent;
+        if exponent == 0 {
+            format!("{}{}", sign, digits)
+        } else if point > 0 {
+            format!("{}{}.{}", sign, &digits[..point as usize], &digits[point as usize..])
+        } else {
+            format!("{}0.{}{}", sign, "0".repeat(-point as usize), digits)
+        }
+    } else {
+        let (first, rest) = digits.split_at(1);
+        let point = if rest.is_empty() { "" } else { "." };
+        let plus = if adjusted >= 0 { "+" } else { "" };
+        format!("{}{}{}{}E{}{}", sign, first, point, rest, plus, adjusted)
+    }
+}
+
+// Reads a decimal string, `Infinity` or `NaN`. Values that would have to be
+// rounded to fit are rejected rather than rounded.
+pub fn parse(text: &str) -> Option<u128> {
+    let (sign, unsigned) = match text.as_bytes().first() {
+        Some(b'-') => (1 << 127, &text[1..]),
+        Some(b'+') => (0, &text[1..]),
+        _ => (0, text),
+    };
+    if unsigned.eq_ignore_ascii_case("infinity") || unsigned.eq_ignore_ascii_case("inf") {
+        return Some(sign | INFINITY);
+    }
+    if unsigned.eq_ignore_ascii_case("nan") {
+        return Some(NAN);
+    }
+
+    let (significand, exponent) = match unsigned.split_once(['e', 'E']) {
+        Some((significand, exponent)) => (significand, exponent),
+        None => (unsigned, "0"),
+    };
+    let (integral, fraction) = significand.split_once('.').unwrap_or((significand, ""));
+    let digits = format!("{}{}", integral, fraction);
+    let exponent_digits = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+    if digits.is_empty()
+        || !digits.bytes().all(|byte| byte.is_ascii_digit())
+        || exponent_digits.is_empty()
+        || !exponent_digits.bytes().all(|byte| byte.is_ascii_digit())
+    {
+        return None;
+    }
+    // Exponents this long are out of range whatever the digits.
+    let mut exponent = exponent.parse::<i64>().ok()?.checked_sub(fraction.len() as i64)?;
+
+    let mut digits = digits.trim_start_matches('0');
+    if digits.is_empty() {
+        // Zero fits at any exponent, clamped to the range.
+        let exponent = exponent.clamp(MIN_EXPONENT, MAX_EXPONENT);
+        return Some(sign | ((exponent + BIAS) as u128) << 113);
+    }
+    // Trailing zeros can move into the exponent when there are too many
+    // digits or the exponent is too small.
+    while digits.ends_with('0') && (digits.len() > MAX_DIGITS || exponent < MIN_EXPONENT) {
+        digits = &digits[..digits.len() - 1];
+        exponent += 1;
+    }
+    if digits.len() > MAX_DIGITS {
+        return None;
+    }
+    // And a large exponent can move into the digits as trailing zeros.
+    let mut coefficient: u128 = digits.parse().ok()?;
+    let mut length = digits.len();
+    while exponent > MAX_EXPONENT && length < MAX_DIGITS {
+        coefficient *= 10;
+        length += 1;
+        exponent -= 1;
+    }
+    if !(MIN_EXPONENT..=MAX_EXPONENT).contains(&exponent) {
+        return None;
+    }
+    Some(sign | ((exponent + BIAS) as u128) << 113 | coefficient)
+}
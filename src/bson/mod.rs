@@ -0,0 +1,790 @@
+use crate::binary;
+use crate::data::*;
+use crate::parse::DEFAULT_MAX_DEPTH;
+use std::io::{Error, ErrorKind, Read, Write};
+
+pub mod decimal128;
+
+// BSON documents, with the types JSON lacks spelled in MongoDB Extended JSON
+// v2: `{"$oid": "..."}` for an ObjectId, `{"$date": ...}` for a datetime,
+// `{"$numberDecimal": "..."}` for a decimal128 and so on. Writing accepts
+// both the canonical and the relaxed form of each wrapper. Plain numbers
+// become an int32 or int64 when they are integers that fit, and a double
+// otherwise; integers beyond int64 are an error, as they are for TOML.
+
+// The form of Extended JSON that reading produces.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub enum ExtendedJson {
+    // Every number and datetime is wrapped, so writing the tree back gives
+    // the same BSON.
+    Canonical,
+    // int32, int64 and finite doubles are plain numbers, and datetimes from
+    // 1970 to 9999 are ISO 8601 strings.
+    #[default]
+    Relaxed,
+}
+
+// The keys that make an object a type wrapper instead of a document.
+const WRAPPERS: [&str; 16] = [
+    "$oid", "$symbol", "$numberInt", "$numberLong", "$numberDouble", "$numberDecimal", "$binary", "$uuid", "$code",
+    "$timestamp", "$regularExpression", "$dbPointer", "$date", "$minKey", "$maxKey", "$undefined",
+];
+
+pub fn to_vec(data: &JsonData) -> Result<Vec<u8>, Error> {
+    let JsonValue::Object(object) = &data.element.value else {
+        return Err(unrepresentable("a top level that is not a document", ""));
+    };
+    if wrapper_key(object).is_some() {
+        return Err(unrepresentable("a top level that is not a document", ""));
+    }
+    let mut out = vec![];
+    write_document(object, "", &mut out)?;
+    Ok(out)
+}
+
+// Writes one document. A stream of documents, like the files `mongodump`
+// writes, is just documents one after another.
+pub fn write<W: Write>(writer: &mut W, data: &JsonData) -> Result<(), Error> {
+    writer.write_all(&to_vec(data)?)
+}
+
+fn unrepresentable(what: &str, path: &str) -> Error {
+    let message = if path.is_empty() {
+        format!("BSON cannot represent {}", what)
+    } else {
+        format!("BSON cannot represent {} at {}", what, path)
+    };
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+fn child_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        String::from(key)
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+// A document is its length, its elements and a NUL. Each element is a type
+// byte, a NUL-terminated key and the value, so the type is filled in once
+// the value has been written.
+fn write_document(object: &JsonObject, path: &str, out: &mut Vec<u8>) -> Result<(), Error> {
+    let start = out.len();
+    out.extend([0; 4]);
+    for member in &object.members {
+        let key = &member.string.string;
+        let kind = out.len();
+        out.push(0);
+        write_cstring(key, "a key", path, out)?;
+        out[kind] = write_value(&member.element.value, &child_path(path, key), out)?;
+    }
+    out.push(0);
+    finish_length(start, path, out)
+}
+
+// An array is a document whose keys are the indexes.
+fn write_array(array: &JsonArray, path: &str, out: &mut Vec<u8>) -> Result<(), Error> {
+    let start = out.len();
+    out.extend([0; 4]);
+    for (index, element) in array.elements.iter().enumerate() {
+        let kind = out.len();
+        out.push(0);
+        out.extend(index.to_string().bytes());
+        out.push(0);
+        out[kind] = write_value(&element.value, &format!("{}[{}]", path, index), out)?;
+    }
+    out.push(0);
+    finish_length(start, path, out)
+}
+
+// Fills in the length that starts a document or a code with scope.
+fn finish_length(start: usize, path: &str, out: &mut [u8]) -> Result<(), Error> {
+    let length = i32::try_from(out.len() - start).map_err(|_| unrepresentable("a document over 2 GiB", path))?;
+    out[start..start + 4].copy_from_slice(&length.to_le_bytes());
+    Ok(())
+}
+
+fn write_cstring(string: &str, what: &str, path: &str, out: &mut Vec<u8>) -> Result<(), Error> {
+    if string.contains('\0') {
+        return Err(unrepresentable(&format!("{} with a NUL character", what), path));
+    }
+    out.extend(string.bytes());
+    out.push(0);
+    Ok(())
+}
+
+// Unlike keys, strings carry their length and may hold NUL.
+fn write_string(string: &str, path: &str, out: &mut Vec<u8>) -> Result<(), Error> {
+    write_length(string.len() + 1, "a string", path, out)?;
+    out.extend(string.bytes());
+    out.push(0);
+    Ok(())
+}
+
+// Strings and binary data start with their length as an int32.
+fn write_length(length: usize, what: &str, path: &str, out: &mut Vec<u8>) -> Result<(), Error> {
+    let length = i32::try_from(length).map_err(|_| unrepresentable(&format!("{} over 2 GiB", what), path))?;
+    out.extend(length.to_le_bytes());
+    Ok(())
+}
+
+// Writes the value and returns its type byte.
+fn write_value(value: &JsonValue, path: &str, out: &mut Vec<u8>) -> Result<u8, Error> {
+    match value {
+        JsonValue::Object(object) => match wrapper_key(object) {
+            Some(key) => write_wrapper(object, key, path, out),
+            None => {
+                write_document(object, path, out)?;
+                Ok(0x03)
+            }
+        },
+        JsonValue::Array(array) => {
+            write_array(array, path, out)?;
+            Ok(0x04)
+        }
+        JsonValue::String(string) => {
+            write_string(&string.string, path, out)?;
+            Ok(0x02)
+        }
+        JsonValue::Number(number) => write_number(number, path, out),
+        JsonValue::True | JsonValue::False => {
+            out.push(u8::from(*value == JsonValue::True));
+            Ok(0x08)
+        }
+        JsonValue::Null => Ok(0x0a),
+    }
+}
+
+fn write_number(number: &JsonNumber, path: &str, out: &mut Vec<u8>) -> Result<u8, Error> {
+    let float = match number {
+        JsonNumber::Float(float) => *float,
+        JsonNumber::Decimal(text) if text.contains(['.', 'e', 'E']) => match text.parse::<f64>() {
+            Ok(float) if float.is_finite() => float,
+            _ => return Err(unrepresentable("a number beyond a double", path)),
+        },
+        _ => {
+            let integer = number.as_i64().ok_or_else(|| unrepresentable("an integer that exceeds int64", path))?;
+            return Ok(match i32::try_from(integer) {
+                Ok(integer) => {
+                    out.extend(integer.to_le_bytes());
+                    0x10
+                }
+                Err(_) => {
+                    out.extend(integer.to_le_bytes());
+                    0x12
+                }
+            });
+        }
+    };
+    out.extend(float.to_le_bytes());
+    Ok(0x01)
+}
+
+fn wrapper_key(object: &JsonObject) -> Option<&str> {
+    object.members.iter().map(|member| member.string.string.as_str()).find(|key| WRAPPERS.contains(key))
+}
+
+// Writes the type a wrapper stands for. An object with a wrapper key and
+// anything else in it is an error rather than a document, as the Extended
+// JSON specification requires.
+fn write_wrapper(object: &JsonObject, key: &str, path: &str, out: &mut Vec<u8>) -> Result<u8, Error> {
+    let invalid = || Error::new(ErrorKind::InvalidData, format!("Invalid Extended JSON {} at {}", key, path));
+    // `$code` is the only wrapper with a second key.
+    let scope = object.get("$scope").filter(|_| key == "$code");
+    if object.members.len() != 1 + usize::from(scope.is_some()) {
+        return Err(invalid());
+    }
+    let Some(element) = object.get(key) else {
+        return Err(invalid());
+    };
+    let value = &element.value;
+    let text = match value {
+        JsonValue::String(string) => Some(string.string.as_str()),
+        _ => None,
+    };
+
+    match key {
+        "$oid" => {
+            out.extend(text.and_then(object_id).ok_or_else(invalid)?);
+            Ok(0x07)
+        }
+        "$symbol" => {
+            write_string(text.ok_or_else(invalid)?, path, out)?;
+            Ok(0x0e)
+        }
+        "$numberInt" => {
+            let integer = text.and_then(|text| text.parse::<i32>().ok()).ok_or_else(invalid)?;
+            out.extend(integer.to_le_bytes());
+            Ok(0x10)
+        }
+        "$numberLong" => {
+            let integer = text.and_then(|text| text.parse::<i64>().ok()).ok_or_else(invalid)?;
+            out.extend(integer.to_le_bytes());
+            Ok(0x12)
+        }
+        "$numberDouble" => {
+            out.extend(text.and_then(parse_double).ok_or_else(invalid)?.to_le_bytes());
+            Ok(0x01)
+        }
+        "$numberDecimal" => {
+            out.extend(text.and_then(decimal128::parse).ok_or_else(invalid)?.to_le_bytes());
+            Ok(0x13)
+        }
+        "$binary" => {
+            let fields = fields(value, &["base64", "subType"]).ok_or_else(invalid)?;
+            let bytes = fields[0].and_then(from_base64).ok_or_else(invalid)?;
+            let subtype = fields[1]
+                .filter(|subtype| (1..=2).contains(&subtype.len()) && subtype.bytes().all(|byte| byte.is_ascii_hexdigit()))
+                .and_then(|subtype| u8::from_str_radix(subtype, 16).ok())
+                .ok_or_else(invalid)?;
+            write_binary(&bytes, subtype, path, out)?;
+            Ok(0x05)
+        }
+        "$uuid" => {
+            write_binary(&text.and_then(uuid).ok_or_else(invalid)?, 4, path, out)?;
+            Ok(0x05)
+        }
+        "$code" => match scope.map(|scope| &scope.value) {
+            None => {
+                write_string(text.ok_or_else(invalid)?, path, out)?;
+                Ok(0x0d)
+            }
+            Some(JsonValue::Object(scope)) if wrapper_key(scope).is_none() => {
+                let start = out.len();
+                out.extend([0; 4]);
+                write_string(text.ok_or_else(invalid)?, path, out)?;
+                write_document(scope, &child_path(path, "$scope"), out)?;
+                finish_length(start, path, out)?;
+                Ok(0x0f)
+            }
+            Some(_) => Err(invalid()),
+        },
+        "$timestamp" => {
+            let JsonValue::Object(timestamp) = value else {
+                return Err(invalid());
+            };
+            let field = |name| timestamp.get(name).and_then(|element| match &element.value {
+                JsonValue::Number(number) => number.as_u64().and_then(|number| u32::try_from(number).ok()),
+                _ => None,
+            });
+            let (Some(time), Some(increment), 2) = (field("t"), field("i"), timestamp.members.len()) else {
+                return Err(invalid());
+            };
+            out.extend(increment.to_le_bytes());
+            out.extend(time.to_le_bytes());
+            Ok(0x11)
+        }
+        "$regularExpression" => {
+            let fields = fields(value, &["pattern", "options"]).ok_or_else(invalid)?;
+            let (Some(pattern), Some(options)) = (fields[0], fields[1]) else {
+                return Err(invalid());
+            };
+            write_cstring(pattern, "a regular expression", path, out)?;
+            write_cstring(&sorted(options), "a regular expression", path, out)?;
+            Ok(0x0b)
+        }
+        "$dbPointer" => {
+            let JsonValue::Object(pointer) = value else {
+                return Err(invalid());
+            };
+            let namespace = pointer.get("$ref").and_then(|element| match &element.value {
+                JsonValue::String(string) => Some(string.string.as_str()),
+                _ => None,
+            });
+            let id = pointer.get("$id").and_then(|element| fields(&element.value, &["$oid"])).and_then(|fields| fields[0]);
+            let (Some(namespace), Some(id), 2) = (namespace, id.and_then(object_id), pointer.members.len()) else {
+                return Err(invalid());
+            };
+            write_string(namespace, path, out)?;
+            out.extend(id);
+            Ok(0x0c)
+        }
+        "$date" => {
+            let millis = match value {
+                JsonValue::String(string) => parse_date(&string.string),
+                JsonValue::Object(_) => fields(value, &["$numberLong"]).and_then(|fields| fields[0]).and_then(|text| text.parse().ok()),
+                // Extended JSON v1 wrote the milliseconds as a number.
+                JsonValue::Number(number) => number.as_i64(),
+                _ => None,
+            };
+            out.extend(millis.ok_or_else(invalid)?.to_le_bytes());
+            Ok(0x09)
+        }
+        "$minKey" | "$maxKey" => match value {
+            JsonValue::Number(number) if number.as_i64() == Some(1) => Ok(if key == "$minKey" { 0xff } else { 0x7f }),
+            _ => Err(invalid()),
+        },
+        "$undefined" if *value == JsonValue::True => Ok(0x06),
+        _ => Err(invalid()),
+    }
+}
+
+// The string values of an object that has exactly these keys. Missing keys
+// and values that are not strings are `None`.
+fn fields<'a>(value: &'a JsonValue, names: &[&str]) -> Option<Vec<Option<&'a str>>> {
+    let JsonValue::Object(object) = value else {
+        return None;
+    };
+    if object.members.len() != names.len() {
+        return None;
+    }
+    let field = |name: &&str| match &object.get(name)?.value {
+        JsonValue::String(string) => Some(string.string.as_str()),
+        _ => None,
+    };
+    Some(names.iter().map(field).collect())
+}
+
+// Subtype 2 is the old binary subtype, which repeats the length inside the
+// data.
+fn write_binary(bytes: &[u8], subtype: u8, path: &str, out: &mut Vec<u8>) -> Result<(), Error> {
+    let old = subtype == 2;
+    write_length(bytes.len() + if old { 4 } else { 0 }, "binary data", path, out)?;
+    out.push(subtype);
+    if old {
+        write_length(bytes.len(), "binary data", path, out)?;
+    }
+    out.extend(bytes);
+    Ok(())
+}
+
+// Regular expression options are stored in alphabetical order.
+fn sorted(options: &str) -> String {
+    let mut options: Vec<char> = options.chars().collect();
+    options.sort_unstable();
+    options.into_iter().collect()
+}
+
+fn parse_double(text: &str) -> Option<f64> {
+    match text {
+        "Infinity" => Some(f64::INFINITY),
+        "-Infinity" => Some(f64::NEG_INFINITY),
+        "NaN" => Some(f64::NAN),
+        // `str::parse` also reads spellings like `inf`, which are not allowed.
+        _ if text.bytes().all(|byte| byte.is_ascii_digit() || b"+-.eE".contains(&byte)) => {
+            text.parse().ok().filter(|float: &f64| float.is_finite())
+        }
+        _ => None,
+    }
+}
+
+fn format_double(float: f64) -> String {
+    if float.is_nan() {
+        return String::from("NaN");
+    }
+    if float.is_infinite() {
+        return String::from(if float > 0.0 { "Infinity" } else { "-Infinity" });
+    }
+    // `{:?}` always writes a fraction or an exponent, like other drivers.
+    let text = format!("{:?}", float);
+    match text.split_once('e') {
+        Some((mantissa, exponent)) if exponent.starts_with('-') => format!("{}E{}", mantissa, exponent),
+        Some((mantissa, exponent)) => format!("{}E+{}", mantissa, exponent),
+        None => text,
+    }
+}
+
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) || !text.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..text.len()).step_by(2).map(|index| u8::from_str_radix(&text[index..index + 2], 16).ok()).collect()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn object_id(text: &str) -> Option<[u8; 12]> {
+    from_hex(text)?.try_into().ok()
+}
+
+// A UUID in its usual 8-4-4-4-12 form.
+fn uuid(text: &str) -> Option<Vec<u8>> {
+    let groups: Vec<&str> = text.split('-').collect();
+    if groups.iter().map(|group| group.len()).collect::<Vec<_>>() != [8, 4, 4, 4, 12] {
+        return None;
+    }
+    from_hex(&groups.concat())
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn to_base64(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    for chunk in bytes.chunks(3) {
+        let mut group = [0; 3];
+        group[..chunk.len()].copy_from_slice(chunk);
+        let bits = u32::from(group[0]) << 16 | u32::from(group[1]) << 8 | u32::from(group[2]);
+        for index in 0..4 {
+            if index <= chunk.len() {
+                out.push(BASE64[(bits >> (18 - 6 * index) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+// Standard base64 with its padding, as Extended JSON writes it.
+fn from_base64(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(4) {
+        return None;
+    }
+    let padding = text.bytes().rev().take_while(|byte| *byte == b'=').count();
+    if padding > 2 {
+        return None;
+    }
+    let mut bytes = vec![];
+    let mut bits = 0;
+    for (index, byte) in text.bytes().enumerate() {
+        let value = if index >= text.len() - padding { 0 } else { BASE64.iter().position(|char| *char == byte)? as u32 };
+        bits = bits << 6 | value;
+        if index % 4 == 3 {
+            bytes.extend(&bits.to_be_bytes()[1..]);
+            bits = 0;
+        }
+    }
+    bytes.truncate(bytes.len() - padding);
+    Some(bytes)
+}
+
+// Days from 1970-01-01 to a date in the proleptic Gregorian calendar, and
+// back, after Howard Hinnant's `days_from_civil` and `civil_from_days`.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    (year_of_era + era * 400 + i64::from(month <= 2), month, day)
+}
+
+const MILLIS_PER_DAY: i64 = 86_400_000;
+
+// The relaxed form of a datetime, which only covers the years 1970 to 9999.
+fn format_date(millis: i64) -> Option<String> {
+    let (year, month, day) = civil_from_days(millis.div_euclid(MILLIS_PER_DAY));
+    if !(1970..=9999).contains(&year) {
+        return None;
+    }
+    let time = millis.rem_euclid(MILLIS_PER_DAY);
+    let (hour, minute, second, millis) = (time / 3_600_000, time / 60_000 % 60, time / 1000 % 60, time % 1000);
+    let fraction = if millis == 0 { String::new() } else { format!(".{:03}", millis) };
+    Some(format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}{}Z", year, month, day, hour, minute, second, fraction))
+}
+
+// Reads an RFC 3339 date and time. Digits past milliseconds are dropped.
+fn parse_date(text: &str) -> Option<i64> {
+    let number = |start: usize, length: usize| -> Option<i64> {
+        let digits = text.get(start..start + length)?;
+        digits.bytes().all(|byte| byte.is_ascii_digit()).then(|| digits.parse().ok())?
+    };
+    let separators = [(4, b'-'), (7, b'-'), (13, b':'), (16, b':')];
+    if !separators.iter().all(|(index, byte)| text.as_bytes().get(*index) == Some(byte))
+        || !matches!(text.as_bytes().get(10), Some(b'T' | b't' | b' '))
+    {
+        return None;
+    }
+    let (year, month, day) = (number(0, 4)?, number(5, 2)?, number(8, 2)?);
+    let (hour, minute, second) = (number(11, 2)?, number(14, 2)?, number(17, 2)?);
+    let days = days_from_civil(year, month, day);
+    if !(1..=12).contains(&month) || civil_from_days(days) != (year, month, day) || hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+
+    let mut rest = text.get(19..)?;
+    let mut millis = 0;
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let digits = fraction.bytes().take_while(u8::is_ascii_digit).count();
+        if digits == 0 {
+            return None;
+        }
+        millis = format!("{:0<3}", &fraction[..digits.min(3)]).parse().ok()?;
+        rest = &fraction[digits..];
+    }
+    let offset = match rest.as_bytes() {
+        [b'Z' | b'z'] => 0,
+        [sign @ (b'+' | b'-'), ..] => {
+            let rest = rest[1..].replacen(':', "", 1);
+            if rest.len() != 4 || !rest.bytes().all(|byte| byte.is_ascii_digit()) {
+                return None;
+            }
+            let minutes = rest[..2].parse::<i64>().ok()? * 60 + rest[2..].parse::<i64>().ok()?;
+            if *sign == b'-' { -minutes } else { minutes }
+        }
+        _ => return None,
+    };
+    Some((days * 86_400 + hour * 3600 + (minute - offset) * 60 + second) * 1000 + millis)
+}
+
+pub fn from_slice(bytes: &[u8], mode: ExtendedJson) -> Result<JsonData, Error> {
+    let mut reader = Reader::new(bytes, mode);
+    let Some(data) = reader.read()? else {
+        return Err(Error::new(ErrorKind::InvalidData, "Empty BSON"));
+    };
+    if reader.offset < bytes.len() {
+        return Err(error(reader.offset, "Unexpected data after the document"));
+    }
+    Ok(data)
+}
+
+// Reads documents written one after another. Each document is read whole
+// before it is decoded.
+pub struct Reader<R> {
+    reader: R,
+    mode: ExtendedJson,
+    offset: usize,
+}
+
+reader_iterator!(Reader);
+
+fn error(offset: usize, message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("BSON byte {}: {}", offset, message))
+}
+
+impl<R: Read> Reader<R> {
+    pub fn new(reader: R, mode: ExtendedJson) -> Reader<R> {
+        Reader { reader, mode, offset: 0 }
+    }
+
+    // The next document, or `None` when the input ends between documents.
+    pub fn read(&mut self) -> Result<Option<JsonData>, Error> {
+        let mut bytes = vec![];
+        match binary::read_up_to(&mut self.reader, 4, &mut bytes)? {
+            0 => return Ok(None),
+            4 => {}
+            length => return Err(error(self.offset + length, "Unexpected end of input")),
+        }
+        let length = i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        if length < 5 {
+            return Err(error(self.offset, "Invalid document length"));
+        }
+        binary::read_up_to(&mut self.reader, length as u64 - 4, &mut bytes)?;
+        if bytes.len() < length as usize {
+            return Err(error(self.offset + bytes.len(), "Unexpected end of input"));
+        }
+
+        let mut cursor = Cursor { bytes: &bytes, position: 0, base: self.offset, mode: self.mode };
+        let members = cursor.document(0)?;
+        self.offset += bytes.len();
+        Ok(Some(JsonData::from(object(members))))
+    }
+}
+
+// Decodes one document that has been read whole. `base` is where it starts
+// in the stream, for error offsets.
+struct Cursor<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    base: usize,
+    mode: ExtendedJson,
+}
+
+impl<'a> Cursor<'a> {
+    fn error(&self, position: usize, message: &str) -> Error {
+        error(self.base + position, message)
+    }
+
+    fn take(&mut self, length: usize) -> Result<&'a [u8], Error> {
+        match self.position.checked_add(length).filter(|end| *end <= self.bytes.len()) {
+            Some(end) => {
+                let bytes = &self.bytes[self.position..end];
+                self.position = end;
+                Ok(bytes)
+            }
+            None => Err(self.error(self.bytes.len(), "Unexpected end of input")),
+        }
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        let mut bytes = [0; N];
+        bytes.copy_from_slice(self.take(N)?);
+        Ok(bytes)
+    }
+
+    fn utf8(&self, bytes: &[u8], start: usize) -> Result<String, Error> {
+        String::from_utf8(bytes.to_vec()).map_err(|_| self.error(start, "Invalid UTF-8 in a string"))
+    }
+
+    fn cstring(&mut self) -> Result<String, Error> {
+        let start = self.position;
+        let Some(length) = self.bytes[start..].iter().position(|byte| *byte == 0) else {
+            return Err(self.error(self.bytes.len(), "Unexpected end of input"));
+        };
+        self.position += length + 1;
+        self.utf8(&self.bytes[start..start + length], start)
+    }
+
+    fn string(&mut self) -> Result<String, Error> {
+        let start = self.position;
+        let length = i32::from_le_bytes(self.array()?);
+        if length < 1 {
+            return Err(self.error(start, "Invalid string length"));
+        }
+        let bytes = self.take(length as usize)?;
+        if bytes.last() != Some(&0) {
+            return Err(self.error(start, "String is missing its terminating NUL"));
+        }
+        self.utf8(&bytes[..bytes.len() - 1], start)
+    }
+
+    // The members of a document, or an array read as one.
+    fn document(&mut self, depth: usize) -> Result<Vec<(String, JsonValue)>, Error> {
+        let start = self.position;
+        if depth > DEFAULT_MAX_DEPTH {
+            return Err(self.error(start, "Nesting too deep"));
+        }
+        let length = i32::from_le_bytes(self.array()?);
+        let end = usize::try_from(length).ok().filter(|length| *length >= 5).map(|length| start + length);
+        let Some(end) = end.filter(|end| *end <= self.bytes.len()) else {
+            return Err(self.error(start, "Invalid document length"));
+        };
+
+        let mut members = vec![];
+        loop {
+            let kind_at = self.position;
+            if kind_at >= end {
+                return Err(self.error(start, "Document length does not match its contents"));
+            }
+            let [kind] = self.array()?;
+            if kind == 0 {
+                if self.position != end {
+                    return Err(self.error(start, "Document length does not match its contents"));
+                }
+                return Ok(members);
+            }
+            if self.position == end {
+                return Err(self.error(kind_at, "Document is missing its terminating NUL"));
+            }
+            let key = self.cstring()?;
+            let value = self.value(kind, kind_at, depth)?;
+            members.push((key, value));
+        }
+    }
+
+    fn value(&mut self, kind: u8, start: usize, depth: usize) -> Result<JsonValue, Error> {
+        let relaxed = self.mode == ExtendedJson::Relaxed;
+        Ok(match kind {
+            0x01 => match f64::from_le_bytes(self.array()?) {
+                float if relaxed && float.is_finite() => JsonValue::Number(JsonNumber::Float(float)),
+                float => wrap("$numberDouble", string(format_double(float))),
+            },
+            0x02 => string(self.string()?),
+            0x03 => object(self.document(depth + 1)?),
+            0x04 => JsonValue::Array(JsonArray {
+                elements: self.document(depth + 1)?.into_iter().map(|(_, value)| JsonElement { value }).collect(),
+            }),
+            0x05 => {
+                let length = i32::from_le_bytes(self.array()?);
+                let [subtype] = self.array()?;
+                let length = usize::try_from(length).map_err(|_| self.error(start, "Invalid binary length"))?;
+                let mut bytes = self.take(length)?;
+                if subtype == 2 {
+                    let inner = bytes.get(..4).map(|inner| i32::from_le_bytes([inner[0], inner[1], inner[2], inner[3]]));
+                    if inner.and_then(|inner| usize::try_from(inner).ok()) != Some(length.saturating_sub(4)) {
+                        return Err(self.error(start, "Invalid binary length"));
+                    }
+                    bytes = &bytes[4..];
+                }
+                wrap("$binary", object(vec![
+                    (String::from("base64"), string(to_base64(bytes))),
+                    (String::from("subType"), string(format!("{:02x}", subtype))),
+                ]))
+            }
+            0x06 => wrap("$undefined", JsonValue::True),
+            0x07 => wrap("$oid", string(to_hex(self.take(12)?))),
+            0x08 => match self.array()? {
+                [0] => JsonValue::False,
+                [1] => JsonValue::True,
+                _ => return Err(self.error(start, "Invalid boolean")),
+            },
+            0x09 => {
+                let millis = i64::from_le_bytes(self.array()?);
+                match format_date(millis).filter(|_| relaxed) {
+                    Some(date) => wrap("$date", string(date)),
+                    None => wrap("$date", wrap("$numberLong", string(millis.to_string()))),
+                }
+            }
+            0x0a => JsonValue::Null,
+            0x0b => {
+                let pattern = self.cstring()?;
+                let options = sorted(&self.cstring()?);
+                wrap("$regularExpression", object(vec![
+                    (String::from("pattern"), string(pattern)),
+                    (String::from("options"), string(options)),
+                ]))
+            }
+            0x0c => {
+                let namespace = self.string()?;
+                let id = to_hex(self.take(12)?);
+                wrap("$dbPointer", object(vec![
+                    (String::from("$ref"), string(namespace)),
+                    (String::from("$id"), wrap("$oid", string(id))),
+                ]))
+            }
+            0x0d => wrap("$code", string(self.string()?)),
+            0x0e => wrap("$symbol", string(self.string()?)),
+            0x0f => {
+                let length_at = self.position;
+                let length = i32::from_le_bytes(self.array()?);
+                let code = self.string()?;
+                let scope = self.document(depth + 1)?;
+                if usize::try_from(length).ok() != Some(self.position - length_at) {
+                    return Err(self.error(start, "Invalid code with scope length"));
+                }
+                object(vec![(String::from("$code"), string(code)), (String::from("$scope"), object(scope))])
+            }
+            0x10 => match i32::from_le_bytes(self.array()?) {
+                integer if relaxed => JsonValue::Number(JsonNumber::Integer(i64::from(integer))),
+                integer => wrap("$numberInt", string(integer.to_string())),
+            },
+            0x11 => {
+                let increment = u32::from_le_bytes(self.array()?);
+                let time = u32::from_le_bytes(self.array()?);
+                wrap("$timestamp", object(vec![
+                    (String::from("t"), JsonValue::Number(JsonNumber::Integer(i64::from(time)))),
+                    (String::from("i"), JsonValue::Number(JsonNumber::Integer(i64::from(increment)))),
+                ]))
+            }
+            0x12 => match i64::from_le_bytes(self.array()?) {
+                integer if relaxed => JsonValue::Number(JsonNumber::Integer(integer)),
+                integer => wrap("$numberLong", string(integer.to_string())),
+            },
+            0x13 => wrap("$numberDecimal", string(decimal128::to_string(u128::from_le_bytes(self.array()?)))),
+            0xff => wrap("$minKey", JsonValue::Number(JsonNumber::Integer(1))),
+            0x7f => wrap("$maxKey", JsonValue::Number(JsonNumber::Integer(1))),
+            _ => return Err(self.error(start, &format!("Unknown element type 0x{:02x}", kind))),
+        })
+    }
+}
+
+fn string(string: String) -> JsonValue {
+    JsonValue::String(JsonString { string })
+}
+
+fn object(members: Vec<(String, JsonValue)>) -> JsonValue {
+    JsonValue::Object(JsonObject {
+        members: members.into_iter().map(|(string, value)| JsonMember {
+            string: JsonString { string },
+            element: JsonElement { value },
+        }).collect(),
+    })
+}
+
+fn wrap(key: &str, value: JsonValue) -> JsonValue {
+    object(vec![(String::from(key), value)])
+}
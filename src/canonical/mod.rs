@@ -0,0 +1,193 @@
+use crate::data::*;
+use crate::write::write_string;
+use std::io::Error;
+
+// The JSON Canonicalization Scheme of RFC 8785: a single byte representation
+// for each value, for signing and hashing. Members are sorted by the UTF-16
+// code units of their keys, numbers are written the way ECMAScript writes
+// doubles, strings only escape what they must, and there is no whitespace.
+
+// Whether the order of object members matters when comparing canonical forms.
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy, Default)]
+pub enum KeyOrder {
+    // Members are sorted, so objects that only differ in order are the same.
+    #[default]
+    Ignored,
+    // Members stay in document order.
+    Significant,
+}
+
+#[derive(Clone, Copy)]
+struct Style {
+    order: KeyOrder,
+    // Reject what the scheme cannot represent instead of writing it anyway.
+    strict: bool,
+}
+
+pub fn canonicalize(data: &JsonData) -> Result<String, Error> {
+    let mut out = String::new();
+    write_value(&data.element.value, &mut out, Style { order: KeyOrder::Ignored, strict: true })?;
+    Ok(out)
+}
+
+// The canonical form used to compare and hash values. Unlike `canonicalize`
+// it cannot fail and it compares numbers exactly: integers and decimals keep
+// every digit, and duplicate keys stay in document order. For everything the
+// scheme can represent exactly the two forms agree.
+pub fn content_string(value: &JsonValue, order: KeyOrder) -> String {
+    let mut out = String::new();
+    // Only strict writing fails.
+    let _ = write_value(value, &mut out, Style { order, strict: false });
+    out
+}
+
+fn write_value(value: &JsonValue, out: &mut String, style: Style) -> Result<(), Error> {
+    match value {
+        JsonValue::Object(object) => write_object(object, out, style)?,
+        JsonValue::Array(array) => {
+            out.push('[');
+            for (index, element) in array.elements.iter().enumerate() {
+                if index > 0 {
+                    out.push(',');
+                }
+                write_value(&element.value, out, style)?;
+            }
+            out.push(']');
+        }
+        // The escapes `write` uses are the ones the scheme requires.
+        JsonValue::String(string) => write_string(&string.string, out),
+        // Every number is an IEEE 754 double to the scheme, so integers past
+        // 2^53 are rounded like any other.
+        JsonValue::Number(number) if style.strict => out.push_str(&format_number(number.as_f64())?),
+        JsonValue::Number(number) => out.push_str(&content_number(number)),
+        JsonValue::True => out.push_str("true"),
+        JsonValue::False => out.push_str("false"),
+        JsonValue::Null => out.push_str("null"),
+    }
+    Ok(())
+}
+
+fn write_object(object: &JsonObject, out: &mut String, style: Style) -> Result<(), Error> {
+    let mut members: Vec<&JsonMember> = object.members.iter().collect();
+    if style.order == KeyOrder::Ignored {
+        members.sort_by(|left, right| left.string.string.encode_utf16().cmp(right.string.string.encode_utf16()));
+    }
+    if style.strict {
+        if let Some(pair) = members.windows(2).find(|pair| pair[0].string.string == pair[1].string.string) {
+            return Err(Error::new(std::io::ErrorKind::InvalidData, format!("Duplicate key {:?} cannot be canonicalized", pair[0].string.string)));
+        }
+    }
+
+    out.push('{');
+    for (index, member) in members.iter().enumerate() {
+        if index > 0 {
+            out.push(',');
+        }
+        write_string(&member.string.string, out);
+        out.push(':');
+        write_value(&member.element.value, out, style)?;
+    }
+    out.push('}');
+    Ok(())
+}
+
+// Number::toString from ECMA-262: the shortest digits that round-trip, laid
+// out in plain notation between 1e-7 and 1e21 and in exponent notation
+// otherwise.
+pub fn format_number(number: f64) -> Result<String, Error> {
+    if !number.is_finite() {
+        return Err(Error::new(std::io::ErrorKind::InvalidData, "Number out of range for canonical JSON"));
+    }
+    if number == 0.0 {
+        return Ok(String::from("0"));
+    }
+
+    // `{:e}` gives the shortest round-trip digits as `d.ddde±x`.
+    let scientific = format!("{:e}", number.abs());
+    let (mantissa, exponent) = scientific.split_once('e').unwrap_or((&scientific, "0"));
+    let mut digits = mantissa.replace('.', "");
+    let point = exponent.parse::<i64>().unwrap_or(0) + 1;
+    if let Some(even) = even_tie(number.abs(), &digits, exponent) {
+        digits = even;
+    }
+    Ok(layout(number < 0.0, &digits, point))
+}
+
+// Content strings compare numbers by exact value. A double stands for the
+// shortest decimal that reads back as it, which is how the scheme writes it.
+// Decimal text that is not in JSON's grammar, which no parser here produces,
+// is kept as it is.
+fn content_number(number: &JsonNumber) -> String {
+    match number {
+        JsonNumber::Integer(integer) => exact_number(&integer.to_string()),
+        JsonNumber::Unsigned(unsigned) => exact_number(&unsigned.to_string()),
+        JsonNumber::Float(float) => format_number(*float).ok(),
+        JsonNumber::Decimal(text) => exact_number(text).or_else(|| Some(text.clone())),
+    }.unwrap_or_else(|| String::from("null"))
+}
+
+// Lays out a number in JSON's grammar the way `format_number` lays out a
+// double, but with every significant digit, so equal values written
+// differently give the same text.
+fn exact_number(text: &str) -> Option<String> {
+    let parts = DecimalParts::parse(text)?;
+    if parts.digits.is_empty() {
+        return Some(String::from("0"));
+    }
+    Some(layout(parts.negative, &parts.digits, parts.point))
+}
+
+// Writes 0.digits × 10^point in plain notation between 1e-7 and 1e21 and in
+// exponent notation otherwise.
+fn layout(negative: bool, digits: &str, point: i64) -> String {
+    let length = digits.len() as i64;
+    let mut out = String::new();
+    if negative {
+        out.push('-');
+    }
+    if length <= point && point <= 21 {
+        out.push_str(digits);
+        out.push_str(&"0".repeat((point - length) as usize));
+    } else if 0 < point && point <= 21 {
+        out.push_str(&digits[..point as usize]);
+        out.push('.');
+        out.push_str(&digits[point as usize..]);
+    } else if -6 < point && point <= 0 {
+        out.push_str("0.");
+        out.push_str(&"0".repeat(-point as usize));
+        out.push_str(digits);
+    } else {
+        out.push_str(&digits[..1]);
+        if length > 1 {
+            out.push('.');
+            out.push_str(&digits[1..]);
+        }
+        let exponent = point - 1;
+        out.push_str(&format!("e{}{}", if exponent < 0 { '-' } else { '+' }, exponent.abs()));
+    }
+    out
+}
+
+// `{:e}` rounds up when the value lies exactly halfway between the two
+// closest shortest candidates, where ECMAScript takes the even one. Both can
+// only round-trip when the double is coarser than the last digit, which needs
+// at least 16 digits, so shorter output is never a tie. Otherwise the exact
+// expansion shows whether the value ends in a 5 right after the shortest digits.
+fn even_tie(number: f64, digits: &str, exponent: &str) -> Option<String> {
+    if digits.len() < 16 || digits.ends_with(['0', '2', '4', '6', '8']) {
+        return None;
+    }
+
+    // 800 digits covers the longest exact expansion of a double.
+    let exact = format!("{:.800e}", number);
+    let (exact_mantissa, exact_exponent) = exact.split_once('e')?;
+    let exact_digits = exact_mantissa.replace('.', "");
+    let rest = &exact_digits[digits.len()..];
+    if exact_exponent != exponent || !rest.starts_with('5') || rest[1..].bytes().any(|digit| digit != b'0') {
+        return None;
+    }
+
+    let lower = &exact_digits[..digits.len()];
+    let round_trips = format!("{}.{}e{}", &lower[..1], &lower[1..], exponent).parse::<f64>() == Ok(number);
+    (lower.ends_with(['0', '2', '4', '6', '8']) && round_trips).then(|| String::from(lower))
+}
@@ -0,0 +1,511 @@
+use crate::data::*;
+use crate::parse::DEFAULT_MAX_DEPTH;
+use crate::binary::{self, Input};
+use std::io::{Error, ErrorKind, Read, Write};
+
+// CBOR (RFC 8949) encoding of the tree. Writing always uses the preferred
+// serialization: the shortest head for every integer and length, and the
+// shortest float that keeps the value. Integers beyond 64 bits become bignums
+// and decimal text a decimal fraction, so no number is rounded.
+//
+// Reading accepts any well-formed item that has a JSON equivalent. Tags other
+// than bignums and decimal fractions are skipped and their content read as
+// if untagged, as section 6.1 suggests. Byte strings, map keys that are not
+// text and simple values other than false, true, null and undefined are
+// rejected.
+
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct CborOptions {
+    // The core deterministic encoding of section 4.2.1: members are sorted by
+    // their encoded keys and every NaN is written as 0xf97e00. Objects that
+    // repeat a key cannot be encoded.
+    pub deterministic: bool,
+}
+
+pub fn to_vec(data: &JsonData, options: &CborOptions) -> Result<Vec<u8>, Error> {
+    let mut out = vec![];
+    write(&mut out, data, options)?;
+    Ok(out)
+}
+
+// Writes one item.
+pub fn write<W: Write>(writer: &mut W, data: &JsonData, options: &CborOptions) -> Result<(), Error> {
+    write_value(writer, &data.element.value, options)
+}
+
+fn write_value<W: Write>(writer: &mut W, value: &JsonValue, options: &CborOptions) -> Result<(), Error> {
+    match value {
+        JsonValue::Object(object) => write_object(writer, object, options),
+        JsonValue::Array(array) => {
+            write_head(writer, 4, array.elements.len() as u64)?;
+            for element in &array.elements {
+                write_value(writer, &element.value, options)?;
+            }
+            Ok(())
+        }
+        JsonValue::String(string) => write_text(writer, &string.string),
+        JsonValue::Number(number) => write_number(writer, number, options),
+        JsonValue::True => writer.write_all(&[0xf5]),
+        JsonValue::False => writer.write_all(&[0xf4]),
+        JsonValue::Null => writer.write_all(&[0xf6]),
+    }
+}
+
+fn write_object<W: Write>(writer: &mut W, object: &JsonObject, options: &CborOptions) -> Result<(), Error> {
+    write_head(writer, 5, object.members.len() as u64)?;
+    if !options.deterministic {
+        for member in &object.members {
+            write_text(writer, &member.string.string)?;
+            write_value(writer, &member.element.value, options)?;
+        }
+        return Ok(());
+    }
+
+    for (key, member) in binary::sorted_members(object, write_text)? {
+        writer.write_all(&key)?;
+        write_value(writer, &member.element.value, options)?;
+    }
+    Ok(())
+}
+
+fn write_text<W: Write>(writer: &mut W, string: &str) -> Result<(), Error> {
+    write_head(writer, 3, string.len() as u64)?;
+    writer.write_all(string.as_bytes())
+}
+
+// The initial byte of an item and the argument that follows it, in as few
+// bytes as the argument allows.
+fn write_head<W: Write>(writer: &mut W, major: u8, argument: u64) -> Result<(), Error> {
+    let (info, size) = match argument {
+        0..=23 => (argument as u8, 0),
+        24..=0xff => (24, 1),
+        0x100..=0xffff => (25, 2),
+        0x1_0000..=0xffff_ffff => (26, 4),
+        _ => (27, 8),
+    };
+    let mut head = [major << 5 | info; 9];
+    head[1..=size].copy_from_slice(&argument.to_be_bytes()[8 - size..]);
+    writer.write_all(&head[..=size])
+}
+
+fn write_number<W: Write>(writer: &mut W, number: &JsonNumber, options: &CborOptions) -> Result<(), Error> {
+    match number {
+        JsonNumber::Integer(integer) => write_integer(writer, *integer),
+        JsonNumber::Unsigned(unsigned) => write_head(writer, 0, *unsigned),
+        JsonNumber::Float(float) => write_float(writer, *float, options),
+        JsonNumber::Decimal(text) => write_decimal(writer, text),
+    }
+}
+
+// Negative integers are stored as -1 - n, which is the bitwise complement.
+fn write_integer<W: Write>(writer: &mut W, integer: i64) -> Result<(), Error> {
+    if integer < 0 {
+        write_head(writer, 1, !integer as u64)
+    } else {
+        write_head(writer, 0, integer as u64)
+    }
+}
+
+fn write_float<W: Write>(writer: &mut W, float: f64, options: &CborOptions) -> Result<(), Error> {
+    // Other NaNs keep their payload unless the encoding has to be
+    // deterministic.
+    if float.is_nan() && (options.deterministic || float.to_bits() == f64::NAN.to_bits()) {
+        return writer.write_all(&[0xf9, 0x7e, 0x00]);
+    }
+    if let Some(half) = to_half(float) {
+        let [high, low] = half.to_be_bytes();
+        writer.write_all(&[0xf9, high, low])
+    } else if !float.is_nan() && f64::from(float as f32).to_bits() == float.to_bits() {
+        writer.write_all(&[0xfa])?;
+        writer.write_all(&(float as f32).to_be_bytes())
+    } else {
+        writer.write_all(&[0xfb])?;
+        writer.write_all(&float.to_be_bytes())
+    }
+}
+
+// The IEEE 754 half-precision float with exactly this value, if there is one.
+fn to_half(float: f64) -> Option<u16> {
+    let bits = float.to_bits();
+    let sign = ((bits >> 48) & 0x8000) as u16;
+    let magnitude = float.abs();
+    if magnitude == 0.0 {
+        return Some(sign);
+    }
+    if magnitude.is_infinite() {
+        return Some(sign | 0x7c00);
+    }
+    let exponent = ((bits >> 52) & 0x7ff) as i32 - 1023;
+    let mantissa = bits & ((1 << 52) - 1);
+    if exponent > 15 {
+        // Too large, or NaN.
+        None
+    } else if exponent >= -14 {
+        if mantissa & ((1 << 42) - 1) != 0 {
+            return None;
+        }
+        Some(sign | ((exponent + 15) as u16) << 10 | (mantissa >> 42) as u16)
+    } else {
+        // Subnormal halves are multiples of 2^-24, and scaling by a power of
+        // two is exact.
+        let scaled = magnitude * 2f64.powi(24);
+        if scaled.fract() != 0.0 {
+            return None;
+        }
+        Some(sign | scaled as u16)
+    }
+}
+
+fn from_half(half: u16) -> f64 {
+    let exponent = i32::from((half >> 10) & 0x1f);
+    let mantissa = f64::from(half & 0x3ff);
+    let magnitude = match exponent {
+        0 => mantissa * 2f64.powi(-24),
+        31 if mantissa == 0.0 => f64::INFINITY,
+        31 => f64::NAN,
+        _ => (1024.0 + mantissa) * 2f64.powi(exponent - 25),
+    };
+    if half & 0x8000 != 0 {
+        -magnitude
+    } else {
+        magnitude
+    }
+}
+
+// Decimal text is written exactly: an integer or bignum when it has no
+// fraction or exponent, and otherwise the decimal fraction [exponent,
+// mantissa] of tag 4.
+fn write_decimal<W: Write>(writer: &mut W, text: &str) -> Result<(), Error> {
+    let unrepresentable = || Error::new(ErrorKind::InvalidData, format!("CBOR cannot represent the number {}", text));
+    let (negative, unsigned) = match text.strip_prefix('-') {
+        Some(unsigned) => (true, unsigned),
+        None => (false, text),
+    };
+    let (significand, exponent) = match unsigned.split_once(['e', 'E']) {
+        Some((significand, exponent)) => (significand, exponent.parse::<i64>().map_err(|_| unrepresentable())?),
+        None => (unsigned, 0),
+    };
+    let (integral, fraction) = significand.split_once('.').unwrap_or((significand, ""));
+    if integral.is_empty() || !integral.bytes().chain(fraction.bytes()).all(|byte| byte.is_ascii_digit()) {
+        return Err(unrepresentable());
+    }
+    let exponent = exponent.checked_sub(fraction.len() as i64).ok_or_else(unrepresentable)?;
+
+    if exponent != 0 {
+        write_head(writer, 6, 4)?;
+        write_head(writer, 4, 2)?;
+        write_integer(writer, exponent)?;
+    }
+    let magnitude = digits_to_bytes(integral.bytes().chain(fraction.bytes())).ok_or_else(unrepresentable)?;
+    write_bignum(writer, negative, magnitude)
+}
+
+// Converting between digits and bytes takes time quadratic in the length, so
+// bignums are limited both ways. 1024 bytes hold any integer of 2466 digits.
+pub const MAX_BIGNUM_BYTES: usize = 1024;
+
+// Writes an integer of any size from its big-endian magnitude, as a plain
+// integer when it fits 64 bits and as a bignum of tag 2 or 3 otherwise.
+fn write_bignum<W: Write>(writer: &mut W, negative: bool, mut magnitude: Vec<u8>) -> Result<(), Error> {
+    // Zero has no negative form.
+    let negative = negative && magnitude.iter().any(|byte| *byte != 0);
+    if negative {
+        decrement(&mut magnitude);
+    }
+    let start = magnitude.iter().position(|byte| *byte != 0).unwrap_or(magnitude.len());
+    let magnitude = &magnitude[start..];
+    if magnitude.len() > MAX_BIGNUM_BYTES {
+        let message = format!("CBOR cannot represent a bignum longer than {} bytes", MAX_BIGNUM_BYTES);
+        return Err(Error::new(ErrorKind::InvalidData, message));
+    }
+    let major = u8::from(negative);
+    if magnitude.len() <= 8 {
+        let mut bytes = [0; 8];
+        bytes[8 - magnitude.len()..].copy_from_slice(magnitude);
+        write_head(writer, major, u64::from_be_bytes(bytes))
+    } else {
+        write_head(writer, 6, 2 + u64::from(major))?;
+        write_head(writer, 2, magnitude.len() as u64)?;
+        writer.write_all(magnitude)
+    }
+}
+
+// Gives up once the magnitude is past what `write_bignum` accepts. The extra
+// byte leaves room for the decrement of a negative bignum.
+fn digits_to_bytes(digits: impl Iterator<Item = u8>) -> Option<Vec<u8>> {
+    // Little-endian while it grows.
+    let mut bytes: Vec<u8> = vec![];
+    for digit in digits {
+        if bytes.len() > MAX_BIGNUM_BYTES + 1 {
+            return None;
+        }
+        let mut carry = u32::from(digit - b'0');
+        for byte in bytes.iter_mut() {
+            let product = u32::from(*byte) * 10 + carry;
+            *byte = product as u8;
+            carry = product >> 8;
+        }
+        if carry > 0 {
+            bytes.push(carry as u8);
+        }
+    }
+    bytes.reverse();
+    Some(bytes)
+}
+
+fn bytes_to_digits(bytes: &[u8]) -> String {
+    let mut bytes = bytes.to_vec();
+    let mut digits = vec![];
+    while bytes.iter().any(|byte| *byte != 0) {
+        let mut remainder = 0;
+        for byte in bytes.iter_mut() {
+            let dividend = remainder << 8 | u32::from(*byte);
+            *byte = (dividend / 10) as u8;
+            remainder = dividend % 10;
+        }
+        digits.push(b'0' + remainder as u8);
+    }
+    if digits.is_empty() {
+        digits.push(b'0');
+    }
+    digits.reverse();
+    String::from_utf8(digits).unwrap()
+}
+
+fn decrement(bytes: &mut [u8]) {
+    for byte in bytes.iter_mut().rev() {
+        let (difference, borrow) = byte.overflowing_sub(1);
+        *byte = difference;
+        if !borrow {
+            return;
+        }
+    }
+}
+
+fn increment(bytes: &mut Vec<u8>) {
+    for byte in bytes.iter_mut().rev() {
+        let (sum, carry) = byte.overflowing_add(1);
+        *byte = sum;
+        if !carry {
+            return;
+        }
+    }
+    bytes.insert(0, 1);
+}
+
+pub fn from_slice(bytes: &[u8]) -> Result<JsonData, Error> {
+    let mut reader = Reader::new(bytes);
+    let Some(data) = reader.read()? else {
+        return Err(Error::new(ErrorKind::InvalidData, "Empty CBOR"));
+    };
+    if reader.input.offset() < bytes.len() {
+        return Err(error(reader.input.offset(), "Unexpected data after the item"));
+    }
+    Ok(data)
+}
+
+// Reads a CBOR sequence (RFC 8742) one item at a time.
+pub struct Reader<R> {
+    input: Input<R>,
+}
+
+reader_iterator!(Reader);
+
+fn error(offset: usize, message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("CBOR byte {}: {}", offset, message))
+}
+
+impl<R: Read> Reader<R> {
+    pub fn new(reader: R) -> Reader<R> {
+        Reader { input: Input::new(reader, error) }
+    }
+
+    // The next item, or `None` when the input ends between items.
+    pub fn read(&mut self) -> Result<Option<JsonData>, Error> {
+        match self.input.next_byte()? {
+            Some(initial) => Ok(Some(JsonData::from(self.value(initial, 0)?))),
+            None => Ok(None),
+        }
+    }
+
+    // The argument that follows the initial byte, or `None` for an
+    // indefinite length.
+    fn argument(&mut self, initial: u8, start: usize) -> Result<Option<u64>, Error> {
+        match initial & 0x1f {
+            info @ 0..=23 => Ok(Some(u64::from(info))),
+            24 => Ok(Some(u64::from(self.input.exact::<1>()?[0]))),
+            25 => Ok(Some(u64::from(u16::from_be_bytes(self.input.exact()?)))),
+            26 => Ok(Some(u64::from(u32::from_be_bytes(self.input.exact()?)))),
+            27 => Ok(Some(u64::from_be_bytes(self.input.exact()?))),
+            31 => Ok(None),
+            _ => Err(error(start, "Reserved additional information")),
+        }
+    }
+
+    fn value(&mut self, initial: u8, depth: usize) -> Result<JsonValue, Error> {
+        let start = self.input.offset() - 1;
+        if depth > DEFAULT_MAX_DEPTH {
+            return Err(error(start, "Nesting too deep"));
+        }
+        let major = initial >> 5;
+        if major == 7 {
+            return self.simple(initial, start);
+        }
+        let argument = self.argument(initial, start)?;
+        match (major, argument) {
+            (0, Some(unsigned)) => Ok(JsonValue::Number(unsigned_number(unsigned))),
+            (1, Some(negative)) => Ok(JsonValue::Number(negative_number(negative))),
+            (2, _) => Err(error(start, "Byte strings are not supported")),
+            (3, _) => Ok(JsonValue::String(JsonString { string: self.text(initial, argument, start)? })),
+            (4, _) => {
+                let mut elements = vec![];
+                while let Some(initial) = self.next_entry(argument, elements.len() as u64)? {
+                    elements.push(JsonElement { value: self.value(initial, depth + 1)? });
+                }
+                Ok(JsonValue::Array(JsonArray { elements }))
+            }
+            (5, _) => {
+                let mut members = vec![];
+                while let Some(initial) = self.next_entry(argument, members.len() as u64)? {
+                    if initial >> 5 != 3 {
+                        return Err(error(self.input.offset() - 1, "Map keys must be text strings"));
+                    }
+                    let key_argument = self.argument(initial, self.input.offset() - 1)?;
+                    let string = self.text(initial, key_argument, self.input.offset() - 1)?;
+                    let initial = self.input.byte()?;
+                    members.push(JsonMember {
+                        string: JsonString { string },
+                        element: JsonElement { value: self.value(initial, depth + 1)? },
+                    });
+                }
+                Ok(JsonValue::Object(JsonObject { members }))
+            }
+            (6, Some(tag @ (2 | 3))) => {
+                let initial = self.input.byte()?;
+                if initial >> 5 != 2 {
+                    return Err(error(self.input.offset() - 1, "A bignum must hold a byte string"));
+                }
+                let argument = self.argument(initial, self.input.offset() - 1)?;
+                let bytes = self.string(initial, argument)?;
+                if bytes.iter().skip_while(|byte| **byte == 0).count() > MAX_BIGNUM_BYTES {
+                    return Err(error(start, &format!("Bignum longer than {} bytes", MAX_BIGNUM_BYTES)));
+                }
+                Ok(JsonValue::Number(bignum(tag == 3, &bytes)))
+            }
+            (6, Some(4)) => self.decimal_fraction(start, depth),
+            (6, Some(_)) => {
+                let initial = self.input.byte()?;
+                self.value(initial, depth + 1)
+            }
+            _ => Err(error(start, "Indefinite length is only allowed for strings, arrays and maps")),
+        }
+    }
+
+    // The initial byte of the next entry in an array or map, or `None` after
+    // the last one.
+    fn next_entry(&mut self, length: Option<u64>, count: u64) -> Result<Option<u8>, Error> {
+        match length {
+            Some(length) if count == length => Ok(None),
+            Some(_) => self.input.byte().map(Some),
+            None => match self.input.byte()? {
+                0xff => Ok(None),
+                initial => Ok(Some(initial)),
+            },
+        }
+    }
+
+    fn text(&mut self, initial: u8, argument: Option<u64>, start: usize) -> Result<String, Error> {
+        let bytes = self.string(initial, argument)?;
+        String::from_utf8(bytes).map_err(|_| error(start, "Invalid UTF-8 in a text string"))
+    }
+
+    // The content of a byte or text string. An indefinite-length string is a
+    // series of definite-length chunks of the same type.
+    fn string(&mut self, initial: u8, argument: Option<u64>) -> Result<Vec<u8>, Error> {
+        let Some(length) = argument else {
+            let mut bytes = vec![];
+            loop {
+                let chunk = self.input.byte()?;
+                if chunk == 0xff {
+                    return Ok(bytes);
+                }
+                if chunk >> 5 != initial >> 5 || chunk & 0x1f == 31 {
+                    return Err(error(self.input.offset() - 1, "Invalid chunk in an indefinite-length string"));
+                }
+                let length = self.argument(chunk, self.input.offset() - 1)?.unwrap_or_default();
+                bytes.extend(self.input.payload(length)?);
+            }
+        };
+        self.input.payload(length)
+    }
+
+    // Tag 4 holds [exponent, mantissa] for the value mantissa * 10^exponent.
+    fn decimal_fraction(&mut self, start: usize, depth: usize) -> Result<JsonValue, Error> {
+        let invalid = || error(start, "A decimal fraction must be an array of two integers");
+        if self.input.byte()? != 0x82 {
+            return Err(invalid());
+        }
+        let initial = self.input.byte()?;
+        let JsonValue::Number(JsonNumber::Integer(exponent)) = self.value(initial, depth + 1)? else {
+            return Err(invalid());
+        };
+        let initial = self.input.byte()?;
+        let mantissa = self.value(initial, depth + 1)?;
+        let text = match &mantissa {
+            JsonValue::Number(JsonNumber::Integer(integer)) => integer.to_string(),
+            JsonValue::Number(JsonNumber::Unsigned(unsigned)) => unsigned.to_string(),
+            JsonValue::Number(JsonNumber::Decimal(text)) if !text.contains('e') => text.clone(),
+            _ => return Err(invalid()),
+        };
+        if exponent == 0 {
+            return Ok(mantissa);
+        }
+        Ok(JsonValue::Number(JsonNumber::Decimal(format!("{}e{}", text, exponent))))
+    }
+
+    fn simple(&mut self, initial: u8, start: usize) -> Result<JsonValue, Error> {
+        match initial & 0x1f {
+            20 => Ok(JsonValue::False),
+            21 => Ok(JsonValue::True),
+            // JSON has no undefined.
+            22 | 23 => Ok(JsonValue::Null),
+            25 => Ok(JsonValue::Number(JsonNumber::Float(from_half(u16::from_be_bytes(self.input.exact()?))))),
+            26 => Ok(JsonValue::Number(JsonNumber::Float(f64::from(f32::from_be_bytes(self.input.exact()?))))),
+            27 => Ok(JsonValue::Number(JsonNumber::Float(f64::from_be_bytes(self.input.exact()?)))),
+            28..=30 => Err(error(start, "Reserved additional information")),
+            31 => Err(error(start, "Unexpected break")),
+            _ => Err(error(start, "Simple values are not supported")),
+        }
+    }
+}
+
+// Integers are stored the way `parse` stores them: `Integer` when they fit
+// an `i64`, then `Unsigned`, then decimal text.
+fn unsigned_number(unsigned: u64) -> JsonNumber {
+    i64::try_from(unsigned).map_or(JsonNumber::Unsigned(unsigned), JsonNumber::Integer)
+}
+
+fn negative_number(negative: u64) -> JsonNumber {
+    match i64::try_from(negative) {
+        Ok(negative) => JsonNumber::Integer(-1 - negative),
+        Err(_) => JsonNumber::Decimal(format!("-{}", u128::from(negative) + 1)),
+    }
+}
+
+fn bignum(negative: bool, bytes: &[u8]) -> JsonNumber {
+    let start = bytes.iter().position(|byte| *byte != 0).unwrap_or(bytes.len());
+    let magnitude = &bytes[start..];
+    if magnitude.len() <= 8 {
+        let mut bytes = [0; 8];
+        bytes[8 - magnitude.len()..].copy_from_slice(magnitude);
+        let argument = u64::from_be_bytes(bytes);
+        return if negative { negative_number(argument) } else { unsigned_number(argument) };
+    }
+    if negative {
+        let mut magnitude = magnitude.to_vec();
+        increment(&mut magnitude);
+        JsonNumber::Decimal(format!("-{}", bytes_to_digits(&magnitude)))
+    } else {
+        JsonNumber::Decimal(bytes_to_digits(magnitude))
+    }
+}
@@ -0,0 +1,446 @@
+use crate::data::{self, NumberMode, Span, Token};
+use crate::parse::{Builder, ParseOptions};
+use std::borrow::Cow;
+use std::fmt;
+use std::io::Error;
+
+// A concrete syntax tree that keeps every byte of its input. Each token holds
+// its original spelling together with the whitespace and comments in front of
+// it, so printing the tree gives back the input unchanged, and an edit only
+// rewrites the tokens it replaces.
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct CstToken {
+    // Whitespace and comments before the token.
+    pub leading: String,
+    pub text: String,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct CstDocument {
+    pub value: CstValue,
+    // Whitespace and comments after the value.
+    pub trailing: String,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum CstValue {
+    Object(CstObject),
+    Array(CstArray),
+    // A string, number, `true`, `false` or `null`, as it was spelled.
+    Scalar(CstToken),
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct CstObject {
+    pub open: CstToken,
+    pub members: Vec<CstMember>,
+    pub close: CstToken,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct CstMember {
+    pub key: CstToken,
+    pub colon: CstToken,
+    pub value: CstValue,
+    pub comma: Option<CstToken>,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct CstArray {
+    pub open: CstToken,
+    pub elements: Vec<CstElement>,
+    pub close: CstToken,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct CstElement {
+    pub value: CstValue,
+    pub comma: Option<CstToken>,
+}
+
+pub fn parse(string: &str) -> Result<CstDocument, Error> {
+    let tokens = crate::lex::lex_lossless(string.as_bytes())?;
+    // Trivia is picked up from the gaps between the other tokens.
+    let tokens = tokens.into_iter()
+        .filter(|(token, _)| !matches!(token, Token::Whitespace | Token::Comment))
+        .map(|(token, span)| (Ok(token), span));
+    let mut tree = Tree { input: string, end: 0 };
+    let value = crate::parse::parse_tokens(tokens, &mut tree, &ParseOptions::default())?;
+    Ok(CstDocument { value, trailing: String::from(&string[tree.end..]) })
+}
+
+struct Tree<'a> {
+    input: &'a str,
+    // The end of the last token, where the next one's trivia starts.
+    end: usize,
+}
+
+impl<'a> Builder<'a> for Tree<'_> {
+    type Value = CstValue;
+    type Key = CstToken;
+    type Member = CstMember;
+    type Element = CstElement;
+    type Token = CstToken;
+
+    fn token(&mut self, span: Span) -> CstToken {
+        let leading = String::from(self.input.get(self.end..span.start).unwrap_or_default());
+        self.end = span.end;
+        CstToken { leading, text: String::from(&self.input[span.start..span.end]) }
+    }
+
+    fn string(&mut self, _string: Cow<'a, str>, span: Span) -> CstValue {
+        CstValue::Scalar(self.token(span))
+    }
+
+    fn number(&mut self, _lexeme: &'a str, span: Span, _mode: NumberMode) -> Result<CstValue, Error> {
+        Ok(CstValue::Scalar(self.token(span)))
+    }
+
+    fn boolean(&mut self, _value: bool, span: Span) -> CstValue {
+        CstValue::Scalar(self.token(span))
+    }
+
+    fn null(&mut self, span: Span) -> CstValue {
+        CstValue::Scalar(self.token(span))
+    }
+
+    fn key(&mut self, _string: Cow<'a, str>, span: Span) -> CstToken {
+        self.token(span)
+    }
+
+    fn member(&mut self, key: CstToken, colon: CstToken, value: CstValue, comma: Option<CstToken>) -> CstMember {
+        CstMember {key, colon, value, comma}
+    }
+
+    fn element(&mut self, value: CstValue, comma: Option<CstToken>) -> CstElement {
+        CstElement {value, comma}
+    }
+
+    fn object(&mut self, open: CstToken, members: Vec<CstMember>, close: CstToken) -> CstValue {
+        CstValue::Object(CstObject {open, members, close})
+    }
+
+    fn array(&mut self, open: CstToken, elements: Vec<CstElement>, close: CstToken) -> CstValue {
+        CstValue::Array(CstArray {open, elements, close})
+    }
+}
+
+// Parses a fragment of plain JSON, such as a string key or a scalar.
+fn plain_value(text: &str) -> Result<data::JsonValue, Error> {
+    let mut tokens = crate::lex::lex_bytes(text.as_bytes())?;
+    Ok(crate::parse::parse(&mut tokens)?.element.value)
+}
+
+fn comma() -> CstToken {
+    CstToken { leading: String::new(), text: String::from(",") }
+}
+
+impl CstValue {
+    // Builds a value spelled the way `write` spells it, with no trivia.
+    pub fn new(value: &data::JsonValue) -> CstValue {
+        let text = crate::write::value_to_string(value);
+        // Written JSON always parses back.
+        parse(&text).map(|document| document.value).unwrap_or_else(|_| CstValue::Scalar(CstToken {
+            leading: String::new(),
+            text,
+        }))
+    }
+
+    pub fn first_token(&self) -> &CstToken {
+        match self {
+            CstValue::Object(object) => &object.open,
+            CstValue::Array(array) => &array.open,
+            CstValue::Scalar(token) => token,
+        }
+    }
+
+    pub fn first_token_mut(&mut self) -> &mut CstToken {
+        match self {
+            CstValue::Object(object) => &mut object.open,
+            CstValue::Array(array) => &mut array.open,
+            CstValue::Scalar(token) => token,
+        }
+    }
+
+    // Replaces the value but keeps the trivia in front of it.
+    pub fn replace(&mut self, value: &data::JsonValue) {
+        let leading = std::mem::take(&mut self.first_token_mut().leading);
+        *self = CstValue::new(value);
+        self.first_token_mut().leading = leading;
+    }
+
+    pub fn as_object(&self) -> Option<&CstObject> {
+        match self {
+            CstValue::Object(object) => Some(object),
+            _ => None,
+        }
+    }
+
+    pub fn as_object_mut(&mut self) -> Option<&mut CstObject> {
+        match self {
+            CstValue::Object(object) => Some(object),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&CstArray> {
+        match self {
+            CstValue::Array(array) => Some(array),
+            _ => None,
+        }
+    }
+
+    pub fn as_array_mut(&mut self) -> Option<&mut CstArray> {
+        match self {
+            CstValue::Array(array) => Some(array),
+            _ => None,
+        }
+    }
+
+    // The value the tree spells, without its trivia.
+    pub fn to_value(&self) -> Result<data::JsonValue, Error> {
+        match self {
+            CstValue::Object(object) => {
+                let mut members = vec![];
+                for member in &object.members {
+                    members.push(data::JsonMember {
+                        string: data::JsonString { string: member.name()? },
+                        element: data::JsonElement { value: member.value.to_value()? },
+                    });
+                }
+                Ok(data::JsonValue::Object(data::JsonObject {members}))
+            }
+            CstValue::Array(array) => {
+                let mut elements = vec![];
+                for element in &array.elements {
+                    elements.push(data::JsonElement { value: element.value.to_value()? });
+                }
+                Ok(data::JsonValue::Array(data::JsonArray {elements}))
+            }
+            CstValue::Scalar(token) => plain_value(&token.text),
+        }
+    }
+}
+
+impl CstMember {
+    // The key with its escapes decoded.
+    pub fn name(&self) -> Result<String, Error> {
+        match plain_value(&self.key.text)? {
+            data::JsonValue::String(string) => Ok(string.string),
+            _ => Err(Error::new(std::io::ErrorKind::InvalidData, "Member key is not a string")),
+        }
+    }
+}
+
+impl CstObject {
+    pub fn get(&self, key: &str) -> Option<&CstValue> {
+        self.position(key).map(|index| &self.members[index].value)
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut CstValue> {
+        self.position(key).map(|index| &mut self.members[index].value)
+    }
+
+    fn position(&self, key: &str) -> Option<usize> {
+        self.members.iter().position(|member| member.name().is_ok_and(|name| name == key))
+    }
+
+    // Replaces the value of an existing key, or adds the key at the end laid
+    // out like the member before it.
+    pub fn insert(&mut self, key: &str, value: &data::JsonValue) {
+        if let Some(existing) = self.get_mut(key) {
+            existing.replace(value);
+            return;
+        }
+
+        let mut member = CstMember {
+            key: CstToken {
+                leading: String::new(),
+                text: crate::write::value_to_string(&data::JsonValue::String(data::JsonString { string: String::from(key) })),
+            },
+            colon: CstToken { leading: String::new(), text: String::from(":") },
+            value: CstValue::new(value),
+            comma: None,
+        };
+        match self.members.last_mut() {
+            Some(last) => {
+                member.key.leading = last.key.leading.clone();
+                member.colon.leading = last.colon.leading.clone();
+                member.value.first_token_mut().leading = last.value.first_token().leading.clone();
+                last.comma = Some(comma());
+            }
+            None => member.value.first_token_mut().leading = String::from(" "),
+        }
+        self.members.push(member);
+    }
+
+    // Removes a key and returns whether it was there. The trivia in front of
+    // the key goes with it.
+    pub fn remove(&mut self, key: &str) -> bool {
+        let Some(index) = self.position(key) else {
+            return false;
+        };
+        let removed = self.members.remove(index);
+        if index == self.members.len() {
+            if let Some(last) = self.members.last_mut() {
+                last.comma = removed.comma;
+            }
+        }
+        true
+    }
+}
+
+impl CstArray {
+    pub fn get(&self, index: usize) -> Option<&CstValue> {
+        self.elements.get(index).map(|element| &element.value)
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut CstValue> {
+        self.elements.get_mut(index).map(|element| &mut element.value)
+    }
+
+    // Adds a value at the end laid out like the element before it.
+    pub fn push(&mut self, value: &data::JsonValue) {
+        let mut value = CstValue::new(value);
+        if let Some(last) = self.elements.last_mut() {
+            value.first_token_mut().leading = last.value.first_token().leading.clone();
+            last.comma = Some(comma());
+        }
+        self.elements.push(CstElement {value, comma: None});
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<CstValue> {
+        if index >= self.elements.len() {
+            return None;
+        }
+        let removed = self.elements.remove(index);
+        if index == self.elements.len() {
+            if let Some(last) = self.elements.last_mut() {
+                last.comma = removed.comma;
+            }
+        }
+        Some(removed.value)
+    }
+}
+
+impl CstDocument {
+    pub fn to_data(&self) -> Result<data::JsonData, Error> {
+        Ok(data::JsonData::from(self.value.to_value()?))
+    }
+
+    // Lays the document out one member or element per line, indented by
+    // `indent` per level. Only whitespace changes; tokens keep their spelling.
+    // Returns false, leaving the document alone, if it has comments, which
+    // would have nowhere sensible to go.
+    pub fn format(&mut self, indent: &str) -> bool {
+        let mut tokens = vec![];
+        self.value.collect_tokens(&mut tokens);
+        if tokens.iter().any(|token| token.leading.contains('/')) || self.trailing.contains('/') {
+            return false;
+        }
+
+        self.value.first_token_mut().leading = String::new();
+        self.value.format(indent, 0);
+        self.trailing = String::from("\n");
+        true
+    }
+}
+
+impl CstValue {
+    fn collect_tokens<'a>(&'a self, tokens: &mut Vec<&'a CstToken>) {
+        match self {
+            CstValue::Object(object) => {
+                tokens.push(&object.open);
+                for member in &object.members {
+                    tokens.push(&member.key);
+                    tokens.push(&member.colon);
+                    member.value.collect_tokens(tokens);
+                    tokens.extend(&member.comma);
+                }
+                tokens.push(&object.close);
+            }
+            CstValue::Array(array) => {
+                tokens.push(&array.open);
+                for element in &array.elements {
+                    element.value.collect_tokens(tokens);
+                    tokens.extend(&element.comma);
+                }
+                tokens.push(&array.close);
+            }
+            CstValue::Scalar(token) => tokens.push(token),
+        }
+    }
+
+    // Sets the whitespace inside the value; its own leading trivia belongs to
+    // the parent.
+    fn format(&mut self, indent: &str, depth: usize) {
+        let inner = format!("\n{}", indent.repeat(depth + 1));
+        let close = format!("\n{}", indent.repeat(depth));
+        match self {
+            CstValue::Object(object) => {
+                for member in &mut object.members {
+                    member.key.leading = inner.clone();
+                    member.colon.leading = String::new();
+                    member.value.first_token_mut().leading = String::from(" ");
+                    member.value.format(indent, depth + 1);
+                    if let Some(comma) = &mut member.comma {
+                        comma.leading = String::new();
+                    }
+                }
+                object.close.leading = if object.members.is_empty() { String::new() } else { close };
+            }
+            CstValue::Array(array) => {
+                for element in &mut array.elements {
+                    element.value.first_token_mut().leading = inner.clone();
+                    element.value.format(indent, depth + 1);
+                    if let Some(comma) = &mut element.comma {
+                        comma.leading = String::new();
+                    }
+                }
+                array.close.leading = if array.elements.is_empty() { String::new() } else { close };
+            }
+            CstValue::Scalar(_) => {}
+        }
+    }
+}
+
+impl fmt::Display for CstToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", self.leading, self.text)
+    }
+}
+
+impl fmt::Display for CstValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CstValue::Object(object) => {
+                write!(f, "{}", object.open)?;
+                for member in &object.members {
+                    write!(f, "{}{}{}", member.key, member.colon, member.value)?;
+                    if let Some(comma) = &member.comma {
+                        write!(f, "{}", comma)?;
+                    }
+                }
+                write!(f, "{}", object.close)
+            }
+            CstValue::Array(array) => {
+                write!(f, "{}", array.open)?;
+                for element in &array.elements {
+                    write!(f, "{}", element.value)?;
+                    if let Some(comma) = &element.comma {
+                        write!(f, "{}", comma)?;
+                    }
+                }
+                write!(f, "{}", array.close)
+            }
+            CstValue::Scalar(token) => write!(f, "{}", token),
+        }
+    }
+}
+
+impl fmt::Display for CstDocument {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", self.value, self.trailing)
+    }
+}
@@ -0,0 +1,224 @@
+use crate::data::*;
+use std::io::Error;
+
+// Writes RFC 4180 CSV from an array of flat objects, one row per object. The
+// header is every key in the order it is first seen, so rows may leave
+// columns out; missing values and nulls are written as empty fields. Lines
+// end with a line feed rather than the RFC's CRLF.
+
+pub fn to_csv(data: &JsonData) -> Result<String, Error> {
+    let JsonValue::Array(array) = &data.element.value else {
+        return Err(Error::new(std::io::ErrorKind::InvalidData, "CSV needs an array of objects"));
+    };
+
+    let mut rows = vec![];
+    for (index, element) in array.elements.iter().enumerate() {
+        match &element.value {
+            JsonValue::Object(object) => rows.push(object),
+            value => {
+                let message = format!("CSV row {} is {}, not an object", index, value.type_name());
+                return Err(Error::new(std::io::ErrorKind::InvalidData, message));
+            }
+        }
+    }
+
+    let mut header: Vec<&str> = vec![];
+    for row in &rows {
+        for member in &row.members {
+            if !header.contains(&member.string.string.as_str()) {
+                header.push(&member.string.string);
+            }
+        }
+    }
+
+    let mut out = String::new();
+    if header.is_empty() {
+        return Ok(out);
+    }
+    write_record(header.iter().map(|name| name.to_string()), &mut out);
+    for (index, row) in rows.iter().enumerate() {
+        let mut fields = vec![];
+        for name in &header {
+            let field = match row.get(name).map(|element| &element.value) {
+                None | Some(JsonValue::Null) => String::new(),
+                Some(JsonValue::String(string)) => string.string.clone(),
+                Some(value @ (JsonValue::Number(_) | JsonValue::True | JsonValue::False)) => crate::write::value_to_string(value),
+                Some(value) => {
+                    let message = format!("CSV cannot hold the {} in row {}, column {:?}", value.type_name(), index, name);
+                    return Err(Error::new(std::io::ErrorKind::InvalidData, message));
+                }
+            };
+            fields.push(field);
+        }
+        write_record(fields.into_iter(), &mut out);
+    }
+    Ok(out)
+}
+
+fn write_record(fields: impl Iterator<Item = String>, out: &mut String) {
+    let start = out.len();
+    for (index, field) in fields.enumerate() {
+        if index > 0 {
+            out.push(',');
+        }
+        write_field(&field, out);
+    }
+    // A single empty field would be a blank line, which `parse` skips at the
+    // end of the text.
+    if out.len() == start {
+        out.push_str("\"\"");
+    }
+    out.push('\n');
+}
+
+// Quotes fields that hold a separator, a quote or a line break.
+pub fn write_field(field: &str, out: &mut String) {
+    if field.contains([',', '"', '\n', '\r']) {
+        out.push('"');
+        out.push_str(&field.replace('"', "\"\""));
+        out.push('"');
+    } else {
+        out.push_str(field);
+    }
+}
+
+// How `parse` turns fields into values. Both are on by default, so what
+// `to_csv` writes reads back as it was, except that missing fields become
+// nulls and strings that look like numbers, booleans or nothing become those.
+// With both off every field stays a string.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct CsvOptions {
+    // Fields that are JSON numbers or `true` or `false` become numbers and
+    // booleans instead of strings.
+    pub infer_types: bool,
+    // Empty fields become null instead of empty strings.
+    pub empty_as_null: bool,
+}
+
+impl Default for CsvOptions {
+    fn default() -> CsvOptions {
+        CsvOptions { infer_types: true, empty_as_null: true }
+    }
+}
+
+pub fn parse(string: &str) -> Result<JsonData, Error> {
+    parse_with_options(string, &CsvOptions::default())
+}
+
+// Reads RFC 4180 CSV with a header line into an array of objects. Every row
+// must have as many fields as the header.
+pub fn parse_with_options(string: &str, options: &CsvOptions) -> Result<JsonData, Error> {
+    let mut records = records(string.strip_prefix('\u{feff}').unwrap_or(string))?.into_iter().peekable();
+    let Some((_, mut header)) = records.next() else {
+        return Ok(JsonData::from(JsonValue::Array(JsonArray { elements: vec![] })));
+    };
+    if header.is_empty() {
+        header.push(String::new());
+    }
+    for (index, name) in header.iter().enumerate() {
+        if header[..index].contains(name) {
+            return Err(Error::new(std::io::ErrorKind::InvalidData, format!("CSV header repeats the column {:?}", name)));
+        }
+    }
+
+    let mut elements = vec![];
+    while let Some((line, mut fields)) = records.next() {
+        // A blank line is skipped at the end of the text, where it only
+        // follows the last line break, and wherever it cannot be a row.
+        // Between rows of a single column it is one empty field.
+        if fields.is_empty() {
+            if records.peek().is_none() || header.len() > 1 {
+                continue;
+            }
+            fields.push(String::new());
+        }
+        if fields.len() != header.len() {
+            let message = format!("CSV line {}: {} fields where the header has {}", line, fields.len(), header.len());
+            return Err(Error::new(std::io::ErrorKind::InvalidData, message));
+        }
+        let members = header.iter().zip(fields).map(|(name, field)| JsonMember {
+            string: JsonString { string: name.clone() },
+            element: JsonElement { value: field_value(field, options) },
+        });
+        elements.push(JsonElement { value: JsonValue::Object(JsonObject { members: members.collect() }) });
+    }
+    Ok(JsonData::from(JsonValue::Array(JsonArray {elements})))
+}
+
+fn field_value(field: String, options: &CsvOptions) -> JsonValue {
+    if field.is_empty() && options.empty_as_null {
+        return JsonValue::Null;
+    }
+    if options.infer_types {
+        match field.as_str() {
+            "true" => return JsonValue::True,
+            "false" => return JsonValue::False,
+            _ => {}
+        }
+        // Only a field that is all number, with no spaces around it.
+        if let Ok(mut tokens) = crate::lex::lex_spanned(field.as_bytes()) {
+            if let (1, Some((Token::Number(lexeme, _), span))) = (tokens.len(), tokens.pop_front()) {
+                if span.start == 0 && span.end == field.len() {
+                    if let Ok(number) = crate::parse::number_from_lexeme(lexeme, span, NumberMode::Exact) {
+                        return JsonValue::Number(number);
+                    }
+                }
+            }
+        }
+    }
+    JsonValue::String(JsonString { string: field })
+}
+
+// Splits the text into records of fields, each with the line it starts on. A
+// blank line is a record with no fields.
+fn records(string: &str) -> Result<Vec<(usize, Vec<String>)>, Error> {
+    let mut records = vec![];
+    let mut chars = string.chars().peekable();
+    let mut line = 1;
+    while chars.peek().is_some() {
+        let start = line;
+        let mut fields = vec![];
+        let mut field = String::new();
+        let mut quoted = false;
+        loop {
+            match chars.next() {
+                Some('"') if field.is_empty() => loop {
+                    quoted = true;
+                    match chars.next() {
+                        Some('"') if chars.peek() == Some(&'"') => {
+                            chars.next();
+                            field.push('"');
+                        }
+                        Some('"') => {
+                            if !matches!(chars.peek(), None | Some(',' | '\n' | '\r')) {
+                                let message = format!("CSV line {}: Unexpected text after a quoted field", line);
+                                return Err(Error::new(std::io::ErrorKind::InvalidData, message));
+                            }
+                            break;
+                        }
+                        Some(char) => {
+                            line += usize::from(char == '\n');
+                            field.push(char);
+                        }
+                        None => {
+                            let message = format!("CSV line {}: Unterminated quoted field", start);
+                            return Err(Error::new(std::io::ErrorKind::InvalidData, message));
+                        }
+                    }
+                },
+                Some(',') => fields.push(std::mem::take(&mut field)),
+                Some('\r') if chars.peek() == Some(&'\n') => {}
+                Some('\n') | None => {
+                    line += 1;
+                    break;
+                }
+                Some(char) => field.push(char),
+            }
+        }
+        if !fields.is_empty() || !field.is_empty() || quoted {
+            fields.push(field);
+        }
+        records.push((start, fields));
+    }
+    Ok(records)
+}
@@ -1,63 +1,86 @@
+use std::borrow::Cow;
+use std::cmp::Ordering;
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
 #[allow(dead_code)]
 #[derive(PartialEq, Eq, Debug)]
-pub enum Token {
+pub enum Token<'a> {
     OpenParen,
     CloseParen,
     OpenBracket,
     CloseBracket,
     Comma,
     Colon,
-    SignPos,
-    SignNeg,
-    Digit(u8),
-    FractionMarker,
-    ExponentMarker,
-    String(String),
+    Number(&'a str, Span),
+    String(Cow<'a, str>),
     True,
     False,
     Null,
     Whitespace,
+    Comment,
 }
 
-#[derive(PartialEq, Eq, Debug)]
+#[derive(PartialEq, Eq, Debug, Clone)]
 pub struct JsonData {
     pub element: JsonElement,
 }
 
-#[derive(PartialEq, Eq, Debug)]
+#[derive(PartialEq, Eq, Debug, Clone)]
 pub struct JsonElement {
     pub value: JsonValue,
 }
 
-#[derive(PartialEq, Eq, Debug)]
+#[derive(PartialEq, Eq, Debug, Clone)]
 pub struct JsonObject {
     pub members: Vec<JsonMember>,
 }
 
-#[derive(PartialEq, Eq, Debug)]
+#[derive(PartialEq, Eq, Debug, Clone)]
 pub struct JsonMember {
     pub string: JsonString,
     pub element: JsonElement,
 }
 
-#[derive(PartialEq, Eq, Debug)]
+#[derive(PartialEq, Eq, Debug, Clone)]
 pub struct JsonArray {
     pub elements: Vec<JsonElement>,
 }
 
-#[derive(PartialEq, Eq, Debug)]
-pub struct JsonNumber {
-    pub integer: i64,
-    pub fraction: Option<u64>,
-    pub exponent: Option<i64>,
+// How a number is stored depends on the `NumberMode` it was parsed with.
+// Integers that fit are kept exactly; `Decimal` keeps the source text for
+// callers that need every digit.
+#[derive(Debug, Clone)]
+pub enum JsonNumber {
+    Integer(i64),
+    Unsigned(u64),
+    Float(f64),
+    Decimal(String),
 }
 
-#[derive(PartialEq, Eq, Debug)]
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub enum NumberMode {
+    // Every number becomes an `f64`. A number beyond its range is an error.
+    Float,
+    // Integers become `i64` or `u64` when they fit, anything else an `f64`.
+    // A number beyond the range of `f64` keeps its text as a `Decimal`
+    // instead of being rounded to infinity.
+    #[default]
+    Exact,
+    // Like `Exact`, but numbers that do not fit an integer keep their text.
+    Arbitrary,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
 pub struct JsonString {
     pub string: String,
 }
 
-#[derive(PartialEq, Eq, Debug)]
+#[derive(PartialEq, Eq, Debug, Clone)]
 pub enum JsonValue {
     Object(JsonObject),
     Array(JsonArray),
@@ -66,4 +89,173 @@ pub enum JsonValue {
     True,
     False,
     Null
-}
\ No newline at end of file
+}
+
+impl From<JsonValue> for JsonData {
+    fn from(value: JsonValue) -> JsonData {
+        JsonData {
+            element: JsonElement { value },
+        }
+    }
+}
+
+impl JsonObject {
+    pub fn get(&self, key: &str) -> Option<&JsonElement> {
+        self.members.iter().find(|member| member.string.string == key).map(|member| &member.element)
+    }
+}
+
+impl JsonNumber {
+    pub fn as_f64(&self) -> f64 {
+        match self {
+            JsonNumber::Integer(integer) => *integer as f64,
+            JsonNumber::Unsigned(unsigned) => *unsigned as f64,
+            JsonNumber::Float(float) => *float,
+            JsonNumber::Decimal(text) => text.parse().unwrap_or(f64::NAN),
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            JsonNumber::Integer(integer) => Some(*integer),
+            JsonNumber::Unsigned(unsigned) => i64::try_from(*unsigned).ok(),
+            JsonNumber::Float(float) => float_to_integer(*float),
+            JsonNumber::Decimal(text) => text.parse().ok().or_else(|| float_to_integer(self.as_f64())),
+        }
+    }
+
+    // `None` for infinities, NaN and decimal text outside JSON's grammar.
+    pub fn decimal_parts(&self) -> Option<DecimalParts> {
+        match self {
+            JsonNumber::Integer(integer) => DecimalParts::parse(&integer.to_string()),
+            JsonNumber::Unsigned(unsigned) => DecimalParts::parse(&unsigned.to_string()),
+            // `{:e}` gives the shortest round-trip digits.
+            JsonNumber::Float(float) if float.is_finite() => DecimalParts::parse(&format!("{:e}", float)),
+            JsonNumber::Float(_) => None,
+            JsonNumber::Decimal(text) => DecimalParts::parse(text),
+        }
+    }
+
+    // Compares by exact value, and by doubles for infinities and NaN.
+    pub fn compare(&self, other: &JsonNumber) -> Option<Ordering> {
+        match (self.decimal_parts(), other.decimal_parts()) {
+            (Some(left), Some(right)) => Some(left.cmp(&right)),
+            _ => self.as_f64().partial_cmp(&other.as_f64()),
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            JsonNumber::Integer(integer) => u64::try_from(*integer).ok(),
+            JsonNumber::Unsigned(unsigned) => Some(*unsigned),
+            JsonNumber::Float(float) => float_to_integer(*float),
+            JsonNumber::Decimal(text) => text.parse().ok().or_else(|| float_to_integer(self.as_f64())),
+        }
+    }
+}
+
+// A finite number as 0.digits × 10^point, with no leading or trailing zeros
+// in `digits`, which is empty for zero. Numbers of the same value have the
+// same parts whatever their variant or spelling, and they order by value. A
+// float stands for the shortest decimal that reads back as it.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct DecimalParts {
+    pub negative: bool,
+    pub digits: String,
+    pub point: i64,
+}
+
+impl DecimalParts {
+    // Reads a number in JSON's grammar, or `None` for other text.
+    pub fn parse(text: &str) -> Option<DecimalParts> {
+        let (negative, text) = match text.strip_prefix('-') {
+            Some(text) => (true, text),
+            None => (false, text),
+        };
+        let (mantissa, exponent) = text.split_once(['e', 'E']).unwrap_or((text, "0"));
+        let exponent = exponent.parse::<i64>().ok()?;
+        let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+        if whole.is_empty() || !whole.bytes().chain(fraction.bytes()).all(|digit| digit.is_ascii_digit()) {
+            return None;
+        }
+
+        let digits = format!("{}{}", whole, fraction);
+        let significant = digits.trim_start_matches('0');
+        let point = exponent.checked_add(whole.len() as i64)?.checked_sub((digits.len() - significant.len()) as i64)?;
+        let significant = significant.trim_end_matches('0');
+        if significant.is_empty() {
+            return Some(DecimalParts { negative: false, digits: String::new(), point: 0 });
+        }
+        Some(DecimalParts { negative, digits: String::from(significant), point })
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.digits.len() as i64 <= self.point
+    }
+
+    fn sign(&self) -> i8 {
+        match (self.digits.is_empty(), self.negative) {
+            (true, _) => 0,
+            (false, true) => -1,
+            (false, false) => 1,
+        }
+    }
+}
+
+impl Ord for DecimalParts {
+    fn cmp(&self, other: &DecimalParts) -> Ordering {
+        // With no leading zeros a larger point is a larger magnitude, and
+        // with no trailing ones the digits then compare as text.
+        let magnitude = self.point.cmp(&other.point).then_with(|| self.digits.cmp(&other.digits));
+        match (self.sign(), other.sign()) {
+            (0, 0) => Ordering::Equal,
+            (1, 1) => magnitude,
+            (-1, -1) => magnitude.reverse(),
+            (left, right) => left.cmp(&right),
+        }
+    }
+}
+
+impl PartialOrd for DecimalParts {
+    fn partial_cmp(&self, other: &DecimalParts) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Only converts floats that hold an integer the target type can represent.
+fn float_to_integer<T: TryFrom<i128>>(float: f64) -> Option<T> {
+    if float.fract() == 0.0 && float.abs() < 1e38 {
+        T::try_from(float as i128).ok()
+    } else {
+        None
+    }
+}
+
+// Floats compare by bit pattern so the tree can be `Eq` and so -0.0 stays
+// distinct from 0.0.
+impl PartialEq for JsonNumber {
+    fn eq(&self, other: &JsonNumber) -> bool {
+        match (self, other) {
+            (JsonNumber::Integer(left), JsonNumber::Integer(right)) => left == right,
+            (JsonNumber::Unsigned(left), JsonNumber::Unsigned(right)) => left == right,
+            (JsonNumber::Float(left), JsonNumber::Float(right)) => left.to_bits() == right.to_bits(),
+            (JsonNumber::Decimal(left), JsonNumber::Decimal(right)) => left == right,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for JsonNumber {}
+
+impl JsonValue {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            JsonValue::Object(_) => "object",
+            JsonValue::Array(_) => "array",
+            JsonValue::String(_) => "string",
+            JsonValue::Number(_) => "number",
+            JsonValue::True | JsonValue::False => "boolean",
+            JsonValue::Null => "null",
+        }
+    }
+}
@@ -0,0 +1,203 @@
+use crate::data::*;
+use crate::write::{value_to_string, write_string, write_value};
+use std::cmp::Ordering;
+
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct DiffOptions {
+    pub ignore_array_order: bool,
+    pub ignore_keys: Vec<String>,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum Change {
+    Added(JsonValue),
+    Removed(JsonValue),
+    Changed(JsonValue, JsonValue),
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Difference {
+    pub path: String,
+    pub change: Change,
+}
+
+pub fn diff(left: &JsonData, right: &JsonData, options: &DiffOptions) -> Vec<Difference> {
+    let mut result = vec![];
+    diff_value(&left.element.value, &right.element.value, "", options, &mut result);
+    result
+}
+
+fn diff_value(left: &JsonValue, right: &JsonValue, path: &str, options: &DiffOptions, result: &mut Vec<Difference>) {
+    match (left, right) {
+        (JsonValue::Object(left), JsonValue::Object(right)) => diff_object(left, right, path, options, result),
+        (JsonValue::Array(left), JsonValue::Array(right)) => {
+            if options.ignore_array_order {
+                diff_array_unordered(left, right, path, options, result);
+            } else {
+                diff_array(left, right, path, options, result);
+            }
+        }
+        // Numbers are the same value whatever their spelling, so 1, 1.0 and
+        // 1e0 do not differ, and neither do 0 and -0.
+        (JsonValue::Number(left_number), JsonValue::Number(right_number)) => {
+            if left_number.compare(right_number) != Some(Ordering::Equal) && left_number != right_number {
+                result.push(Difference {
+                    path: String::from(path),
+                    change: Change::Changed(left.clone(), right.clone()),
+                });
+            }
+        }
+        _ => {
+            if left != right {
+                result.push(Difference {
+                    path: String::from(path),
+                    change: Change::Changed(left.clone(), right.clone()),
+                });
+            }
+        }
+    }
+}
+
+fn diff_object(left: &JsonObject, right: &JsonObject, path: &str, options: &DiffOptions, result: &mut Vec<Difference>) {
+    for member in &left.members {
+        let key = &member.string.string;
+        if options.ignore_keys.contains(key) {
+            continue;
+        }
+        let member_path = pointer_push(path, key);
+        match right.get(key) {
+            Some(element) => diff_value(&member.element.value, &element.value, &member_path, options, result),
+            None => result.push(Difference {
+                path: member_path,
+                change: Change::Removed(member.element.value.clone()),
+            }),
+        }
+    }
+
+    for member in &right.members {
+        let key = &member.string.string;
+        if options.ignore_keys.contains(key) || left.get(key).is_some() {
+            continue;
+        }
+        result.push(Difference {
+            path: pointer_push(path, key),
+            change: Change::Added(member.element.value.clone()),
+        });
+    }
+}
+
+fn diff_array(left: &JsonArray, right: &JsonArray, path: &str, options: &DiffOptions, result: &mut Vec<Difference>) {
+    for (index, element) in left.elements.iter().enumerate() {
+        let element_path = pointer_push(path, &index.to_string());
+        match right.elements.get(index) {
+            Some(other) => diff_value(&element.value, &other.value, &element_path, options, result),
+            None => result.push(Difference {
+                path: element_path,
+                change: Change::Removed(element.value.clone()),
+            }),
+        }
+    }
+
+    for (index, element) in right.elements.iter().enumerate().skip(left.elements.len()) {
+        result.push(Difference {
+            path: pointer_push(path, &index.to_string()),
+            change: Change::Added(element.value.clone()),
+        });
+    }
+}
+
+fn diff_array_unordered(left: &JsonArray, right: &JsonArray, path: &str, options: &DiffOptions, result: &mut Vec<Difference>) {
+    let mut matched = vec![false; right.elements.len()];
+
+    for (index, element) in left.elements.iter().enumerate() {
+        let found = right.elements.iter().enumerate().position(|(other_index, other)| {
+            !matched[other_index] && values_equal(&element.value, &other.value, options)
+        });
+        match found {
+            Some(other_index) => matched[other_index] = true,
+            None => result.push(Difference {
+                path: pointer_push(path, &index.to_string()),
+                change: Change::Removed(element.value.clone()),
+            }),
+        }
+    }
+
+    for (index, element) in right.elements.iter().enumerate() {
+        if !matched[index] {
+            result.push(Difference {
+                path: pointer_push(path, &index.to_string()),
+                change: Change::Added(element.value.clone()),
+            });
+        }
+    }
+}
+
+fn values_equal(left: &JsonValue, right: &JsonValue, options: &DiffOptions) -> bool {
+    let mut differences = vec![];
+    diff_value(left, right, "", options, &mut differences);
+    differences.is_empty()
+}
+
+fn pointer_push(path: &str, token: &str) -> String {
+    format!("{}/{}", path, token.replace('~', "~0").replace('/', "~1"))
+}
+
+pub fn format_human(differences: &[Difference], color: bool) -> String {
+    let (red, green, yellow, reset) = if color {
+        ("\x1b[31m", "\x1b[32m", "\x1b[33m", "\x1b[0m")
+    } else {
+        ("", "", "", "")
+    };
+
+    let mut result = String::new();
+    for difference in differences {
+        let path = if difference.path.is_empty() { "(root)" } else { difference.path.as_str() };
+        match &difference.change {
+            Change::Added(value) => {
+                result.push_str(&format!("{}+ {}: {}{}\n", green, path, value_to_string(value), reset));
+            }
+            Change::Removed(value) => {
+                result.push_str(&format!("{}- {}: {}{}\n", red, path, value_to_string(value), reset));
+            }
+            Change::Changed(old, new) => {
+                result.push_str(&format!("{}~ {}: {} -> {}{}\n", yellow, path, value_to_string(old), value_to_string(new), reset));
+            }
+        }
+    }
+    result
+}
+
+pub fn format_json(differences: &[Difference]) -> String {
+    let mut result = String::from("[");
+    for (index, difference) in differences.iter().enumerate() {
+        if index > 0 {
+            result.push(',');
+        }
+        result.push_str("{\"op\":");
+        match &difference.change {
+            Change::Added(value) => {
+                result.push_str("\"add\",\"path\":");
+                write_string(&difference.path, &mut result);
+                result.push_str(",\"value\":");
+                write_value(value, &mut result);
+            }
+            Change::Removed(value) => {
+                result.push_str("\"remove\",\"path\":");
+                write_string(&difference.path, &mut result);
+                result.push_str(",\"value\":");
+                write_value(value, &mut result);
+            }
+            Change::Changed(old, new) => {
+                result.push_str("\"change\",\"path\":");
+                write_string(&difference.path, &mut result);
+                result.push_str(",\"old\":");
+                write_value(old, &mut result);
+                result.push_str(",\"new\":");
+                write_value(new, &mut result);
+            }
+        }
+        result.push('}');
+    }
+    result.push(']');
+    result
+}
@@ -0,0 +1,86 @@
+use crate::canonical::{content_string, KeyOrder};
+use crate::data::*;
+use std::hash::{Hash, Hasher};
+
+pub mod sha256;
+
+use sha256::sha256;
+
+// Content hashing of values. Both the digest and `ContentKey` work on the
+// canonical form from `canonical`, so whitespace, escapes and number spelling
+// never matter: `1.0` and `1e0` are the same content, as are `"\u0041"`
+// and `"A"`. Numbers compare exactly, so integers past 2^53 stay apart.
+// Whether member order matters is up to the caller.
+
+impl JsonValue {
+    // SHA-256 of the canonical form, with member order ignored.
+    pub fn digest(&self) -> [u8; 32] {
+        self.digest_with(KeyOrder::Ignored)
+    }
+
+    pub fn digest_with(&self, order: KeyOrder) -> [u8; 32] {
+        sha256(content_string(self, order).as_bytes())
+    }
+}
+
+impl JsonData {
+    pub fn digest(&self) -> [u8; 32] {
+        self.element.value.digest()
+    }
+
+    pub fn digest_with(&self, order: KeyOrder) -> [u8; 32] {
+        self.element.value.digest_with(order)
+    }
+}
+
+pub fn to_hex(digest: &[u8]) -> String {
+    digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// A value that compares and hashes by content, for use as a map or set key.
+// Keys with different orders are never equal.
+#[derive(Debug, Clone)]
+pub struct ContentKey {
+    value: JsonValue,
+    order: KeyOrder,
+    // The canonical form, computed once since every comparison needs it.
+    content: String,
+}
+
+impl ContentKey {
+    pub fn new(value: JsonValue, order: KeyOrder) -> ContentKey {
+        let content = content_string(&value, order);
+        ContentKey { value, order, content }
+    }
+
+    pub fn value(&self) -> &JsonValue {
+        &self.value
+    }
+
+    pub fn order(&self) -> KeyOrder {
+        self.order
+    }
+
+    pub fn into_value(self) -> JsonValue {
+        self.value
+    }
+
+    pub fn digest(&self) -> [u8; 32] {
+        sha256(self.content.as_bytes())
+    }
+}
+
+impl PartialEq for ContentKey {
+    fn eq(&self, other: &ContentKey) -> bool {
+        self.order == other.order && self.content == other.content
+    }
+}
+
+impl Eq for ContentKey {}
+
+impl Hash for ContentKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.order.hash(state);
+        self.content.hash(state);
+    }
+}
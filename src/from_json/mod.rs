@@ -0,0 +1,235 @@
+use crate::data::*;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io::Error;
+
+pub use cc_json_parser_derive::FromJson;
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct FromJsonError {
+    pub path: String,
+    pub message: String,
+}
+
+impl FromJsonError {
+    pub fn new(message: String) -> FromJsonError {
+        FromJsonError { path: String::new(), message }
+    }
+
+    pub fn within(mut self, token: &str) -> FromJsonError {
+        self.path = format!("/{}{}", token.replace('~', "~0").replace('/', "~1"), self.path);
+        self
+    }
+}
+
+impl fmt::Display for FromJsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = if self.path.is_empty() { "(root)" } else { self.path.as_str() };
+        write!(f, "{}: {}", path, self.message)
+    }
+}
+
+impl From<FromJsonError> for Error {
+    fn from(err: FromJsonError) -> Error {
+        Error::new(std::io::ErrorKind::InvalidData, err.to_string())
+    }
+}
+
+pub trait FromJson: Sized {
+    fn from_json(value: &JsonValue) -> Result<Self, FromJsonError>;
+
+    // The value to use when an object member is absent, if absence is allowed.
+    fn from_missing() -> Option<Self> {
+        None
+    }
+}
+
+pub fn from_json<T: FromJson>(data: &JsonData) -> Result<T, FromJsonError> {
+    T::from_json(&data.element.value)
+}
+
+pub fn expect_object(value: &JsonValue) -> Result<&JsonObject, FromJsonError> {
+    match value {
+        JsonValue::Object(object) => Ok(object),
+        _ => Err(unexpected("object", value)),
+    }
+}
+
+pub fn field<T: FromJson>(object: &JsonObject, key: &str) -> Result<T, FromJsonError> {
+    match object.get(key) {
+        Some(element) => T::from_json(&element.value).map_err(|err| err.within(key)),
+        None => T::from_missing().ok_or_else(|| FromJsonError::new(String::from("Missing field")).within(key)),
+    }
+}
+
+pub fn field_or_default<T: FromJson + Default>(object: &JsonObject, key: &str) -> Result<T, FromJsonError> {
+    match object.get(key) {
+        Some(element) => T::from_json(&element.value).map_err(|err| err.within(key)),
+        None => Ok(T::default()),
+    }
+}
+
+pub fn unexpected(expected: &str, value: &JsonValue) -> FromJsonError {
+    FromJsonError::new(format!("Expected {} but found {}", expected, value.type_name()))
+}
+
+impl FromJson for JsonValue {
+    fn from_json(value: &JsonValue) -> Result<Self, FromJsonError> {
+        Ok(value.clone())
+    }
+}
+
+impl FromJson for bool {
+    fn from_json(value: &JsonValue) -> Result<Self, FromJsonError> {
+        match value {
+            JsonValue::True => Ok(true),
+            JsonValue::False => Ok(false),
+            _ => Err(unexpected("boolean", value)),
+        }
+    }
+}
+
+impl FromJson for String {
+    fn from_json(value: &JsonValue) -> Result<Self, FromJsonError> {
+        match value {
+            JsonValue::String(string) => Ok(string.string.clone()),
+            _ => Err(unexpected("string", value)),
+        }
+    }
+}
+
+impl FromJson for char {
+    fn from_json(value: &JsonValue) -> Result<Self, FromJsonError> {
+        let string = String::from_json(value)?;
+        let mut chars = string.chars();
+        match (chars.next(), chars.next()) {
+            (Some(char), None) => Ok(char),
+            _ => Err(FromJsonError::new(format!("Expected a single character but found {:?}", string))),
+        }
+    }
+}
+
+fn integer_from_json(value: &JsonValue) -> Result<i128, FromJsonError> {
+    let JsonValue::Number(number) = value else {
+        return Err(unexpected("integer", value));
+    };
+    if let Some(integer) = number.as_i64() {
+        return Ok(i128::from(integer));
+    }
+    if let Some(unsigned) = number.as_u64() {
+        return Ok(i128::from(unsigned));
+    }
+    let float = number.as_f64();
+    if float.fract() == 0.0 && float.abs() < 1e38 {
+        Ok(float as i128)
+    } else {
+        Err(FromJsonError::new(format!("Expected integer but found {}", float)))
+    }
+}
+
+macro_rules! impl_from_json_integer {
+    ($($type:ty),*) => {
+        $(
+            impl FromJson for $type {
+                fn from_json(value: &JsonValue) -> Result<Self, FromJsonError> {
+                    let integer = integer_from_json(value)?;
+                    <$type>::try_from(integer).map_err(|_| {
+                        FromJsonError::new(format!("{} is out of range for {}", integer, stringify!($type)))
+                    })
+                }
+            }
+        )*
+    };
+}
+
+impl_from_json_integer!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+impl FromJson for f64 {
+    fn from_json(value: &JsonValue) -> Result<Self, FromJsonError> {
+        match value {
+            JsonValue::Number(number) => Ok(number.as_f64()),
+            _ => Err(unexpected("number", value)),
+        }
+    }
+}
+
+impl FromJson for f32 {
+    fn from_json(value: &JsonValue) -> Result<Self, FromJsonError> {
+        f64::from_json(value).map(|float| float as f32)
+    }
+}
+
+impl<T: FromJson> FromJson for Option<T> {
+    fn from_json(value: &JsonValue) -> Result<Self, FromJsonError> {
+        match value {
+            JsonValue::Null => Ok(None),
+            _ => T::from_json(value).map(Some),
+        }
+    }
+
+    fn from_missing() -> Option<Self> {
+        Some(None)
+    }
+}
+
+impl<T: FromJson> FromJson for Box<T> {
+    fn from_json(value: &JsonValue) -> Result<Self, FromJsonError> {
+        T::from_json(value).map(Box::new)
+    }
+}
+
+impl<T: FromJson> FromJson for Vec<T> {
+    fn from_json(value: &JsonValue) -> Result<Self, FromJsonError> {
+        let JsonValue::Array(array) = value else {
+            return Err(unexpected("array", value));
+        };
+        array.elements.iter().enumerate().map(|(index, element)| {
+            T::from_json(&element.value).map_err(|err| err.within(&index.to_string()))
+        }).collect()
+    }
+}
+
+impl<T: FromJson> FromJson for HashMap<String, T> {
+    fn from_json(value: &JsonValue) -> Result<Self, FromJsonError> {
+        expect_object(value)?.members.iter().map(|member| {
+            let key = &member.string.string;
+            T::from_json(&member.element.value).map(|value| (key.clone(), value)).map_err(|err| err.within(key))
+        }).collect()
+    }
+}
+
+impl<T: FromJson> FromJson for BTreeMap<String, T> {
+    fn from_json(value: &JsonValue) -> Result<Self, FromJsonError> {
+        expect_object(value)?.members.iter().map(|member| {
+            let key = &member.string.string;
+            T::from_json(&member.element.value).map(|value| (key.clone(), value)).map_err(|err| err.within(key))
+        }).collect()
+    }
+}
+
+macro_rules! impl_from_json_tuple {
+    ($length:literal => $($name:ident $index:tt),*) => {
+        impl<$($name: FromJson),*> FromJson for ($($name,)*) {
+            fn from_json(value: &JsonValue) -> Result<Self, FromJsonError> {
+                let JsonValue::Array(array) = value else {
+                    return Err(unexpected("array", value));
+                };
+                if array.elements.len() != $length {
+                    return Err(FromJsonError::new(format!(
+                        "Expected {} elements but found {}", $length, array.elements.len()
+                    )));
+                }
+                Ok(($(
+                    $name::from_json(&array.elements[$index].value).map_err(|err| err.within(stringify!($index)))?,
+                )*))
+            }
+        }
+    };
+}
+
+impl_from_json_tuple!(1 => A 0);
+impl_from_json_tuple!(2 => A 0, B 1);
+impl_from_json_tuple!(3 => A 0, B 1, C 2);
+impl_from_json_tuple!(4 => A 0, B 1, C 2, D 3);
+impl_from_json_tuple!(5 => A 0, B 1, C 2, D 3, E 4);
+impl_from_json_tuple!(6 => A 0, B 1, C 2, D 3, E 4, F 5);
@@ -0,0 +1,117 @@
+use crate::data::*;
+
+// Builds a `JsonValue` from JSON-like syntax:
+//
+//     json!({"name": name, "ports": [80, 443], "tls": null})
+//
+// Literals are read the same way the parser reads document text, so the result
+// compares equal to a parsed document. Any other expression, inside or as the
+// whole input, is converted with `ToJson`; wrap it in parentheses if it starts
+// with a literal, as in `(1 + 2)`.
+#[macro_export]
+macro_rules! json {
+    (null) => {
+        $crate::data::JsonValue::Null
+    };
+    ([]) => {
+        $crate::data::JsonValue::Array($crate::data::JsonArray { elements: vec![] })
+    };
+    ([ $($tt:tt)+ ]) => {
+        $crate::data::JsonValue::Array($crate::data::JsonArray { elements: $crate::json!(@array [] $($tt)+) })
+    };
+    ({}) => {
+        $crate::data::JsonValue::Object($crate::data::JsonObject { members: vec![] })
+    };
+    ({ $($tt:tt)+ }) => {
+        $crate::data::JsonValue::Object($crate::data::JsonObject { members: $crate::json!(@object [] $($tt)+) })
+    };
+    (- $literal:literal) => {
+        $crate::json_macro::json_literal!($crate - $literal)
+    };
+    ($literal:literal) => {
+        $crate::json_macro::json_literal!($crate $literal)
+    };
+
+    (@array [$($elements:expr,)*]) => {
+        vec![$($elements),*]
+    };
+    (@array [$($elements:expr,)*] null $(, $($rest:tt)*)?) => {
+        $crate::json!(@array [$($elements,)* $crate::json!(@element null),] $($($rest)*)?)
+    };
+    (@array [$($elements:expr,)*] [$($array:tt)*] $(, $($rest:tt)*)?) => {
+        $crate::json!(@array [$($elements,)* $crate::json!(@element [$($array)*]),] $($($rest)*)?)
+    };
+    (@array [$($elements:expr,)*] {$($object:tt)*} $(, $($rest:tt)*)?) => {
+        $crate::json!(@array [$($elements,)* $crate::json!(@element {$($object)*}),] $($($rest)*)?)
+    };
+    (@array [$($elements:expr,)*] - $literal:literal $(, $($rest:tt)*)?) => {
+        $crate::json!(@array [$($elements,)* $crate::json!(@element - $literal),] $($($rest)*)?)
+    };
+    (@array [$($elements:expr,)*] $literal:literal $(, $($rest:tt)*)?) => {
+        $crate::json!(@array [$($elements,)* $crate::json!(@element $literal),] $($($rest)*)?)
+    };
+    (@array [$($elements:expr,)*] $value:expr $(, $($rest:tt)*)?) => {
+        $crate::json!(@array [$($elements,)* $crate::json!(@element ($value)),] $($($rest)*)?)
+    };
+
+    (@object [$($members:expr,)*]) => {
+        vec![$($members),*]
+    };
+    (@object [$($members:expr,)*] $key:tt : null $(, $($rest:tt)*)?) => {
+        $crate::json!(@object [$($members,)* $crate::json!(@member $key, null),] $($($rest)*)?)
+    };
+    (@object [$($members:expr,)*] $key:tt : [$($array:tt)*] $(, $($rest:tt)*)?) => {
+        $crate::json!(@object [$($members,)* $crate::json!(@member $key, [$($array)*]),] $($($rest)*)?)
+    };
+    (@object [$($members:expr,)*] $key:tt : {$($object:tt)*} $(, $($rest:tt)*)?) => {
+        $crate::json!(@object [$($members,)* $crate::json!(@member $key, {$($object)*}),] $($($rest)*)?)
+    };
+    (@object [$($members:expr,)*] $key:tt : - $literal:literal $(, $($rest:tt)*)?) => {
+        $crate::json!(@object [$($members,)* $crate::json!(@member $key, - $literal),] $($($rest)*)?)
+    };
+    (@object [$($members:expr,)*] $key:tt : $literal:literal $(, $($rest:tt)*)?) => {
+        $crate::json!(@object [$($members,)* $crate::json!(@member $key, $literal),] $($($rest)*)?)
+    };
+    (@object [$($members:expr,)*] $key:tt : $value:expr $(, $($rest:tt)*)?) => {
+        $crate::json!(@object [$($members,)* $crate::json!(@member $key, ($value)),] $($($rest)*)?)
+    };
+
+    (@element ($value:expr)) => {
+        $crate::data::JsonElement { value: $crate::to_json::ToJson::to_json(&$value) }
+    };
+    (@element $($value:tt)+) => {
+        $crate::data::JsonElement { value: $crate::json!($($value)+) }
+    };
+    (@member ($key:expr), $($value:tt)+) => {
+        $crate::data::JsonMember {
+            string: $crate::data::JsonString { string: String::from($key) },
+            element: $crate::json!(@element $($value)+),
+        }
+    };
+    (@member $key:literal, $($value:tt)+) => {
+        $crate::data::JsonMember {
+            string: $crate::data::JsonString { string: $crate::json_macro::json_key!($key) },
+            element: $crate::json!(@element $($value)+),
+        }
+    };
+
+    // Last, so the `@` rules above are never read as an expression.
+    ($value:expr) => {
+        $crate::to_json::ToJson::to_json(&$value)
+    };
+}
+
+pub use crate::json;
+
+#[doc(hidden)]
+pub use cc_json_parser_derive::{json_key, json_literal};
+
+// Reads the JSON text `json_literal!` makes of a number literal with the lexer
+// and parser, so the value matches a document holding the same number.
+pub fn literal(text: &str) -> JsonValue {
+    let value = crate::lex::lex(&mut &*text).and_then(|mut tokens| crate::parse::parse(&mut tokens));
+    match value {
+        Ok(json_data) => json_data.element.value,
+        Err(err) => panic!("json! cannot represent the number {}: {}", text, err),
+    }
+}
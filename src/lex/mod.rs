@@ -1,238 +1,406 @@
-use crate::data::Token;
+use crate::data::{Span, Token};
+use std::borrow::Cow;
 use std::io::Error;
 use std::collections::VecDeque;
 
-pub fn lex(string: &mut &str) -> Result<VecDeque<Token>, Error> {
+pub mod simd;
+
+// Below this size building the structural index costs more than it saves.
+const INDEXED_THRESHOLD: usize = 4096;
+
+pub fn lex<'a>(string: &mut &'a str) -> Result<VecDeque<Token<'a>>, Error> {
+    let tokens = lex_bytes(string.as_bytes())?;
+    *string = &string[string.len()..];
+    Ok(tokens)
+}
+
+// Lexes raw input. Strings are checked to be valid UTF-8 as they are read, so
+// the input does not need to be a `str`.
+pub fn lex_bytes(input: &[u8]) -> Result<VecDeque<Token<'_>>, Error> {
+    if input.len() >= INDEXED_THRESHOLD {
+        lex_indexed(input)
+    } else {
+        lex_scalar(input)
+    }
+}
+
+pub fn lex_scalar(input: &[u8]) -> Result<VecDeque<Token<'_>>, Error> {
     let mut result: VecDeque<Token> = VecDeque::new();
+    let mut position = 0;
 
-    while !string.is_empty() {
-        if let Some(token) = lex_open_paren(string)? {
-            result.push_back(token);
-        } else if let Some(token) = lex_close_paren(string)? {
-            result.push_back(token);
-        } else if let Some(token) = lex_open_bracket(string)? {
-            result.push_back(token);
-        } else if let Some(token) = lex_close_bracket(string)? {
-            result.push_back(token);
-        } else if let Some(token) = lex_comma(string)? {
+    while position < input.len() {
+        if let Some(token) = lex_token(input, &mut position)? {
             result.push_back(token);
-        } else if let Some(token) = lex_colon(string)? {
-            result.push_back(token);
-        } else if let Some(token) = lex_string(string)? {
-            result.push_back(token);
-        } else if let Some(token) = lex_true(string)? {
-            result.push_back(token);
-        } else if let Some(token) = lex_false(string)? {
-            result.push_back(token);
-        } else if let Some(token) = lex_null(string)? {
-            result.push_back(token);
-        } else if let Some(_token) = lex_whitespace(string)? {
-            // Do nothing about whitespace for now
-            // result.push_back(token);
-        } else if let Some(token) = lex_number(string)? {
-            result.push_back(token);
-        } else {
-            return Err(Error::new(std::io::ErrorKind::Other, "Invalid Character"));
         }
     }
 
     Ok(result)
 }
 
-fn lex_open_paren(string: &mut &str) -> Result<Option<Token>, Error> {
-    if string.starts_with('{') {
-        *string = &string[1..];
-        return Ok(Some(Token::OpenParen));
+// Like `lex_scalar`, but keeps the byte range of every token for callers that
+// need to point back into the source.
+pub fn lex_spanned(input: &[u8]) -> Result<VecDeque<(Token<'_>, Span)>, Error> {
+    let mut result = VecDeque::new();
+    let mut position = 0;
+
+    while position < input.len() {
+        let start = position;
+        if let Some(token) = lex_token(input, &mut position)? {
+            result.push_back((token, Span { start, end: position }));
+        }
     }
-    Ok(None)
+
+    Ok(result)
 }
 
-fn lex_close_paren(string: &mut &str) -> Result<Option<Token>, Error> {
-    if string.starts_with('}') {
-        *string = &string[1..];
-        return Ok(Some(Token::CloseParen));
+// Like `lex_spanned`, but carries on after an error. The bytes that could
+// not be lexed are skipped and take the error's place in the output.
+pub fn lex_recovering(input: &[u8]) -> VecDeque<(Result<Token<'_>, Error>, Span)> {
+    let mut result = VecDeque::new();
+    let mut position = 0;
+
+    while position < input.len() {
+        let start = position;
+        match lex_token(input, &mut position) {
+            Ok(Some(token)) => result.push_back((Ok(token), Span { start, end: position })),
+            Ok(None) => {}
+            Err(error) => {
+                position = skip_invalid(input, start);
+                result.push_back((Err(error), Span { start, end: position }));
+            }
+        }
     }
-    Ok(None)
+
+    result
 }
 
-fn lex_open_bracket(string: &mut &str) -> Result<Option<Token>, Error> {
-    if string.starts_with('[') {
-        *string = &string[1..];
-        return Ok(Some(Token::OpenBracket));
+// Finds where lexing can resume after an error at `start`. A broken string
+// is skipped up to its closing quote, as long as that is on the same line;
+// anything else up to the next whitespace, quote or structural character.
+fn skip_invalid(input: &[u8], start: usize) -> usize {
+    let mut index = start + 1;
+    if input[start] == b'"' {
+        while let Some(&byte) = input.get(index) {
+            match byte {
+                b'\\' => index += 2,
+                b'"' => return index + 1,
+                b'\n' => return index,
+                _ => index += 1,
+            }
+        }
+        return input.len();
     }
-    Ok(None)
-}
 
-fn lex_close_bracket(string: &mut &str) -> Result<Option<Token>, Error> {
-    if string.starts_with(']') {
-        *string = &string[1..];
-        return Ok(Some(Token::CloseBracket));
+    while let Some(&byte) = input.get(index) {
+        if matches!(byte, b' ' | b'\n' | b'\t' | b'\r' | b'"' | b'{' | b'}' | b'[' | b']' | b',' | b':') {
+            break;
+        }
+        index += 1;
     }
-    Ok(None)
+    index
 }
 
-fn lex_comma(string: &mut &str) -> Result<Option<Token>, Error> {
-    if string.starts_with(',') {
-        *string = &string[1..];
-        return Ok(Some(Token::Comma));
+// Lexes every byte of the input into a token, so the spans cover the whole
+// input and it can be put back together exactly. Whitespace and comments
+// become `Whitespace` and `Comment` tokens; comments are only accepted here,
+// for editing files that carry them.
+pub fn lex_lossless(input: &[u8]) -> Result<VecDeque<(Token<'_>, Span)>, Error> {
+    let mut result = VecDeque::new();
+    let mut position = 0;
+
+    while position < input.len() {
+        let start = position;
+        let token = match (input[position], input.get(position + 1)) {
+            (b' ' | b'\n' | b'\t' | b'\r', _) => {
+                lex_whitespace(input, &mut position);
+                Token::Whitespace
+            }
+            (b'/', Some(b'/' | b'*')) => {
+                lex_comment(input, &mut position)?;
+                Token::Comment
+            }
+            _ => match lex_token(input, &mut position)? {
+                Some(token) => token,
+                None => continue,
+            },
+        };
+        result.push_back((token, Span { start, end: position }));
     }
-    Ok(None)
+
+    Ok(result)
 }
 
-fn lex_colon(string: &mut &str) -> Result<Option<Token>, Error> {
-    if string.starts_with(':') {
-        *string = &string[1..];
-        return Ok(Some(Token::Colon));
+// Skips a `//` comment up to the end of its line, or a `/* */` comment.
+fn lex_comment(input: &[u8], position: &mut usize) -> Result<(), Error> {
+    let body = *position + 2;
+    if input[*position + 1] == b'/' {
+        *position = input[body..].iter().position(|&byte| byte == b'\n').map_or(input.len(), |end| body + end);
+    } else {
+        match input[body..].windows(2).position(|pair| pair == b"*/") {
+            Some(end) => *position = body + end + 2,
+            None => return Err(string_error("Unterminated comment", *position)),
+        }
     }
-    Ok(None)
+    Ok(())
 }
 
-fn lex_string(string: &mut &str) -> Result<Option<Token>, Error> {
-    if string.starts_with('"') {
-        *string = &string[1..];
-        let mut new_string = String::new();
-        while !string.starts_with('"') {
-            // Handle Escape
-            if string.starts_with('\\') {
-                *string = &string[1..];
-                new_string.push_str(lex_escape(string)?.as_str());
-            } else {
-                let new_char = string.chars().next().unwrap();
-                match new_char {
-                    '\u{0020}' ..= '\u{10FFFF}' => {
-                        new_string.push(new_char);
-                        *string = &string[1..];
-                    }
-                    _ => {
-                        return Err(Error::new(std::io::ErrorKind::InvalidData, "Invalid Unicode Character in String"))
-                    }
-                }
+// Lexes using the positions found by `simd::structural_index`. Each token is
+// still read by `lex_token`, except that strings known to be plain printable
+// ASCII are sliced out directly. Anything between two positions also goes
+// through `lex_token`, so the tokens and errors are the same as the scalar
+// lexer's.
+pub fn lex_indexed(input: &[u8]) -> Result<VecDeque<Token<'_>>, Error> {
+    let Some(index) = simd::structural_index(input) else {
+        return lex_scalar(input);
+    };
+    let mut result: VecDeque<Token> = VecDeque::with_capacity(index.len());
+    let mut position = 0;
+
+    let mut entries = index.iter();
+    while let Some(&entry) = entries.next() {
+        let start = (entry & simd::POSITION) as usize;
+        while position < start {
+            if let Some(token) = lex_token(input, &mut position)? {
+                result.push_back(token);
             }
         }
-        *string = &string[1..];
-        return Ok(Some(Token::String(new_string)));
-    }
-    Ok(None)
-}
-
-fn lex_escape(string: &mut &str) -> Result<String, Error> {
-    if string.starts_with('"') {
-        *string = &string[1..];
-        Ok(String::from("\""))
-    } else if string.starts_with('\\') {
-        *string = &string[1..];
-        Ok(String::from("\\"))
-    } else if string.starts_with('/') {
-        *string = &string[1..];
-        Ok(String::from("/"))
-    } else if string.starts_with('b') {
-        *string = &string[1..];
-        Ok(String::from("\\b"))
-    } else if string.starts_with('f') {
-        *string = &string[1..];
-        Ok(String::from("\\f"))
-    } else if string.starts_with('n') {
-        *string = &string[1..];
-        Ok(String::from("\n"))
-    } else if string.starts_with('r') {
-        *string = &string[1..];
-        Ok(String::from("\r"))
-    } else if string.starts_with('t') {
-        *string = &string[1..];
-        Ok(String::from("\t"))
-    } else if string.starts_with('u') {
-        *string = &string[1..];
-        Ok(lex_escape_hex(string)?)
-    } else {
-        Err(Error::new(std::io::ErrorKind::InvalidData, "Invalid Escape Character"))
+        if position > start {
+            continue;
+        }
+
+        if entry & simd::STRING != 0 {
+            let close = entries.next().map(|&close| (close & simd::POSITION) as usize);
+            if let (0, Some(close)) = (entry & simd::SLOW, close) {
+                result.push_back(Token::String(Cow::Borrowed(utf8(&input[start + 1..close]))));
+                position = close + 1;
+                continue;
+            }
+        }
+        if let Some(token) = lex_token(input, &mut position)? {
+            result.push_back(token);
+        }
     }
+
+    while position < input.len() {
+        if let Some(token) = lex_token(input, &mut position)? {
+            result.push_back(token);
+        }
+    }
+
+    Ok(result)
 }
 
-fn lex_escape_hex(string: &mut &str) -> Result<String, Error> {
-    if string.len() < 4 {
-        Err(Error::new(std::io::ErrorKind::InvalidData, "Not enough hex characters in escape"))
-    } else {
-        let mut new_string = String::from("\\u");
-        let mut chars = string.chars();
-        for _ in 0..4 {
-            let new_char = chars.next().unwrap();
-            match new_char {
-                '0'..='9' | 'a'..='f' | 'A'..='F' => {
-                    new_string.push(new_char);
-                }
-                _ => {
-                    return Err(Error::new(std::io::ErrorKind::InvalidData, "Invalid Escape Hex Character"))
-                }
+// Reads one token starting at `position`, which must be inside the input, and
+// moves `position` past it. Whitespace is skipped but produces no token.
+fn lex_token<'a>(input: &'a [u8], position: &mut usize) -> Result<Option<Token<'a>>, Error> {
+    let rest = &input[*position..];
+    let (token, length) = match rest[0] {
+        b'{' => (Token::OpenParen, 1),
+        b'}' => (Token::CloseParen, 1),
+        b'[' => (Token::OpenBracket, 1),
+        b']' => (Token::CloseBracket, 1),
+        b',' => (Token::Comma, 1),
+        b':' => (Token::Colon, 1),
+        b'"' => return lex_string(input, position).map(Some),
+        b't' if rest.starts_with(b"true") => (Token::True, 4),
+        b'f' if rest.starts_with(b"false") => (Token::False, 5),
+        b'n' if rest.starts_with(b"null") => (Token::Null, 4),
+        b' ' | b'\n' | b'\t' | b'\r' => {
+            lex_whitespace(input, position);
+            return Ok(None);
+        }
+        b'-' | b'0'..=b'9' => return lex_number(input, position).map(Some),
+        _ => {
+            let message = format!("Invalid Character at offset {}", *position);
+            return Err(Error::new(std::io::ErrorKind::InvalidData, message));
+        }
+    };
+    *position += length;
+    Ok(Some(token))
+}
+
+fn lex_whitespace(input: &[u8], position: &mut usize) {
+    while let Some(b' ' | b'\n' | b'\t' | b'\r') = input.get(*position) {
+        *position += 1;
+    }
+}
+
+fn lex_string<'a>(input: &'a [u8], position: &mut usize) -> Result<Token<'a>, Error> {
+    let start = *position + 1;
+    let mut index = start;
+    // Strings without escapes are borrowed from the input. The first escape
+    // switches to an owned copy; `copied` marks how much of the input is in it.
+    let mut owned: Option<String> = None;
+    let mut copied = start;
+
+    loop {
+        match input.get(index) {
+            None => return Err(string_error("Unterminated string", *position)),
+            Some(b'"') => break,
+            Some(b'\\') => {
+                let owned = owned.get_or_insert_with(String::new);
+                owned.push_str(utf8(&input[copied..index]));
+                index += 1;
+                owned.push(lex_escape(input, &mut index)?);
+                copied = index;
             }
+            Some(0x00..=0x1f) => return Err(string_error("Invalid Unicode Character in String", index)),
+            Some(0x20..=0x7f) => index += 1,
+            Some(_) => index += utf8_sequence_length(input, index)?,
         }
-        *string = &string[4..];
-        Ok(new_string)
     }
+
+    let value = match owned {
+        Some(mut owned) => {
+            owned.push_str(utf8(&input[copied..index]));
+            Cow::Owned(owned)
+        }
+        None => Cow::Borrowed(utf8(&input[start..index])),
+    };
+    *position = index + 1;
+    Ok(Token::String(value))
 }
 
-fn lex_true(string: &mut &str) -> Result<Option<Token>, Error> {
-    if string.starts_with("true") {
-        *string = &string[4..];
-        return Ok(Some(Token::True));
+// Checks the multi-byte UTF-8 sequence starting at `index` and returns its
+// length. Follows the well-formed byte sequences table of the Unicode
+// standard, so overlong forms, surrogates and values past U+10FFFF are
+// rejected.
+fn utf8_sequence_length(input: &[u8], index: usize) -> Result<usize, Error> {
+    let (length, second) = match input[index] {
+        0xc2..=0xdf => (2, 0x80..=0xbf),
+        0xe0 => (3, 0xa0..=0xbf),
+        0xe1..=0xec | 0xee..=0xef => (3, 0x80..=0xbf),
+        0xed => (3, 0x80..=0x9f),
+        0xf0 => (4, 0x90..=0xbf),
+        0xf1..=0xf3 => (4, 0x80..=0xbf),
+        0xf4 => (4, 0x80..=0x8f),
+        _ => return Err(string_error("Invalid UTF-8 sequence", index)),
+    };
+
+    let valid = input.get(index + 1).is_some_and(|byte| second.contains(byte))
+        && (index + 2..index + length).all(|continuation| {
+            input.get(continuation).is_some_and(|byte| (0x80..=0xbf).contains(byte))
+        });
+    if valid {
+        Ok(length)
+    } else {
+        Err(string_error("Invalid UTF-8 sequence", index))
     }
-    Ok(None)
 }
 
-fn lex_false(string: &mut &str) -> Result<Option<Token>, Error> {
-    if string.starts_with("false") {
-        *string = &string[5..];
-        return Ok(Some(Token::False));
+fn utf8(bytes: &[u8]) -> &str {
+    // SAFETY: callers only pass printable ASCII, or string contents that
+    // `lex_string` has checked with `utf8_sequence_length`.
+    unsafe { std::str::from_utf8_unchecked(bytes) }
+}
+
+fn lex_escape(input: &[u8], index: &mut usize) -> Result<char, Error> {
+    let escaped = match input.get(*index) {
+        Some(b'"') => '"',
+        Some(b'\\') => '\\',
+        Some(b'/') => '/',
+        Some(b'b') => '\u{8}',
+        Some(b'f') => '\u{c}',
+        Some(b'n') => '\n',
+        Some(b'r') => '\r',
+        Some(b't') => '\t',
+        Some(b'u') => {
+            *index += 1;
+            return lex_escape_unicode(input, index);
+        }
+        _ => return Err(string_error("Invalid Escape Character", *index)),
+    };
+    *index += 1;
+    Ok(escaped)
+}
+
+// Decodes the code unit of a `\u` escape. A high surrogate followed by an
+// escaped low surrogate combines into one character. Unpaired surrogates have
+// no `char`, so they decode to U+FFFD; an escape after an unpaired high
+// surrogate is left for the next call.
+fn lex_escape_unicode(input: &[u8], index: &mut usize) -> Result<char, Error> {
+    let unit = lex_escape_hex(input, index)?;
+    if (0xd800..=0xdbff).contains(&unit) && input.get(*index..*index + 2) == Some(b"\\u") {
+        let mut next = *index + 2;
+        if let Ok(low @ 0xdc00..=0xdfff) = lex_escape_hex(input, &mut next) {
+            *index = next;
+            let code_point = 0x10000 + ((unit - 0xd800) << 10) + (low - 0xdc00);
+            return Ok(char::from_u32(code_point).unwrap_or(char::REPLACEMENT_CHARACTER));
+        }
     }
-    Ok(None)
+    Ok(char::from_u32(unit).unwrap_or(char::REPLACEMENT_CHARACTER))
 }
 
-fn lex_null(string: &mut &str) -> Result<Option<Token>, Error> {
-    if string.starts_with("null") {
-        *string = &string[4..];
-        return Ok(Some(Token::Null));
+fn lex_escape_hex(input: &[u8], index: &mut usize) -> Result<u32, Error> {
+    let Some(digits) = input.get(*index..*index + 4) else {
+        return Err(string_error("Not enough hex characters in escape", *index));
+    };
+    if let Some(invalid) = digits.iter().position(|digit| !digit.is_ascii_hexdigit()) {
+        return Err(string_error("Invalid Escape Hex Character", *index + invalid));
     }
-    Ok(None)
+
+    *index += 4;
+    Ok(u32::from_str_radix(utf8(digits), 16).unwrap_or(0))
+}
+
+fn string_error(message: &str, offset: usize) -> Error {
+    Error::new(std::io::ErrorKind::InvalidData, format!("{} at offset {}", message, offset))
 }
 
-fn lex_whitespace(string: &mut &str) -> Result<Option<Token>, Error> {
-    let mut found_whitespace = false;
-    while string.starts_with(' ') || string.starts_with('\n') || string.starts_with('\t') || string.starts_with('\r') {
-        *string = &string[1..];
-        found_whitespace = true;
+// Scans a whole number per RFC 8259:
+// [ minus ] ( "0" / digit1-9 *DIGIT ) [ "." 1*DIGIT ] [ ( "e" / "E" ) [ "-" / "+" ] 1*DIGIT ]
+fn lex_number<'a>(input: &'a [u8], position: &mut usize) -> Result<Token<'a>, Error> {
+    let offset = *position;
+    let mut index = offset;
+
+    if input.get(index) == Some(&b'-') {
+        index += 1;
     }
 
-    if found_whitespace {
-        return Ok(Some(Token::Whitespace));
+    match input.get(index) {
+        Some(b'0') => {
+            index += 1;
+            if input.get(index).is_some_and(u8::is_ascii_digit) {
+                return Err(number_error("No leading zeros allowed", offset));
+            }
+        }
+        Some(b'1'..=b'9') => {
+            index = skip_digits(input, index);
+        }
+        _ => return Err(number_error("No digits following sign", offset)),
     }
-    Ok(None)
-}
 
-fn lex_number(string: &mut &str) -> Result<Option<Token>, Error> {
-    let char = string.chars().next();
-    match char {
-        Some('-') => {
-            *string = &string[1..];
-            Ok(Some(Token::SignNeg))
-        },
-        Some('+') => {
-            *string = &string[1..];
-            Ok(Some(Token::SignPos))
-        },
-        Some('0'..='9') => {
-            *string = &string[1..];
-            let dig = char.unwrap().to_digit(10).unwrap();
-            Ok(Some(Token::Digit(dig as u8)))
-        },
-        Some('.') => {
-            *string = &string[1..];
-            Ok(Some(Token::FractionMarker))
-        },
-        Some('e') | Some('E') => {
-            *string = &string[1..];
-            Ok(Some(Token::ExponentMarker))
+    if input.get(index) == Some(&b'.') {
+        let digits = index + 1;
+        index = skip_digits(input, digits);
+        if index == digits {
+            return Err(number_error("No fraction component after fraction marker", offset));
         }
-        _ => {
-            Ok(None)
+    }
+
+    if let Some(b'e' | b'E') = input.get(index) {
+        index += 1;
+        if let Some(b'-' | b'+') = input.get(index) {
+            index += 1;
+        }
+        let digits = index;
+        index = skip_digits(input, digits);
+        if index == digits {
+            return Err(number_error("No digits following exponent", offset));
         }
     }
-}
\ No newline at end of file
+
+    *position = index;
+    Ok(Token::Number(utf8(&input[offset..index]), Span { start: offset, end: index }))
+}
+
+fn skip_digits(input: &[u8], mut index: usize) -> usize {
+    while input.get(index).is_some_and(u8::is_ascii_digit) {
+        index += 1;
+    }
+    index
+}
+
+fn number_error(message: &str, offset: usize) -> Error {
+    Error::new(std::io::ErrorKind::InvalidData, format!("{} in number at offset {}", message, offset))
+}
@@ -0,0 +1,272 @@
+// Stage one of the indexed lexer: finds where tokens start in 64-byte blocks,
+// in the style of simdjson. Each block is classified into bitmasks (one bit per
+// byte) with SIMD compares, and the masks are then combined with plain integer
+// operations to tell which quotes are escaped and which bytes are inside
+// strings.
+
+// Set on an index entry for the opening quote of a string. The entry after it
+// is the closing quote.
+pub const STRING: u32 = 1 << 30;
+// Set on an opening quote when the string holds a backslash, a control
+// character or non-ASCII bytes, so it has to go through the full string lexer.
+pub const SLOW: u32 = 1 << 31;
+pub const POSITION: u32 = !(STRING | SLOW);
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Masks {
+    pub backslash: u64,
+    pub quote: u64,
+    pub structural: u64,
+    pub whitespace: u64,
+    pub control: u64,
+    pub non_ascii: u64,
+}
+
+type Classify = fn(&[u8; 64]) -> Masks;
+
+// Returns the start of every token outside strings, plus both quotes of every
+// string, in order. Returns `None` for input too long to fit the positions.
+pub fn structural_index(bytes: &[u8]) -> Option<Vec<u32>> {
+    structural_index_with(bytes, classifier())
+}
+
+pub fn structural_index_with(bytes: &[u8], classify: Classify) -> Option<Vec<u32>> {
+    if bytes.len() > POSITION as usize {
+        return None;
+    }
+
+    let mut index = Vec::with_capacity(bytes.len() / 4);
+    let mut state = State::default();
+    let mut chunks = bytes.chunks_exact(64);
+    let mut base = 0;
+    for chunk in chunks.by_ref() {
+        let block: &[u8; 64] = chunk.try_into().unwrap();
+        state.block(classify(block), base, &mut index);
+        base += 64;
+    }
+
+    // Pad the tail with spaces, which never start a token.
+    let remainder = chunks.remainder();
+    if !remainder.is_empty() {
+        let mut block = [b' '; 64];
+        block[..remainder.len()].copy_from_slice(remainder);
+        state.block(classify(&block), base, &mut index);
+    }
+    Some(index)
+}
+
+#[derive(Default)]
+struct State {
+    // Whether the first byte of the next block is escaped by a backslash.
+    escaped: u64,
+    // All ones when the previous block ended inside a string.
+    in_string: u64,
+    // Whether the previous block ended in the middle of a scalar.
+    scalar: u64,
+    // Index entry of the string that is currently open.
+    open: Option<usize>,
+}
+
+impl State {
+    fn block(&mut self, masks: Masks, base: u32, index: &mut Vec<u32>) {
+        let escaped = find_escaped(masks.backslash, &mut self.escaped);
+        let quote = masks.quote & !escaped;
+
+        // Bits from an opening quote up to, but not including, its closing quote.
+        let in_string = prefix_xor(quote) ^ self.in_string;
+        self.in_string = ((in_string as i64) >> 63) as u64;
+
+        let structural = masks.structural & !in_string;
+        let scalar = !(masks.structural | masks.whitespace | quote) & !in_string;
+        let scalar_start = scalar & !(scalar << 1 | self.scalar);
+        self.scalar = scalar >> 63;
+        let slow = (masks.backslash | masks.control | masks.non_ascii) & in_string;
+
+        let mut events = structural | quote | scalar_start | slow;
+        while events != 0 {
+            let bit = events.trailing_zeros();
+            events &= events - 1;
+            let position = base + bit;
+
+            if slow & (1 << bit) != 0 {
+                if let Some(open) = self.open {
+                    index[open] |= SLOW;
+                }
+            } else if quote & (1 << bit) != 0 && in_string & (1 << bit) != 0 {
+                self.open = Some(index.len());
+                index.push(position | STRING);
+            } else {
+                if quote & (1 << bit) != 0 {
+                    self.open = None;
+                }
+                index.push(position);
+            }
+        }
+    }
+}
+
+// Marks the bytes escaped by a backslash. A run of backslashes escapes the
+// byte after it when the run has odd length, which is found by adding the
+// backslashes to the run starts and watching where the carries land.
+fn find_escaped(backslash: u64, carry: &mut u64) -> u64 {
+    const EVEN_BITS: u64 = 0x5555_5555_5555_5555;
+
+    let backslash = backslash & !*carry;
+    let follows_escape = backslash << 1 | *carry;
+    let odd_sequence_starts = backslash & !EVEN_BITS & !follows_escape;
+    let (sequences_starting_on_even_bits, overflow) = odd_sequence_starts.overflowing_add(backslash);
+    *carry = overflow as u64;
+    let invert_mask = sequences_starting_on_even_bits << 1;
+    (EVEN_BITS ^ invert_mask) & follows_escape
+}
+
+// Each bit becomes the xor of itself and every bit below it.
+fn prefix_xor(mut mask: u64) -> u64 {
+    mask ^= mask << 1;
+    mask ^= mask << 2;
+    mask ^= mask << 4;
+    mask ^= mask << 8;
+    mask ^= mask << 16;
+    mask ^= mask << 32;
+    mask
+}
+
+pub fn classifier() -> Classify {
+    #[cfg(target_arch = "x86_64")]
+    {
+        if std::arch::is_x86_feature_detected!("avx2") {
+            return x86::classify_avx2;
+        }
+        x86::classify_sse2
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        classify_scalar
+    }
+}
+
+#[cfg_attr(target_arch = "x86_64", allow(dead_code))]
+pub fn classify_scalar(block: &[u8; 64]) -> Masks {
+    let mut masks = Masks::default();
+    for (bit, &byte) in block.iter().enumerate() {
+        let bit = 1 << bit;
+        match byte {
+            b'\\' => masks.backslash |= bit,
+            b'"' => masks.quote |= bit,
+            b'{' | b'}' | b'[' | b']' | b':' | b',' => masks.structural |= bit,
+            b' ' | b'\t' | b'\n' | b'\r' => masks.whitespace |= bit,
+            _ => {}
+        }
+        if byte < 0x20 {
+            masks.control |= bit;
+        }
+        if byte >= 0x80 {
+            masks.non_ascii |= bit;
+        }
+    }
+    masks
+}
+
+#[cfg(target_arch = "x86_64")]
+pub mod x86 {
+    use super::Masks;
+    use std::arch::x86_64::*;
+
+    // SSE2 is part of the x86_64 baseline, so this needs no detection.
+    pub fn classify_sse2(block: &[u8; 64]) -> Masks {
+        let mut masks = Masks::default();
+        for lane in 0..4 {
+            // SAFETY: the block has 64 bytes, so every 16-byte load is in bounds,
+            // and SSE2 is always available on x86_64.
+            let lane_masks = unsafe { classify_sse2_lane(block.as_ptr().add(lane * 16)) };
+            let shift = lane * 16;
+            masks.backslash |= lane_masks.backslash << shift;
+            masks.quote |= lane_masks.quote << shift;
+            masks.structural |= lane_masks.structural << shift;
+            masks.whitespace |= lane_masks.whitespace << shift;
+            masks.control |= lane_masks.control << shift;
+            masks.non_ascii |= lane_masks.non_ascii << shift;
+        }
+        masks
+    }
+
+    unsafe fn classify_sse2_lane(pointer: *const u8) -> Masks {
+        let bytes = _mm_loadu_si128(pointer as *const __m128i);
+        let equals = |byte: u8| _mm_cmpeq_epi8(bytes, _mm_set1_epi8(byte as i8));
+        let any = |set: &[u8]| set.iter().fold(_mm_setzero_si128(), |mask, &byte| _mm_or_si128(mask, equals(byte)));
+        let bits = |mask: __m128i| _mm_movemask_epi8(mask) as u16 as u64;
+
+        // A byte is below 0x20 exactly when its unsigned minimum with 0x1f is itself.
+        let control = _mm_cmpeq_epi8(_mm_min_epu8(bytes, _mm_set1_epi8(0x1f)), bytes);
+        Masks {
+            backslash: bits(equals(b'\\')),
+            quote: bits(equals(b'"')),
+            structural: bits(any(b"{}[]:,")),
+            whitespace: bits(any(b" \t\n\r")),
+            control: bits(control),
+            non_ascii: bits(bytes),
+        }
+    }
+
+    pub fn classify_avx2(block: &[u8; 64]) -> Masks {
+        let mut masks = Masks::default();
+        for lane in 0..2 {
+            // SAFETY: `classifier` only hands out this function after detecting
+            // AVX2, and both 32-byte loads are within the 64-byte block.
+            let lane_masks = unsafe { classify_avx2_lane(block.as_ptr().add(lane * 32)) };
+            let shift = lane * 32;
+            masks.backslash |= lane_masks.backslash << shift;
+            masks.quote |= lane_masks.quote << shift;
+            masks.structural |= lane_masks.structural << shift;
+            masks.whitespace |= lane_masks.whitespace << shift;
+            masks.control |= lane_masks.control << shift;
+            masks.non_ascii |= lane_masks.non_ascii << shift;
+        }
+        masks
+    }
+
+    // Closures would not reliably inherit the AVX2 feature, so the compares
+    // are spelled out.
+    #[target_feature(enable = "avx2")]
+    unsafe fn classify_avx2_lane(pointer: *const u8) -> Masks {
+        let bytes = _mm256_loadu_si256(pointer as *const __m256i);
+        let backslash = _mm256_cmpeq_epi8(bytes, _mm256_set1_epi8(b'\\' as i8));
+        let quote = _mm256_cmpeq_epi8(bytes, _mm256_set1_epi8(b'"' as i8));
+        let structural = _mm256_or_si256(
+            _mm256_or_si256(
+                _mm256_or_si256(
+                    _mm256_cmpeq_epi8(bytes, _mm256_set1_epi8(b'{' as i8)),
+                    _mm256_cmpeq_epi8(bytes, _mm256_set1_epi8(b'}' as i8)),
+                ),
+                _mm256_or_si256(
+                    _mm256_cmpeq_epi8(bytes, _mm256_set1_epi8(b'[' as i8)),
+                    _mm256_cmpeq_epi8(bytes, _mm256_set1_epi8(b']' as i8)),
+                ),
+            ),
+            _mm256_or_si256(
+                _mm256_cmpeq_epi8(bytes, _mm256_set1_epi8(b':' as i8)),
+                _mm256_cmpeq_epi8(bytes, _mm256_set1_epi8(b',' as i8)),
+            ),
+        );
+        let whitespace = _mm256_or_si256(
+            _mm256_or_si256(
+                _mm256_cmpeq_epi8(bytes, _mm256_set1_epi8(b' ' as i8)),
+                _mm256_cmpeq_epi8(bytes, _mm256_set1_epi8(b'\t' as i8)),
+            ),
+            _mm256_or_si256(
+                _mm256_cmpeq_epi8(bytes, _mm256_set1_epi8(b'\n' as i8)),
+                _mm256_cmpeq_epi8(bytes, _mm256_set1_epi8(b'\r' as i8)),
+            ),
+        );
+        let control = _mm256_cmpeq_epi8(_mm256_min_epu8(bytes, _mm256_set1_epi8(0x1f)), bytes);
+
+        Masks {
+            backslash: _mm256_movemask_epi8(backslash) as u32 as u64,
+            quote: _mm256_movemask_epi8(quote) as u32 as u64,
+            structural: _mm256_movemask_epi8(structural) as u32 as u64,
+            whitespace: _mm256_movemask_epi8(whitespace) as u32 as u64,
+            control: _mm256_movemask_epi8(control) as u32 as u64,
+            non_ascii: _mm256_movemask_epi8(bytes) as u32 as u64,
+        }
+    }
+}
@@ -0,0 +1,30 @@
+pub mod lex;
+pub mod data;
+pub mod parse;
+pub mod write;
+pub mod diff;
+pub mod schema;
+pub mod from_json;
+pub mod to_json;
+#[cfg(feature = "serde")]
+pub mod serde_support;
+pub mod json_macro;
+pub mod borrowed;
+pub mod spanned;
+pub mod cst;
+pub mod recover;
+pub mod lsp;
+pub mod canonical;
+pub mod digest;
+pub mod yaml;
+pub mod toml;
+pub mod csv;
+#[cfg(any(feature = "cbor", feature = "msgpack", feature = "bson"))]
+#[macro_use]
+pub mod binary;
+#[cfg(feature = "cbor")]
+pub mod cbor;
+#[cfg(feature = "msgpack")]
+pub mod msgpack;
+#[cfg(feature = "bson")]
+pub mod bson;
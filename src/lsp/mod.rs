@@ -0,0 +1,580 @@
+use crate::data::*;
+use crate::json_macro::json;
+use crate::recover::Recovered;
+use crate::schema::{FileResolver, Schema};
+use crate::spanned::{self, Lines, SourceSpan};
+use crate::to_json::ToJson;
+use std::collections::HashMap;
+use std::io::{BufRead, Error, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+// A Language Server Protocol server for JSON documents, spoken over a pair of
+// streams (stdin and stdout for the `lsp` subcommand). Documents are synced in
+// full on every change and parsed with the recovering parser, so every request
+// works on a tree even while the document is broken.
+//
+// A document is checked against the schema named by its `$schema` member when
+// that is a local path, or else against the schema given on the command line.
+
+// LSP symbol kinds.
+const SYMBOL_STRING: u64 = 15;
+const SYMBOL_NUMBER: u64 = 16;
+const SYMBOL_BOOLEAN: u64 = 17;
+const SYMBOL_ARRAY: u64 = 18;
+const SYMBOL_OBJECT: u64 = 19;
+const SYMBOL_NULL: u64 = 21;
+
+// Longer messages are skipped unread instead of trusting the client with the
+// size of the buffer.
+pub const MAX_MESSAGE_LENGTH: usize = 64 << 20;
+
+const COMPLETION_PROPERTY: u64 = 10;
+const SEVERITY_ERROR: u64 = 1;
+const SEVERITY_WARNING: u64 = 2;
+
+// JSON-RPC error codes.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+pub struct Server {
+    documents: HashMap<String, String>,
+    schema: Option<Schema>,
+    // Schemas named by documents, by path.
+    schemas: HashMap<PathBuf, CachedSchema>,
+    shutdown: bool,
+}
+
+// A schema file as it was compiled, kept until the file changes. Files that do
+// not hold a usable schema are kept too, as `None`.
+struct CachedSchema {
+    modified: Option<SystemTime>,
+    schema: Option<Schema>,
+}
+
+// Serves requests until the client sends `exit` or closes the input. Returns
+// whether the client asked for a shutdown first, which decides the exit code.
+pub fn serve<R: BufRead, W: Write>(input: &mut R, output: &mut W, schema: Option<Schema>) -> Result<bool, Error> {
+    let mut server = Server::new(schema);
+    while let Some(incoming) = read_message(input)? {
+        let message = match incoming {
+            Incoming::Message(message) => message,
+            Incoming::Invalid(code, message) => {
+                write_message(output, &error_response(None, code, &message))?;
+                continue;
+            }
+        };
+        let method = string_at(&message, &["method"]).unwrap_or_default();
+        if method == "exit" {
+            break;
+        }
+        for reply in server.handle(&message) {
+            write_message(output, &reply)?;
+        }
+    }
+    Ok(server.shutdown)
+}
+
+// A message read off the stream.
+pub enum Incoming {
+    Message(JsonValue),
+    // A message that could be skipped but not read, with the JSON-RPC error
+    // code and message to answer it with.
+    Invalid(i64, String),
+}
+
+// Reads one message framed by a `Content-Length` header. Returns `None` at the
+// end of the input, and an error only when the framing itself is lost.
+pub fn read_message<R: BufRead>(input: &mut R) -> Result<Option<Incoming>, Error> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+
+    let Some(length) = length else {
+        return Err(Error::new(std::io::ErrorKind::InvalidData, "Missing Content-Length header"));
+    };
+    if length > MAX_MESSAGE_LENGTH {
+        std::io::copy(&mut std::io::Read::take(&mut *input, length as u64), &mut std::io::sink())?;
+        let message = format!("Message of {} bytes is longer than the limit of {}", length, MAX_MESSAGE_LENGTH);
+        return Ok(Some(Incoming::Invalid(INVALID_REQUEST, message)));
+    }
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    let message = crate::lex::lex_bytes(&body).and_then(|mut tokens| crate::parse::parse(&mut tokens));
+    Ok(Some(match message {
+        Ok(message) => Incoming::Message(message.element.value),
+        Err(error) => Incoming::Invalid(PARSE_ERROR, format!("Parse error: {}", error)),
+    }))
+}
+
+pub fn write_message<W: Write>(output: &mut W, message: &JsonValue) -> Result<(), Error> {
+    let body = crate::write::value_to_string(message);
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+impl Server {
+    pub fn new(schema: Option<Schema>) -> Server {
+        Server { documents: HashMap::new(), schema, schemas: HashMap::new(), shutdown: false }
+    }
+
+    // Returns the messages to send back: the response to a request, and any
+    // notifications it causes.
+    pub fn handle(&mut self, message: &JsonValue) -> Vec<JsonValue> {
+        let method = string_at(message, &["method"]).unwrap_or_default();
+        let params = value_at(message, &["params"]).unwrap_or(&JsonValue::Null);
+        let id = value_at(message, &["id"]).cloned();
+        let uri = string_at(params, &["textDocument", "uri"]).map(String::from);
+
+        let result = match (method, uri) {
+            ("initialize", _) => Some(capabilities()),
+            ("shutdown", _) => {
+                self.shutdown = true;
+                Some(JsonValue::Null)
+            }
+            ("textDocument/didOpen", Some(uri)) => {
+                let text = string_at(params, &["textDocument", "text"]).unwrap_or_default();
+                self.documents.insert(uri.clone(), String::from(text));
+                return vec![self.diagnostics(&uri)];
+            }
+            ("textDocument/didChange", Some(uri)) => {
+                // Full sync, so the last change holds the whole text.
+                if let Some(JsonValue::Array(changes)) = value_at(params, &["contentChanges"]) {
+                    if let Some(text) = changes.elements.last().and_then(|change| string_at(&change.value, &["text"])) {
+                        self.documents.insert(uri.clone(), String::from(text));
+                    }
+                }
+                return vec![self.diagnostics(&uri)];
+            }
+            // A schema, or a file one refers to, may have changed.
+            ("workspace/didChangeWatchedFiles", _) => {
+                self.schemas.clear();
+                let mut uris: Vec<String> = self.documents.keys().cloned().collect();
+                uris.sort();
+                return uris.iter().map(|uri| self.diagnostics(uri)).collect();
+            }
+            ("textDocument/didClose", Some(uri)) => {
+                self.documents.remove(&uri);
+                return vec![notification("textDocument/publishDiagnostics", json!({"uri": uri, "diagnostics": []}))];
+            }
+            (method, Some(uri)) if method.starts_with("textDocument/") && id.is_some() => {
+                let Some(text) = self.documents.get(&uri) else {
+                    return vec![error_response(id, INVALID_PARAMS, "Unknown document")];
+                };
+                let offset = position_offset(text, params);
+                // Parsed once here and shared by whatever the request needs.
+                let recovered = crate::recover::parse(text);
+                match method {
+                    "textDocument/documentSymbol" => Some(document_symbols(text, &recovered)),
+                    "textDocument/foldingRange" => Some(folding_ranges(&recovered)),
+                    "textDocument/formatting" => Some(formatting(text, params)),
+                    "textDocument/hover" => Some(hover(text, &recovered, offset)),
+                    "textDocument/completion" => {
+                        let schema = schema_for(&mut self.schemas, &uri, &recovered);
+                        Some(completion(text, &recovered, offset, schema.or(self.schema.as_ref())))
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+
+        match (id, result) {
+            (Some(id), Some(result)) => vec![json!({"jsonrpc": "2.0", "id": id, "result": result})],
+            (Some(id), None) => vec![error_response(Some(id), METHOD_NOT_FOUND, "Method not found")],
+            // Notifications that need no answer.
+            (None, _) => vec![],
+        }
+    }
+
+    fn diagnostics(&mut self, uri: &str) -> JsonValue {
+        let text = self.documents.get(uri).map(String::as_str).unwrap_or_default();
+        let lines = Lines::new(text);
+        let recovered = crate::recover::parse(text);
+
+        let mut diagnostics: Vec<JsonValue> = recovered.errors.iter().map(|error| json!({
+            "range": range(&lines, error.span),
+            "severity": SEVERITY_ERROR,
+            "source": "cc_json_parser",
+            "message": (error.message.as_str()),
+        })).collect();
+
+        // Schema checks only make sense once the document parses.
+        if recovered.errors.is_empty() {
+            let schema = schema_for(&mut self.schemas, uri, &recovered);
+            if let Some(schema) = schema.or(self.schema.as_ref()) {
+                // Without errors the tree has no error nodes, so this is
+                // always there.
+                if let Some(data) = recovered.data.clone().without_spans() {
+                    for violation in schema.validate(&data) {
+                        let element = find_pointer(&recovered.data.element, &violation.instance_path);
+                        diagnostics.push(json!({
+                            "range": range(&lines, element.span),
+                            "severity": SEVERITY_WARNING,
+                            "source": "cc_json_parser",
+                            "message": (violation.to_string()),
+                        }));
+                    }
+                }
+            }
+        }
+
+        notification("textDocument/publishDiagnostics", json!({"uri": uri, "diagnostics": diagnostics}))
+    }
+}
+
+// The schema named by the document's own `$schema` member, when that is a path
+// on disk next to the document. It is compiled again only when the file's
+// modification time changes.
+fn schema_for<'a>(schemas: &'a mut HashMap<PathBuf, CachedSchema>, uri: &str, recovered: &Recovered) -> Option<&'a Schema> {
+    let path = schema_path(uri, recovered)?;
+    let modified = std::fs::metadata(&path).and_then(|metadata| metadata.modified()).ok();
+    if schemas.get(&path).is_none_or(|cached| cached.modified != modified) {
+        let schema = compile_schema(&path);
+        schemas.insert(path.clone(), CachedSchema { modified, schema });
+    }
+    schemas.get(&path)?.schema.as_ref()
+}
+
+fn schema_path(uri: &str, recovered: &Recovered) -> Option<PathBuf> {
+    let spanned::JsonValue::Object(object) = &recovered.data.element.value else {
+        return None;
+    };
+    let spanned::JsonValue::String(reference) = &object.get("$schema")?.value else {
+        return None;
+    };
+    let directory = uri_path(uri)?.parent()?.to_path_buf();
+    match reference.string.strip_prefix("file://") {
+        Some(path) => Some(PathBuf::from(path)),
+        None if reference.string.contains("://") => None,
+        None => Some(directory.join(&reference.string)),
+    }
+}
+
+fn compile_schema(path: &Path) -> Option<Schema> {
+    let schema = std::fs::read_to_string(path).ok()?;
+    let mut tokens = crate::lex::lex(&mut schema.as_str()).ok()?;
+    let schema = crate::parse::parse(&mut tokens).ok()?;
+    let directory = path.parent().unwrap_or(Path::new("."));
+    Schema::compile_with_resolver(&schema, &FileResolver::new(directory)).ok()
+}
+
+fn capabilities() -> JsonValue {
+    json!({
+        "capabilities": {
+            "textDocumentSync": 1,
+            "documentSymbolProvider": true,
+            "foldingRangeProvider": true,
+            "documentFormattingProvider": true,
+            "hoverProvider": true,
+            "completionProvider": {"triggerCharacters": ["\""]}
+        },
+        "serverInfo": {"name": "cc_json_parser"}
+    })
+}
+
+fn notification(method: &str, params: JsonValue) -> JsonValue {
+    json!({"jsonrpc": "2.0", "method": method, "params": params})
+}
+
+fn error_response(id: Option<JsonValue>, code: i64, message: &str) -> JsonValue {
+    json!({"jsonrpc": "2.0", "id": (id.unwrap_or(JsonValue::Null)), "error": {"code": code, "message": message}})
+}
+
+fn value_at<'a>(value: &'a JsonValue, path: &[&str]) -> Option<&'a JsonValue> {
+    path.iter().try_fold(value, |value, key| match value {
+        JsonValue::Object(object) => object.get(key).map(|element| &element.value),
+        _ => None,
+    })
+}
+
+fn string_at<'a>(value: &'a JsonValue, path: &[&str]) -> Option<&'a str> {
+    match value_at(value, path)? {
+        JsonValue::String(string) => Some(&string.string),
+        _ => None,
+    }
+}
+
+fn uri_path(uri: &str) -> Option<PathBuf> {
+    let path = uri.strip_prefix("file://")?;
+    Some(PathBuf::from(percent_decode(path)))
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = vec![];
+    let mut index = 0;
+    while index < bytes.len() {
+        let escape = bytes.get(index + 1..index + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[index], escape) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn position(lines: &Lines, offset: usize) -> JsonValue {
+    let (line, character) = lines.utf16_position(offset);
+    json!({"line": line, "character": character})
+}
+
+fn range(lines: &Lines, span: SourceSpan) -> JsonValue {
+    json!({"start": (position(lines, span.start.offset)), "end": (position(lines, span.end.offset))})
+}
+
+// The offset of the request's `position`, or the start of the text.
+fn position_offset(text: &str, params: &JsonValue) -> usize {
+    let number = |key| match value_at(params, &["position", key]) {
+        Some(JsonValue::Number(number)) => number.as_u64().unwrap_or(0) as usize,
+        _ => 0,
+    };
+    Lines::new(text).utf16_offset(number("line"), number("character"))
+}
+
+fn contains(span: SourceSpan, offset: usize) -> bool {
+    span.start.offset <= offset && offset <= span.end.offset
+}
+
+fn symbol_kind(value: &spanned::JsonValue) -> u64 {
+    match value {
+        spanned::JsonValue::Object(_) => SYMBOL_OBJECT,
+        spanned::JsonValue::Array(_) => SYMBOL_ARRAY,
+        spanned::JsonValue::String(_) => SYMBOL_STRING,
+        spanned::JsonValue::Number(_) => SYMBOL_NUMBER,
+        spanned::JsonValue::True | spanned::JsonValue::False => SYMBOL_BOOLEAN,
+        spanned::JsonValue::Null | spanned::JsonValue::Error => SYMBOL_NULL,
+    }
+}
+
+fn document_symbols(text: &str, recovered: &Recovered) -> JsonValue {
+    let lines = Lines::new(text);
+    JsonValue::Array(JsonArray { elements: symbol_children(&lines, &recovered.data.element.value) })
+}
+
+// Members of an object are named by their keys, elements of an array by their
+// indices.
+fn symbol_children(lines: &Lines, value: &spanned::JsonValue) -> Vec<JsonElement> {
+    let symbol = |name: String, span: SourceSpan, selection: SourceSpan, value: &spanned::JsonValue| JsonElement {
+        value: json!({
+            "name": name,
+            "kind": (symbol_kind(value)),
+            "range": (range(lines, span)),
+            "selectionRange": (range(lines, selection)),
+            "children": (JsonValue::Array(JsonArray { elements: symbol_children(lines, value) })),
+        }),
+    };
+
+    match value {
+        spanned::JsonValue::Object(object) => object.members.iter().map(|member| {
+            symbol(member.string.string.clone(), member.span, member.string.span, &member.element.value)
+        }).collect(),
+        spanned::JsonValue::Array(array) => array.elements.iter().enumerate().map(|(index, element)| {
+            symbol(index.to_string(), element.span, element.span, &element.value)
+        }).collect(),
+        _ => vec![],
+    }
+}
+
+fn folding_ranges(recovered: &Recovered) -> JsonValue {
+    let mut ranges = vec![];
+    collect_folding_ranges(&recovered.data.element, &mut ranges);
+    JsonValue::Array(JsonArray { elements: ranges })
+}
+
+// Folds from the line that opens an object or array to the line before the one
+// that closes it, so the closing bracket stays visible.
+fn collect_folding_ranges(element: &spanned::JsonElement, ranges: &mut Vec<JsonElement>) {
+    let children: Vec<&spanned::JsonElement> = match &element.value {
+        spanned::JsonValue::Object(object) => object.members.iter().map(|member| &member.element).collect(),
+        spanned::JsonValue::Array(array) => array.elements.iter().collect(),
+        _ => return,
+    };
+
+    let start = element.span.start.line - 1;
+    let end = element.span.end.line - 1;
+    if end > start + 1 {
+        ranges.push(JsonElement { value: json!({"startLine": start, "endLine": (end - 1)}) });
+    }
+    for child in children {
+        collect_folding_ranges(child, ranges);
+    }
+}
+
+// Re-indents a valid document, keeping the spelling of every token. Documents
+// with syntax errors or comments are left alone.
+fn formatting(text: &str, params: &JsonValue) -> JsonValue {
+    let tab_size = match value_at(params, &["options", "tabSize"]) {
+        Some(JsonValue::Number(number)) => number.as_u64().unwrap_or(4) as usize,
+        _ => 4,
+    };
+    let indent = match value_at(params, &["options", "insertSpaces"]) {
+        Some(JsonValue::False) => String::from("\t"),
+        _ => " ".repeat(tab_size),
+    };
+
+    let Ok(mut document) = crate::cst::parse(text) else {
+        return json!([]);
+    };
+    if !document.format(&indent) || document.to_string() == text {
+        return json!([]);
+    }
+    let lines = Lines::new(text);
+    json!([{"range": {"start": (position(&lines, 0)), "end": (position(&lines, text.len()))}, "newText": (document.to_string())}])
+}
+
+// Shows the JSON Pointer of the innermost value under the cursor.
+fn hover(text: &str, recovered: &Recovered, offset: usize) -> JsonValue {
+    let lines = Lines::new(text);
+    let mut path = vec![];
+    let element = element_at(&recovered.data.element, offset, &mut path);
+    if !contains(element.span, offset) {
+        return JsonValue::Null;
+    }
+
+    let pointer: String = path.iter().map(|token| format!("/{}", token.replace('~', "~0").replace('/', "~1"))).collect();
+    json!({"contents": {"kind": "plaintext", "value": pointer}, "range": (range(&lines, element.span))})
+}
+
+// Descends to the innermost element containing `offset`, collecting the keys
+// and indices on the way. A cursor on a key counts as being on its value.
+fn element_at<'a>(element: &'a spanned::JsonElement, offset: usize, path: &mut Vec<String>) -> &'a spanned::JsonElement {
+    match &element.value {
+        spanned::JsonValue::Object(object) => {
+            for member in &object.members {
+                if contains(member.span, offset) {
+                    path.push(member.string.string.clone());
+                    return element_at(&member.element, offset, path);
+                }
+            }
+        }
+        spanned::JsonValue::Array(array) => {
+            for (index, child) in array.elements.iter().enumerate() {
+                if contains(child.span, offset) {
+                    path.push(index.to_string());
+                    return element_at(child, offset, path);
+                }
+            }
+        }
+        _ => {}
+    }
+    element
+}
+
+fn find_pointer<'a>(element: &'a spanned::JsonElement, pointer: &str) -> &'a spanned::JsonElement {
+    let mut element = element;
+    for token in pointer.split('/').skip(1) {
+        let token = token.replace("~1", "/").replace("~0", "~");
+        let child = match &element.value {
+            spanned::JsonValue::Object(object) => object.get(&token),
+            spanned::JsonValue::Array(array) => token.parse::<usize>().ok().and_then(|index| array.elements.get(index)),
+            _ => None,
+        };
+        match child {
+            Some(child) => element = child,
+            None => break,
+        }
+    }
+    element
+}
+
+// Offers the properties the schema declares for the object around the cursor
+// that it does not have yet. Each replaces the key typed so far, with its
+// opening quote and any closing one the editor added, so completing after the
+// `"` that triggers it does not double the quotes.
+fn completion(text: &str, recovered: &Recovered, offset: usize, schema: Option<&Schema>) -> JsonValue {
+    let Some(schema) = schema else {
+        return json!([]);
+    };
+    let mut path = vec![];
+    let Some(object) = object_at(&recovered.data.element, offset, &mut path) else {
+        return json!([]);
+    };
+
+    let lines = Lines::new(text);
+    let (start, end) = typed_key(text, offset);
+    let replaced = json!({"start": (position(&lines, start)), "end": (position(&lines, end))});
+    let items: Vec<JsonValue> = schema.property_suggestions(&path).into_iter()
+        .filter(|(name, _)| object.get(name).is_none_or(|element| contains(element.span, offset)))
+        .map(|(name, description)| {
+            let key = crate::write::value_to_string(&name.to_json());
+            json!({
+                "label": (name.as_str()),
+                "kind": COMPLETION_PROPERTY,
+                "documentation": description,
+                "filterText": (key.as_str()),
+                "textEdit": {"range": (replaced.clone()), "newText": (format!("{}: ", key))},
+            })
+        })
+        .collect();
+    items.to_json()
+}
+
+// The bytes around `offset` that belong to a key being typed: the word under
+// the cursor, the quote before it and the quote after it.
+fn typed_key(text: &str, offset: usize) -> (usize, usize) {
+    let bytes = text.as_bytes();
+    let in_word = |byte: &u8| !byte.is_ascii_whitespace() && !b"\"{}[],:".contains(byte);
+    let mut start = offset - bytes[..offset].iter().rev().take_while(|byte| in_word(byte)).count();
+    let mut end = offset + bytes[offset..].iter().take_while(|byte| in_word(byte)).count();
+    if start > 0 && bytes[start - 1] == b'"' {
+        start -= 1;
+        if bytes.get(end) == Some(&b'"') {
+            end += 1;
+        }
+    }
+    (start, end)
+}
+
+// The innermost object whose braces enclose `offset`, with the path to it.
+fn object_at<'a>(element: &'a spanned::JsonElement, offset: usize, path: &mut Vec<String>) -> Option<&'a spanned::JsonObject> {
+    if !(element.span.start.offset < offset && offset < element.span.end.offset) {
+        return None;
+    }
+    match &element.value {
+        spanned::JsonValue::Object(object) => {
+            for member in &object.members {
+                path.push(member.string.string.clone());
+                if let Some(inner) = object_at(&member.element, offset, path) {
+                    return Some(inner);
+                }
+                path.pop();
+            }
+            Some(object)
+        }
+        spanned::JsonValue::Array(array) => {
+            for (index, child) in array.elements.iter().enumerate() {
+                path.push(index.to_string());
+                if let Some(inner) = object_at(child, offset, path) {
+                    return Some(inner);
+                }
+                path.pop();
+            }
+            None
+        }
+        _ => None,
+    }
+}
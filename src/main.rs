@@ -1,57 +1,373 @@
 use std::env;
 use std::fs::File;
-use std::io::{self, Error, BufReader, BufRead};
+use std::io::{self, Error, BufReader, BufRead, IsTerminal, Read};
+use std::path::Path;
 use std::process::ExitCode;
 
 
-mod lex;
-mod data;
-mod parse;
+// The modules live in the library, where the derive macros and other crates
+// can reach them, and are imported here by name for the CLI and its tests.
+#[allow(unused_imports)]
+use cc_json_parser::{
+    borrowed, canonical, cst, csv, data, diff, digest, from_json, json_macro, lex, lsp, parse, recover, schema, spanned,
+    to_json, toml, write, yaml,
+};
+#[cfg(feature = "bson")]
+#[allow(unused_imports)]
+use cc_json_parser::bson;
+#[cfg(feature = "cbor")]
+#[allow(unused_imports)]
+use cc_json_parser::cbor;
+#[cfg(feature = "msgpack")]
+#[allow(unused_imports)]
+use cc_json_parser::msgpack;
+#[cfg(feature = "serde")]
+#[allow(unused_imports)]
+use cc_json_parser::serde_support;
 
 use data::JsonData;
+use diff::DiffOptions;
+use schema::{FileResolver, Schema};
 
 fn main() -> ExitCode {
     let args: Vec<String> = env::args().skip(1).collect();
-    let filenames = parse_args(args);
+    if args.first().map(String::as_str) == Some("diff") {
+        return run_diff(&args[1..]);
+    }
+    if args.first().map(String::as_str) == Some("canonicalize") {
+        return run_canonicalize(&args[1..]);
+    }
+    if args.first().map(String::as_str) == Some("convert") {
+        return run_convert(&args[1..]);
+    }
+    if args.first().map(String::as_str) == Some("lsp") {
+        return run_lsp(&args[1..]);
+    }
+
+    let (filenames, schema_filename, all_errors) = match parse_args(args) {
+        Ok(args) => args,
+        Err(message) => {
+            println!("{}", message);
+            return ExitCode::from(2);
+        }
+    };
     let mut status = 0;
 
+    let mut schema = None;
+    if let Some(schema_filename) = schema_filename {
+        match load_schema(&schema_filename) {
+            Ok(compiled) => schema = Some(compiled),
+            Err(err) => {
+                println!("Invalid schema: {:?} - {:?}", schema_filename, err.to_string());
+                return ExitCode::from(2);
+            }
+        }
+    }
+
     if filenames.is_empty() {
-        let stdin = io::stdin();
-        let result = json_valid(&mut stdin.lock());
-        if result.is_err() {
-            println!("Invalid: stdin - {:?}", result.err().unwrap().to_string());
+        let mut bytes = vec![];
+        let result = io::stdin().lock().read_to_end(&mut bytes).and_then(|_| json_valid(&mut bytes.as_slice()));
+        let syntax_error = result.is_err();
+        if !report("stdin", result, schema.as_ref()) {
             status = 1;
-        } else {
-            println!("Valid: stdin");
+            if all_errors && syntax_error {
+                report_syntax_errors(&bytes);
+            }
         }
     }
 
     for filename in filenames {
-        let result = handle_file(filename.as_str());
-        if result.is_err() {
-            println!("Invalid: {:?} - {:?}", filename, result.err().unwrap().to_string());
+        let result = load_document(filename.as_str());
+        let syntax_error = result.is_err() && Format::from_filename(&filename) == Format::Json;
+        if !report(&format!("{:?}", filename), result, schema.as_ref()) {
             status = 1;
-        } else {
-            println!("Valid: {:?}", filename);
+            if all_errors && syntax_error {
+                if let Ok(bytes) = std::fs::read(&filename) {
+                    report_syntax_errors(&bytes);
+                }
+            }
         }
     }
 
     ExitCode::from(status)
 }
 
-fn parse_args(args: Vec<String>) -> Vec<String> {
+fn parse_args(args: Vec<String>) -> Result<(Vec<String>, Option<String>, bool), String> {
     let mut file_result: Vec<String> = vec![];
-    for arg in args {
-        file_result.push(arg);
+    let mut schema_result = None;
+    let mut all_errors = false;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "--schema" {
+            match args.next() {
+                Some(schema) => schema_result = Some(schema),
+                None => return Err(String::from("Missing schema file after --schema")),
+            }
+        } else if arg == "--all-errors" {
+            all_errors = true;
+        } else {
+            file_result.push(arg);
+        }
+    }
+
+    Ok((file_result, schema_result, all_errors))
+}
+
+fn report(name: &str, result: Result<JsonData, Error>, schema: Option<&Schema>) -> bool {
+    match result {
+        Err(err) => {
+            println!("Invalid: {} - {:?}", name, err.to_string());
+            false
+        }
+        Ok(json_data) => {
+            let violations = schema.map(|schema| schema.validate(&json_data)).unwrap_or_default();
+            if violations.is_empty() {
+                println!("Valid: {}", name);
+                true
+            } else {
+                println!("Invalid: {} - {} schema violation(s)", name, violations.len());
+                for violation in violations {
+                    println!("    {}", violation);
+                }
+                false
+            }
+        }
+    }
+}
+
+// Lists every syntax error in an invalid document, not just the first one.
+fn report_syntax_errors(bytes: &[u8]) {
+    for error in recover::parse_bytes(bytes).errors {
+        println!("    {}", error);
+    }
+}
+
+fn load_schema(filename: &str) -> Result<Schema, Error> {
+    let schema = load_document(filename)?;
+    let directory = Path::new(filename).parent().unwrap_or(Path::new("."));
+    Schema::compile_with_resolver(&schema, &FileResolver::new(directory))
+}
+
+fn run_diff(args: &[String]) -> ExitCode {
+    let mut options = DiffOptions::default();
+    let mut json_output = false;
+    let mut color = io::stdout().is_terminal();
+    let mut filenames: Vec<&String> = vec![];
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--ignore-order" => options.ignore_array_order = true,
+            "--ignore-key" => {
+                if let Some(key) = args.next() {
+                    options.ignore_keys.push(key.clone());
+                } else {
+                    eprintln!("Missing key after --ignore-key");
+                    return ExitCode::from(2);
+                }
+            }
+            "--json" => json_output = true,
+            "--no-color" => color = false,
+            _ => filenames.push(arg),
+        }
+    }
+
+    if filenames.len() != 2 {
+        eprintln!("Usage: cc_json_parser diff [--ignore-order] [--ignore-key KEY] [--json] [--no-color] a.json b.json");
+        return ExitCode::from(2);
+    }
+
+    let mut documents = vec![];
+    for filename in filenames {
+        match load_document(filename.as_str()) {
+            Ok(json_data) => documents.push(json_data),
+            Err(err) => {
+                eprintln!("Invalid: {:?} - {:?}", filename, err.to_string());
+                return ExitCode::from(2);
+            }
+        }
+    }
+
+    let differences = diff::diff(&documents[0], &documents[1], &options);
+    if json_output {
+        println!("{}", diff::format_json(&differences));
+    } else {
+        print!("{}", diff::format_human(&differences, color));
+    }
+
+    if differences.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(1)
+    }
+}
+
+// Prints the RFC 8785 canonical form with no trailing newline, so the output
+// can be signed or hashed as it is.
+fn run_canonicalize(args: &[String]) -> ExitCode {
+    let result = match args {
+        [] => json_valid(&mut io::stdin().lock()),
+        [filename] => handle_file(filename),
+        _ => {
+            eprintln!("Usage: cc_json_parser canonicalize [file.json]");
+            return ExitCode::from(2);
+        }
+    };
+
+    match result.and_then(|json_data| canonical::canonicalize(&json_data)) {
+        Ok(canonical) => {
+            print!("{}", canonical);
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("Invalid: {:?}", err.to_string());
+            ExitCode::from(1)
+        }
+    }
+}
+
+// The formats `convert` reads and writes. Documents given by file name are
+// read by their extension, so diff and schema validation work on YAML, TOML
+// and CSV files too.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+enum Format {
+    Json,
+    Yaml,
+    Toml,
+    Csv,
+}
+
+impl Format {
+    fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "json" => Some(Format::Json),
+            "yaml" | "yml" => Some(Format::Yaml),
+            "toml" => Some(Format::Toml),
+            "csv" => Some(Format::Csv),
+            _ => None,
+        }
+    }
+
+    fn from_filename(filename: &str) -> Format {
+        let extension = Path::new(filename).extension().and_then(|extension| extension.to_str());
+        extension.and_then(Format::from_name).unwrap_or(Format::Json)
+    }
+
+    fn read(self, bytes: &[u8], csv_options: &csv::CsvOptions) -> Result<JsonData, Error> {
+        if self == Format::Json {
+            return json_valid(&mut BufReader::new(bytes));
+        }
+        let text = std::str::from_utf8(bytes).map_err(|err| Error::new(io::ErrorKind::InvalidData, err))?;
+        match self {
+            Format::Yaml => yaml::parse(text),
+            Format::Toml => toml::parse(text),
+            _ => csv::parse_with_options(text, csv_options),
+        }
+    }
+
+    fn write(self, json_data: &JsonData) -> Result<String, Error> {
+        match self {
+            Format::Json => Ok(format!("{}\n", write::value_to_string(&json_data.element.value))),
+            Format::Yaml => Ok(yaml::to_yaml(json_data)),
+            Format::Toml => toml::to_toml(json_data),
+            Format::Csv => csv::to_csv(json_data),
+        }
+    }
+}
+
+fn load_document(filename: &str) -> Result<JsonData, Error> {
+    match Format::from_filename(filename) {
+        Format::Json => handle_file(filename),
+        format => format.read(&std::fs::read(filename)?, &csv::CsvOptions::default()),
+    }
+}
+
+fn run_convert(args: &[String]) -> ExitCode {
+    const USAGE: &str = "Usage: cc_json_parser convert [--from json|yaml|toml|csv] [--to json|yaml|toml|csv] [--no-infer] [file]";
+    let mut from = None;
+    let mut to = Format::Json;
+    let mut csv_options = csv::CsvOptions::default();
+    let mut filenames = vec![];
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--from" | "--to" => match args.next().and_then(|name| Format::from_name(name)) {
+                Some(format) if arg == "--from" => from = Some(format),
+                Some(format) => to = format,
+                None => {
+                    eprintln!("{}", USAGE);
+                    return ExitCode::from(2);
+                }
+            },
+            // Reads every CSV field as a string.
+            "--no-infer" => csv_options = csv::CsvOptions { infer_types: false, empty_as_null: false },
+            _ => filenames.push(arg),
+        }
+    }
+
+    // Without --from, a file is read by its extension and stdin as JSON.
+    let result = match filenames[..] {
+        [] => {
+            let mut bytes = vec![];
+            let from = from.unwrap_or(Format::Json);
+            io::stdin().lock().read_to_end(&mut bytes).and_then(|_| from.read(&bytes, &csv_options))
+        }
+        [filename] => {
+            let from = from.unwrap_or_else(|| Format::from_filename(filename));
+            std::fs::read(filename).and_then(|bytes| from.read(&bytes, &csv_options))
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    match result.and_then(|json_data| to.write(&json_data)) {
+        Ok(converted) => {
+            print!("{}", converted);
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("Invalid: {:?}", err.to_string());
+            ExitCode::from(1)
+        }
+    }
+}
+
+fn run_lsp(args: &[String]) -> ExitCode {
+    let mut schema = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), args.next()) {
+            ("--schema", Some(filename)) => match load_schema(filename) {
+                Ok(compiled) => schema = Some(compiled),
+                Err(err) => {
+                    eprintln!("Invalid schema: {:?} - {:?}", filename, err.to_string());
+                    return ExitCode::from(2);
+                }
+            },
+            _ => {
+                eprintln!("Usage: cc_json_parser lsp [--schema schema.json]");
+                return ExitCode::from(2);
+            }
+        }
     }
 
-    file_result
+    // Stdout carries the protocol, so problems go to stderr.
+    match lsp::serve(&mut io::stdin().lock(), &mut io::stdout().lock(), schema) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(1),
+        Err(err) => {
+            eprintln!("lsp: {}", err);
+            ExitCode::from(1)
+        }
+    }
 }
 
 fn json_valid<R: BufRead>(reader: &mut R) -> Result<JsonData, Error> {
-    let mut s = String::new();
-    reader.read_to_string(&mut s)?;
-    let mut tokens = lex::lex(&mut s.as_str())?;
+    let mut bytes = vec![];
+    reader.read_to_end(&mut bytes)?;
+    let mut tokens = lex::lex_bytes(&bytes)?;
     let json_data = parse::parse(&mut tokens)?;
     Ok(json_data)
 }
@@ -67,18 +383,11 @@ mod tests {
     use super::*;
 
     use crate::data::*;
+    use crate::json_macro::json;
 
     #[test]
     fn test_step_1_valid() {
-        let expected = JsonData {
-            element: JsonElement {
-                value: JsonValue::Object(
-                    JsonObject {
-                        members: vec![]
-                    }
-                )
-            }
-        };
+        let expected = JsonData::from(json!({}));
 
         let json_result = handle_file("tests/step1/valid.json");
         assert!(json_result.is_ok());
@@ -94,26 +403,7 @@ mod tests {
 
     #[test]
     fn test_step_2_valid() {
-        let expected = JsonData {
-            element: JsonElement {
-                value: JsonValue::Object(
-                    JsonObject {
-                        members: vec![
-                            JsonMember {
-                                string: JsonString {
-                                    string: String::from("key")
-                                },
-                                element: JsonElement {
-                                    value: JsonValue::String(JsonString {
-                                        string: String::from("value")
-                                    })
-                                }
-                            }
-                        ]
-                    }
-                )
-            }
-        };
+        let expected = JsonData::from(json!({"key": "value"}));
 
         let json_result = handle_file("tests/step2/valid.json");
         assert!(json_result.is_ok());
@@ -129,36 +419,10 @@ mod tests {
 
     #[test]
     fn test_step_2_valid_2() {
-        let expected = JsonData {
-            element: JsonElement {
-                value: JsonValue::Object(
-                    JsonObject {
-                        members: vec![
-                            JsonMember {
-                                string: JsonString {
-                                    string: String::from("key")
-                                },
-                                element: JsonElement {
-                                    value: JsonValue::String(JsonString {
-                                        string: String::from("value")
-                                    })
-                                }
-                            },
-                            JsonMember {
-                                string: JsonString {
-                                    string: String::from("key2")
-                                },
-                                element: JsonElement {
-                                    value: JsonValue::String(JsonString {
-                                        string: String::from("value")
-                                    })
-                                }
-                            }
-                        ]
-                    }
-                )
-            }
-        };
+        let expected = JsonData::from(json!({
+            "key": "value",
+            "key2": "value"
+        }));
 
         let json_result = handle_file("tests/step2/valid2.json");
         assert!(json_result.is_ok());
@@ -169,342 +433,81 @@ mod tests {
     fn test_step_2_invalid_2() {
         let json_result = handle_file("tests/step2/invalid2.json");
         assert!(json_result.is_err());
-        assert!(json_result.err().unwrap().to_string() == "Invalid Character");
+        assert!(json_result.err().unwrap().to_string() == "Invalid Character at offset 22");
     }
 
     #[test]
     fn test_step_3_valid() {
-        let expected = JsonData {
-            element: JsonElement {
-                value: JsonValue::Object(
-                    JsonObject {
-                        members: vec![
-                            JsonMember {
-                                string: JsonString {
-                                    string: String::from("key1")
-                                },
-                                element: JsonElement {
-                                    value: JsonValue::True
-                                }
-                            },
-                            JsonMember {
-                                string: JsonString {
-                                    string: String::from("key2")
-                                },
-                                element: JsonElement {
-                                    value: JsonValue::False
-                                }
-                            },
-                            JsonMember {
-                                string: JsonString {
-                                    string: String::from("key3")
-                                },
-                                element: JsonElement {
-                                    value: JsonValue::Null
-                                }
-                            },
-                            JsonMember {
-                                string: JsonString {
-                                    string: String::from("key4")
-                                },
-                                element: JsonElement {
-                                    value: JsonValue::String(JsonString {
-                                        string: String::from("value"),
-                                    })
-                                }
-                            },
-                            JsonMember {
-                                string: JsonString {
-                                    string: String::from("key5")
-                                },
-                                element: JsonElement {
-                                    value: JsonValue::Number(JsonNumber {
-                                        integer: 101,
-                                        fraction: None,
-                                        exponent: None,
-                                    })
-                                }
-                            },
-                        ]
-                    }
-                )
-            }
-        };
+        let expected = JsonData::from(json!({
+            "key1": true,
+            "key2": false,
+            "key3": null,
+            "key4": "value",
+            "key5": 101
+        }));
 
         let json_result = handle_file("tests/step3/valid.json");
         assert!(json_result.is_ok());
-        assert_eq!(json_result.unwrap(), expected);    }
+        assert_eq!(json_result.unwrap(), expected);
+    }
 
     #[test]
     fn test_step_3_invalid() {
         let json_result = handle_file("tests/step3/invalid.json");
         assert!(json_result.is_err());
-        assert!(json_result.err().unwrap().to_string() == "Invalid Character");
+        assert!(json_result.err().unwrap().to_string() == "Invalid Character at offset 28");
     }
 
     #[test]
     fn test_step_4_valid() {
-        let expected = JsonData {
-            element: JsonElement {
-                value: JsonValue::Object(
-                    JsonObject {
-                        members: vec![
-                            JsonMember {
-                                string: JsonString {
-                                    string: String::from("key")
-                                },
-                                element: JsonElement {
-                                    value: JsonValue::String(JsonString {
-                                        string: String::from("value")
-                                    })
-                                }
-                            },
-                            JsonMember {
-                                string: JsonString {
-                                    string: String::from("key-n")
-                                },
-                                element: JsonElement {
-                                    value: JsonValue::Number(JsonNumber {
-                                        integer: 101,
-                                        fraction: None,
-                                        exponent: None,
-                                    })
-                                }
-                            },
-                            JsonMember {
-                                string: JsonString {
-                                    string: String::from("key-o")
-                                },
-                                element: JsonElement {
-                                    value: JsonValue::Object(JsonObject {
-                                        members: vec![]
-                                    })
-                                }
-                            },
-                            JsonMember {
-                                string: JsonString {
-                                    string: String::from("key-l")
-                                },
-                                element: JsonElement {
-                                    value: JsonValue::Array(JsonArray {
-                                        elements: vec![]
-                                    })
-                                }
-                            },
-                        ]
-                    }
-                )
-            }
-        };
+        let expected = JsonData::from(json!({
+            "key": "value",
+            "key-n": 101,
+            "key-o": {},
+            "key-l": []
+        }));
 
         let json_result = handle_file("tests/step4/valid.json");
         assert!(json_result.is_ok());
-        assert_eq!(json_result.unwrap(), expected);    
+        assert_eq!(json_result.unwrap(), expected);
     }
 
     #[test]
     fn test_step_4_valid_2() {
-        let expected = JsonData {
-            element: JsonElement {
-                value: JsonValue::Object(
-                    JsonObject {
-                        members: vec![
-                            JsonMember {
-                                string: JsonString {
-                                    string: String::from("key")
-                                },
-                                element: JsonElement {
-                                    value: JsonValue::String(JsonString {
-                                        string: String::from("value")
-                                    })
-                                }
-                            },
-                            JsonMember {
-                                string: JsonString {
-                                    string: String::from("key-n")
-                                },
-                                element: JsonElement {
-                                    value: JsonValue::Number(JsonNumber {
-                                        integer: 101,
-                                        fraction: None,
-                                        exponent: None,
-                                    })
-                                }
-                            },
-                            JsonMember {
-                                string: JsonString {
-                                    string: String::from("key-o")
-                                },
-                                element: JsonElement {
-                                    value: JsonValue::Object(JsonObject {
-                                        members: vec![
-                                            JsonMember {
-                                                string: JsonString {
-                                                    string: String::from("inner key")
-                                                },
-                                                element: JsonElement {
-                                                    value: JsonValue::String(JsonString {
-                                                        string: String::from("inner value")
-                                                    })
-                                                }
-                                            }
-                                        ]
-                                    })
-                                }
-                            },
-                            JsonMember {
-                                string: JsonString {
-                                    string: String::from("key-l")
-                                },
-                                element: JsonElement {
-                                    value: JsonValue::Array(JsonArray {
-                                        elements: vec![
-                                            JsonElement {
-                                                value: JsonValue::String(JsonString {
-                                                    string: String::from("list value")
-                                                })
-                                            }
-                                        ]
-                                    })
-                                }
-                            },
-                        ]
-                    }
-                )
-            }
-        };
+        let expected = JsonData::from(json!({
+            "key": "value",
+            "key-n": 101,
+            "key-o": {
+                "inner key": "inner value"
+            },
+            "key-l": ["list value"]
+        }));
 
         let json_result = handle_file("tests/step4/valid2.json");
         assert!(json_result.is_ok());
-        assert_eq!(json_result.unwrap(), expected);       }
+        assert_eq!(json_result.unwrap(), expected);
+    }
 
     #[test]
     fn test_step_4_invalid() {
         let json_result = handle_file("tests/step4/invalid.json");
         assert!(json_result.is_err());
-        assert!(json_result.err().unwrap().to_string() == "Invalid Character");
+        assert!(json_result.err().unwrap().to_string() == "Invalid Character at offset 97");
     }
 
     #[test]
     fn test_step_5_valid() {
-        let expected = JsonData {
-            element: JsonElement {
-                value: JsonValue::Object(
-                    JsonObject {
-                        members: vec![
-                            JsonMember {
-                                string: JsonString {
-                                    string: String::from("key1")
-                                },
-                                element: JsonElement {
-                                    value: JsonValue::Number(JsonNumber { 
-                                        integer: 100, 
-                                        fraction: None, 
-                                        exponent: None,
-                                    })
-                                }
-                            },
-                            JsonMember {
-                                string: JsonString {
-                                    string: String::from("key2")
-                                },
-                                element: JsonElement {
-                                    value: JsonValue::Number(JsonNumber { 
-                                        integer: 100, 
-                                        fraction: Some(0), 
-                                        exponent: None,
-                                    })
-                                }
-                            },
-                            JsonMember {
-                                string: JsonString {
-                                    string: String::from("key3")
-                                },
-                                element: JsonElement {
-                                    value: JsonValue::Number(JsonNumber { 
-                                        integer: 100, 
-                                        fraction: Some(0), 
-                                        exponent: Some(10),
-                                    })
-                                }
-                            },
-                            JsonMember {
-                                string: JsonString {
-                                    string: String::from("key4")
-                                },
-                                element: JsonElement {
-                                    value: JsonValue::Number(JsonNumber { 
-                                        integer: 100, 
-                                        fraction: Some(0), 
-                                        exponent: Some(-10),
-                                    })
-                                }
-                            },
-                            JsonMember {
-                                string: JsonString {
-                                    string: String::from("key5")
-                                },
-                                element: JsonElement {
-                                    value: JsonValue::Number(JsonNumber { 
-                                        integer: 100, 
-                                        fraction: Some(0), 
-                                        exponent: Some(10),
-                                    })
-                                }
-                            },
-                            JsonMember {
-                                string: JsonString {
-                                    string: String::from("key6")
-                                },
-                                element: JsonElement {
-                                    value: JsonValue::Number(JsonNumber { 
-                                        integer: 999, 
-                                        fraction: Some(9999), 
-                                        exponent: Some(999),
-                                    })
-                                }
-                            },
-                            JsonMember {
-                                string: JsonString {
-                                    string: String::from("key7")
-                                },
-                                element: JsonElement {
-                                    value: JsonValue::Number(JsonNumber { 
-                                        integer: 0, 
-                                        fraction: Some(0), 
-                                        exponent: Some(0),
-                                    })
-                                }
-                            },
-                            JsonMember {
-                                string: JsonString {
-                                    string: String::from("key8")
-                                },
-                                element: JsonElement {
-                                    value: JsonValue::Number(JsonNumber { 
-                                        integer: 100, 
-                                        fraction: None, 
-                                        exponent: Some(8),
-                                    })
-                                }
-                            },
-                            JsonMember {
-                                string: JsonString {
-                                    string: String::from("key9")
-                                },
-                                element: JsonElement {
-                                    value: JsonValue::Number(JsonNumber { 
-                                        integer: 9_223_372_036_854_775_807i64, 
-                                        fraction: None, 
-                                        exponent: None,
-                                    })
-                                }
-                            },
-                        ]
-                    }
-                )
-            }
-        };
+        let expected = JsonData::from(json!({
+            "key1": 100,
+            "key2": 100.0,
+            "key3": 100.0e+10,
+            "key4": 100.0e-10,
+            "key5": 100.0e10,
+            "key6": 999.9999e999,
+            "key7": 0.0E0,
+            "key8": 100e8,
+            "key9": 9223372036854775807
+        }));
 
         let json_result = handle_file("tests/step5/valid.json");
         assert!(json_result.is_ok());
@@ -513,25 +516,8 @@ mod tests {
 
     #[test]
     fn test_step_5_valid_2() {
-        let expected = JsonData {
-            element: JsonElement {
-                value: JsonValue::Array(
-                    JsonArray {
-                        elements: vec![
-                            JsonElement {
-                                value: JsonValue::True
-                            },
-                            JsonElement {
-                                value: JsonValue::False
-                            },
-                            JsonElement {
-                                value: JsonValue::Null
-                            },
-                        ]
-                    }
-                )
-            }
-        };
+        let expected = JsonData::from(json!([true, false, null]));
+
         let json_result = handle_file("tests/step5/valid2.json");
         assert!(json_result.is_ok());
         assert_eq!(json_result.unwrap(), expected);
@@ -539,48 +525,2285 @@ mod tests {
 
     #[test]
     fn test_step_5_valid_3() {
-        let expected = JsonData {
-            element: JsonElement {
-                value: JsonValue::Object(
-                    JsonObject {
-                        members: vec![
-                            JsonMember {
-                                string: JsonString{
-                                    string: String::from("chars")
-                                },
-                                element: JsonElement {
-                                    value: JsonValue::String(JsonString {
-                                        string: String::from("\"\\/\\b\\f\n\r\t")
-                                    })
-                                }
-                            },
-                            JsonMember {
-                                string: JsonString{
-                                    string: String::from("never\ngive\nup")
-                                },
-                                element: JsonElement {
-                                    value: JsonValue::String(JsonString {
-                                        string: String::from("never\nsurrender")
-                                    })
-                                }
-                            },
-                            JsonMember {
-                                string: JsonString{
-                                    string: String::from("hex")
-                                },
-                                element: JsonElement {
-                                    value: JsonValue::String(JsonString {
-                                        string: String::from("\\u0000\\uFFFF")
-                                    })
-                                }
-                            },
-                        ]
-                    }
-                )
-            }
-        };
+        let expected = JsonData::from(json!({
+            "chars": "\"\\/\u{8}\u{c}\n\r\t",
+            "never\ngive\nup": "never\nsurrender",
+            "hex": "\u{0}\u{ffff}"
+        }));
+
         let json_result = handle_file("tests/step5/valid3.json");
         assert!(json_result.is_ok());
         assert_eq!(json_result.unwrap(), expected);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_json_macro() {
+        use crate::write::value_to_string;
+
+        let name = "api";
+        let ports = vec![80, 443];
+        let value = json!({
+            "name": name,
+            "ports": ports,
+            "weights": [1, 2.5, -3, null, true],
+            "nested": {"list": [[], {}], "sum": (1 + 2)},
+            (format!("{}-key", name)): 'x',
+            r"raw\key": -1.5e-3
+        });
+        assert_eq!(
+            value_to_string(&value),
+            r#"{"name":"api","ports":[80,443],"weights":[1,2.5,-3,null,true],"nested":{"list":[[],{}],"sum":3},"api-key":"x","raw\\key":-0.0015}"#
+        );
+
+        // Rust spellings of numbers that JSON does not have.
+        let value = json!([0xff, -0o17, 0b101, 1_000u32, 2.5f32, 1e3, 007, 1., -0x8000_0000_0000_0000i128, 'é', "\u{1F600}\n"]);
+        assert_eq!(value_to_string(&value), r#"[255,-15,5,1000,2.5,1000.0,7,1.0,-9223372036854775808,"é","😀\n"]"#);
+        assert_eq!(json!([1f64, 1e0]), json!([1.0, 1.0]));
+
+        // A whole value from an expression.
+        assert_eq!(json!(name), json!("api"));
+        assert_eq!(json!(ports), json!([80, 443]));
+        assert_eq!(json!(Some(1.5)), json!(1.5));
+        assert_eq!(json!((1 + 2)), json!(3));
+    }
+
+    #[test]
+    fn test_diff() {
+        use crate::diff::*;
+
+        let left = handle_file("tests/diff/left.json").unwrap();
+        let right = handle_file("tests/diff/right.json").unwrap();
+        let differences = diff(&left, &right, &DiffOptions::default());
+        let paths: Vec<&str> = differences.iter().map(|difference| difference.path.as_str()).collect();
+        assert_eq!(paths, vec!["/version", "/tags/0", "/tags/1", "/tags/2", "/meta/updated", "/meta/license", "/removed"]);
+        assert_eq!(differences[6].change, Change::Removed(JsonValue::Null));
+        assert_eq!(differences[5].change, Change::Added(JsonValue::String(JsonString {
+            string: String::from("MIT")
+        })));
+    }
+
+    #[test]
+    fn test_diff_numbers() {
+        use crate::diff::*;
+
+        let left = json_valid(&mut r#"{"a": 1, "b": [0, 1e0], "c": 2}"#.as_bytes()).unwrap();
+        let right = json_valid(&mut r#"{"a": 1.0, "b": [-0, 1], "c": 2.5}"#.as_bytes()).unwrap();
+        let differences = diff(&left, &right, &DiffOptions::default());
+        let paths: Vec<&str> = differences.iter().map(|difference| difference.path.as_str()).collect();
+        assert_eq!(paths, vec!["/c"]);
+
+        let options = DiffOptions { ignore_array_order: true, ..Default::default() };
+        let left = json_valid(&mut "[1, 2.0, 1e0]".as_bytes()).unwrap();
+        let right = json_valid(&mut "[2, 1.0, 1]".as_bytes()).unwrap();
+        assert_eq!(diff(&left, &right, &options), vec![]);
+    }
+
+    #[test]
+    fn test_diff_ignore_order_and_keys() {
+        use crate::diff::*;
+
+        let left = handle_file("tests/diff/left.json").unwrap();
+        let right = handle_file("tests/diff/right.json").unwrap();
+        let options = DiffOptions {
+            ignore_array_order: true,
+            ignore_keys: vec![String::from("updated"), String::from("license"), String::from("removed")],
+        };
+        let differences = diff(&left, &right, &options);
+        assert_eq!(differences, vec![Difference {
+            path: String::from("/version"),
+            change: Change::Changed(
+                JsonValue::String(JsonString { string: String::from("0.1.0") }),
+                JsonValue::String(JsonString { string: String::from("0.2.0") }),
+            ),
+        }]);
+        assert_eq!(format_json(&differences), r#"[{"op":"change","path":"/version","old":"0.1.0","new":"0.2.0"}]"#);
+        assert_eq!(format_human(&differences, false), "~ /version: \"0.1.0\" -> \"0.2.0\"\n");
+    }
+
+    struct FixtureResolver;
+
+    impl crate::schema::Resolver for FixtureResolver {
+        fn resolve(&self, uri: &str) -> Result<JsonData, Error> {
+            let filename = uri.replace("https://example.com/", "tests/schema/");
+            handle_file(&filename)
+        }
+    }
+
+    #[test]
+    fn test_schema_valid() {
+        use crate::schema::*;
+
+        let schema = Schema::compile_with_resolver(&handle_file("tests/schema/schema.json").unwrap(), &FixtureResolver).unwrap();
+        let instance = handle_file("tests/schema/valid.json").unwrap();
+        assert_eq!(schema.validate(&instance), vec![]);
+    }
+
+    #[test]
+    fn test_schema_invalid() {
+        use crate::schema::*;
+
+        let schema = Schema::compile_with_resolver(&handle_file("tests/schema/schema.json").unwrap(), &FixtureResolver).unwrap();
+        let instance = handle_file("tests/schema/invalid.json").unwrap();
+        let errors = schema.validate(&instance);
+        let summary: Vec<String> = errors.iter().map(|error| {
+            format!("{} {} {}", error.instance_path, error.keyword, error.schema_path)
+        }).collect();
+        assert_eq!(summary, vec![
+            "/name pattern /properties/name/pattern",
+            "/port maximum /properties/port/maximum",
+            "/mode enum /properties/mode/enum",
+            "/servers/0/weight oneOf /properties/servers/items/$ref/properties/weight/oneOf",
+            "/servers/0 required /properties/servers/items/$ref/required",
+            "/servers/1/weight oneOf /properties/servers/items/$ref/properties/weight/oneOf",
+            "/servers/1 required /properties/servers/items/$ref/required",
+            "/servers uniqueItems /properties/servers/uniqueItems",
+            "/limits/connections multipleOf /properties/limits/$ref/properties/connections/multipleOf",
+            "/extra additionalProperties /additionalProperties",
+        ]);
+    }
+
+    #[test]
+    fn test_schema_unresolved_reference() {
+        use crate::schema::*;
+
+        let result = Schema::compile(&handle_file("tests/schema/schema.json").unwrap());
+        assert!(result.is_err());
+        assert_eq!(result.err().unwrap().to_string(), "Cannot resolve remote schema \"https://example.com/limits.json\"");
+    }
+
+    #[test]
+    fn test_schema_patterns() {
+        use crate::schema::*;
+
+        let schema = json_valid(&mut r#"{
+            "patternProperties": {
+                "^x-[a-z]+$": { "type": "string", "pattern": "^(\\d{1,3}\\.){3}\\d{1,3}$" },
+                "^[A-Z_]+$": { "type": "integer" }
+            },
+            "additionalProperties": false
+        }"#.as_bytes()).unwrap();
+        let schema = Schema::compile(&schema).unwrap();
+
+        let valid = json_valid(&mut r#"{"x-host": "10.0.0.1", "MAX_SIZE": 10}"#.as_bytes()).unwrap();
+        assert!(schema.is_valid(&valid));
+
+        let invalid = json_valid(&mut r#"{"x-host": "10.0.0", "MAX_SIZE": "10", "x-Bad": 1}"#.as_bytes()).unwrap();
+        let keywords: Vec<String> = schema.validate(&invalid).into_iter().map(|error| error.keyword).collect();
+        assert_eq!(keywords, vec!["pattern", "type", "additionalProperties"]);
+
+        // Patterns that only a reference reaches.
+        for (schema, instance, keywords) in [
+            (r##"{"$ref": "#/x", "x": {"pattern": "^a"}}"##, r#""ba""#, vec!["pattern"]),
+            (r##"{"$ref": "#/x", "x": {"pattern": "^a"}}"##, r#""ab""#, vec![]),
+            (r##"{"$ref": "#/x", "x": {"patternProperties": {"^a": {"type": "string"}}}}"##, r#"{"ab": 1}"#, vec!["type"]),
+            (r##"{"$ref": "#/x", "x": {"$ref": "#/x/y", "y": {"pattern": "^a"}}}"##, r#""b""#, vec!["pattern"]),
+        ] {
+            let schema = Schema::compile(&json_valid(&mut schema.as_bytes()).unwrap()).unwrap();
+            let errors = schema.validate(&json_valid(&mut instance.as_bytes()).unwrap());
+            let found: Vec<String> = errors.into_iter().map(|error| error.keyword).collect();
+            assert_eq!(found, keywords, "{}", instance);
+        }
+
+        // Matching is linear in the text, even for patterns that make a
+        // backtracking matcher take exponential time.
+        let schema = Schema::compile(&JsonData::from(json!({"pattern": "^(a*)*b$"}))).unwrap();
+        let started = std::time::Instant::now();
+        assert!(!schema.is_valid(&JsonData::from(JsonValue::String(JsonString { string: "a".repeat(10_000) }))));
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+        assert!(schema.is_valid(&JsonData::from(json!("aaab"))));
+        for (pattern, text, expected) in [
+            ("^(a|ab)(c|bcd)(d*)$", "abcd", true),
+            ("^(?:a|b)+?c{2,3}$", "abbaccc", true),
+            ("^(?:a|b)+?c{2,3}$", "abbacccc", false),
+            ("\\bcat\\b", "a cat!", true),
+            ("\\bcat\\b", "concat", false),
+            ("^()*$", "", true),
+            ("x?$", "abc", true),
+            ("^.$", "\u{2028}", false),
+            ("^.$", "\u{2029}", false),
+            ("^.$", "\u{85}", true),
+            ("^\\x41[\\b]$", "A\u{8}", true),
+            ("^[a-c\\d-]+$", "b-1", true),
+        ] {
+            let schema = Schema::compile(&JsonData::from(json!({"pattern": pattern}))).unwrap();
+            assert_eq!(schema.is_valid(&JsonData::from(JsonValue::String(JsonString { string: String::from(text) }))), expected, "{} {}", pattern, text);
+        }
+        assert_eq!(Schema::compile(&JsonData::from(json!({"pattern": "(a{1000}){1000}"}))).err().unwrap().to_string(), "Invalid pattern \"(a{1000}){1000}\": Pattern is too large");
+
+        // Syntax the matcher does not support is an error, not a different pattern.
+        for (pattern, message) in [
+            ("(a)\\1", "Pattern unsupported: backreference \\1"),
+            ("[a-\\d]", "Pattern unsupported: class escape in a range"),
+            ("[\\w-z]", "Pattern unsupported: class escape in a range"),
+            ("\\p{L}", "Pattern unsupported: escape \\p"),
+            ("\\01", "Pattern unsupported: octal escape"),
+            ("\\x4", "Invalid hex escape in pattern"),
+        ] {
+            let error = Schema::compile(&JsonData::from(json!({"pattern": pattern}))).err().unwrap();
+            assert_eq!(error.to_string(), format!("Invalid pattern {:?}: {}", pattern, message));
+        }
+    }
+
+    #[test]
+    fn test_schema_numbers() {
+        use crate::schema::*;
+
+        // Arbitrary mode keeps every digit, which doubles would round away.
+        let options = parse::ParseOptions { numbers: NumberMode::Arbitrary, ..Default::default() };
+        let read = |text: &str| parse::parse_with_options(&mut lex::lex_bytes(text.as_bytes()).unwrap(), &options).unwrap();
+        for (schema, instance, keywords) in [
+            (r#"{"maximum": 9007199254740992}"#, "9007199254740993", vec!["maximum"]),
+            (r#"{"maximum": 9007199254740992}"#, "9007199254740992.0", vec![]),
+            (r#"{"minimum": 18446744073709551615}"#, "18446744073709551614", vec!["minimum"]),
+            (r#"{"exclusiveMinimum": 0.1}"#, "0.1000000000000000000001", vec![]),
+            (r#"{"exclusiveMaximum": 1}"#, "1e0", vec!["exclusiveMaximum"]),
+            (r#"{"enum": [9007199254740993]}"#, "9007199254740992", vec!["enum"]),
+            (r#"{"enum": [9007199254740993]}"#, "9007199254740993", vec![]),
+            (r#"{"const": 1}"#, "1.0", vec![]),
+            (r#"{"uniqueItems": true}"#, "[100, 1e2]", vec!["uniqueItems"]),
+            (r#"{"multipleOf": 0.01}"#, "0.07", vec![]),
+            (r#"{"multipleOf": 0.01}"#, "0.071", vec!["multipleOf"]),
+            (r#"{"multipleOf": 3}"#, "123456789012345678901234567890", vec![]),
+            (r#"{"multipleOf": 3}"#, "123456789012345678901234567891", vec!["multipleOf"]),
+            (r#"{"multipleOf": 3}"#, "12345678901234567890123456789e-1", vec!["multipleOf"]),
+            (r#"{"multipleOf": 7}"#, "7e400", vec![]),
+            (r#"{"type": "integer"}"#, "1.5e0", vec!["type"]),
+            (r#"{"type": "integer"}"#, "12345678901234567890.000e3", vec![]),
+        ] {
+            let compiled = Schema::compile(&read(schema)).unwrap();
+            let found: Vec<String> = compiled.validate(&read(instance)).into_iter().map(|error| error.keyword).collect();
+            assert_eq!(found, keywords, "{} {}", schema, instance);
+        }
+
+        let schema = Schema::compile(&read(r#"{"maximum": 9007199254740992}"#)).unwrap();
+        assert_eq!(schema.validate(&read("9007199254740993"))[0].message, "9007199254740993 is greater than the maximum of 9007199254740992");
+    }
+
+    #[test]
+    fn test_schema_file_resolver() {
+        let schema = load_schema("tests/schema/schema.json").unwrap();
+        assert!(report("valid", handle_file("tests/schema/valid.json"), Some(&schema)));
+        assert!(!report("invalid", handle_file("tests/schema/invalid.json"), Some(&schema)));
+        assert_eq!(schema.validate(&handle_file("tests/schema/invalid.json").unwrap()).len(), 10);
+    }
+
+    #[test]
+    fn test_from_json_derive() {
+        use crate::from_json::*;
+        use std::collections::HashMap;
+
+        #[derive(FromJson, PartialEq, Debug, Default)]
+        struct Limits {
+            connections: u32,
+            #[json(default)]
+            timeout: Option<f64>,
+        }
+
+        #[derive(FromJson, PartialEq, Debug)]
+        enum Mode {
+            #[json(rename = "debug")]
+            Debug,
+            #[json(rename = "release")]
+            Release,
+        }
+
+        #[derive(FromJson, PartialEq, Debug)]
+        struct Config {
+            name: String,
+            #[json(rename = "listen-port")]
+            port: u16,
+            mode: Mode,
+            tags: Vec<String>,
+            env: HashMap<String, String>,
+            origin: (f64, f64),
+            parent: Option<String>,
+            #[json(default)]
+            retries: u8,
+            #[json(skip)]
+            loaded: bool,
+            #[json(flatten)]
+            limits: Limits,
+        }
+
+        let json_data = json_valid(&mut r#"{
+            "name": "api",
+            "listen-port": 8080,
+            "mode": "release",
+            "tags": ["a", "b"],
+            "env": {"HOME": "/root"},
+            "origin": [1.5, -2],
+            "loaded": true,
+            "connections": 100
+        }"#.as_bytes()).unwrap();
+        let config: Config = from_json(&json_data).unwrap();
+        assert_eq!(config, Config {
+            name: String::from("api"),
+            port: 8080,
+            mode: Mode::Release,
+            tags: vec![String::from("a"), String::from("b")],
+            env: HashMap::from([(String::from("HOME"), String::from("/root"))]),
+            origin: (1.5, -2.0),
+            parent: None,
+            retries: 0,
+            loaded: false,
+            limits: Limits { connections: 100, timeout: None },
+        });
+
+        // Unit variants of tagged enums are objects holding just the tag.
+        #[derive(FromJson, PartialEq, Debug)]
+        #[json(tag = "type")]
+        enum Internal {
+            Start,
+            #[json(rename = "stop")]
+            Stop,
+        }
+
+        #[derive(FromJson, PartialEq, Debug)]
+        #[json(tag = "t", content = "c")]
+        enum Adjacent {
+            Start,
+        }
+
+        let json_data = json_valid(&mut r#"[{"type": "Start"}, {"type": "stop"}]"#.as_bytes()).unwrap();
+        assert_eq!(from_json::<Vec<Internal>>(&json_data).unwrap(), vec![Internal::Start, Internal::Stop]);
+        let json_data = json_valid(&mut r#"{"t": "Start"}"#.as_bytes()).unwrap();
+        assert_eq!(from_json::<Adjacent>(&json_data).unwrap(), Adjacent::Start);
+        let json_data = json_valid(&mut r#"[{"type": "Pause"}]"#.as_bytes()).unwrap();
+        assert_eq!(from_json::<Vec<Internal>>(&json_data).unwrap_err().to_string(), "/0/type: Unknown variant \"Pause\"");
+        let json_data = json_valid(&mut r#""Start""#.as_bytes()).unwrap();
+        assert_eq!(from_json::<Internal>(&json_data).unwrap_err().to_string(), "(root): Expected object but found string");
+    }
+
+    #[test]
+    fn test_from_json_error_path() {
+        use crate::from_json::*;
+
+        #[derive(FromJson, Debug)]
+        #[allow(dead_code)]
+        struct Server {
+            host: String,
+            port: u16,
+        }
+
+        let json_data = json_valid(&mut r#"[{"host": "a", "port": 80}, {"host": "b", "port": 70000}]"#.as_bytes()).unwrap();
+        let err = from_json::<Vec<Server>>(&json_data).unwrap_err();
+        assert_eq!(err.path, "/1/port");
+        assert_eq!(err.to_string(), "/1/port: 70000 is out of range for u16");
+
+        let json_data = json_valid(&mut r#"[{"port": 80}]"#.as_bytes()).unwrap();
+        let err = from_json::<Vec<Server>>(&json_data).unwrap_err();
+        assert_eq!(err.to_string(), "/0/host: Missing field");
+    }
+
+    #[test]
+    fn test_to_json_derive() {
+        use crate::to_json::*;
+        use crate::write::value_to_string;
+        use std::collections::HashMap;
+
+        #[derive(ToJson)]
+        struct Limits {
+            connections: u32,
+            timeout: Option<f64>,
+        }
+
+        #[derive(ToJson)]
+        struct Config {
+            name: String,
+            #[json(rename = "listen-port")]
+            port: u16,
+            ratio: f64,
+            tags: Vec<&'static str>,
+            env: HashMap<String, bool>,
+            origin: (i32, i32),
+            #[json(skip)]
+            #[allow(dead_code)]
+            loaded: bool,
+            #[json(flatten)]
+            limits: Limits,
+        }
+
+        let config = Config {
+            name: String::from("api"),
+            port: 8080,
+            ratio: -0.0125,
+            tags: vec!["a", "b"],
+            env: HashMap::from([(String::from("b"), false), (String::from("a"), true)]),
+            origin: (1, -2),
+            loaded: true,
+            limits: Limits { connections: 100, timeout: None },
+        };
+        assert_eq!(
+            value_to_string(&config.to_json()),
+            r#"{"name":"api","listen-port":8080,"ratio":-0.0125,"tags":["a","b"],"env":{"a":true,"b":false},"origin":[1,-2],"connections":100,"timeout":null}"#
+        );
+    }
+
+    #[test]
+    fn test_to_json_enum_representations() {
+        use crate::to_json::*;
+        use crate::write::value_to_string;
+
+        #[derive(ToJson)]
+        enum External {
+            Unit,
+            #[json(rename = "new")]
+            Newtype(u8),
+            Tuple(u8, bool),
+            Struct { x: i8 },
+        }
+
+        #[derive(ToJson)]
+        #[json(tag = "type")]
+        enum Internal {
+            Unit,
+            Newtype(std::collections::BTreeMap<String, u8>),
+            Struct { x: i8 },
+            Nested(Point),
+        }
+
+        #[derive(ToJson)]
+        struct Point {
+            x: i8,
+            y: i8,
+        }
+
+        #[derive(ToJson)]
+        #[json(tag = "t", content = "c")]
+        enum Adjacent {
+            Unit,
+            Tuple(u8, bool),
+            Struct { x: i8 },
+        }
+
+        let external = vec![External::Unit, External::Newtype(1), External::Tuple(2, true), External::Struct { x: -3 }];
+        assert_eq!(value_to_string(&external.to_json()), r#"["Unit",{"new":1},{"Tuple":[2,true]},{"Struct":{"x":-3}}]"#);
+
+        let map = std::collections::BTreeMap::from([(String::from("k"), 1)]);
+        let internal = vec![Internal::Unit, Internal::Newtype(map), Internal::Struct { x: -3 }, Internal::Nested(Point { x: 1, y: 2 })];
+        assert_eq!(
+            value_to_string(&internal.to_json()),
+            r#"[{"type":"Unit"},{"type":"Newtype","k":1},{"type":"Struct","x":-3},{"type":"Nested","x":1,"y":2}]"#
+        );
+
+        let adjacent = vec![Adjacent::Unit, Adjacent::Tuple(2, false), Adjacent::Struct { x: -3 }];
+        assert_eq!(
+            value_to_string(&adjacent.to_json()),
+            r#"[{"t":"Unit"},{"t":"Tuple","c":[2,false]},{"t":"Struct","c":{"x":-3}}]"#
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        use crate::serde_support::*;
+        use serde::{Deserialize, Serialize};
+        use std::collections::BTreeMap;
+
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        enum Shape {
+            Point,
+            Circle(f64),
+            Rect { width: u32, height: u32 },
+        }
+
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct Drawing {
+            name: String,
+            visible: bool,
+            layer: Option<u8>,
+            shapes: Vec<Shape>,
+            labels: BTreeMap<String, (i32, i32)>,
+        }
+
+        let text = r#"{
+            "name": "sketch",
+            "visible": true,
+            "layer": null,
+            "shapes": ["Point", {"Circle": 2.5}, {"Rect": {"width": 3, "height": 4}}],
+            "labels": {"origin": [0, -1]}
+        }"#;
+        let drawing: Drawing = from_str(text).unwrap();
+        assert_eq!(drawing, Drawing {
+            name: String::from("sketch"),
+            visible: true,
+            layer: None,
+            shapes: vec![Shape::Point, Shape::Circle(2.5), Shape::Rect { width: 3, height: 4 }],
+            labels: BTreeMap::from([(String::from("origin"), (0, -1))]),
+        });
+
+        let written = to_string(&drawing).unwrap();
+        assert_eq!(
+            written,
+            r#"{"name":"sketch","visible":true,"layer":null,"shapes":["Point",{"Circle":2.5},{"Rect":{"width":3,"height":4}}],"labels":{"origin":[0,-1]}}"#
+        );
+        assert_eq!(from_str::<Drawing>(&written).unwrap(), drawing);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_json_value() {
+        use crate::serde_support::*;
+
+        let json_data = handle_file("tests/step4/valid2.json").unwrap();
+        let value: JsonValue = from_data(&json_data).unwrap();
+        assert_eq!(value, json_data.element.value);
+        assert_eq!(to_value(&json_data).unwrap(), json_data.element.value);
+
+        let err = from_str::<Vec<u8>>("[1, \"two\"]").unwrap_err();
+        assert_eq!(err.to_string(), "invalid type: string \"two\", expected u8");
+
+        // Decimals keep every digit through serde in both directions.
+        let text = "[1e400,0.1000000000000000000001,123456789012345678901234567890]";
+        let options = parse::ParseOptions { numbers: NumberMode::Arbitrary, ..Default::default() };
+        let json_data = parse::parse_with_options(&mut lex::lex_bytes(text.as_bytes()).unwrap(), &options).unwrap();
+        assert_eq!(from_data::<JsonValue>(&json_data).unwrap(), json_data.element.value);
+        assert_eq!(to_value(&json_data).unwrap(), json_data.element.value);
+        assert_eq!(to_string(&json_data).unwrap(), text);
+        let (large, small, integer): (f64, f32, u128) = from_data(&json_data).unwrap();
+        assert_eq!((large, small, integer), (f64::INFINITY, 0.1, 123456789012345678901234567890));
+        let err = from_data::<(f64, u64, u64)>(&json_data).unwrap_err();
+        assert_eq!(err.to_string(), "invalid type: floating point `0.1`, expected u64");
+    }
+
+    #[test]
+    fn test_borrowed_strings() {
+        use std::borrow::Cow;
+
+        let input = String::from(r#"{"plain": "value", "esc\naped": ["a\tb", 1.5, null]}"#);
+        let json_data = borrowed::parse(&input).unwrap();
+
+        let borrowed::JsonValue::Object(object) = &json_data.element.value else {
+            panic!("expected object");
+        };
+        assert!(matches!(object.members[0].string.string, Cow::Borrowed("plain")));
+        assert!(matches!(&object.members[0].element.value, borrowed::JsonValue::String(string) if matches!(string.string, Cow::Borrowed("value"))));
+        assert!(matches!(&object.members[1].string.string, Cow::Owned(string) if string == "esc\naped"));
+
+        let expected = JsonData::from(json!({"plain": "value", "esc\naped": ["a\tb", 1.5, null]}));
+        assert_eq!(json_data.into_owned(), expected);
+        assert_eq!(json_valid(&mut input.as_bytes()).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_borrowed_errors_match_owned() {
+        for filename in ["tests/step1/invalid.json", "tests/step2/invalid.json", "tests/step3/invalid.json", "tests/step4/invalid.json"] {
+            let input = std::fs::read_to_string(filename).unwrap();
+            let owned = handle_file(filename).unwrap_err().to_string();
+            assert_eq!(borrowed::parse(&input).unwrap_err().to_string(), owned);
+        }
+    }
+
+    #[test]
+    fn test_number_lexeme() {
+        let mut input = "[-0.25e+3, 10]";
+        let tokens = lex::lex(&mut input).unwrap();
+        assert_eq!(tokens[1], Token::Number("-0.25e+3", Span { start: 1, end: 9 }));
+        assert_eq!(tokens[3], Token::Number("10", Span { start: 11, end: 13 }));
+
+        for (input, message) in [
+            ("[1, 012]", "No leading zeros allowed in number at offset 4"),
+            ("-", "No digits following sign in number at offset 0"),
+            ("1.", "No fraction component after fraction marker in number at offset 0"),
+            ("[1.5e]", "No digits following exponent in number at offset 1"),
+            ("+5", "Invalid Character at offset 0"),
+        ] {
+            let result = json_valid(&mut BufReader::new(input.as_bytes()));
+            assert_eq!(result.unwrap_err().to_string(), message, "{}", input);
+        }
+
+        // Large inputs go through the indexed lexer, which must report the
+        // same offset.
+        let long = format!("[{}x]", "1, ".repeat(2000));
+        let result = json_valid(&mut BufReader::new(long.as_bytes()));
+        assert_eq!(result.unwrap_err().to_string(), "Invalid Character at offset 6001");
+    }
+
+    #[test]
+    fn test_number_modes() {
+        let parse_number = |input: &str, numbers: NumberMode| {
+            let mut tokens = lex::lex(&mut &*input).unwrap();
+            let options = parse::ParseOptions { numbers, ..Default::default() };
+            parse::parse_with_options(&mut tokens, &options).map(|data| match data.element.value {
+                JsonValue::Number(number) => number,
+                value => panic!("{:?}", value),
+            })
+        };
+
+        for (input, expected) in [
+            ("9223372036854775807", JsonNumber::Integer(i64::MAX)),
+            ("-9223372036854775808", JsonNumber::Integer(i64::MIN)),
+            ("18446744073709551615", JsonNumber::Unsigned(u64::MAX)),
+            ("18446744073709551616", JsonNumber::Float(18446744073709551616.0)),
+            ("-0", JsonNumber::Float(-0.0)),
+            ("-0.5", JsonNumber::Float(-0.5)),
+            ("1.05", JsonNumber::Float(1.05)),
+            ("1e400", JsonNumber::Decimal(String::from("1e400"))),
+            ("-1.5E309", JsonNumber::Decimal(String::from("-1.5E309"))),
+            ("1.7976931348623157e308", JsonNumber::Float(f64::MAX)),
+        ] {
+            assert_eq!(parse_number(input, NumberMode::Exact).unwrap(), expected, "{}", input);
+        }
+
+        assert_eq!(parse_number("12", NumberMode::Float).unwrap(), JsonNumber::Float(12.0));
+        assert_eq!(parse_number("12", NumberMode::Arbitrary).unwrap(), JsonNumber::Integer(12));
+        assert_eq!(parse_number("0.1e1", NumberMode::Arbitrary).unwrap(), JsonNumber::Decimal(String::from("0.1e1")));
+        assert_eq!(parse_number("1e400", NumberMode::Float).unwrap_err().to_string(), "Number out of range at offset 0");
+
+        // An integer too long for `f64` is kept whole rather than rejected.
+        let long = format!("-{}", "9".repeat(400));
+        assert_eq!(parse_number(&long, NumberMode::Exact).unwrap(), JsonNumber::Decimal(long.clone()));
+        assert!(parse_number(&long, NumberMode::Float).is_err());
+
+        // Both the fast path and the fallback must agree with the standard library.
+        for input in [
+            "0.1", "0.3", "-2.5e-3", "123456789012345", "1234567890123456789", "9007199254740993",
+            "1e22", "1e23", "4.9e-324", "2.2250738585072014e-308", "1.7976931348623157e308",
+            "0.000001", "3.141592653589793", "7e-22", "123.456e-20", "1E+2",
+        ] {
+            let expected: f64 = input.parse().unwrap();
+            assert_eq!(parse_number(input, NumberMode::Float).unwrap(), JsonNumber::Float(expected), "{}", input);
+        }
+    }
+
+    fn json_fixtures(directory: &Path) -> Vec<std::path::PathBuf> {
+        let mut files = vec![];
+        for entry in std::fs::read_dir(directory).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                files.extend(json_fixtures(&path));
+            } else if path.extension().is_some_and(|extension| extension == "json") {
+                files.push(path);
+            }
+        }
+        files.sort();
+        files
+    }
+
+    // A small xorshift generator so the differential tests are repeatable.
+    struct Random(u64);
+
+    impl Random {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, bound: usize) -> usize {
+            (self.next() % bound as u64) as usize
+        }
+    }
+
+    fn random_document(random: &mut Random, depth: usize, out: &mut String) {
+        const WHITESPACE: [&str; 5] = ["", " ", "\n", "\t  ", "\r\n    "];
+        const STRINGS: [&str; 11] = ["", "a", "key", "with space", "\\\\", "\\\"quoted\\\"", "\\n\\t\\/", "\\u00e9\\uD83D", "é", "日本語", "🎉 party"];
+
+        out.push_str(WHITESPACE[random.below(WHITESPACE.len())]);
+        match if depth == 0 { 2 + random.below(4) } else { random.below(6) } {
+            0 => {
+                out.push('{');
+                for member in 0..random.below(5) {
+                    if member > 0 {
+                        out.push(',');
+                    }
+                    out.push('"');
+                    out.push_str(STRINGS[random.below(STRINGS.len())]);
+                    out.push_str("\":");
+                    random_document(random, depth - 1, out);
+                }
+                out.push('}');
+            }
+            1 => {
+                out.push('[');
+                for element in 0..random.below(5) {
+                    if element > 0 {
+                        out.push(',');
+                    }
+                    random_document(random, depth - 1, out);
+                }
+                out.push(']');
+            }
+            2 => {
+                out.push('"');
+                for _ in 0..random.below(4) {
+                    out.push_str(STRINGS[random.below(STRINGS.len())]);
+                }
+                out.push('"');
+            }
+            3 => out.push_str(["0", "-12.5e3", "1E+2", "3.25", "-0"][random.below(5)]),
+            4 => out.push_str(["true", "false", "null"][random.below(3)]),
+            _ => out.push_str(&"x".repeat(random.below(70))),
+        }
+        out.push_str(WHITESPACE[random.below(WHITESPACE.len())]);
+    }
+
+    // Byte-at-a-time version of `simd::structural_index`.
+    fn reference_index(bytes: &[u8]) -> Vec<u32> {
+        let mut index = vec![];
+        let mut open = None;
+        let mut escaped = false;
+        let mut scalar = false;
+        for (position, &byte) in bytes.iter().enumerate() {
+            let position = position as u32;
+            let quote = byte == b'"' && !escaped;
+            escaped = byte == b'\\' && !escaped;
+
+            if let Some(entry) = open {
+                if quote {
+                    index.push(position);
+                    open = None;
+                } else if byte == b'\\' || !(0x20..0x80).contains(&byte) {
+                    index[entry] |= lex::simd::SLOW;
+                }
+                continue;
+            }
+            match byte {
+                _ if quote => {
+                    open = Some(index.len());
+                    index.push(position | lex::simd::STRING);
+                    scalar = false;
+                }
+                b'{' | b'}' | b'[' | b']' | b':' | b',' => {
+                    index.push(position);
+                    scalar = false;
+                }
+                b' ' | b'\t' | b'\n' | b'\r' => scalar = false,
+                _ => {
+                    if !scalar {
+                        index.push(position);
+                    }
+                    scalar = true;
+                }
+            }
+        }
+        index
+    }
+
+    fn assert_same_lexing(input: &[u8]) {
+        let text = String::from_utf8_lossy(input);
+        assert_eq!(lex::simd::structural_index(input).unwrap(), reference_index(input), "{:?}", text);
+        let scalar = lex::lex_scalar(input).map_err(|err| err.to_string());
+        let indexed = lex::lex_indexed(input).map_err(|err| err.to_string());
+        assert_eq!(indexed, scalar, "{:?}", text);
+    }
+
+    #[test]
+    fn test_indexed_lexer_matches_scalar() {
+        for file in json_fixtures(Path::new("tests")) {
+            assert_same_lexing(&std::fs::read(file).unwrap());
+        }
+
+        let mut random = Random(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..2000 {
+            let mut input = String::new();
+            random_document(&mut random, 4, &mut input);
+            assert_same_lexing(input.as_bytes());
+
+            // Break the document in a few places, including bytes that end
+            // strings early or are not valid UTF-8.
+            let mut bytes = input.into_bytes();
+            for _ in 0..3 {
+                const REPLACEMENTS: &[u8] = b"{}[],: \n\x01ae-09tx\"\\\x80\xc3\xff";
+                let position = random.below(bytes.len());
+                bytes[position] = REPLACEMENTS[random.below(REPLACEMENTS.len())];
+            }
+            assert_same_lexing(&bytes);
+        }
+    }
+
+    #[test]
+    fn test_simd_classifier_matches_scalar() {
+        let mut random = Random(0x2545_f491_4f6c_dd1d);
+        for _ in 0..1000 {
+            const BYTES: &[u8] = b"\"\\{}[]:, \t\n\rax\x00\x1f\x20\x7f\x80\xff";
+            let mut block = [0u8; 64];
+            for byte in block.iter_mut() {
+                *byte = BYTES[random.below(BYTES.len())];
+            }
+            let expected = lex::simd::classify_scalar(&block);
+            #[cfg(target_arch = "x86_64")]
+            {
+                assert_eq!(lex::simd::x86::classify_sse2(&block), expected);
+                if std::arch::is_x86_feature_detected!("avx2") {
+                    assert_eq!(lex::simd::x86::classify_avx2(&block), expected);
+                }
+            }
+            assert_eq!(lex::simd::classifier()(&block), expected);
+        }
+    }
+
+    #[test]
+    fn test_lex_utf8() {
+        let tokens = lex::lex_bytes("[\"é\", \"caf\\u00e9 ☕\\n\", \"🎉\"]".as_bytes()).unwrap();
+        assert_eq!(tokens[1], Token::String(std::borrow::Cow::Borrowed("é")));
+        assert_eq!(tokens[3], Token::String(std::borrow::Cow::Owned(String::from("café ☕\n"))));
+        assert_eq!(tokens[5], Token::String(std::borrow::Cow::Borrowed("🎉")));
+
+        for (input, message) in [
+            (&b"[\"\xc3\"]"[..], "Invalid UTF-8 sequence at offset 2"),
+            (b"\"\xc0\xaf\"", "Invalid UTF-8 sequence at offset 1"),
+            (b"\"ok \xed\xa0\x80\"", "Invalid UTF-8 sequence at offset 4"),
+            (b"\"\xf4\x90\x80\x80\"", "Invalid UTF-8 sequence at offset 1"),
+            (b"\"\xe2\x98", "Invalid UTF-8 sequence at offset 1"),
+            (b"{\"abc", "Unterminated string at offset 1"),
+            (b"\"\\u12\"", "Not enough hex characters in escape at offset 3"),
+            (b"\"\\u12x4\"", "Invalid Escape Hex Character at offset 5"),
+            (b"\"\\q\"", "Invalid Escape Character at offset 2"),
+            (b"\"tab\there\"", "Invalid Unicode Character in String at offset 4"),
+            (b"\xc3\xa9", "Invalid Character at offset 0"),
+        ] {
+            let result = json_valid(&mut BufReader::new(input));
+            assert_eq!(result.unwrap_err().to_string(), message, "{:?}", String::from_utf8_lossy(input));
+        }
+    }
+
+    // Parses with both tree builders and checks the properties the fuzz target
+    // checks: no panics, only `InvalidData` errors, agreement between the
+    // lexers and the parsers, and a lossless write and re-parse.
+    fn check_parse_properties(bytes: &[u8]) {
+        let owned = json_valid(&mut BufReader::new(bytes));
+        if let Err(err) = &owned {
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{}", err);
+        }
+
+        let scalar = lex::lex_scalar(bytes).map_err(|err| err.to_string());
+        let indexed = lex::lex_indexed(bytes).map_err(|err| err.to_string());
+        assert_eq!(scalar, indexed);
+
+        let owned = owned.map_err(|err| err.to_string());
+        if let Ok(string) = std::str::from_utf8(bytes) {
+            let borrowed = borrowed::parse(string).map(borrowed::JsonData::into_owned);
+            assert_eq!(borrowed.map_err(|err| err.to_string()), owned);
+
+            let recovered = recover::parse(string);
+            match &owned {
+                Ok(data) => {
+                    assert_eq!(recovered.errors, vec![]);
+                    assert_eq!(recovered.data.without_spans().as_ref(), Some(data));
+                }
+                Err(_) => assert!(!recovered.errors.is_empty()),
+            }
+        }
+
+        if let Ok(data) = owned {
+            let written = write::value_to_string(&data.element.value);
+            assert_eq!(json_valid(&mut BufReader::new(written.as_bytes())).unwrap(), data);
+        }
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn test_parse_arbitrary_bytes(bytes in proptest::collection::vec(proptest::num::u8::ANY, 0..256)) {
+            check_parse_properties(&bytes);
+        }
+
+        #[test]
+        fn test_parse_json_fragments(fragments in proptest::collection::vec(proptest::sample::select(vec![
+            "{", "}", "[", "]", ",", ":", " ", "\n", "\"", "\"key\"", "\\", "\\u00e9", "\\uZZ", "\\n",
+            "true", "tru", "false", "null", "0", "-", "12", ".5", "e+3", "E", "1.5e-3", "é", "\u{1f389}", "\x01",
+        ]), 0..64)) {
+            check_parse_properties(fragments.concat().as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_nesting_limit() {
+        let nested = |depth: usize| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+        assert!(json_valid(&mut BufReader::new(nested(parse::DEFAULT_MAX_DEPTH).as_bytes())).is_ok());
+        for input in [nested(parse::DEFAULT_MAX_DEPTH + 1), "[{\"a\":".repeat(100_000)] {
+            let result = json_valid(&mut BufReader::new(input.as_bytes()));
+            assert_eq!(result.unwrap_err().to_string(), "Invalid Json: Nesting too deep");
+            assert_eq!(borrowed::parse(&input).unwrap_err().to_string(), "Invalid Json: Nesting too deep");
+        }
+    }
+
+    #[test]
+    fn test_json_test_suite() {
+        let mut implementation_defined = std::collections::HashMap::new();
+        for line in std::fs::read_to_string("tests/JSONTestSuite/implementation_defined.txt").unwrap().lines() {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut fields = line.splitn(3, ' ');
+            let (file, behavior) = (fields.next().unwrap(), fields.next().unwrap());
+            implementation_defined.insert(String::from(file), behavior == "accept");
+        }
+
+        let mut failures = vec![];
+        for path in json_fixtures(Path::new("tests/JSONTestSuite/test_parsing")) {
+            let name = path.file_name().unwrap().to_string_lossy().into_owned();
+            let bytes = std::fs::read(&path).unwrap();
+            let accepted = json_valid(&mut BufReader::new(bytes.as_slice())).is_ok();
+            let expected = match &name[..2] {
+                "y_" => true,
+                "n_" => false,
+                _ => match implementation_defined.remove(&name) {
+                    Some(expected) => expected,
+                    None => {
+                        failures.push(format!("{} has no entry in implementation_defined.txt", name));
+                        continue;
+                    }
+                },
+            };
+
+            let outcome = if accepted { "accepted" } else { "rejected" };
+            if accepted == expected {
+                println!("pass {} ({})", name, outcome);
+            } else {
+                println!("FAIL {} ({})", name, outcome);
+                failures.push(format!("{} was {}", name, outcome));
+            }
+            check_parse_properties(&bytes);
+        }
+
+        for name in implementation_defined.keys() {
+            failures.push(format!("{} is listed in implementation_defined.txt but missing", name));
+        }
+        assert!(failures.is_empty(), "{:#?}", failures);
+    }
+
+    #[test]
+    fn test_escape_round_trip() {
+        let parse_string = |input: &str| match json_valid(&mut BufReader::new(input.as_bytes())).unwrap().element.value {
+            JsonValue::String(string) => string.string,
+            value => panic!("{:?}", value),
+        };
+
+        for (escape, expected) in [
+            (r#"\""#, "\""), (r"\\", "\\"), (r"\/", "/"), (r"\b", "\u{8}"), (r"\f", "\u{c}"),
+            (r"\n", "\n"), (r"\r", "\r"), (r"\t", "\t"), (r"\u0041", "A"), (r"\u00e9", "é"),
+            (r"\u0000", "\0"), (r"\uD834\uDD1E", "𝄞"), (r"\uD800", "\u{fffd}"), (r"\uDC00\u0041", "\u{fffd}A"),
+            (r"\uD800\uD800\n", "\u{fffd}\u{fffd}\n"),
+        ] {
+            let decoded = parse_string(&format!("\"{}\"", escape));
+            assert_eq!(decoded, expected, "{}", escape);
+
+            let written = write::value_to_string(&JsonValue::String(JsonString { string: decoded.clone() }));
+            assert_eq!(parse_string(&written), decoded, "{}", written);
+        }
+
+        // Every control character is written back escaped.
+        let controls: String = (0u8..0x20).map(char::from).collect();
+        let written = write::value_to_string(&JsonValue::String(JsonString { string: controls.clone() }));
+        assert!(written.bytes().all(|byte| byte >= 0x20), "{}", written);
+        assert!(written.contains(r"\b\t\n\u000b\f\r"), "{}", written);
+        assert_eq!(parse_string(&written), controls);
+    }
+
+    #[test]
+    fn test_source_spans() {
+        let input = "{\n  \"name\": \"café\",\n  \"port\": 70000,\n  \"hosts\": [\"a\", true]\n}\n";
+        let json_data = spanned::parse(input).unwrap();
+        let position = |offset, line, column| spanned::Position { offset, line, column };
+
+        assert_eq!(json_data.element.span.start, position(0, 1, 1));
+        assert_eq!(json_data.element.span.end, position(input.len() - 1, 5, 2));
+
+        let spanned::JsonValue::Object(object) = &json_data.element.value else {
+            panic!("expected object");
+        };
+        let port = object.member("port").unwrap();
+        assert_eq!(port.string.span.start, position(23, 3, 3));
+        assert_eq!(port.string.span.end, position(29, 3, 9));
+        assert_eq!(port.element.span.start, position(31, 3, 11));
+        assert_eq!(port.element.span.end, position(36, 3, 16));
+        assert_eq!(port.span.start, port.string.span.start);
+        assert_eq!(port.span.end, port.element.span.end);
+
+        // Columns count characters, so the comma after "café" is column 17.
+        let name = object.get("name").unwrap();
+        assert_eq!(name.span.end.column, 17);
+        assert_eq!(&input[name.span.start.offset..name.span.end.offset], "\"café\"");
+
+        let spanned::JsonValue::Array(hosts) = &object.get("hosts").unwrap().value else {
+            panic!("expected array");
+        };
+        assert_eq!(&input[hosts.elements[1].span.start.offset..hosts.elements[1].span.end.offset], "true");
+        assert_eq!(hosts.elements[1].span.start.line, 4);
+
+        assert_eq!(json_data.without_spans().unwrap(), json_valid(&mut input.as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn test_line_positions() {
+        let text = "aé😀\n".repeat(40) + &"xé😀".repeat(200) + "\nend";
+        let lines = spanned::Lines::new(&text);
+        for (offset, _) in text.char_indices() {
+            let line = text[..offset].matches('\n').count();
+            let start = text[..offset].rfind('\n').map_or(0, |newline| newline + 1);
+            let position = lines.position(offset);
+            assert_eq!((position.line, position.column), (line + 1, text[start..offset].chars().count() + 1), "{}", offset);
+            assert_eq!(lines.utf16_position(offset), (line, text[start..offset].encode_utf16().count()), "{}", offset);
+        }
+
+        // Positions on one long line take time in proportion to the text.
+        let input = format!("[{}0]", "{\"é\": [1, 2]}, ".repeat(50_000));
+        let start = std::time::Instant::now();
+        let json_data = spanned::parse(&input).unwrap();
+        assert!(start.elapsed() < std::time::Duration::from_secs(5));
+        assert_eq!(json_data.element.span.end.column, input.chars().count() + 1);
+    }
+
+    #[test]
+    fn test_source_spans_match_plain_parse() {
+        for filename in json_fixtures(Path::new("tests")) {
+            let bytes = std::fs::read(&filename).unwrap();
+            let Ok(input) = String::from_utf8(bytes) else {
+                continue;
+            };
+            let plain = json_valid(&mut input.as_bytes()).map_err(|err| err.to_string());
+            let spanned = spanned::parse(&input).map(|data| data.without_spans().unwrap()).map_err(|err| err.to_string());
+            assert_eq!(spanned, plain, "{}", filename.display());
+        }
+    }
+
+    #[test]
+    fn test_cst_round_trip() {
+        for filename in json_fixtures(Path::new("tests")) {
+            let Ok(input) = std::fs::read_to_string(&filename) else {
+                continue;
+            };
+            let plain = json_valid(&mut input.as_bytes());
+            match (cst::parse(&input), plain) {
+                (Ok(document), Ok(plain)) => {
+                    assert_eq!(document.to_string(), input, "{}", filename.display());
+                    assert_eq!(document.to_data().unwrap(), plain, "{}", filename.display());
+                }
+                // Only comments are accepted beyond what the plain parser takes.
+                (Ok(document), Err(_)) => {
+                    assert_eq!(document.to_string(), input, "{}", filename.display());
+                    let tokens = lex::lex_lossless(input.as_bytes()).unwrap();
+                    assert!(tokens.iter().any(|(token, _)| *token == Token::Comment), "{}", filename.display());
+                }
+                (Err(_), plain) => assert!(plain.is_err(), "{}", filename.display()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_cst_edits() {
+        let input = "// package manifest\n{\n    \"name\":  \"demo\",\n    \"version\": \"1.2.3\", /* bumped by CI */\n    \"ratio\": 1.50e0,\n    \"tags\": [ \"a\",\t\"b\" ]\n}\n";
+        let mut document = cst::parse(input).unwrap();
+        assert_eq!(document.to_string(), input);
+
+        let object = document.value.as_object_mut().unwrap();
+        object.get_mut("version").unwrap().replace(&json!("1.2.4"));
+        assert_eq!(document.to_string(), input.replace("1.2.3", "1.2.4"));
+
+        let object = document.value.as_object_mut().unwrap();
+        object.insert("private", &json!(true));
+        object.get_mut("tags").unwrap().as_array_mut().unwrap().push(&json!("c"));
+        assert!(object.remove("name"));
+        assert!(!object.remove("missing"));
+        assert_eq!(
+            document.to_string(),
+            "// package manifest\n{\n    \"version\": \"1.2.4\", /* bumped by CI */\n    \"ratio\": 1.50e0,\n    \"tags\": [ \"a\",\t\"b\",\t\"c\" ],\n    \"private\": true\n}\n"
+        );
+
+        let object = document.value.as_object_mut().unwrap();
+        assert!(object.remove("private"));
+        assert_eq!(object.get("tags").unwrap().as_array().unwrap().get(2).unwrap().to_string(), "\t\"c\"");
+
+        let mut empty = cst::parse("{ }").unwrap();
+        empty.value.as_object_mut().unwrap().insert("a", &json!([1, 2]));
+        assert_eq!(empty.to_string(), "{\"a\": [1,2] }");
+
+        for (input, message) in [
+            ("/* open", "Unterminated comment at offset 0"),
+            ("// only a comment", "Empty Json"),
+            ("{\"a\" 1}", "No colon in member"),
+            ("[1 2]", "No Closing Bracket on Array"),
+        ] {
+            assert_eq!(cst::parse(input).unwrap_err().to_string(), message, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_parsers_agree() {
+        let input = "{\"a\": [1, -2.5e3, \"x\\ny\"], \"b\": {\"c\": null, \"d\": [true, false, {}]}, \"e\": []}";
+        let plain = parse::parse(&mut lex::lex_bytes(input.as_bytes()).unwrap()).unwrap();
+        assert_eq!(borrowed::parse(input).unwrap().into_owned(), plain);
+        assert_eq!(spanned::parse(input).unwrap().without_spans().unwrap(), plain);
+        assert_eq!(cst::parse(input).unwrap().to_data().unwrap(), plain);
+        assert!(recover::parse(input).errors.is_empty());
+
+        for (input, message) in [
+            ("{\"a\":}", "No element for string"),
+            ("{\"a\": 1,}", "Failed to parse members"),
+            ("{1: 2}", "No Closing Paren on Object"),
+            ("{\"a\" 1}", "No colon in member"),
+            ("[,]", "No Closing Bracket on Array"),
+            ("[1,]", "Failed to parse element"),
+            ("[1 2]", "No Closing Bracket on Array"),
+            (":", "Invalid Json: No Element"),
+            ("[1] 2", "Invalid Json: Additional Data Left Over"),
+        ] {
+            let plain = parse::parse(&mut lex::lex_bytes(input.as_bytes()).unwrap()).unwrap_err().to_string();
+            assert_eq!(plain, message, "{}", input);
+            assert_eq!(borrowed::parse(input).unwrap_err().to_string(), message, "{}", input);
+            assert_eq!(spanned::parse(input).unwrap_err().to_string(), message, "{}", input);
+            assert_eq!(cst::parse(input).unwrap_err().to_string(), message, "{}", input);
+            assert!(!recover::parse(input).errors.is_empty(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_recover_errors() {
+        let input = "{\n  \"a\": 1 \"b\": tru,\n  \"c\": [1,, 2,],\n  \"d\": \"bad \\q\"\n  \"e\": {\"f\" 3}\n";
+        let recovered = recover::parse(input);
+        let errors: Vec<String> = recovered.errors.iter().map(ToString::to_string).collect();
+        assert_eq!(errors, vec![
+            "2:10: Expected ',' or '}'",
+            "2:15: Invalid Character at offset 16",
+            "3:11: Expected a value",
+            "3:14: Trailing comma",
+            "4:8: Invalid Escape Character at offset 51",
+            "5:3: Expected ',' or '}'",
+            "5:13: No colon in member",
+            "6:1: No Closing Paren on Object",
+        ]);
+
+        // Everything that could be read is still in the tree.
+        let spanned::JsonValue::Object(object) = &recovered.data.element.value else {
+            panic!("expected object");
+        };
+        let keys: Vec<&str> = object.members.iter().map(|member| member.string.string.as_str()).collect();
+        assert_eq!(keys, vec!["a", "b", "c", "d", "e"]);
+        assert_eq!(object.get("b").unwrap().value, spanned::JsonValue::Error);
+        assert_eq!(object.get("d").unwrap().value, spanned::JsonValue::Error);
+        let spanned::JsonValue::Array(array) = &object.get("c").unwrap().value else {
+            panic!("expected array");
+        };
+        assert_eq!(array.elements.len(), 2);
+        assert_eq!(recovered.data.clone().without_spans(), None);
+
+        for (input, expected) in [
+            ("", vec!["1:1: Empty Json"]),
+            ("[1] [2]", vec!["1:5: Invalid Json: Additional Data Left Over"]),
+            ("{\"a\": [1, 2}", vec!["1:12: No Closing Bracket on Array"]),
+            ("[{\"a\": 1]", vec!["1:9: No Closing Paren on Object"]),
+            ("{1: 2, , \"b\"}", vec!["1:2: Expected a string key", "1:8: Expected a string key", "1:13: No colon in member", "1:13: Expected a value"]),
+        ] {
+            let errors: Vec<String> = recover::parse(input).errors.iter().map(ToString::to_string).collect();
+            assert_eq!(errors, expected, "{}", input);
+        }
+
+        // Invalid UTF-8 is one of the errors, and later positions count the
+        // original bytes.
+        let recovered = recover::parse_bytes(b"{\"a\": \"\xff\", \"b\" 1}");
+        let errors: Vec<String> = recovered.errors.iter().map(ToString::to_string).collect();
+        assert_eq!(errors, vec!["1:7: Invalid UTF-8 sequence at offset 7", "1:16: No colon in member"]);
+        assert_eq!(recovered.errors[1].span.start.offset, 15);
+
+        let deep = format!("{}{}", "[".repeat(200), "]".repeat(200));
+        let errors = recover::parse(&deep).errors;
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "Invalid Json: Nesting too deep");
+    }
+
+    fn lsp_session(messages: &[JsonValue], schema: Option<Schema>) -> (Vec<JsonValue>, bool) {
+        let mut input = vec![];
+        for message in messages {
+            lsp::write_message(&mut input, message).unwrap();
+        }
+        let mut output = vec![];
+        let shutdown = lsp::serve(&mut input.as_slice(), &mut output, schema).unwrap();
+
+        let mut replies = vec![];
+        let mut output = output.as_slice();
+        while let Some(reply) = lsp::read_message(&mut output).unwrap() {
+            let lsp::Incoming::Message(reply) = reply else {
+                panic!("unreadable reply");
+            };
+            replies.push(reply);
+        }
+        (replies, shutdown)
+    }
+
+    fn field<'a>(value: &'a JsonValue, path: &[&str]) -> &'a JsonValue {
+        path.iter().fold(value, |value, key| match value {
+            JsonValue::Object(object) => &object.get(key).unwrap_or_else(|| panic!("no {} in {:?}", key, value)).value,
+            JsonValue::Array(array) => &array.elements[key.parse::<usize>().unwrap()].value,
+            _ => panic!("no {} in {:?}", key, value),
+        })
+    }
+
+    #[test]
+    fn test_lsp_session() {
+        let directory = std::env::current_dir().unwrap().join("tests/lsp");
+        let uri = format!("file://{}/config.json", directory.display());
+        let text = "{\n  \"$schema\": \"schema.json\",\n  \"name\": \"api\",\n  \"servers\": [\n    {\"host\": \"a\", \"port\": 70000},\n    {}\n  ]\n}\n";
+        let document = json!({"textDocument": {"uri": (uri.as_str())}});
+        let at = |line: u64, character: u64| json!({"textDocument": {"uri": (uri.as_str())}, "position": {"line": line, "character": character}});
+
+        let (replies, shutdown) = lsp_session(&[
+            json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}}),
+            json!({"jsonrpc": "2.0", "method": "initialized", "params": {}}),
+            json!({"jsonrpc": "2.0", "method": "textDocument/didOpen", "params": {"textDocument": {"uri": (uri.as_str()), "languageId": "json", "version": 1, "text": text}}}),
+            json!({"jsonrpc": "2.0", "id": 2, "method": "textDocument/documentSymbol", "params": (document.clone())}),
+            json!({"jsonrpc": "2.0", "id": 3, "method": "textDocument/foldingRange", "params": (document.clone())}),
+            json!({"jsonrpc": "2.0", "id": 4, "method": "textDocument/hover", "params": (at(4, 27))}),
+            json!({"jsonrpc": "2.0", "id": 5, "method": "textDocument/completion", "params": (at(5, 5))}),
+            json!({"jsonrpc": "2.0", "id": 6, "method": "textDocument/formatting", "params": {"textDocument": {"uri": (uri.as_str())}, "options": {"tabSize": 2, "insertSpaces": true}}}),
+            json!({"jsonrpc": "2.0", "id": 7, "method": "textDocument/definition", "params": (at(0, 0))}),
+            json!({"jsonrpc": "2.0", "method": "textDocument/didChange", "params": {"textDocument": {"uri": (uri.as_str()), "version": 2}, "contentChanges": [{"text": "{\"a\": 1 \"b\": }"}]}}),
+            json!({"jsonrpc": "2.0", "method": "textDocument/didChange", "params": {"textDocument": {"uri": (uri.as_str()), "version": 3}, "contentChanges": [{"text": "{\n  \"$schema\": \"schema.json\",\n  \"servers\": [{\"ho\"}]\n}"}]}}),
+            json!({"jsonrpc": "2.0", "id": 9, "method": "textDocument/completion", "params": (at(2, 18))}),
+            json!({"jsonrpc": "2.0", "id": 8, "method": "shutdown"}),
+            json!({"jsonrpc": "2.0", "method": "exit"}),
+        ], None);
+        assert!(shutdown);
+        assert_eq!(replies.len(), 12);
+
+        assert_eq!(field(&replies[0], &["id"]), &json!(1));
+        assert_eq!(field(&replies[0], &["result", "capabilities", "hoverProvider"]), &json!(true));
+
+        // The document names its schema, which limits the port.
+        let diagnostics = field(&replies[1], &["params", "diagnostics"]);
+        assert_eq!(field(&replies[1], &["method"]), &json!("textDocument/publishDiagnostics"));
+        assert_eq!(field(diagnostics, &["0", "range"]), &json!({"start": {"line": 4, "character": 26}, "end": {"line": 4, "character": 31}}));
+        assert!(matches!(diagnostics, JsonValue::Array(array) if array.elements.len() == 1));
+        assert_eq!(field(diagnostics, &["0", "severity"]), &json!(2));
+
+        let symbols = field(&replies[2], &["result"]);
+        assert_eq!(field(symbols, &["2", "name"]), &json!("servers"));
+        assert_eq!(field(symbols, &["2", "kind"]), &json!(18));
+        assert_eq!(field(symbols, &["2", "selectionRange", "start"]), &json!({"line": 3, "character": 2}));
+        assert_eq!(field(symbols, &["2", "children", "0", "children", "1", "name"]), &json!("port"));
+
+        assert_eq!(field(&replies[3], &["result"]), &json!([{"startLine": 0, "endLine": 6}, {"startLine": 3, "endLine": 5}]));
+        assert_eq!(field(&replies[4], &["result", "contents", "value"]), &json!("/servers/0/port"));
+
+        let completions = field(&replies[5], &["result"]);
+        assert_eq!(field(completions, &["0", "label"]), &json!("host"));
+        assert_eq!(field(completions, &["0", "documentation"]), &json!("Name or address to bind"));
+        assert_eq!(field(completions, &["0", "textEdit", "newText"]), &json!("\"host\": "));
+        assert_eq!(field(completions, &["0", "textEdit", "range"]), &json!({"start": {"line": 5, "character": 5}, "end": {"line": 5, "character": 5}}));
+        assert_eq!(field(completions, &["1", "label"]), &json!("port"));
+
+        let formatted = field(&replies[6], &["result", "0", "newText"]);
+        assert_eq!(formatted, &json!("{\n  \"$schema\": \"schema.json\",\n  \"name\": \"api\",\n  \"servers\": [\n    {\n      \"host\": \"a\",\n      \"port\": 70000\n    },\n    {}\n  ]\n}\n"));
+
+        assert_eq!(field(&replies[7], &["error", "code"]), &json!(-32601));
+
+        let diagnostics = field(&replies[8], &["params", "diagnostics"]);
+        assert_eq!(field(diagnostics, &["0", "message"]), &json!("Expected ',' or '}'"));
+        assert_eq!(field(diagnostics, &["1", "message"]), &json!("Expected a value"));
+
+        // Completing a key typed after the triggering quote replaces both
+        // quotes instead of adding more.
+        let completions = field(&replies[10], &["result"]);
+        assert_eq!(field(completions, &["0", "filterText"]), &json!("\"host\""));
+        assert_eq!(field(completions, &["0", "textEdit"]), &json!({
+            "range": {"start": {"line": 2, "character": 15}, "end": {"line": 2, "character": 19}},
+            "newText": "\"host\": ",
+        }));
+        assert_eq!(field(&replies[11], &["result"]), &JsonValue::Null);
+    }
+
+    #[test]
+    fn test_lsp_schema_cache() {
+        let directory = std::env::temp_dir().join(format!("cc_json_parser_lsp_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let schema = directory.join("schema.json");
+        let write_schema = |maximum: u64, modified: std::time::SystemTime| {
+            std::fs::write(&schema, format!("{{\"properties\": {{\"port\": {{\"maximum\": {}}}}}}}", maximum)).unwrap();
+            std::fs::File::options().write(true).open(&schema).unwrap().set_modified(modified).unwrap();
+        };
+        let epoch = std::time::SystemTime::UNIX_EPOCH;
+        let uri = format!("file://{}/config.json", directory.display());
+        let text = "{\"$schema\": \"schema.json\", \"port\": 70000}";
+        let change = json!({"jsonrpc": "2.0", "method": "textDocument/didChange", "params": {"textDocument": {"uri": (uri.as_str())}, "contentChanges": [{"text": text}]}});
+        let count = |replies: Vec<JsonValue>| match field(&replies[0], &["params", "diagnostics"]) {
+            JsonValue::Array(array) => array.elements.len(),
+            value => panic!("{:?}", value),
+        };
+
+        let mut server = lsp::Server::new(None);
+        write_schema(65535, epoch + std::time::Duration::from_secs(1));
+        assert_eq!(count(server.handle(&json!({"jsonrpc": "2.0", "method": "textDocument/didOpen", "params": {"textDocument": {"uri": (uri.as_str()), "text": text}}}))), 1);
+
+        // A new modification time compiles the schema again.
+        write_schema(100000, epoch + std::time::Duration::from_secs(2));
+        assert_eq!(count(server.handle(&change)), 0);
+
+        // The same modification time keeps the compiled schema until the
+        // client reports the change.
+        write_schema(65535, epoch + std::time::Duration::from_secs(2));
+        assert_eq!(count(server.handle(&change)), 0);
+        let replies = server.handle(&json!({"jsonrpc": "2.0", "method": "workspace/didChangeWatchedFiles", "params": {"changes": [{"uri": (format!("file://{}", schema.display())), "type": 2}]}}));
+        assert_eq!(count(replies), 1);
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_lsp_unreadable_messages() {
+        let mut input = vec![];
+        lsp::write_message(&mut input, &json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}})).unwrap();
+        input.extend_from_slice(b"Content-Length: 8\r\n\r\n{\"id\": 2");
+        lsp::write_message(&mut input, &json!({"jsonrpc": "2.0", "id": 3, "method": "shutdown"})).unwrap();
+        // The body is never sent, so nothing may be allocated for it.
+        input.extend_from_slice(format!("Content-Length: {}\r\n\r\n", usize::MAX).as_bytes());
+
+        let mut output = vec![];
+        assert!(lsp::serve(&mut input.as_slice(), &mut output, None).unwrap());
+        let mut output = output.as_slice();
+        let mut replies = vec![];
+        while let Some(lsp::Incoming::Message(reply)) = lsp::read_message(&mut output).unwrap() {
+            replies.push(reply);
+        }
+        assert_eq!(replies.len(), 4);
+        assert_eq!(field(&replies[0], &["id"]), &json!(1));
+        assert_eq!(field(&replies[1], &["id"]), &JsonValue::Null);
+        assert_eq!(field(&replies[1], &["error", "code"]), &json!(-32700));
+        assert_eq!(field(&replies[2], &["id"]), &json!(3));
+        assert_eq!(field(&replies[3], &["error", "code"]), &json!(-32600));
+    }
+
+    #[test]
+    fn test_canonical_vectors() {
+        for entry in std::fs::read_dir("tests/canonical/input").unwrap() {
+            let input = entry.unwrap().path();
+            let output = Path::new("tests/canonical/output").join(input.file_name().unwrap());
+            let json_data = handle_file(input.to_str().unwrap()).unwrap();
+            let canonical = canonical::canonicalize(&json_data).unwrap();
+            assert_eq!(canonical, std::fs::read_to_string(&output).unwrap(), "{}", input.display());
+
+            // Canonical output is a fixed point.
+            let reparsed = json_valid(&mut canonical.as_bytes()).unwrap();
+            assert_eq!(canonical::canonicalize(&reparsed).unwrap(), canonical);
+        }
+    }
+
+    #[test]
+    fn test_canonical_numbers() {
+        // The IEEE 754 samples from RFC 8785 appendix B.
+        for (bits, expected) in [
+            (0x0000000000000000u64, "0"),
+            (0x8000000000000000, "0"),
+            (0x0000000000000001, "5e-324"),
+            (0x8000000000000001, "-5e-324"),
+            (0x7fefffffffffffff, "1.7976931348623157e+308"),
+            (0xffefffffffffffff, "-1.7976931348623157e+308"),
+            (0x4340000000000000, "9007199254740992"),
+            (0xc340000000000000, "-9007199254740992"),
+            (0x4430000000000000, "295147905179352830000"),
+            (0x44b52d02c7e14af5, "9.999999999999997e+22"),
+            (0x44b52d02c7e14af6, "1e+23"),
+            (0x44b52d02c7e14af7, "1.0000000000000001e+23"),
+            (0x444b1ae4d6e2ef4e, "999999999999999700000"),
+            (0x444b1ae4d6e2ef4f, "999999999999999900000"),
+            (0x444b1ae4d6e2ef50, "1e+21"),
+            (0x3eb0c6f7a0b5ed8c, "9.999999999999997e-7"),
+            (0x3eb0c6f7a0b5ed8d, "0.000001"),
+            (0x41b3de4355555553, "333333333.3333332"),
+            (0x41b3de4355555554, "333333333.33333325"),
+            (0x41b3de4355555555, "333333333.3333333"),
+            (0x41b3de4355555556, "333333333.3333334"),
+            (0x41b3de4355555557, "333333333.33333343"),
+            (0xbecbf647612f3696, "-0.0000033333333333333333"),
+            (0x43143ff3c1cb0959, "1424953923781206.2"),
+        ] {
+            assert_eq!(canonical::format_number(f64::from_bits(bits)).unwrap(), expected, "{:#018x}", bits);
+        }
+
+        for bits in [0x7fffffffffffffffu64, 0x7ff0000000000000] {
+            assert!(canonical::format_number(f64::from_bits(bits)).is_err());
+        }
+
+        // Integers are doubles to the scheme, and numbers past them have no form.
+        let canonical = |input: &str| canonical::canonicalize(&json_valid(&mut input.as_bytes()).unwrap()).map_err(|err| err.to_string());
+        assert_eq!(canonical("[9007199254740993, -0, 1e400]"), Err(String::from("Number out of range for canonical JSON")));
+        assert_eq!(canonical("[9007199254740993, -0, 100, 1.5e-7]"), Ok(String::from("[9007199254740992,0,100,1.5e-7]")));
+        assert_eq!(canonical(r#"{"a": 1, "a": 2}"#), Err(String::from("Duplicate key \"a\" cannot be canonicalized")));
+    }
+
+    #[test]
+    fn test_sha256_vectors() {
+        use digest::sha256::{sha256, Sha256};
+
+        for (input, expected) in [
+            (&b""[..], "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"),
+            (b"abc", "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"),
+            (b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq", "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"),
+            (
+                b"abcdefghbcdefghicdefghijdefghijkefghijklfghijklmghijklmnhijklmnoijklmnopjklmnopqklmnopqrlmnopqrsmnopqrstnopqrstu",
+                "cf5b16a778af8380036ce59e7b0492370b249b11e8f07a51afac45037afee9d1",
+            ),
+        ] {
+            assert_eq!(digest::to_hex(&sha256(input)), expected);
+        }
+
+        // A million `a`s, fed in pieces that do not line up with the blocks.
+        let mut hasher = Sha256::default();
+        for _ in 0..1000 {
+            hasher.update(&[b'a'; 999]);
+        }
+        hasher.update(&[b'a'; 1000]);
+        assert_eq!(digest::to_hex(&hasher.finish()), "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0");
+    }
+
+    #[test]
+    fn test_content_digest() {
+        use canonical::KeyOrder;
+        use digest::ContentKey;
+        use std::collections::HashSet;
+
+        let parse = |input: &str| json_valid(&mut input.as_bytes()).unwrap();
+        let compact = parse(r#"{"a":[1,"A",true],"b":{"c":null}}"#);
+        let spaced = parse("{ \"a\" : [ 1.0, \"\\u0041\", true ],\n  \"b\": {\"c\": null} }");
+        let reordered = parse(r#"{"b":{"c":null},"a":[1e0,"A",true]}"#);
+        let different = parse(r#"{"a":[1,"A",false],"b":{"c":null}}"#);
+
+        // The digest is SHA-256 of the canonical form.
+        assert_eq!(compact.digest(), digest::sha256::sha256(canonical::canonicalize(&compact).unwrap().as_bytes()));
+        assert_eq!(compact.digest(), spaced.digest());
+        assert_eq!(compact.digest(), reordered.digest());
+        assert_ne!(compact.digest(), different.digest());
+        assert_eq!(compact.digest_with(KeyOrder::Significant), spaced.digest_with(KeyOrder::Significant));
+        assert_ne!(compact.digest_with(KeyOrder::Significant), reordered.digest_with(KeyOrder::Significant));
+
+        // Values the strict form rejects still hash.
+        assert_ne!(parse("[1e400]").digest(), parse("[1e401]").digest());
+        assert_ne!(parse(r#"{"a":1,"a":2}"#).digest(), parse(r#"{"a":2,"a":1}"#).digest());
+
+        let keys = |order| -> HashSet<ContentKey> {
+            [&compact, &spaced, &reordered, &different].into_iter()
+                .map(|data| ContentKey::new(data.element.value.clone(), order))
+                .collect()
+        };
+        assert_eq!(keys(KeyOrder::Ignored).len(), 2);
+        assert_eq!(keys(KeyOrder::Significant).len(), 3);
+        assert_ne!(
+            ContentKey::new(compact.element.value.clone(), KeyOrder::Ignored),
+            ContentKey::new(compact.element.value.clone(), KeyOrder::Significant),
+        );
+        assert_eq!(ContentKey::new(spaced.element.value.clone(), KeyOrder::Ignored).digest(), compact.digest());
+        let key = ContentKey::new(compact.element.value.clone(), KeyOrder::Significant);
+        assert_eq!(key.value(), &compact.element.value);
+        assert_eq!(key.order(), KeyOrder::Significant);
+        assert_eq!(key.into_value(), compact.element.value);
+
+        // Integers past 2^53 keep every digit rather than rounding to a double.
+        assert_ne!(parse("[9007199254740993]").digest(), parse("[9007199254740992]").digest());
+        assert_ne!(parse("[18446744073709551615]").digest(), parse("[18446744073709551614]").digest());
+        assert_ne!(
+            ContentKey::new(parse("9007199254740993").element.value, KeyOrder::Ignored),
+            ContentKey::new(parse("9007199254740992").element.value, KeyOrder::Ignored),
+        );
+        assert_eq!(parse("[9007199254740992]").digest(), parse("[9007199254740992.0]").digest());
+        assert_eq!(parse("[-0]").digest(), parse("[0.0]").digest());
+
+        // Decimals keep their value whatever their spelling.
+        let decimal = parse("[1e400, 1E400, 10e399, 1.0e+400, 0.1e401]");
+        let JsonValue::Array(array) = &decimal.element.value else { panic!() };
+        let keys: HashSet<ContentKey> = array.elements.iter()
+            .map(|element| ContentKey::new(element.value.clone(), KeyOrder::Ignored))
+            .collect();
+        assert_eq!(keys.len(), 1);
+        assert_eq!(parse("[1e400]").digest(), parse("[1E400]").digest());
+        let options = parse::ParseOptions { numbers: NumberMode::Arbitrary, ..Default::default() };
+        let arbitrary = |input: &str| parse::parse_with_options(&mut lex::lex_bytes(input.as_bytes()).unwrap(), &options).unwrap();
+        assert_eq!(arbitrary("[0.50, 5e-1, 0.5]").digest(), parse("[0.5, 0.5, 0.5]").digest());
+        assert_eq!(arbitrary("[123456789012345678901234567890]").digest(), arbitrary("[1.2345678901234567890123456789e29]").digest());
+        assert_ne!(arbitrary("[123456789012345678901234567890]").digest(), arbitrary("[123456789012345678901234567891]").digest());
+        assert_ne!(arbitrary("[1e-400]").digest(), arbitrary("[1e-401]").digest());
+    }
+
+    #[test]
+    fn test_convert_writers() {
+        let document = handle_file("tests/convert/document.json").unwrap();
+        assert_eq!(yaml::to_yaml(&document), std::fs::read_to_string("tests/convert/document.yaml").unwrap());
+        assert_eq!(toml::to_toml(&document).unwrap(), std::fs::read_to_string("tests/convert/document.toml").unwrap());
+        let rows = handle_file("tests/convert/rows.json").unwrap();
+        assert_eq!(csv::to_csv(&rows).unwrap(), std::fs::read_to_string("tests/convert/rows.csv").unwrap());
+
+        let parse = |input: &str| json_valid(&mut input.as_bytes()).unwrap();
+        for (input, expected) in [
+            ("null", "null\n"),
+            ("[]", "[]\n"),
+            (r#""no""#, "\"no\"\n"),
+            (r#""-x""#, "\"-x\"\n"),
+            (r#""a # b""#, "\"a # b\"\n"),
+            (r#""caf\u00e9 \u0085""#, "\"caf\u{e9} \\u0085\"\n"),
+            (r#"{"\ufeffa":"b\ufeff"}"#, "\"\\ufeffa\": \"b\\ufeff\"\n"),
+            ("[[],{}]", "- []\n- {}\n"),
+            ("[1e5,2.5E-3,1E400]", "- 100000.0\n- 0.0025\n- 1.0e+400\n"),
+        ] {
+            assert_eq!(yaml::to_yaml(&parse(input)), expected, "{}", input);
+        }
+
+        for (input, error) in [
+            ("[]", "TOML cannot represent a top level that is not an object"),
+            (r#"{"a":{"b":[1,null]}}"#, "TOML cannot represent null at a.b[1]"),
+            (r#"{"a":[1,"2"]}"#, "TOML cannot represent an array of both integers and strings at a"),
+            (r#"{"a":[{"b":1},2]}"#, "TOML cannot represent an array of both tables and integers at a"),
+            (r#"{"a":[1,2.5]}"#, "TOML cannot represent an array of both integers and floats at a"),
+            (r#"{"a":{"b":1e400}}"#, "TOML cannot represent a number beyond a double at a.b"),
+            (r#"{"a b":{"c":18446744073709551615}}"#, r#"TOML cannot represent an integer beyond 64 bits at "a b".c"#),
+            (r#"{"a":1,"a":2}"#, r#"TOML cannot represent the repeated key "a""#),
+        ] {
+            assert_eq!(toml::to_toml(&parse(input)).unwrap_err().to_string(), error, "{}", input);
+        }
+        assert_eq!(toml::to_toml(&parse(r#"{"a":[[1],["x"]],"b":[{"c":{}}]}"#)).unwrap(), "a = [[1], [\"x\"]]\n\n[[b]]\n\n[b.c]\n");
+        let options = parse::ParseOptions { numbers: NumberMode::Arbitrary, ..Default::default() };
+        let arbitrary = |input: &str| parse::parse_with_options(&mut lex::lex_bytes(input.as_bytes()).unwrap(), &options).unwrap();
+        assert_eq!(toml::to_toml(&arbitrary(r#"{"a":[1e0,0e-400,2.5]}"#)).unwrap(), "a = [1e0, 0e-400, 2.5]\n");
+        let err = toml::to_toml(&arbitrary(r#"{"a":[-1e-400]}"#)).unwrap_err();
+        assert_eq!(err.to_string(), "TOML cannot represent a number beyond a double at a[0]");
+
+        for (input, error) in [
+            ("{}", "CSV needs an array of objects"),
+            ("[{},[]]", "CSV row 1 is array, not an object"),
+            (r#"[{"a":{}}]"#, r#"CSV cannot hold the object in row 0, column "a""#),
+        ] {
+            assert_eq!(csv::to_csv(&parse(input)).unwrap_err().to_string(), error, "{}", input);
+        }
+        assert_eq!(csv::to_csv(&parse("[{},{}]")).unwrap(), "");
+    }
+
+    #[test]
+    fn test_convert_readers() {
+        let read = |filename: &str| std::fs::read_to_string(filename).unwrap();
+        assert_eq!(yaml::parse(&read("tests/convert/features.yaml")).unwrap(), handle_file("tests/convert/features.json").unwrap());
+        assert_eq!(toml::parse(&read("tests/convert/config.toml")).unwrap(), handle_file("tests/convert/config.json").unwrap());
+
+        // What the writers write reads back the same.
+        let document = handle_file("tests/convert/document.json").unwrap();
+        assert_eq!(yaml::parse(&read("tests/convert/document.yaml")).unwrap(), document);
+        assert_eq!(toml::parse(&read("tests/convert/document.toml")).unwrap().digest(), document.digest());
+        let mut rows = handle_file("tests/convert/rows.json").unwrap();
+        let JsonValue::Array(array) = &mut rows.element.value else { panic!() };
+        for element in &mut array.elements {
+            let JsonValue::Object(object) = &mut element.value else { panic!() };
+            for name in ["id", "name", "price", "tags", "active"] {
+                if object.get(name).is_none() {
+                    object.members.push(JsonMember { string: JsonString { string: String::from(name) }, element: JsonElement { value: JsonValue::Null } });
+                }
+            }
+            object.members.sort_by_key(|member| ["id", "name", "price", "tags", "active"].iter().position(|name| *name == member.string.string));
+        }
+        assert_eq!(csv::parse(&read("tests/convert/rows.csv")).unwrap(), rows);
+
+        let float = |value: &JsonValue| match value {
+            JsonValue::Number(JsonNumber::Float(float)) => *float,
+            _ => panic!("{:?}", value),
+        };
+        let yaml = |input: &str| yaml::parse(input).unwrap().element.value;
+        assert_eq!(float(&yaml(".inf")), f64::INFINITY);
+        assert_eq!(float(&yaml("-.Inf")), f64::NEG_INFINITY);
+        let toml = |input: &str| match toml::parse(input).unwrap().element.value {
+            JsonValue::Object(object) => object.members[0].element.value.clone(),
+            value => panic!("{:?}", value),
+        };
+        assert_eq!(float(&toml("a = -inf")), f64::NEG_INFINITY);
+        assert!(float(&toml("a = nan")).is_nan());
+        assert!(float(&yaml(".NaN")).is_nan());
+        assert_eq!(yaml(""), JsonValue::Null);
+        assert_eq!(yaml("--- |\n  text\n"), JsonValue::String(JsonString { string: String::from("text\n") }));
+        assert_eq!(yaml("0x_1"), JsonValue::String(JsonString { string: String::from("0x_1") }));
+        assert_eq!(yaml("0x+1"), JsonValue::String(JsonString { string: String::from("0x+1") }));
+        assert_eq!(yaml("0o+7"), JsonValue::String(JsonString { string: String::from("0o+7") }));
+        assert_eq!(yaml("0x"), JsonValue::String(JsonString { string: String::from("0x") }));
+        assert_eq!(yaml("[0xFf, 0o17]"), json!([255, 15]));
+        assert_eq!(yaml("a: b:c"), json!({"a": "b:c"}));
+        assert_eq!(yaml("18446744073709551615"), JsonValue::Number(JsonNumber::Unsigned(u64::MAX)));
+        // Only spaces and tabs are whitespace to YAML.
+        assert_eq!(yaml("\u{a0}: \u{a0}x\u{3000}"), json!({"\u{a0}": "\u{a0}x\u{3000}"}));
+        for (input, error) in [
+            ("a: &anchor 1", "YAML line 1: YAML anchors are not supported"),
+            ("a: 1\nb: *anchor", "YAML line 2: YAML aliases are not supported"),
+            ("- !!str 1", "YAML line 1: YAML tags are not supported"),
+            ("? a\n: 1", "YAML line 1: YAML complex keys are not supported"),
+            ("a: 1\n---\nb: 2", "YAML line 2: Multiple YAML documents are not supported"),
+            ("a: 1\na: 2", "YAML line 2: Duplicate key \"a\""),
+            ("a:\n  b: 1\n c: 2", "YAML line 3: Bad indentation"),
+            ("a: 1\n  b: 2", "YAML line 2: Bad indentation"),
+            ("- a\nb: 1", "YAML line 2: Bad indentation"),
+            ("a:\n\t- 1", "YAML line 2: Tabs are not allowed in indentation"),
+            ("a: [1, 2", "YAML line 1: Unclosed flow collection"),
+            ("a: [1 }", "YAML line 1: Expected ',' or ']'"),
+            ("a: {b: 1, b: 2}", "YAML line 1: Duplicate key \"b\""),
+            ("a: \"open\n\nb: 1", "YAML line 1: Unterminated string"),
+            ("a: \"\\q\"", "YAML line 1: Invalid escape \\q"),
+            ("a: 'x' y", "YAML line 1: Unexpected content after a string"),
+            ("a: b: c", "YAML line 1: Unexpected ':' in a plain scalar"),
+            ("- a: b: c", "YAML line 1: Unexpected ':' in a plain scalar"),
+            ("a:\n  b: c:", "YAML line 2: Unexpected ':' in a plain scalar"),
+            ("a: |x\n  text", "YAML line 1: Invalid block scalar header"),
+        ] {
+            assert_eq!(yaml::parse(input).unwrap_err().to_string(), error, "{:?}", input);
+        }
+        let nested = format!("{}1{}", "[".repeat(parse::DEFAULT_MAX_DEPTH + 2), "]".repeat(parse::DEFAULT_MAX_DEPTH + 2));
+        assert_eq!(yaml::parse(&nested).unwrap_err().to_string(), "YAML line 1: Nesting too deep");
+        let nested: String = (0..parse::DEFAULT_MAX_DEPTH + 2).map(|depth| format!("{}a:\n", " ".repeat(depth))).collect();
+        assert!(yaml::parse(&nested).unwrap_err().to_string().ends_with("Nesting too deep"));
+
+        for (input, error) in [
+            ("a = 1\na = 2", "TOML line 2: Key \"a\" is already defined"),
+            ("[a]\n[a]", "TOML line 2: Table \"a\" is defined twice"),
+            ("a.b = 1\n[a]", "TOML line 2: Table \"a\" is defined twice"),
+            ("a = 1\n[a]", "TOML line 2: Key \"a\" is already defined as a non-table value"),
+            ("[a]\n[[a]]", "TOML line 2: Key \"a\" cannot be redefined as an array of tables"),
+            ("[a.b]\nc = 1\n[a]\nb.d = 1", "TOML line 4: Key \"b\" is already defined"),
+            ("a = [1]\n[[a]]", "TOML line 2: Key \"a\" cannot be redefined as an array of tables"),
+            ("a = {b = 1,}", "TOML line 1: Expected a key"),
+            ("a = 01", "TOML line 1: Invalid value \"01\""),
+            ("a = 1__0", "TOML line 1: Invalid value \"1__0\""),
+            ("a = 9223372036854775808", "TOML line 1: Number out of range \"9223372036854775808\""),
+            ("a = 1979-13-01", "TOML line 1: Invalid value \"1979-13-01\""),
+            ("a = 'line\nbreak'", "TOML line 1: Unterminated string"),
+            ("a = \"\\uD800\"", "TOML line 1: Invalid escape \\uD800"),
+            ("a = 1 b = 2", "TOML line 1: Expected the end of the line"),
+            ("[a", "TOML line 1: Expected ']' after a table header"),
+            ("a = [1, 2\n", "TOML line 2: Expected ',' or ']' in an array"),
+            ("a = ", "TOML line 1: Expected a value"),
+        ] {
+            assert_eq!(toml::parse(input).unwrap_err().to_string(), error, "{:?}", input);
+        }
+
+        let strings = csv::CsvOptions { infer_types: false, empty_as_null: false };
+        assert_eq!(
+            csv::parse_with_options("a,b\n1,\n", &strings).unwrap(),
+            JsonData::from(json!([{"a": "1", "b": ""}])),
+        );
+        assert_eq!(csv::parse("a,b,c\r\n1e2, 1,true\r\n").unwrap(), JsonData::from(json!([{"a": 100.0, "b": " 1", "c": true}])));
+        assert_eq!(csv::parse("").unwrap(), JsonData::from(json!([])));
+
+        // A trailing blank line is not a row, whatever the number of columns,
+        // but a blank line between rows of one column is an empty field.
+        assert_eq!(csv::parse("a\n1\n\n").unwrap(), JsonData::from(json!([{"a": 1}])));
+        assert_eq!(csv::parse("a,b\n1,2\n\n").unwrap(), JsonData::from(json!([{"a": 1, "b": 2}])));
+        assert_eq!(csv::parse("a\n1\n\n2\n").unwrap(), JsonData::from(json!([{"a": 1}, {"a": null}, {"a": 2}])));
+        assert_eq!(csv::parse("a\n1\n\"\"\n").unwrap(), JsonData::from(json!([{"a": 1}, {"a": null}])));
+
+        // The default options read back what `to_csv` writes, for strings that
+        // do not look like other values.
+        let rows = JsonData::from(json!([
+            {"name": "a, \"quoted\"\nname", "count": 18446744073709551615u64, "ratio": 0.5, "ok": true, "note": null},
+            {"name": "b", "count": -3, "ratio": 1e300, "ok": false, "note": "x"},
+        ]));
+        assert_eq!(csv::parse(&csv::to_csv(&rows).unwrap()).unwrap(), rows);
+        let column = JsonData::from(json!([{"a": 1}, {"a": null}, {"a": null}]));
+        assert_eq!(csv::to_csv(&column).unwrap(), "a\n1\n\"\"\n\"\"\n");
+        assert_eq!(csv::parse(&csv::to_csv(&column).unwrap()).unwrap(), column);
+        for (input, error) in [
+            ("a,b\n1\n", "CSV line 2: 1 fields where the header has 2"),
+            ("a,a\n1,2\n", "CSV header repeats the column \"a\""),
+            ("a\n\"open\n", "CSV line 2: Unterminated quoted field"),
+            ("a,b\n\"x\"y,2\n", "CSV line 2: Unexpected text after a quoted field"),
+        ] {
+            assert_eq!(csv::parse(input).unwrap_err().to_string(), error, "{:?}", input);
+        }
+    }
+
+    fn arbitrary_value() -> impl proptest::strategy::Strategy<Value = JsonValue> {
+        use proptest::prelude::*;
+
+        let string = || proptest::string::string_regex("(.|\n|[ #:'\"-]){0,8}").unwrap();
+        let leaf = prop_oneof![
+            Just(JsonValue::Null),
+            Just(JsonValue::True),
+            Just(JsonValue::False),
+            any::<i64>().prop_map(|integer| JsonValue::Number(JsonNumber::Integer(integer))),
+            proptest::num::f64::NORMAL.prop_map(|float| JsonValue::Number(JsonNumber::Float(float))),
+            string().prop_map(|string| JsonValue::String(JsonString {string})),
+        ];
+        leaf.prop_recursive(4, 32, 4, move |inner| prop_oneof![
+            proptest::collection::vec(inner.clone(), 0..4).prop_map(|values| JsonValue::Array(JsonArray {
+                elements: values.into_iter().map(|value| JsonElement {value}).collect(),
+            })),
+            proptest::collection::hash_map(string(), inner, 0..4).prop_map(|members| JsonValue::Object(JsonObject {
+                members: members.into_iter().map(|(string, value)| JsonMember {
+                    string: JsonString {string},
+                    element: JsonElement {value},
+                }).collect(),
+            })),
+        ])
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn test_convert_round_trip(value in arbitrary_value()) {
+            let data = JsonData::from(value);
+            let written = yaml::to_yaml(&data);
+            assert_eq!(yaml::parse(&written).unwrap(), data, "{}", written);
+            // TOML writes tables after the other keys, so only the content
+            // has to match.
+            if let Ok(written) = toml::to_toml(&data) {
+                assert_eq!(toml::parse(&written).unwrap().digest(), data.digest(), "{}", written);
+            }
+        }
+    }
+
+    // Decodes the hex in the tables of binary test vectors.
+    #[cfg(any(feature = "cbor", feature = "msgpack", feature = "bson"))]
+    fn from_hex(text: &str) -> Vec<u8> {
+        (0..text.len()).step_by(2).map(|index| u8::from_str_radix(&text[index..index + 2], 16).unwrap()).collect()
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn test_cbor_vectors() {
+        use cbor::*;
+
+        let decimal = |text: &str| JsonValue::Number(JsonNumber::Decimal(String::from(text)));
+        let float = |float: f64| JsonValue::Number(JsonNumber::Float(float));
+
+        // The examples of RFC 8949 appendix A that have a JSON equivalent,
+        // which are all in preferred serialization.
+        for (encoded, value) in [
+            ("00", json!(0)),
+            ("17", json!(23)),
+            ("1818", json!(24)),
+            ("1903e8", json!(1000)),
+            ("1a000f4240", json!(1000000)),
+            ("1b000000e8d4a51000", json!(1000000000000)),
+            ("1bffffffffffffffff", JsonValue::Number(JsonNumber::Unsigned(u64::MAX))),
+            ("c249010000000000000000", decimal("18446744073709551616")),
+            ("3bffffffffffffffff", decimal("-18446744073709551616")),
+            ("c349010000000000000000", decimal("-18446744073709551617")),
+            ("20", json!(-1)),
+            ("3863", json!(-100)),
+            ("3903e7", json!(-1000)),
+            ("f90000", float(0.0)),
+            ("f98000", float(-0.0)),
+            ("f93c00", float(1.0)),
+            ("fb3ff199999999999a", float(1.1)),
+            ("f97bff", float(65504.0)),
+            ("fa47c35000", float(100000.0)),
+            ("fa7f7fffff", float(3.4028234663852886e38)),
+            ("fb7e37e43c8800759c", float(1.0e300)),
+            ("f90001", float(5.960464477539063e-8)),
+            ("f90400", float(0.00006103515625)),
+            ("fbc010666666666666", float(-4.1)),
+            ("f97c00", float(f64::INFINITY)),
+            ("f9fc00", float(f64::NEG_INFINITY)),
+            ("f97e00", float(f64::NAN)),
+            ("f4", json!(false)),
+            ("f5", json!(true)),
+            ("f6", json!(null)),
+            ("60", json!("")),
+            ("62225c", json!("\"\\")),
+            ("63e6b0b4", json!("水")),
+            ("64f0908591", json!("𐅑")),
+            ("80", json!([])),
+            ("8301820203820405", json!([1, [2, 3], [4, 5]])),
+            ("a26161016162820203", json!({"a": 1, "b": [2, 3]})),
+            ("826161a161626163", json!(["a", {"b": "c"}])),
+            // The decimal fraction example of section 3.4.4.
+            ("c48221196ab3", decimal("27315e-2")),
+        ] {
+            let data = JsonData::from(value);
+            assert_eq!(digest::to_hex(&to_vec(&data, &CborOptions::default()).unwrap()), encoded);
+            assert_eq!(from_slice(&from_hex(encoded)).unwrap(), data, "{}", encoded);
+        }
+        let long = "98190102030405060708090a0b0c0d0e0f101112131415161718181819";
+        let array = JsonData::from(JsonValue::Array(JsonArray {
+            elements: (1..=25).map(|integer| JsonElement { value: JsonValue::Number(JsonNumber::Integer(integer)) }).collect(),
+        }));
+        assert_eq!(digest::to_hex(&to_vec(&array, &CborOptions::default()).unwrap()), long);
+        assert_eq!(from_slice(&from_hex(long)).unwrap(), array);
+
+        // Encodings that are valid but never written: indefinite lengths,
+        // longer heads and floats than needed, undefined, and tags.
+        for (encoded, value) in [
+            ("f7", json!(null)),
+            ("1b0000000000000001", json!(1)),
+            ("fb3ff0000000000000", float(1.0)),
+            ("7f657374726561646d696e67ff", json!("streaming")),
+            ("9fff", json!([])),
+            ("9f018202039f0405ffff", json!([1, [2, 3], [4, 5]])),
+            ("83019f0203ff820405", json!([1, [2, 3], [4, 5]])),
+            ("bf61610161629f0203ffff", json!({"a": 1, "b": [2, 3]})),
+            ("bf6346756ef563416d7421ff", json!({"Fun": true, "Amt": -2})),
+            ("c074323031332d30332d32315432303a30343a30305a", json!("2013-03-21T20:04:00Z")),
+            ("c11a514b67b0", json!(1363896240)),
+            ("c1fb41d452d9ec200000", float(1363896240.5)),
+            ("d82076687474703a2f2f7777772e6578616d706c652e636f6d", json!("http://www.example.com")),
+            ("c2420100", json!(256)),
+            ("c48200c249010000000000000000", decimal("18446744073709551616")),
+        ] {
+            assert_eq!(from_slice(&from_hex(encoded)).unwrap(), JsonData::from(value), "{}", encoded);
+        }
+
+        let nested = format!("{}00", "81".repeat(200));
+        for (encoded, message) in [
+            ("", "Empty CBOR"),
+            ("0001", "CBOR byte 1: Unexpected data after the item"),
+            ("8301", "CBOR byte 2: Unexpected end of input"),
+            ("7bffffffffffffffff00", "CBOR byte 10: Unexpected end of input"),
+            ("4101", "CBOR byte 0: Byte strings are not supported"),
+            ("a1016161", "CBOR byte 1: Map keys must be text strings"),
+            ("62c328", "CBOR byte 0: Invalid UTF-8 in a text string"),
+            ("7f01ff", "CBOR byte 1: Invalid chunk in an indefinite-length string"),
+            ("3f", "CBOR byte 0: Indefinite length is only allowed for strings, arrays and maps"),
+            ("1c", "CBOR byte 0: Reserved additional information"),
+            ("f0", "CBOR byte 0: Simple values are not supported"),
+            ("ff", "CBOR byte 0: Unexpected break"),
+            ("c201", "CBOR byte 1: A bignum must hold a byte string"),
+            ("c48161", "CBOR byte 0: A decimal fraction must be an array of two integers"),
+            (&nested, "CBOR byte 129: Nesting too deep"),
+        ] {
+            assert_eq!(from_slice(&from_hex(encoded)).unwrap_err().to_string(), message, "{}", encoded);
+        }
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn test_cbor_options() {
+        use cbor::*;
+
+        let deterministic = CborOptions { deterministic: true };
+        let encode = |value: JsonValue, options: &CborOptions| to_vec(&JsonData::from(value), options).map(|bytes| digest::to_hex(&bytes));
+
+        // Shorter keys sort first, then keys of the same length by their bytes.
+        let object = json!({"b": 1, "aa": 2, "a": 3});
+        assert_eq!(encode(object.clone(), &CborOptions::default()).unwrap(), "a361620162616102616103");
+        assert_eq!(encode(object, &deterministic).unwrap(), "a361610361620162616102");
+        assert_eq!(
+            encode(json!({"a": 1, "a": 2}), &deterministic).unwrap_err().to_string(),
+            "Duplicate key \"a\" cannot be encoded deterministically"
+        );
+
+        let nan = JsonValue::Number(JsonNumber::Float(f64::from_bits(0x7ff8_0000_0000_0001)));
+        assert_eq!(encode(nan.clone(), &CborOptions::default()).unwrap(), "fb7ff8000000000001");
+        assert_eq!(encode(nan, &deterministic).unwrap(), "f97e00");
+
+        let decimal = |text: &str| JsonValue::Number(JsonNumber::Decimal(String::from(text)));
+        assert_eq!(encode(decimal("1.5"), &deterministic).unwrap(), "c482200f");
+        assert_eq!(encode(decimal("-1e400"), &deterministic).unwrap(), "c48219019020");
+        assert_eq!(encode(decimal("123456789012345678901234567890"), &deterministic).unwrap(), "c24d018ee90ff6c373e0ee4e3f0ad2");
+
+        // Bignums are limited to `MAX_BIGNUM_BYTES` in both directions, which
+        // holds 2466 digits but not 2467, and the check does not wait for the
+        // whole conversion.
+        let nines = |count: usize| "9".repeat(count);
+        let written = encode(decimal(&nines(2466)), &deterministic).unwrap();
+        assert_eq!(from_slice(&from_hex(&written)).unwrap(), JsonData::from(decimal(&nines(2466))));
+        assert_eq!(encode(decimal(&format!("-{}", nines(2466))), &deterministic).unwrap().len(), written.len());
+        let message = "CBOR cannot represent a bignum longer than 1024 bytes";
+        assert_eq!(encode(decimal(&nines(2467)), &deterministic).unwrap_err().to_string(), message);
+        let message = format!("CBOR cannot represent the number {}", nines(1_000_000));
+        assert_eq!(encode(decimal(&nines(1_000_000)), &deterministic).unwrap_err().to_string(), message);
+        assert_eq!(encode(decimal(&format!("{}1", "0".repeat(1_000_000))), &deterministic).unwrap(), "01");
+        let long = format!("c259040101{}", "00".repeat(1024));
+        assert_eq!(from_slice(&from_hex(&long)).unwrap_err().to_string(), "CBOR byte 0: Bignum longer than 1024 bytes");
+        let widest = format!("c2590400{}", "ff".repeat(1024));
+        assert!(matches!(from_slice(&from_hex(&widest)).unwrap().element.value, JsonValue::Number(JsonNumber::Decimal(_))));
+        let negative = format!("c3{}", &widest[2..]);
+        assert_eq!(encode(from_slice(&from_hex(&negative)).unwrap().element.value, &deterministic).unwrap(), negative);
+
+        // A sequence of items, written to one stream and read back in turn.
+        let documents = [json!({"id": 1}), json!([true, null]), json!("three")];
+        let mut stream = vec![];
+        for document in &documents {
+            write(&mut stream, &JsonData::from(document.clone()), &CborOptions::default()).unwrap();
+        }
+        let read: Vec<JsonData> = Reader::new(std::io::BufReader::new(&stream[..])).collect::<Result<_, _>>().unwrap();
+        assert_eq!(read, documents.map(JsonData::from));
+        let mut reader = Reader::new(&stream[..stream.len() - 1]);
+        assert!(reader.read().unwrap().is_some());
+        assert!(reader.read().unwrap().is_some());
+        assert_eq!(reader.read().unwrap_err().to_string(), "CBOR byte 13: Unexpected end of input");
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn test_msgpack_vectors() {
+        use msgpack::*;
+
+        let float = |float: f64| JsonValue::Number(JsonNumber::Float(float));
+        let long_string = "x".repeat(32);
+        let long_array = JsonValue::Array(JsonArray { elements: vec![JsonElement { value: json!(1) }; 16] });
+
+        for (encoded, value) in [
+            ("c0", json!(null)),
+            ("c2", json!(false)),
+            ("c3", json!(true)),
+            ("00", json!(0)),
+            ("7f", json!(127)),
+            ("cc80", json!(128)),
+            ("cd0100", json!(256)),
+            ("ce00010000", json!(65536)),
+            ("cf0000000100000000", json!(4294967296)),
+            ("cfffffffffffffffff", JsonValue::Number(JsonNumber::Unsigned(u64::MAX))),
+            ("ff", json!(-1)),
+            ("e0", json!(-32)),
+            ("d0df", json!(-33)),
+            ("d1ff7f", json!(-129)),
+            ("d2ffff7fff", json!(-32769)),
+            ("d3ffffffff7fffffff", json!(-2147483649)),
+            ("cb3ff8000000000000", float(1.5)),
+            ("cb7ff0000000000000", float(f64::INFINITY)),
+            ("a0", json!("")),
+            ("a3e6b0b4", json!("水")),
+            (&format!("d920{}", "78".repeat(32)), JsonValue::String(JsonString { string: long_string.clone() })),
+            ("90", json!([])),
+            ("93010203", json!([1, 2, 3])),
+            (&format!("dc0010{}", "01".repeat(16)), long_array),
+            ("80", json!({})),
+            ("82a16101a16292a163c0", json!({"a": 1, "b": ["c", null]})),
+        ] {
+            let data = JsonData::from(value);
+            assert_eq!(digest::to_hex(&to_vec(&data, &MsgpackOptions::default()).unwrap()), encoded);
+            assert_eq!(from_slice(&from_hex(encoded)).unwrap(), data, "{}", encoded);
+        }
+
+        // Valid encodings that are longer than needed.
+        for (encoded, value) in [
+            ("cc01", json!(1)),
+            ("d3ffffffffffffffff", json!(-1)),
+            ("ca3fc00000", float(1.5)),
+            ("d90161", json!("a")),
+            ("dc000101", json!([1])),
+            ("de0001a16101", json!({"a": 1})),
+        ] {
+            assert_eq!(from_slice(&from_hex(encoded)).unwrap(), JsonData::from(value), "{}", encoded);
+        }
+
+        let nested = format!("{}00", "91".repeat(200));
+        for (encoded, message) in [
+            ("", "Empty MessagePack"),
+            ("0000", "MessagePack byte 1: Unexpected data after the value"),
+            ("9201", "MessagePack byte 2: Unexpected end of input"),
+            ("dbffffffff61", "MessagePack byte 6: Unexpected end of input"),
+            ("c40101", "MessagePack byte 0: Binary values are not supported"),
+            ("d40101", "MessagePack byte 0: Extension types are not supported"),
+            ("c1", "MessagePack byte 0: Invalid marker 0xc1"),
+            ("810101", "MessagePack byte 1: Map keys must be strings"),
+            ("a2c328", "MessagePack byte 0: Invalid UTF-8 in a string"),
+            (&nested, "MessagePack byte 129: Nesting too deep"),
+        ] {
+            assert_eq!(from_slice(&from_hex(encoded)).unwrap_err().to_string(), message, "{}", encoded);
+        }
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn test_msgpack_options() {
+        use msgpack::*;
+
+        let deterministic = MsgpackOptions { deterministic: true };
+        let encode = |value: JsonValue, options: &MsgpackOptions| to_vec(&JsonData::from(value), options).map(|bytes| digest::to_hex(&bytes));
+
+        let object = json!({"b": 1, "aa": 2, "a": 3});
+        assert_eq!(encode(object.clone(), &MsgpackOptions::default()).unwrap(), "83a16201a2616102a16103");
+        assert_eq!(encode(object, &deterministic).unwrap(), "83a16103a16201a2616102");
+        assert_eq!(
+            encode(json!({"a": 1, "a": 2}), &deterministic).unwrap_err().to_string(),
+            "Duplicate key \"a\" cannot be encoded deterministically"
+        );
+
+        let nan = JsonValue::Number(JsonNumber::Float(f64::from_bits(0x7ff8_0000_0000_0001)));
+        assert_eq!(encode(nan.clone(), &MsgpackOptions::default()).unwrap(), "cb7ff8000000000001");
+        assert_eq!(encode(nan, &deterministic).unwrap(), "cb7ff8000000000000");
+
+        // Decimal text becomes a float64 or an integer, when either holds it.
+        let decimal = |text: &str| JsonValue::Number(JsonNumber::Decimal(String::from(text)));
+        assert_eq!(encode(decimal("1.5"), &deterministic).unwrap(), "cb3ff8000000000000");
+        assert_eq!(encode(decimal("-12"), &deterministic).unwrap(), "f4");
+        for text in ["1e400", "18446744073709551616"] {
+            assert_eq!(
+                encode(decimal(text), &deterministic).unwrap_err().to_string(),
+                format!("MessagePack cannot represent the number {}", text)
+            );
+        }
+
+        let documents = [json!({"id": 1}), json!([true, null]), json!("three")];
+        let mut stream = vec![];
+        for document in &documents {
+            write(&mut stream, &JsonData::from(document.clone()), &MsgpackOptions::default()).unwrap();
+        }
+        let read: Vec<JsonData> = Reader::new(std::io::BufReader::new(&stream[..])).collect::<Result<_, _>>().unwrap();
+        assert_eq!(read, documents.map(JsonData::from));
+    }
+
+    #[cfg(any(feature = "cbor", feature = "msgpack"))]
+    proptest::proptest! {
+        #[test]
+        fn test_binary_round_trip(value in arbitrary_value()) {
+            let data = JsonData::from(value);
+            let mut reversed = data.clone();
+            reverse_members(&mut reversed.element.value);
+            #[cfg(feature = "cbor")]
+            {
+                let written = cbor::to_vec(&data, &cbor::CborOptions::default()).unwrap();
+                assert_eq!(cbor::from_slice(&written).unwrap(), data);
+                // Member order is the only thing a deterministic encoding
+                // loses.
+                let deterministic = cbor::CborOptions { deterministic: true };
+                let written = cbor::to_vec(&data, &deterministic).unwrap();
+                assert_eq!(cbor::from_slice(&written).unwrap().digest(), data.digest());
+                assert_eq!(cbor::to_vec(&reversed, &deterministic).unwrap(), written);
+            }
+            #[cfg(feature = "msgpack")]
+            {
+                let written = msgpack::to_vec(&data, &msgpack::MsgpackOptions::default()).unwrap();
+                assert_eq!(msgpack::from_slice(&written).unwrap(), data);
+                let deterministic = msgpack::MsgpackOptions { deterministic: true };
+                let written = msgpack::to_vec(&data, &deterministic).unwrap();
+                assert_eq!(msgpack::from_slice(&written).unwrap().digest(), data.digest());
+                assert_eq!(msgpack::to_vec(&reversed, &deterministic).unwrap(), written);
+            }
+        }
+    }
+
+    #[cfg(any(feature = "cbor", feature = "msgpack"))]
+    fn reverse_members(value: &mut JsonValue) {
+        match value {
+            JsonValue::Object(object) => {
+                object.members.reverse();
+                for member in &mut object.members {
+                    reverse_members(&mut member.element.value);
+                }
+            }
+            JsonValue::Array(array) => {
+                for element in &mut array.elements {
+                    reverse_members(&mut element.value);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    #[cfg(feature = "bson")]
+    #[test]
+    fn test_bson_vectors() {
+        use bson::*;
+
+        let parse = |input: &str| json_valid(&mut input.as_bytes()).unwrap();
+
+        // Examples from the BSON corpus of the MongoDB specifications, with
+        // their canonical and relaxed Extended JSON. Both forms write the
+        // same bytes.
+        for (encoded, canonical, relaxed) in [
+            ("0500000000", "{}", "{}"),
+            ("0c0000001061000100000000", r#"{"a":{"$numberInt":"1"}}"#, r#"{"a":1}"#),
+            ("0c0000001069000000008000", r#"{"i":{"$numberInt":"-2147483648"}}"#, r#"{"i":-2147483648}"#),
+            ("10000000126100000000800000000000", r#"{"a":{"$numberLong":"2147483648"}}"#, r#"{"a":2147483648}"#),
+            ("10000000126100ffffffffffffff7f00", r#"{"a":{"$numberLong":"9223372036854775807"}}"#, r#"{"a":9223372036854775807}"#),
+            ("10000000016400000000000000f03f00", r#"{"d":{"$numberDouble":"1.0"}}"#, r#"{"d":1.0}"#),
+            ("10000000016400000000000000008000", r#"{"d":{"$numberDouble":"-0.0"}}"#, r#"{"d":-0.0}"#),
+            ("100000000164002a1bf5f41022b14300", r#"{"d":{"$numberDouble":"1.2345678921232E+18"}}"#, r#"{"d":1.2345678921232E+18}"#),
+            ("10000000016400000000000000f07f00", r#"{"d":{"$numberDouble":"Infinity"}}"#, r#"{"d":{"$numberDouble":"Infinity"}}"#),
+            ("0e00000002610002000000620000", r#"{"a":"b"}"#, r#"{"a":"b"}"#),
+            ("0e00000002610002000000000000", r#"{"a":"\u0000"}"#, r#"{"a":"\u0000"}"#),
+            ("160000000378000e0000000261000200000062000000", r#"{"x":{"a":"b"}}"#, r#"{"x":{"a":"b"}}"#),
+            (
+                "1d00000004610015000000103000010000000231000200000062000000",
+                r#"{"a":[{"$numberInt":"1"},"b"]}"#,
+                r#"{"a":[1,"b"]}"#,
+            ),
+            (
+                "13000000057800060000000202000000ffff00",
+                r#"{"x":{"$binary":{"base64":"//8=","subType":"02"}}}"#,
+                r#"{"x":{"$binary":{"base64":"//8=","subType":"02"}}}"#,
+            ),
+            (
+                "1d000000057800100000000473ffd26444b34c6990e8e7d1dfc035d400",
+                r#"{"x":{"$binary":{"base64":"c//SZESzTGmQ6OfR38A11A==","subType":"04"}}}"#,
+                r#"{"x":{"$binary":{"base64":"c//SZESzTGmQ6OfR38A11A==","subType":"04"}}}"#,
+            ),
+            ("0800000006610000", r#"{"a":{"$undefined":true}}"#, r#"{"a":{"$undefined":true}}"#),
+            (
+                "1400000007610056e1fc72e0c917e9c471416100",
+                r#"{"a":{"$oid":"56e1fc72e0c917e9c4714161"}}"#,
+                r#"{"a":{"$oid":"56e1fc72e0c917e9c4714161"}}"#,
+            ),
+            ("090000000862000100", r#"{"b":true}"#, r#"{"b":true}"#),
+            ("10000000096100000000000000000000", r#"{"a":{"$date":{"$numberLong":"0"}}}"#, r#"{"a":{"$date":"1970-01-01T00:00:00Z"}}"#),
+            (
+                "10000000096100c5d8d6cc3b01000000",
+                r#"{"a":{"$date":{"$numberLong":"1356351330501"}}}"#,
+                r#"{"a":{"$date":"2012-12-24T12:15:30.501Z"}}"#,
+            ),
+            (
+                "10000000096100c33ce7b9bdffffff00",
+                r#"{"a":{"$date":{"$numberLong":"-284643869501"}}}"#,
+                r#"{"a":{"$date":{"$numberLong":"-284643869501"}}}"#,
+            ),
+            ("080000000a610000", r#"{"a":null}"#, r#"{"a":null}"#),
+            (
+                "0f0000000b610061626300696d0000",
+                r#"{"a":{"$regularExpression":{"pattern":"abc","options":"im"}}}"#,
+                r#"{"a":{"$regularExpression":{"pattern":"abc","options":"im"}}}"#,
+            ),
+            (
+                "1a0000000c610002000000620056e1fc72e0c917e9c471416100",
+                r#"{"a":{"$dbPointer":{"$ref":"b","$id":{"$oid":"56e1fc72e0c917e9c4714161"}}}}"#,
+                r#"{"a":{"$dbPointer":{"$ref":"b","$id":{"$oid":"56e1fc72e0c917e9c4714161"}}}}"#,
+            ),
+            ("0e0000000d610002000000620000", r#"{"a":{"$code":"b"}}"#, r#"{"a":{"$code":"b"}}"#),
+            ("0e0000000e610002000000620000", r#"{"a":{"$symbol":"b"}}"#, r#"{"a":{"$symbol":"b"}}"#),
+            ("160000000f61000e0000000100000000050000000000", r#"{"a":{"$code":"","$scope":{}}}"#, r#"{"a":{"$code":"","$scope":{}}}"#),
+            (
+                "100000001161002a00000015cd5b0700",
+                r#"{"a":{"$timestamp":{"t":123456789,"i":42}}}"#,
+                r#"{"a":{"$timestamp":{"t":123456789,"i":42}}}"#,
+            ),
+            (
+                "180000001364000100000000000000000000000000403000",
+                r#"{"d":{"$numberDecimal":"1"}}"#,
+                r#"{"d":{"$numberDecimal":"1"}}"#,
+            ),
+            ("08000000ff610000", r#"{"a":{"$minKey":1}}"#, r#"{"a":{"$minKey":1}}"#),
+            ("080000007f610000", r#"{"a":{"$maxKey":1}}"#, r#"{"a":{"$maxKey":1}}"#),
+        ] {
+            let bytes = from_hex(encoded);
+            assert_eq!(from_slice(&bytes, ExtendedJson::Canonical).unwrap(), parse(canonical), "{}", encoded);
+            assert_eq!(from_slice(&bytes, ExtendedJson::Relaxed).unwrap(), parse(relaxed), "{}", encoded);
+            assert_eq!(digest::to_hex(&to_vec(&parse(canonical)).unwrap()), encoded, "{}", canonical);
+            assert_eq!(digest::to_hex(&to_vec(&parse(relaxed)).unwrap()), encoded, "{}", relaxed);
+        }
+
+        // Relaxed numbers take the smallest type, so an int64 that fits 32
+        // bits comes back as an int32.
+        let int64 = from_hex("10000000126100010000000000000000");
+        assert_eq!(from_slice(&int64, ExtendedJson::Relaxed).unwrap(), parse(r#"{"a":1}"#));
+        assert_eq!(digest::to_hex(&to_vec(&parse(r#"{"a":1}"#)).unwrap()), "0c0000001061000100000000");
+
+        // Other spellings that writing accepts.
+        for (input, encoded) in [
+            (r#"{"a":{"$date":"2012-12-24T13:15:30.5019+01:00"}}"#, "10000000096100c5d8d6cc3b01000000"),
+            (r#"{"a":{"$date":1356351330501}}"#, "10000000096100c5d8d6cc3b01000000"),
+            (r#"{"x":{"$uuid":"73ffd264-44b3-4c69-90e8-e7d1dfc035d4"}}"#, "1d000000057800100000000473ffd26444b34c6990e8e7d1dfc035d400"),
+            (r#"{"x":{"$binary":{"subType":"2","base64":"//8="}}}"#, "13000000057800060000000202000000ffff00"),
+            (r#"{"a":{"$regularExpression":{"pattern":"abc","options":"mi"}}}"#, "0f0000000b610061626300696d0000"),
+            (r#"{"a":{"$scope":{},"$code":""}}"#, "160000000f61000e0000000100000000050000000000"),
+        ] {
+            assert_eq!(digest::to_hex(&to_vec(&parse(input)).unwrap()), encoded, "{}", input);
+        }
+
+        for (input, error) in [
+            ("[]", "BSON cannot represent a top level that is not a document"),
+            (r#"{"$oid":"56e1fc72e0c917e9c4714161"}"#, "BSON cannot represent a top level that is not a document"),
+            (r#"{"a\u0000":1}"#, "BSON cannot represent a key with a NUL character"),
+            (r#"{"a":{"b\u0000":1}}"#, "BSON cannot represent a key with a NUL character at a"),
+            (r#"{"a":{"b":18446744073709551615}}"#, "BSON cannot represent an integer that exceeds int64 at a.b"),
+            (r#"{"a":1e400}"#, "BSON cannot represent a number beyond a double at a"),
+            (
+                r#"{"a":{"$regularExpression":{"pattern":"\u0000","options":""}}}"#,
+                "BSON cannot represent a regular expression with a NUL character at a",
+            ),
+            (r#"{"a":[1,{"$oid":"xyz"}]}"#, "Invalid Extended JSON $oid at a[1]"),
+            (r#"{"a":{"$oid":"56e1fc72e0c917e9c4714161","b":1}}"#, "Invalid Extended JSON $oid at a"),
+            (r#"{"a":{"$numberInt":"2147483648"}}"#, "Invalid Extended JSON $numberInt at a"),
+            (r#"{"a":{"$numberLong":1}}"#, "Invalid Extended JSON $numberLong at a"),
+            (r#"{"a":{"$numberDouble":"inf"}}"#, "Invalid Extended JSON $numberDouble at a"),
+            (r#"{"a":{"$numberDecimal":"1E-6177"}}"#, "Invalid Extended JSON $numberDecimal at a"),
+            (r#"{"a":{"$binary":{"base64":"//8","subType":"00"}}}"#, "Invalid Extended JSON $binary at a"),
+            (r#"{"a":{"$date":"2012-02-30T00:00:00Z"}}"#, "Invalid Extended JSON $date at a"),
+            (r#"{"a":{"$timestamp":{"t":-1,"i":0}}}"#, "Invalid Extended JSON $timestamp at a"),
+            (r#"{"a":{"$minKey":0}}"#, "Invalid Extended JSON $minKey at a"),
+        ] {
+            assert_eq!(to_vec(&parse(input)).unwrap_err().to_string(), error, "{}", input);
+        }
+
+        let mut nested = vec![5, 0, 0, 0, 0];
+        for _ in 0..200 {
+            let mut outer = ((nested.len() + 8) as i32).to_le_bytes().to_vec();
+            outer.extend([3, b'a', 0]);
+            outer.extend(&nested);
+            outer.push(0);
+            nested = outer;
+        }
+        let nested = digest::to_hex(&nested);
+        for (encoded, message) in [
+            ("", "Empty BSON"),
+            ("0500", "BSON byte 2: Unexpected end of input"),
+            ("0400000000", "BSON byte 0: Invalid document length"),
+            ("0c00000010610001000000", "BSON byte 11: Unexpected end of input"),
+            ("05000000000000", "BSON byte 5: Unexpected data after the document"),
+            ("0500000001", "BSON byte 4: Document is missing its terminating NUL"),
+            ("0d000000036100060000000000", "BSON byte 7: Document length does not match its contents"),
+            ("0800000020610000", "BSON byte 4: Unknown element type 0x20"),
+            ("090000000862000200", "BSON byte 4: Invalid boolean"),
+            ("0e00000002610002000000ff0000", "BSON byte 7: Invalid UTF-8 in a string"),
+            ("0e00000002610002000000620100", "BSON byte 7: String is missing its terminating NUL"),
+            (&nested, "BSON byte 903: Nesting too deep"),
+        ] {
+            assert_eq!(from_slice(&from_hex(encoded), ExtendedJson::Relaxed).unwrap_err().to_string(), message, "{}", encoded);
+        }
+
+        // Documents written one after another, as `mongodump` writes them.
+        let documents = [parse(r#"{"_id":{"$oid":"56e1fc72e0c917e9c4714161"}}"#), parse(r#"{"n":[true,null]}"#)];
+        let mut stream = vec![];
+        for document in &documents {
+            write(&mut stream, document).unwrap();
+        }
+        let read: Vec<JsonData> = Reader::new(std::io::BufReader::new(&stream[..]), ExtendedJson::Relaxed).collect::<Result<_, _>>().unwrap();
+        assert_eq!(read, documents);
+    }
+
+    #[cfg(feature = "bson")]
+    #[test]
+    fn test_decimal128() {
+        use bson::decimal128::{parse, to_string};
+
+        // From the decimal128 tests of the BSON corpus.
+        for (encoded, text) in [
+            ("00000000000000000000000000004030", "0"),
+            ("000000000000000000000000000040b0", "-0"),
+            ("010000000000000000000000000040b0", "-1"),
+            ("01000000000000000000000000003e30", "0.1"),
+            ("01000000000000000000000000004630", "1E+3"),
+            ("01000000000000000000000000000000", "1E-6176"),
+            ("ffffffff638e8d37c087adbe09edff5f", "9.999999999999999999999999999999999E+6144"),
+            ("f2af967ed05c82de3297ff6fde3cf22f", "0.000001234567890123456789012345678901234"),
+            ("f2af967ed05c82de3297ff6fde3cf02f", "1.234567890123456789012345678901234E-7"),
+            ("f2af967ed05c82de3297ff6fde3c3c30", "12345678901234567890123456789012.34"),
+            ("64000000000000000000000000002cb0", "-1.00E-8"),
+            ("b36a0000000000000000000000003c30", "273.15"),
+            ("00000000000000000000000000000078", "Infinity"),
+            ("000000000000000000000000000000f8", "-Infinity"),
+            ("0000000000000000000000000000007c", "NaN"),
+        ] {
+            let bits = u128::from_le_bytes(from_hex(encoded).try_into().unwrap());
+            assert_eq!(to_string(bits), text);
+            assert_eq!(parse(text), Some(bits), "{}", text);
+        }
+
+        // Coefficients past 34 digits are zero, in either layout.
+        assert_eq!(to_string(0x6c10 << 112), "0");
+        assert_eq!(to_string(0x3040 << 112 | 10u128.pow(34)), "0");
+
+        // Exponents out of range are clamped when that keeps the value.
+        for (input, text) in [
+            ("+1", "1"),
+            ("1e3", "1E+3"),
+            ("-0.0", "-0.0"),
+            ("inf", "Infinity"),
+            ("-Infinity", "-Infinity"),
+            ("1E+6112", "1.0E+6112"),
+            ("0E+6300", "0E+6111"),
+            ("0E-6300", "0E-6176"),
+            ("10E-6177", "1E-6176"),
+            ("12345678901234567890123456789012340", "1.234567890123456789012345678901234E+34"),
+        ] {
+            assert_eq!(parse(input).map(to_string).as_deref(), Some(text), "{}", input);
+        }
+        for input in ["", ".", "1e", "e1", "abc", "1.2.3", "1E-6177", "1E+6145", "12345678901234567890123456789012345"] {
+            assert_eq!(parse(input), None, "{}", input);
+        }
+    }
+
+    #[cfg(feature = "bson")]
+    proptest::proptest! {
+        #[test]
+        fn test_bson_round_trip(value in arbitrary_value()) {
+            let data = JsonData::from(JsonValue::Object(JsonObject {
+                members: vec![JsonMember { string: JsonString { string: String::from("value") }, element: JsonElement { value } }],
+            }));
+            // Keys holding NUL cannot be written.
+            if let Ok(written) = bson::to_vec(&data) {
+                assert_eq!(bson::from_slice(&written, bson::ExtendedJson::Relaxed).unwrap(), data);
+                let canonical = bson::from_slice(&written, bson::ExtendedJson::Canonical).unwrap();
+                assert_eq!(bson::to_vec(&canonical).unwrap(), written);
+            }
+        }
+    }
+}
@@ -0,0 +1,292 @@
+use crate::data::*;
+use crate::parse::DEFAULT_MAX_DEPTH;
+use crate::binary::{self, Input};
+use std::io::{Error, ErrorKind, Read, Write};
+
+// MessagePack encoding of the tree. Integers, strings, arrays and maps use
+// the smallest format that holds them, and floats are always float64.
+// MessagePack has no numbers beyond 64 bits: decimal text with a fraction or
+// exponent is written as the nearest float64, and integers that do not fit
+// are an error, as they are for TOML.
+//
+// Reading rejects bin and ext values, and map keys that are not strings.
+
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct MsgpackOptions {
+    // Members are sorted by their encoded keys and every NaN is written with
+    // the same bits, so equal trees always encode to the same bytes. Objects
+    // that repeat a key cannot be encoded.
+    pub deterministic: bool,
+}
+
+pub fn to_vec(data: &JsonData, options: &MsgpackOptions) -> Result<Vec<u8>, Error> {
+    let mut out = vec![];
+    write(&mut out, data, options)?;
+    Ok(out)
+}
+
+// Writes one value.
+pub fn write<W: Write>(writer: &mut W, data: &JsonData, options: &MsgpackOptions) -> Result<(), Error> {
+    write_value(writer, &data.element.value, options)
+}
+
+fn write_value<W: Write>(writer: &mut W, value: &JsonValue, options: &MsgpackOptions) -> Result<(), Error> {
+    match value {
+        JsonValue::Object(object) => write_object(writer, object, options),
+        JsonValue::Array(array) => {
+            match array.elements.len() {
+                length @ 0..=15 => writer.write_all(&[0x90 | length as u8])?,
+                length => write_length(writer, length, [0xdc, 0xdd], "an array")?,
+            }
+            for element in &array.elements {
+                write_value(writer, &element.value, options)?;
+            }
+            Ok(())
+        }
+        JsonValue::String(string) => write_string(writer, &string.string),
+        JsonValue::Number(number) => write_number(writer, number, options),
+        JsonValue::True => writer.write_all(&[0xc3]),
+        JsonValue::False => writer.write_all(&[0xc2]),
+        JsonValue::Null => writer.write_all(&[0xc0]),
+    }
+}
+
+fn write_object<W: Write>(writer: &mut W, object: &JsonObject, options: &MsgpackOptions) -> Result<(), Error> {
+    match object.members.len() {
+        length @ 0..=15 => writer.write_all(&[0x80 | length as u8])?,
+        length => write_length(writer, length, [0xde, 0xdf], "a map")?,
+    }
+    if !options.deterministic {
+        for member in &object.members {
+            write_string(writer, &member.string.string)?;
+            write_value(writer, &member.element.value, options)?;
+        }
+        return Ok(());
+    }
+
+    for (key, member) in binary::sorted_members(object, write_string)? {
+        writer.write_all(&key)?;
+        write_value(writer, &member.element.value, options)?;
+    }
+    Ok(())
+}
+
+fn write_string<W: Write>(writer: &mut W, string: &str) -> Result<(), Error> {
+    match string.len() {
+        length @ 0..=31 => writer.write_all(&[0xa0 | length as u8])?,
+        length @ 32..=0xff => writer.write_all(&[0xd9, length as u8])?,
+        length => write_length(writer, length, [0xda, 0xdb], "a string")?,
+    }
+    writer.write_all(string.as_bytes())
+}
+
+// The 16 and 32-bit forms of a length, with their markers.
+fn write_length<W: Write>(writer: &mut W, length: usize, markers: [u8; 2], what: &str) -> Result<(), Error> {
+    if let Ok(length) = u16::try_from(length) {
+        writer.write_all(&[markers[0]])?;
+        writer.write_all(&length.to_be_bytes())
+    } else if let Ok(length) = u32::try_from(length) {
+        writer.write_all(&[markers[1]])?;
+        writer.write_all(&length.to_be_bytes())
+    } else {
+        Err(Error::new(ErrorKind::InvalidData, format!("MessagePack cannot hold {} of length {}", what, length)))
+    }
+}
+
+fn write_number<W: Write>(writer: &mut W, number: &JsonNumber, options: &MsgpackOptions) -> Result<(), Error> {
+    match number {
+        JsonNumber::Integer(integer) if *integer >= 0 => write_unsigned(writer, *integer as u64),
+        JsonNumber::Integer(integer) => write_negative(writer, *integer),
+        JsonNumber::Unsigned(unsigned) => write_unsigned(writer, *unsigned),
+        JsonNumber::Float(float) if float.is_nan() && options.deterministic => write_float(writer, f64::NAN),
+        JsonNumber::Float(float) => write_float(writer, *float),
+        JsonNumber::Decimal(text) => {
+            if !text.contains(['.', 'e', 'E']) {
+                if let Ok(integer) = text.parse::<i64>() {
+                    return write_number(writer, &JsonNumber::Integer(integer), options);
+                }
+                if let Ok(unsigned) = text.parse::<u64>() {
+                    return write_unsigned(writer, unsigned);
+                }
+            } else if let Ok(float) = text.parse::<f64>() {
+                if float.is_finite() {
+                    return write_float(writer, float);
+                }
+            }
+            Err(Error::new(ErrorKind::InvalidData, format!("MessagePack cannot represent the number {}", text)))
+        }
+    }
+}
+
+fn write_unsigned<W: Write>(writer: &mut W, unsigned: u64) -> Result<(), Error> {
+    match unsigned {
+        0..=0x7f => writer.write_all(&[unsigned as u8]),
+        0x80..=0xff => writer.write_all(&[0xcc, unsigned as u8]),
+        0x100..=0xffff => {
+            writer.write_all(&[0xcd])?;
+            writer.write_all(&(unsigned as u16).to_be_bytes())
+        }
+        0x1_0000..=0xffff_ffff => {
+            writer.write_all(&[0xce])?;
+            writer.write_all(&(unsigned as u32).to_be_bytes())
+        }
+        _ => {
+            writer.write_all(&[0xcf])?;
+            writer.write_all(&unsigned.to_be_bytes())
+        }
+    }
+}
+
+fn write_negative<W: Write>(writer: &mut W, integer: i64) -> Result<(), Error> {
+    if integer >= -32 {
+        writer.write_all(&[integer as u8])
+    } else if let Ok(integer) = i8::try_from(integer) {
+        writer.write_all(&[0xd0, integer as u8])
+    } else if let Ok(integer) = i16::try_from(integer) {
+        writer.write_all(&[0xd1])?;
+        writer.write_all(&integer.to_be_bytes())
+    } else if let Ok(integer) = i32::try_from(integer) {
+        writer.write_all(&[0xd2])?;
+        writer.write_all(&integer.to_be_bytes())
+    } else {
+        writer.write_all(&[0xd3])?;
+        writer.write_all(&integer.to_be_bytes())
+    }
+}
+
+fn write_float<W: Write>(writer: &mut W, float: f64) -> Result<(), Error> {
+    writer.write_all(&[0xcb])?;
+    writer.write_all(&float.to_be_bytes())
+}
+
+pub fn from_slice(bytes: &[u8]) -> Result<JsonData, Error> {
+    let mut reader = Reader::new(bytes);
+    let Some(data) = reader.read()? else {
+        return Err(Error::new(ErrorKind::InvalidData, "Empty MessagePack"));
+    };
+    if reader.input.offset() < bytes.len() {
+        return Err(error(reader.input.offset(), "Unexpected data after the value"));
+    }
+    Ok(data)
+}
+
+// Reads a stream of MessagePack values one at a time.
+pub struct Reader<R> {
+    input: Input<R>,
+}
+
+reader_iterator!(Reader);
+
+fn error(offset: usize, message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("MessagePack byte {}: {}", offset, message))
+}
+
+impl<R: Read> Reader<R> {
+    pub fn new(reader: R) -> Reader<R> {
+        Reader { input: Input::new(reader, error) }
+    }
+
+    // The next value, or `None` when the input ends between values.
+    pub fn read(&mut self) -> Result<Option<JsonData>, Error> {
+        match self.input.next_byte()? {
+            Some(marker) => Ok(Some(JsonData::from(self.value(marker, 0)?))),
+            None => Ok(None),
+        }
+    }
+
+    fn value(&mut self, marker: u8, depth: usize) -> Result<JsonValue, Error> {
+        let start = self.input.offset() - 1;
+        if depth > DEFAULT_MAX_DEPTH {
+            return Err(error(start, "Nesting too deep"));
+        }
+        let number = |number| Ok(JsonValue::Number(number));
+        match marker {
+            0x00..=0x7f => number(JsonNumber::Integer(i64::from(marker))),
+            0x80..=0x8f => self.map(u32::from(marker & 0x0f), depth),
+            0x90..=0x9f => self.array(u32::from(marker & 0x0f), depth),
+            0xa0..=0xbf | 0xd9..=0xdb => {
+                let length = self.string_length(marker)?;
+                Ok(JsonValue::String(JsonString { string: self.string(length, start)? }))
+            }
+            0xc0 => Ok(JsonValue::Null),
+            0xc2 => Ok(JsonValue::False),
+            0xc3 => Ok(JsonValue::True),
+            0xc4..=0xc6 => Err(error(start, "Binary values are not supported")),
+            0xc7..=0xc9 | 0xd4..=0xd8 => Err(error(start, "Extension types are not supported")),
+            0xca => number(JsonNumber::Float(f64::from(f32::from_be_bytes(self.input.exact()?)))),
+            0xcb => number(JsonNumber::Float(f64::from_be_bytes(self.input.exact()?))),
+            0xcc => number(JsonNumber::Integer(i64::from(self.input.exact::<1>()?[0]))),
+            0xcd => number(JsonNumber::Integer(i64::from(u16::from_be_bytes(self.input.exact()?)))),
+            0xce => number(JsonNumber::Integer(i64::from(u32::from_be_bytes(self.input.exact()?)))),
+            0xcf => {
+                let unsigned = u64::from_be_bytes(self.input.exact()?);
+                number(i64::try_from(unsigned).map_or(JsonNumber::Unsigned(unsigned), JsonNumber::Integer))
+            }
+            0xd0 => number(JsonNumber::Integer(i64::from(i8::from_be_bytes(self.input.exact()?)))),
+            0xd1 => number(JsonNumber::Integer(i64::from(i16::from_be_bytes(self.input.exact()?)))),
+            0xd2 => number(JsonNumber::Integer(i64::from(i32::from_be_bytes(self.input.exact()?)))),
+            0xd3 => number(JsonNumber::Integer(i64::from_be_bytes(self.input.exact()?))),
+            0xdc => {
+                let length = u16::from_be_bytes(self.input.exact()?);
+                self.array(u32::from(length), depth)
+            }
+            0xdd => {
+                let length = u32::from_be_bytes(self.input.exact()?);
+                self.array(length, depth)
+            }
+            0xde => {
+                let length = u16::from_be_bytes(self.input.exact()?);
+                self.map(u32::from(length), depth)
+            }
+            0xdf => {
+                let length = u32::from_be_bytes(self.input.exact()?);
+                self.map(length, depth)
+            }
+            0xe0..=0xff => number(JsonNumber::Integer(i64::from(marker as i8))),
+            0xc1 => Err(error(start, "Invalid marker 0xc1")),
+        }
+    }
+
+    // The length that follows one of the string markers.
+    fn string_length(&mut self, marker: u8) -> Result<u32, Error> {
+        match marker {
+            0xd9 => Ok(u32::from(self.input.exact::<1>()?[0])),
+            0xda => Ok(u32::from(u16::from_be_bytes(self.input.exact()?))),
+            0xdb => Ok(u32::from_be_bytes(self.input.exact()?)),
+            _ => Ok(u32::from(marker & 0x1f)),
+        }
+    }
+
+    fn string(&mut self, length: u32, start: usize) -> Result<String, Error> {
+        let bytes = self.input.payload(u64::from(length))?;
+        String::from_utf8(bytes).map_err(|_| error(start, "Invalid UTF-8 in a string"))
+    }
+
+    fn array(&mut self, length: u32, depth: usize) -> Result<JsonValue, Error> {
+        let mut elements = vec![];
+        for _ in 0..length {
+            let marker = self.input.byte()?;
+            elements.push(JsonElement { value: self.value(marker, depth + 1)? });
+        }
+        Ok(JsonValue::Array(JsonArray { elements }))
+    }
+
+    fn map(&mut self, length: u32, depth: usize) -> Result<JsonValue, Error> {
+        let mut members = vec![];
+        for _ in 0..length {
+            let marker = self.input.byte()?;
+            if !matches!(marker, 0xa0..=0xbf | 0xd9..=0xdb) {
+                return Err(error(self.input.offset() - 1, "Map keys must be strings"));
+            }
+            let start = self.input.offset() - 1;
+            let length = self.string_length(marker)?;
+            let string = self.string(length, start)?;
+            let marker = self.input.byte()?;
+            members.push(JsonMember {
+                string: JsonString { string },
+                element: JsonElement { value: self.value(marker, depth + 1)? },
+            });
+        }
+        Ok(JsonValue::Object(JsonObject { members }))
+    }
+}
@@ -1,282 +1,511 @@
 use crate::data::*;
-use std::io::Error;
+use std::borrow::Cow;
+use std::io::{Error, ErrorKind};
 use std::collections::VecDeque;
+use std::iter::Peekable;
 
-pub fn parse(tokens: &mut VecDeque<Token>) -> Result<JsonData, Error> {
-    if tokens.is_empty() {
-        return Err(Error::new(std::io::ErrorKind::InvalidData, "Empty Json"));
-    }
+// Arrays and objects nested deeper than this are rejected instead of
+// recursing until the stack runs out.
+pub const DEFAULT_MAX_DEPTH: usize = 128;
 
-    if let Some(element) = parse_element(tokens)? {
-        if tokens.is_empty() {
-            Ok(JsonData {element})
-        } else {
-            Err(Error::new(std::io::ErrorKind::InvalidData, "Invalid Json: Additional Data Left Over"))
-        }
-    } else {
-        Err(Error::new(std::io::ErrorKind::InvalidData, "Invalid Json: No Element"))
-    }
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct ParseOptions {
+    pub numbers: NumberMode,
+    pub max_depth: usize,
 }
 
-fn parse_element(tokens: &mut VecDeque<Token>) -> Result<Option<JsonElement>, Error> {
-    if let Some(value) = parse_value(tokens)? {
-        Ok(Some(JsonElement {value}))
-    } else {
-        Ok(None)
+impl Default for ParseOptions {
+    fn default() -> ParseOptions {
+        ParseOptions { numbers: NumberMode::default(), max_depth: DEFAULT_MAX_DEPTH }
     }
 }
 
-fn parse_elements(tokens: &mut VecDeque<Token>) -> Result<Option<Vec<JsonElement>>, Error> {
-    if let Some(element) = parse_element(tokens)? {
-        let mut elements = vec![element];
+// Builds a tree from what the parser reads. Every tree in the crate is built
+// through this, so the grammar lives only in this module. Tokens are handed
+// over in the order they appear in the input.
+pub trait Builder<'a> {
+    type Value;
+    type Key;
+    type Member;
+    type Element;
+    // A brace, bracket, colon or comma.
+    type Token;
+
+    fn token(&mut self, span: Span) -> Self::Token;
+    fn string(&mut self, string: Cow<'a, str>, span: Span) -> Self::Value;
+    fn number(&mut self, lexeme: &'a str, span: Span, mode: NumberMode) -> Result<Self::Value, Error>;
+    fn boolean(&mut self, value: bool, span: Span) -> Self::Value;
+    fn null(&mut self, span: Span) -> Self::Value;
+    fn key(&mut self, string: Cow<'a, str>, span: Span) -> Self::Key;
+    fn member(&mut self, key: Self::Key, colon: Self::Token, value: Self::Value, comma: Option<Self::Token>) -> Self::Member;
+    fn element(&mut self, value: Self::Value, comma: Option<Self::Token>) -> Self::Element;
+    fn object(&mut self, open: Self::Token, members: Vec<Self::Member>, close: Self::Token) -> Self::Value;
+    fn array(&mut self, open: Self::Token, elements: Vec<Self::Element>, close: Self::Token) -> Self::Value;
+}
 
-        while let Some(&Token::Comma) = tokens.front() {
-            tokens.pop_front();
-            if let Some(element) = parse_element(tokens)? {
-                elements.push(element);
-            } else {
-                return Err(Error::new(std::io::ErrorKind::InvalidData, "Failed to parse element"));
-            }
-        }
-        
-        return Ok(Some(elements));
-    }
+// A builder that can stand in a value for whatever could not be read.
+pub trait Recovering<'a>: Builder<'a> {
+    fn invalid(&mut self, span: Span) -> Self::Value;
+}
+
+pub type SpannedToken<'a> = (Result<Token<'a>, Error>, Span);
 
-    Ok(None)
+pub fn parse(tokens: &mut VecDeque<Token>) -> Result<JsonData, Error> {
+    parse_with_options(tokens, &ParseOptions::default())
 }
 
-fn parse_value(tokens: &mut VecDeque<Token>) -> Result<Option<JsonValue>, Error> {
-    if let Some(&Token::True) = tokens.front() {
-        tokens.pop_front();
-        Ok(Some(JsonValue::True))
-    } else if let Some(&Token::False) = tokens.front() {
-        tokens.pop_front();
-        Ok(Some(JsonValue::False))
-    } else if let Some(&Token::Null) = tokens.front() {
-        tokens.pop_front();
-        Ok(Some(JsonValue::Null))
-    } else if let Some(object) = parse_object(tokens)? {
-        Ok(Some(JsonValue::Object(object)))
-    } else if let Some(array) = parse_array(tokens)? {
-        Ok(Some(JsonValue::Array(array)))
-    } else if let Some(string) = parse_string(tokens)? {
-        Ok(Some(JsonValue::String(string)))
-    } else if let Some(number) = parse_number(tokens)? {
-        Ok(Some(JsonValue::Number(number)))
-    } else {
-        Ok(None)
-    }
+pub fn parse_with_options(tokens: &mut VecDeque<Token>, options: &ParseOptions) -> Result<JsonData, Error> {
+    // This tree reports no positions, so the tokens need none.
+    let tokens = tokens.drain(..).map(|token| (Ok(token), Span { start: 0, end: 0 }));
+    let value = parse_tokens(tokens, &mut Tree, options)?;
+    Ok(JsonData { element: JsonElement { value } })
 }
 
-fn parse_object(tokens: &mut VecDeque<Token>) -> Result<Option<JsonObject>, Error> {
-    let mut result = JsonObject {members: vec![]};
-    
-    if let Some(&Token::OpenParen) = tokens.front() {
-        tokens.pop_front();
-    } else {
-        return Ok(None);
+// Parses the tokens into whatever the builder makes, stopping at the first
+// error.
+pub fn parse_tokens<'a, B: Builder<'a>>(tokens: impl IntoIterator<Item = SpannedToken<'a>>, builder: &mut B, options: &ParseOptions) -> Result<B::Value, Error> {
+    let mut parser = Parser { tokens: tokens.into_iter().peekable(), builder, options, recovery: None, length: 0 };
+    parser.document()
+}
+
+// Parses the tokens without stopping at errors. They are returned with their
+// spans in the order they were found, and whatever could not be read becomes
+// an invalid value. After an error the parser picks up again at the next
+// comma or closing bracket. `length` is the length of the input, for errors
+// at its end.
+pub fn parse_recovering<'a, B: Recovering<'a>>(tokens: impl IntoIterator<Item = SpannedToken<'a>>, builder: &mut B, options: &ParseOptions, length: usize) -> (B::Value, Vec<(String, Span)>) {
+    let recovery = Recovery { errors: vec![], invalid: B::invalid };
+    let mut parser = Parser { tokens: tokens.into_iter().peekable(), builder, options, recovery: Some(recovery), length };
+    let value = parser.document();
+    let mut errors = parser.recovery.map_or_else(Vec::new, |recovery| recovery.errors);
+    let value = match value {
+        Ok(value) => value,
+        // Errors are collected while recovering, so none is returned here.
+        Err(error) => {
+            let span = Span { start: 0, end: length };
+            errors.push((error.to_string(), span));
+            builder.invalid(span)
+        }
+    };
+    (value, errors)
+}
+
+// The plain tree in `data`.
+struct Tree;
+
+impl<'a> Builder<'a> for Tree {
+    type Value = JsonValue;
+    type Key = JsonString;
+    type Member = JsonMember;
+    type Element = JsonElement;
+    type Token = ();
+
+    fn token(&mut self, _span: Span) {}
+
+    fn string(&mut self, string: Cow<'a, str>, _span: Span) -> JsonValue {
+        JsonValue::String(JsonString { string: string.into_owned() })
     }
 
-    if let Some(members) = parse_members(tokens)? {
-        result.members = members;
+    fn number(&mut self, lexeme: &'a str, span: Span, mode: NumberMode) -> Result<JsonValue, Error> {
+        Ok(JsonValue::Number(number_from_lexeme(lexeme, span, mode)?))
     }
 
-    if let Some(&Token::CloseParen) = tokens.front() {
-        tokens.pop_front();
-    } else {
-        return Err(Error::new(std::io::ErrorKind::InvalidData, "No Closing Paren on Object"));
+    fn boolean(&mut self, value: bool, _span: Span) -> JsonValue {
+        if value { JsonValue::True } else { JsonValue::False }
     }
 
-    Ok(Some(result))
-}
+    fn null(&mut self, _span: Span) -> JsonValue {
+        JsonValue::Null
+    }
 
-fn parse_array(tokens: &mut VecDeque<Token>) -> Result<Option<JsonArray>, Error> {
-    let mut result = JsonArray {elements: vec![]};
-    
-    if let Some(&Token::OpenBracket) = tokens.front() {
-        tokens.pop_front();
-    } else {
-        return Ok(None);
+    fn key(&mut self, string: Cow<'a, str>, _span: Span) -> JsonString {
+        JsonString { string: string.into_owned() }
     }
 
-    if let Some(elements) = parse_elements(tokens)? {
-        result.elements = elements;
+    fn member(&mut self, string: JsonString, _colon: (), value: JsonValue, _comma: Option<()>) -> JsonMember {
+        JsonMember { string, element: JsonElement { value } }
     }
 
-    if let Some(&Token::CloseBracket) = tokens.front() {
-        tokens.pop_front();
-    } else {
-        return Err(Error::new(std::io::ErrorKind::InvalidData, "No Closing Bracket on Array"));
+    fn element(&mut self, value: JsonValue, _comma: Option<()>) -> JsonElement {
+        JsonElement { value }
     }
 
-    Ok(Some(result))
-}
+    fn object(&mut self, _open: (), members: Vec<JsonMember>, _close: ()) -> JsonValue {
+        JsonValue::Object(JsonObject { members })
+    }
 
-fn parse_string(tokens: &mut VecDeque<Token>) -> Result<Option<JsonString>, Error> {
-    if let Some(&Token::String(_)) = tokens.front() {
-        if let Token::String(string) = tokens.pop_front().unwrap() {
-            return Ok(Some(JsonString { string }));
-        } else {
-            return Err(Error::new(std::io::ErrorKind::InvalidData, "String not parsed correctly"));
-        }
+    fn array(&mut self, _open: (), elements: Vec<JsonElement>, _close: ()) -> JsonValue {
+        JsonValue::Array(JsonArray { elements })
     }
+}
 
-    Ok(None)
+struct Recovery<'a, B: Builder<'a>> {
+    errors: Vec<(String, Span)>,
+    invalid: fn(&mut B, Span) -> B::Value,
 }
 
-fn parse_number(tokens: &mut VecDeque<Token>) -> Result<Option<JsonNumber>, Error> {
-    if let Some(integer) = parse_integer(tokens)? {
+struct Parser<'a, 'b, I: Iterator<Item = SpannedToken<'a>>, B: Builder<'a>> {
+    tokens: Peekable<I>,
+    builder: &'b mut B,
+    options: &'b ParseOptions,
+    // Only set when recovering from errors.
+    recovery: Option<Recovery<'a, B>>,
+    length: usize,
+}
 
-        let mut fraction = None;
-        if let Some(new_fraction) = parse_fraction(tokens)? {
-            fraction = Some(new_fraction);
-        }
+impl<'a, I: Iterator<Item = SpannedToken<'a>>, B: Builder<'a>> Parser<'a, '_, I, B> {
+    // The strict parsers have always worded some errors their own way, so
+    // those are given separately.
+    fn error(&self, message: &str, strict: &str) -> Error {
+        Error::new(ErrorKind::InvalidData, if self.recovery.is_some() { message } else { strict })
+    }
 
-        let mut exponent = None;
-        if let Some(new_exponent) = parse_exponent(tokens)? {
-            exponent = Some(new_exponent);
-        }
+    // Returns the error unless recovering, in which case it is kept.
+    fn report(&mut self, error: Error, span: Span) -> Result<(), Error> {
+        let Some(recovery) = &mut self.recovery else {
+            return Err(error);
+        };
+        recovery.errors.push((error.to_string(), span));
+        Ok(())
+    }
 
-        Ok(Some(JsonNumber {
-            integer,
-            fraction,
-            exponent,
-        }))
-    } else {
-        Ok(None)
+    // Like `report`, but also stands in a value for what could not be read.
+    fn invalid(&mut self, error: Error, span: Span) -> Result<B::Value, Error> {
+        let Some(recovery) = &mut self.recovery else {
+            return Err(error);
+        };
+        recovery.errors.push((error.to_string(), span));
+        let invalid = recovery.invalid;
+        Ok(invalid(self.builder, span))
+    }
+
+    // An empty span where the next token starts, or at the end of the input.
+    fn next_span(&mut self) -> Span {
+        let start = self.tokens.peek().map_or(self.length, |(_, span)| span.start);
+        Span { start, end: start }
     }
-}
 
-fn parse_integer(tokens: &mut VecDeque<Token>) -> Result<Option<i64>, Error> {
-    let mut neg_sign = false;
-    let mut found_nums = false;
-    let mut int_value: i64 = 0;
-    let mut chars_found: usize = 0;
-    if let Some(&Token::SignNeg) = tokens.front() {
-        tokens.pop_front();
-        neg_sign = true;
+    // The next token, or `Err` for bytes the lexer could not read.
+    fn peek(&mut self) -> Option<Result<&Token<'a>, ()>> {
+        self.tokens.peek().map(|(token, _)| token.as_ref().map_err(|_| ()))
     }
 
-    while let Some(&Token::Digit(val)) = tokens.front() {
-        tokens.pop_front();
-        found_nums = true;
-        int_value *= 10;
-        int_value += val as i64;
-        chars_found += 1;
+    // Consumes the next token, which has already been peeked.
+    fn skip(&mut self) -> Span {
+        let span = self.next_span();
+        self.tokens.next().map_or(span, |(_, span)| span)
     }
 
-    if neg_sign && !found_nums {
-        Err(Error::new(std::io::ErrorKind::InvalidData, "No digits following sign"))
-    } else if found_nums {
-        if int_value.to_string().len() < chars_found {
-            return Err(Error::new(std::io::ErrorKind::InvalidData, "No leading zeros allowed"));
+    fn next_if(&mut self, expected: &Token) -> Option<Span> {
+        self.tokens.next_if(|(token, _)| token.as_ref().is_ok_and(|token| token == expected)).map(|(_, span)| span)
+    }
+
+    fn document(&mut self) -> Result<B::Value, Error> {
+        if self.tokens.peek().is_none() {
+            let span = self.next_span();
+            return self.invalid(self.error("Empty Json", "Empty Json"), span);
         }
-        if neg_sign {
-            int_value = -int_value;
+
+        let (value, _) = self.element(0, "Invalid Json: No Element")?;
+        if let Some((_, first)) = self.tokens.next() {
+            let end = self.tokens.by_ref().last().map_or(first.end, |(_, span)| span.end);
+            let error = self.error("Invalid Json: Additional Data Left Over", "Invalid Json: Additional Data Left Over");
+            self.report(error, Span { start: first.start, end })?;
         }
-        Ok(Some(int_value))
-    } else {
-        Ok(None)
+        Ok(value)
+    }
+
+    // Reads a value and returns it with its span. When no value starts at the
+    // next token, `missing` is the strict parsers' error. Commas and closing
+    // brackets are left for the caller to resynchronize on; any other
+    // unexpected token is consumed.
+    fn element(&mut self, depth: usize, missing: &str) -> Result<(B::Value, Span), Error> {
+        let next = self.tokens.next_if(|(token, _)| {
+            !matches!(token, Ok(Token::Comma | Token::CloseParen | Token::CloseBracket))
+        });
+        let Some((token, span)) = next else {
+            let span = self.next_span();
+            let value = self.invalid(self.error("Expected a value", missing), span)?;
+            return Ok((value, span));
+        };
+
+        let value = match token {
+            Ok(Token::True) => self.builder.boolean(true, span),
+            Ok(Token::False) => self.builder.boolean(false, span),
+            Ok(Token::Null) => self.builder.null(span),
+            Ok(Token::String(string)) => self.builder.string(string, span),
+            Ok(Token::Number(lexeme, number_span)) => match self.builder.number(lexeme, number_span, self.options.numbers) {
+                Ok(value) => value,
+                Err(error) => self.invalid(error, span)?,
+            },
+            Ok(token @ (Token::OpenParen | Token::OpenBracket)) => match enter_nested(self.options, depth) {
+                Ok(depth) => {
+                    let open = self.builder.token(span);
+                    let (value, end) = if token == Token::OpenParen {
+                        self.object(open, depth)?
+                    } else {
+                        self.array(open, depth)?
+                    };
+                    return Ok((value, Span { start: span.start, end }));
+                }
+                Err(error) => {
+                    let span = Span { start: span.start, end: self.skip_nested() };
+                    let value = self.invalid(error, span)?;
+                    return Ok((value, span));
+                }
+            },
+            Ok(_) => self.invalid(self.error("Expected a value", missing), span)?,
+            Err(error) => self.invalid(error, span)?,
+        };
+        Ok((value, span))
     }
-}
 
-fn parse_fraction(tokens: &mut VecDeque<Token>) -> Result<Option<u64>, Error> {
-    if let Some(&Token::FractionMarker) = tokens.front() {
-        tokens.pop_front();
-        let mut fraction_found = false;
-        let mut fraction_value: u64 = 0;
-
-        while let Some(&Token::Digit(val)) = tokens.front() {
-            tokens.pop_front();
-            fraction_value *= 10;
-            fraction_value += val as u64;
-            fraction_found = true;
+    // Skips the rest of an array or object that is nested too deeply, and
+    // returns the offset past its closing bracket.
+    fn skip_nested(&mut self) -> usize {
+        let mut open = 1;
+        for (token, span) in self.tokens.by_ref() {
+            match token {
+                Ok(Token::OpenParen | Token::OpenBracket) => open += 1,
+                Ok(Token::CloseParen | Token::CloseBracket) => open -= 1,
+                _ => {}
+            }
+            if open == 0 {
+                return span.end;
+            }
         }
+        self.length
+    }
 
-        if fraction_found {
-            return Ok(Some(fraction_value));
-        } else {
-            return Err(Error::new(std::io::ErrorKind::InvalidData, "No fraction component after fraction marker"));
+    // Expects the opening brace to be consumed already. Returns the object and
+    // the offset just past its closing brace.
+    fn object(&mut self, open: B::Token, depth: usize) -> Result<(B::Value, usize), Error> {
+        let mut members = vec![];
+        // What the strict parsers report when no member follows.
+        let mut missing = "No Closing Paren on Object";
+
+        loop {
+            let span = self.next_span();
+            let member = match self.peek() {
+                None | Some(Ok(Token::CloseBracket)) => {
+                    self.report(self.error("No Closing Paren on Object", missing), span)?;
+                    let close = self.builder.token(span);
+                    return Ok((self.builder.object(open, members, close), span.end));
+                }
+                Some(Ok(Token::CloseParen)) => {
+                    let span = self.skip();
+                    let close = self.builder.token(span);
+                    return Ok((self.builder.object(open, members, close), span.end));
+                }
+                Some(Ok(Token::String(_))) => match self.tokens.next() {
+                    Some((Ok(Token::String(string)), span)) => {
+                        let key = self.builder.key(string, span);
+                        let colon = self.colon()?;
+                        let (value, _) = self.element(depth, "No element for string")?;
+                        Some((key, colon, value))
+                    }
+                    _ => None,
+                },
+                Some(Ok(Token::Comma)) => {
+                    let span = self.skip();
+                    self.report(self.error("Expected a string key", missing), span)?;
+                    continue;
+                }
+                // Anything else is read as a key that cannot be used,
+                // followed by its value if there is a colon. A lexer error is
+                // reported when the element is read.
+                key => {
+                    let lexed = key.is_some_and(|key| key.is_ok());
+                    if self.recovery.is_none() {
+                        return Err(Error::new(ErrorKind::InvalidData, missing));
+                    }
+                    let (_, span) = self.element(depth, missing)?;
+                    if lexed {
+                        self.report(self.error("Expected a string key", missing), span)?;
+                    }
+                    if self.next_if(&Token::Colon).is_some() {
+                        self.element(depth, missing)?;
+                    }
+                    None
+                }
+            };
+
+            let comma = self.separator(Token::CloseParen, "Expected ',' or '}'", "No Closing Paren on Object", "Failed to parse members")?;
+            missing = if comma.is_some() { "Failed to parse members" } else { "No Closing Paren on Object" };
+            if let Some((key, colon, value)) = member {
+                members.push(self.builder.member(key, colon, value, comma));
+            }
         }
     }
-    Ok(None)
-}
 
-fn parse_exponent(tokens: &mut VecDeque<Token>) -> Result<Option<i64>, Error> {
-    if let Some(&Token::ExponentMarker) = tokens.front() {
-        tokens.pop_front();
-        let mut found_exponent = false;
-        let mut exponent_value: i64 = 0;
-        let mut found_sign = false;
-        let mut neg_sign = false;
-
-        if let Some(&Token::SignNeg) = tokens.front() {
-            tokens.pop_front();
-            found_sign = true;
-            neg_sign = true;
-        } else if let Some(&Token::SignPos) = tokens.front() {
-            tokens.pop_front();
-            found_sign = true;
+    // Reads the colon after a key. A missing one is reported and read as an
+    // empty token where it should have been.
+    fn colon(&mut self) -> Result<B::Token, Error> {
+        let span = match self.next_if(&Token::Colon) {
+            Some(span) => span,
+            None => {
+                let span = self.next_span();
+                self.report(self.error("No colon in member", "No colon in member"), span)?;
+                span
+            }
+        };
+        Ok(self.builder.token(span))
+    }
+
+    // Expects the opening bracket to be consumed already. Returns the array
+    // and the offset just past its closing bracket.
+    fn array(&mut self, open: B::Token, depth: usize) -> Result<(B::Value, usize), Error> {
+        let mut elements = vec![];
+        // What the strict parsers report when no element follows.
+        let mut missing = "No Closing Bracket on Array";
+
+        loop {
+            let span = self.next_span();
+            match self.peek() {
+                None | Some(Ok(Token::CloseParen)) => {
+                    self.report(self.error("No Closing Bracket on Array", missing), span)?;
+                    let close = self.builder.token(span);
+                    return Ok((self.builder.array(open, elements, close), span.end));
+                }
+                Some(Ok(Token::CloseBracket)) => {
+                    let span = self.skip();
+                    let close = self.builder.token(span);
+                    return Ok((self.builder.array(open, elements, close), span.end));
+                }
+                Some(Ok(Token::Comma)) => {
+                    self.report(self.error("Expected a value", missing), span)?;
+                    self.skip();
+                    continue;
+                }
+                _ => {}
+            }
+
+            let (value, _) = self.element(depth, missing)?;
+            let comma = self.separator(Token::CloseBracket, "Expected ',' or ']'", "No Closing Bracket on Array", "Failed to parse element")?;
+            missing = if comma.is_some() { "Failed to parse element" } else { "No Closing Bracket on Array" };
+            elements.push(self.builder.element(value, comma));
         }
+    }
 
-        while let Some(&Token::Digit(val)) = tokens.front() {
-            tokens.pop_front();
-            exponent_value *= 10;
-            exponent_value += val as i64;
-            found_exponent = true;
+    // Reads the comma after a member or element. A missing comma is reported
+    // but not inserted, so the next token is read as the next entry. The
+    // strict parsers report `unclosed` for a missing comma and `trailing` for
+    // one before the closing bracket.
+    fn separator(&mut self, close: Token, message: &str, unclosed: &str, trailing: &str) -> Result<Option<B::Token>, Error> {
+        if let Some(span) = self.next_if(&Token::Comma) {
+            let comma = self.builder.token(span);
+            if self.peek() == Some(Ok(&close)) {
+                self.report(self.error("Trailing comma", trailing), span)?;
+            }
+            return Ok(Some(comma));
         }
 
-        if found_sign && !found_exponent {
-            return Err(Error::new(std::io::ErrorKind::InvalidData, "No digits following sign"));
-        } else if found_exponent {
-            if neg_sign {
-                exponent_value = -exponent_value;
+        match self.peek() {
+            None | Some(Ok(Token::CloseParen | Token::CloseBracket)) => {}
+            Some(_) => {
+                let span = self.next_span();
+                self.report(self.error(message, unclosed), span)?;
             }
-            return Ok(Some(exponent_value));
-        } else {
-            return Err(Error::new(std::io::ErrorKind::InvalidData, "No digits following exponent"));
         }
+        Ok(None)
     }
-    Ok(None)
 }
 
-fn parse_members(tokens: &mut VecDeque<Token>) -> Result<Option<Vec<JsonMember>>, Error> {
-    if let Some(member) = parse_member(tokens)? {
-        let mut members = vec![member];
+// Returns the depth inside a new array or object, or an error past the limit.
+pub fn enter_nested(options: &ParseOptions, depth: usize) -> Result<usize, Error> {
+    if depth < options.max_depth {
+        Ok(depth + 1)
+    } else {
+        Err(Error::new(ErrorKind::InvalidData, "Invalid Json: Nesting too deep"))
+    }
+}
 
-        while let Some(&Token::Comma) = tokens.front() {
-            tokens.pop_front();
-            if let Some(member) = parse_member(tokens)? {
-                members.push(member);
-            } else {
-                return Err(Error::new(std::io::ErrorKind::InvalidData, "Failed to parse members"));
-            }
+// The lexer has already checked the grammar, so the lexeme is a valid JSON
+// number and only needs converting.
+pub fn number_from_lexeme(lexeme: &str, span: Span, mode: NumberMode) -> Result<JsonNumber, Error> {
+    let integral = !lexeme.contains(['.', 'e', 'E']);
+    if integral && mode != NumberMode::Float {
+        if let Some(integer) = parse_integer(lexeme) {
+            return Ok(integer);
         }
-        
-        return Ok(Some(members));
     }
-    
-    Ok(None)
-}
+    if mode == NumberMode::Arbitrary {
+        return Ok(JsonNumber::Decimal(String::from(lexeme)));
+    }
 
-fn parse_member(tokens: &mut VecDeque<Token>) -> Result<Option<JsonMember>, Error> {
-    let string;
+    // The grammar has no upper bound, so a valid document can hold numbers
+    // beyond `f64`. Only `Float` mode has nowhere else to put them.
+    let float = parse_float(lexeme);
+    match mode {
+        _ if float.is_finite() => Ok(JsonNumber::Float(float)),
+        NumberMode::Float => {
+            Err(Error::new(std::io::ErrorKind::InvalidData, format!("Number out of range at offset {}", span.start)))
+        }
+        _ => Ok(JsonNumber::Decimal(String::from(lexeme))),
+    }
+}
 
-    if let Some(parsed_string) = parse_string(tokens)? {
-        string = parsed_string;
-    } else {
-        return Ok(None);
+// Accumulates the digits with overflow checks, so anything that does not fit
+// an `i64` or `u64` falls through to the float path instead of wrapping.
+fn parse_integer(lexeme: &str) -> Option<JsonNumber> {
+    let (negative, digits) = match lexeme.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, lexeme),
+    };
+    let mut magnitude: u64 = 0;
+    for digit in digits.bytes() {
+        magnitude = magnitude.checked_mul(10)?.checked_add(u64::from(digit - b'0'))?;
     }
 
-    if let Some(&Token::Colon) = tokens.front() {
-        tokens.pop_front();
+    if negative {
+        if magnitude == 0 {
+            // -0 has no integer form, so keep its sign as a float.
+            Some(JsonNumber::Float(-0.0))
+        } else if magnitude <= i64::MAX as u64 + 1 {
+            Some(JsonNumber::Integer((magnitude as i64).wrapping_neg()))
+        } else {
+            None
+        }
     } else {
-        return Err(Error::new(std::io::ErrorKind::InvalidData, "No colon in member"));
+        match i64::try_from(magnitude) {
+            Ok(integer) => Some(JsonNumber::Integer(integer)),
+            Err(_) => Some(JsonNumber::Unsigned(magnitude)),
+        }
     }
+}
 
-    if let Some(element) = parse_element(tokens)? {
-        Ok(Some(JsonMember {string, element}))
-    } else {
-        Err(Error::new(std::io::ErrorKind::InvalidData, "No element for string"))
+// Most numbers in real documents have a short mantissa and a small exponent.
+// Both then fit an `f64` exactly, and a single multiply or divide rounds
+// correctly (Clinger's fast path). Everything else goes to `str::parse`, whose
+// Eisel-Lemire implementation is correctly rounded for every input.
+fn parse_float(lexeme: &str) -> f64 {
+    const POWERS: [f64; 23] = [
+        1e0, 1e1, 1e2, 1e3, 1e4, 1e5, 1e6, 1e7, 1e8, 1e9, 1e10, 1e11,
+        1e12, 1e13, 1e14, 1e15, 1e16, 1e17, 1e18, 1e19, 1e20, 1e21, 1e22,
+    ];
+
+    let (mantissa, exponent) = lexeme.split_once(['e', 'E']).unwrap_or((lexeme, "0"));
+    let (negative, mantissa) = match mantissa.strip_prefix('-') {
+        Some(mantissa) => (true, mantissa),
+        None => (false, mantissa),
+    };
+    let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+
+    // Fifteen digits stay below 2^53, so the mantissa converts exactly.
+    if whole.len() + fraction.len() <= 15 {
+        if let Ok(exponent) = exponent.parse::<i64>() {
+            let digits = whole.bytes().chain(fraction.bytes())
+                .fold(0u64, |value, digit| value * 10 + u64::from(digit - b'0')) as f64;
+            let exponent = exponent.saturating_sub(fraction.len() as i64);
+            let value = match exponent {
+                0..=22 => Some(digits * POWERS[exponent as usize]),
+                -22..=-1 => Some(digits / POWERS[-exponent as usize]),
+                _ => None,
+            };
+            if let Some(value) = value {
+                return if negative { -value } else { value };
+            }
+        }
     }
-}
\ No newline at end of file
+    lexeme.parse().unwrap_or(f64::NAN)
+}
@@ -0,0 +1,54 @@
+use crate::parse::ParseOptions;
+use crate::spanned::{JsonData, Lines, SourceSpan, Tree};
+use std::fmt;
+
+// A parser that keeps going after a syntax error, for editors and linters that
+// want every problem in a document at once. Errors are collected instead of
+// returned, and whatever could not be read becomes a `JsonValue::Error` node in
+// the spanned tree. After an error the parser picks up again at the next comma
+// or closing bracket.
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct SyntaxError {
+    pub message: String,
+    pub span: SourceSpan,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Recovered {
+    pub data: JsonData,
+    // In the order they appear in the document.
+    pub errors: Vec<SyntaxError>,
+}
+
+pub fn parse(string: &str) -> Recovered {
+    parse_with_options(string, &ParseOptions::default())
+}
+
+pub fn parse_with_options(string: &str, options: &ParseOptions) -> Recovered {
+    parse_bytes_with_options(string.as_bytes(), options)
+}
+
+// Input that is not valid UTF-8 is read as it is, so invalid sequences are
+// reported with the other errors and every offset is into `bytes`.
+pub fn parse_bytes(bytes: &[u8]) -> Recovered {
+    parse_bytes_with_options(bytes, &ParseOptions::default())
+}
+
+pub fn parse_bytes_with_options(bytes: &[u8], options: &ParseOptions) -> Recovered {
+    let tokens = crate::lex::lex_recovering(bytes);
+    let mut tree = Tree { lines: Lines::from_bytes(bytes) };
+    let (element, errors) = crate::parse::parse_recovering(tokens, &mut tree, options, bytes.len());
+
+    let mut errors: Vec<SyntaxError> = errors.into_iter()
+        .map(|(message, span)| SyntaxError { message, span: tree.lines.span(span) })
+        .collect();
+    errors.sort_by_key(|error| error.span.start.offset);
+    Recovered { data: JsonData {element}, errors }
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.span.start.line, self.span.start.column, self.message)
+    }
+}
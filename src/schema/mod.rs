@@ -0,0 +1,843 @@
+use crate::data::*;
+use crate::write::value_to_string;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::Error;
+use std::path::{Path, PathBuf};
+
+mod regex;
+
+use regex::Regex;
+
+const MAX_REF_DEPTH: usize = 64;
+
+pub trait Resolver {
+    fn resolve(&self, uri: &str) -> Result<JsonData, Error>;
+}
+
+pub struct NoResolver;
+
+impl Resolver for NoResolver {
+    fn resolve(&self, uri: &str) -> Result<JsonData, Error> {
+        Err(Error::new(std::io::ErrorKind::NotFound, format!("Cannot resolve remote schema {:?}", uri)))
+    }
+}
+
+// Resolves references against schema files on disk. Relative and `file://`
+// URIs are read as paths, and any other URI is looked up by its file name in
+// the schema directory so that published schemas can be used offline.
+pub struct FileResolver {
+    directory: PathBuf,
+}
+
+impl FileResolver {
+    pub fn new(directory: &Path) -> FileResolver {
+        FileResolver { directory: directory.to_path_buf() }
+    }
+}
+
+impl Resolver for FileResolver {
+    fn resolve(&self, uri: &str) -> Result<JsonData, Error> {
+        let path = if let Some(path) = uri.strip_prefix("file://") {
+            PathBuf::from(path)
+        } else if uri.contains("://") || uri.starts_with("urn:") {
+            self.directory.join(uri.rsplit('/').next().unwrap_or(uri))
+        } else {
+            self.directory.join(uri)
+        };
+
+        let string = std::fs::read_to_string(&path)?;
+        let mut tokens = crate::lex::lex(&mut string.as_str())?;
+        crate::parse::parse(&mut tokens)
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct ValidationError {
+    pub instance_path: String,
+    pub schema_path: String,
+    pub keyword: String,
+    pub message: String,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let instance_path = if self.instance_path.is_empty() { "(root)" } else { self.instance_path.as_str() };
+        write!(f, "{}: {} ({} at #{})", instance_path, self.message, self.keyword, self.schema_path)
+    }
+}
+
+struct Resource {
+    value: JsonValue,
+    base: String,
+}
+
+pub struct Schema {
+    root_uri: String,
+    resources: HashMap<String, Resource>,
+    patterns: HashMap<String, Regex>,
+}
+
+#[derive(Clone)]
+struct Scope {
+    base: String,
+    instance_path: String,
+    schema_path: String,
+    depth: usize,
+}
+
+impl Scope {
+    fn instance(&self, token: &str) -> Scope {
+        Scope {
+            instance_path: pointer_push(&self.instance_path, token),
+            ..self.clone()
+        }
+    }
+
+    fn keyword(&self, token: &str) -> Scope {
+        Scope {
+            schema_path: pointer_push(&self.schema_path, token),
+            ..self.clone()
+        }
+    }
+
+    fn error(&self, keyword: &str, message: String) -> ValidationError {
+        ValidationError {
+            instance_path: self.instance_path.clone(),
+            schema_path: pointer_push(&self.schema_path, keyword),
+            keyword: String::from(keyword),
+            message,
+        }
+    }
+}
+
+impl Schema {
+    pub fn compile(schema: &JsonData) -> Result<Schema, Error> {
+        Schema::compile_with_resolver(schema, &NoResolver)
+    }
+
+    pub fn compile_with_resolver(schema: &JsonData, resolver: &dyn Resolver) -> Result<Schema, Error> {
+        let root = &schema.element.value;
+        let root_uri = match root {
+            JsonValue::Object(object) => string_keyword(object, "$id").map(strip_fragment).unwrap_or_default(),
+            _ => String::new(),
+        };
+
+        let mut compiled = Schema {
+            root_uri: root_uri.clone(),
+            resources: HashMap::new(),
+            patterns: HashMap::new(),
+        };
+        compiled.resources.insert(root_uri.clone(), Resource { value: root.clone(), base: root_uri.clone() });
+
+        let mut references = vec![];
+        compiled.register(root, &root_uri, &mut references)?;
+
+        let mut resolved = HashSet::new();
+        while let Some(reference) = references.pop() {
+            if !resolved.insert(reference.clone()) {
+                continue;
+            }
+            let document = strip_fragment(&reference);
+            if !compiled.resources.contains_key(&document) {
+                let remote = resolver.resolve(&document)?;
+                let value = remote.element.value;
+                compiled.resources.insert(document.clone(), Resource { value: value.clone(), base: document.clone() });
+                compiled.register(&value, &document, &mut references)?;
+            }
+            // A reference can point where `register` does not walk, as in
+            // `{"$ref": "#/x", "x": {...}}`, so its target is registered too.
+            if let Some((target @ JsonValue::Object(_), base)) = compiled.resolve_reference(&reference) {
+                let (target, base) = (target.clone(), String::from(base));
+                compiled.register(&target, &base, &mut references)?;
+            }
+        }
+
+        Ok(compiled)
+    }
+
+    pub fn validate(&self, instance: &JsonData) -> Vec<ValidationError> {
+        let root = &self.resources[&self.root_uri];
+        let scope = Scope {
+            base: root.base.clone(),
+            instance_path: String::new(),
+            schema_path: String::new(),
+            depth: 0,
+        };
+        let mut errors = vec![];
+        self.validate_value(&instance.element.value, &root.value, &scope, &mut errors);
+        errors
+    }
+
+    pub fn is_valid(&self, instance: &JsonData) -> bool {
+        self.validate(instance).is_empty()
+    }
+
+    // The properties the schema declares for the object at `path`, with their
+    // descriptions, for editors offering completions. Follows `$ref` and the
+    // branches of `allOf`, `anyOf` and `oneOf` along the way.
+    pub fn property_suggestions(&self, path: &[String]) -> Vec<(String, Option<String>)> {
+        let root = &self.resources[&self.root_uri];
+        let mut schemas = self.expand(&root.value, &root.base, 0);
+        for token in path {
+            let mut children = vec![];
+            for (schema, base) in &schemas {
+                for child in self.child_schemas(schema, token) {
+                    children.extend(self.expand(child, base, 0));
+                }
+            }
+            schemas = children;
+        }
+
+        let mut suggestions: Vec<(String, Option<String>)> = vec![];
+        for (schema, _) in schemas {
+            let Some(JsonValue::Object(properties)) = schema.get("properties").map(|element| &element.value) else {
+                continue;
+            };
+            for member in &properties.members {
+                if suggestions.iter().any(|(name, _)| *name == member.string.string) {
+                    continue;
+                }
+                let description = match &member.element.value {
+                    JsonValue::Object(property) => string_keyword(property, "description").map(String::from),
+                    _ => None,
+                };
+                suggestions.push((member.string.string.clone(), description));
+            }
+        }
+        suggestions
+    }
+
+    // The object schemas that apply where `schema` does, with their bases.
+    fn expand<'a>(&'a self, schema: &'a JsonValue, base: &str, depth: usize) -> Vec<(&'a JsonObject, String)> {
+        let JsonValue::Object(object) = schema else {
+            return vec![];
+        };
+        if depth >= MAX_REF_DEPTH {
+            return vec![];
+        }
+
+        let mut base = String::from(base);
+        if let Some(id) = string_keyword(object, "$id") {
+            base = strip_fragment(&join_uri(&base, id));
+        }
+        let mut result = vec![(object, base.clone())];
+        if let Some(reference) = string_keyword(object, "$ref") {
+            if let Some((target, target_base)) = self.resolve_reference(&join_uri(&base, reference)) {
+                result.extend(self.expand(target, target_base, depth + 1));
+            }
+        }
+        for keyword in ["allOf", "anyOf", "oneOf"] {
+            if let Some(JsonValue::Array(branches)) = schema_keyword(object, keyword) {
+                for branch in &branches.elements {
+                    result.extend(self.expand(&branch.value, &base, depth + 1));
+                }
+            }
+        }
+        result
+    }
+
+    // The subschemas that apply to the member or element named by `token`.
+    fn child_schemas<'a>(&'a self, schema: &'a JsonObject, token: &str) -> Vec<&'a JsonValue> {
+        let mut children = vec![];
+        let property = match schema_keyword(schema, "properties") {
+            Some(JsonValue::Object(properties)) => properties.get(token),
+            _ => None,
+        };
+        if let Some(property) = property {
+            children.push(&property.value);
+        }
+        if let Some(JsonValue::Object(patterns)) = schema_keyword(schema, "patternProperties") {
+            for pattern in &patterns.members {
+                if self.patterns.get(&pattern.string.string).is_some_and(|regex| regex.is_match(token)) {
+                    children.push(&pattern.element.value);
+                }
+            }
+        }
+        if property.is_none() {
+            children.extend(schema_keyword(schema, "additionalProperties"));
+        }
+
+        if let Ok(index) = token.parse::<usize>() {
+            match (schema_keyword(schema, "prefixItems"), schema_keyword(schema, "items")) {
+                (Some(JsonValue::Array(prefix)), _) if index < prefix.elements.len() => children.push(&prefix.elements[index].value),
+                (_, Some(JsonValue::Array(items))) => children.extend(items.elements.get(index).map(|element| &element.value)),
+                (_, Some(items)) => children.push(items),
+                _ => {}
+            }
+        }
+        children
+    }
+
+    fn register(&mut self, schema: &JsonValue, base: &str, references: &mut Vec<String>) -> Result<(), Error> {
+        let object = match schema {
+            JsonValue::Object(object) => object,
+            JsonValue::True | JsonValue::False => return Ok(()),
+            _ => return Err(Error::new(std::io::ErrorKind::InvalidData, "Schema must be an object or boolean")),
+        };
+
+        let mut base = String::from(base);
+        if let Some(id) = string_keyword(object, "$id") {
+            base = strip_fragment(&join_uri(&base, id));
+            self.resources.insert(base.clone(), Resource { value: schema.clone(), base: base.clone() });
+        }
+        if let Some(anchor) = string_keyword(object, "$anchor") {
+            self.resources.insert(format!("{}#{}", base, anchor), Resource { value: schema.clone(), base: base.clone() });
+        }
+        if let Some(reference) = string_keyword(object, "$ref") {
+            references.push(join_uri(&base, reference));
+        }
+        if let Some(pattern) = string_keyword(object, "pattern") {
+            self.compile_pattern(pattern)?;
+        }
+        if let Some(JsonValue::Object(patterns)) = object.get("patternProperties").map(|element| &element.value) {
+            for member in &patterns.members {
+                self.compile_pattern(&member.string.string)?;
+            }
+        }
+
+        for member in &object.members {
+            let value = &member.element.value;
+            match member.string.string.as_str() {
+                "properties" | "patternProperties" | "$defs" | "definitions" | "dependentSchemas" => {
+                    if let JsonValue::Object(schemas) = value {
+                        for schema in &schemas.members {
+                            self.register(&schema.element.value, &base, references)?;
+                        }
+                    }
+                }
+                "prefixItems" | "allOf" | "anyOf" | "oneOf" => {
+                    if let JsonValue::Array(schemas) = value {
+                        for schema in &schemas.elements {
+                            self.register(&schema.value, &base, references)?;
+                        }
+                    }
+                }
+                "items" => match value {
+                    JsonValue::Array(schemas) => {
+                        for schema in &schemas.elements {
+                            self.register(&schema.value, &base, references)?;
+                        }
+                    }
+                    _ => self.register(value, &base, references)?,
+                },
+                "additionalProperties" | "additionalItems" | "contains" | "propertyNames" | "not" | "if" | "then" | "else" => {
+                    self.register(value, &base, references)?;
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    fn compile_pattern(&mut self, pattern: &str) -> Result<(), Error> {
+        if !self.patterns.contains_key(pattern) {
+            let regex = Regex::new(pattern)
+                .map_err(|err| Error::new(std::io::ErrorKind::InvalidData, format!("Invalid pattern {:?}: {}", pattern, err)))?;
+            self.patterns.insert(String::from(pattern), regex);
+        }
+        Ok(())
+    }
+
+    fn resolve_reference(&self, reference: &str) -> Option<(&JsonValue, &str)> {
+        if let Some(resource) = self.resources.get(reference) {
+            return Some((&resource.value, &resource.base));
+        }
+
+        let (document, fragment) = reference.split_once('#')?;
+        let resource = self.resources.get(document)?;
+        let mut value = &resource.value;
+        for token in fragment.split('/').skip(1) {
+            let token = token.replace("~1", "/").replace("~0", "~");
+            value = match value {
+                JsonValue::Object(object) => &object.get(&token)?.value,
+                JsonValue::Array(array) => &array.elements.get(token.parse::<usize>().ok()?)?.value,
+                _ => return None,
+            };
+        }
+        Some((value, &resource.base))
+    }
+
+    fn check(&self, instance: &JsonValue, schema: &JsonValue, scope: &Scope) -> Vec<ValidationError> {
+        let mut errors = vec![];
+        self.validate_value(instance, schema, scope, &mut errors);
+        errors
+    }
+
+    fn validate_value(&self, instance: &JsonValue, schema: &JsonValue, scope: &Scope, errors: &mut Vec<ValidationError>) {
+        let object = match schema {
+            JsonValue::Object(object) => object,
+            JsonValue::False => {
+                errors.push(ValidationError {
+                    instance_path: scope.instance_path.clone(),
+                    schema_path: scope.schema_path.clone(),
+                    keyword: String::from("false"),
+                    message: String::from("No value is allowed by a false schema"),
+                });
+                return;
+            }
+            _ => return,
+        };
+
+        let mut scope = scope.clone();
+        if let Some(id) = string_keyword(object, "$id") {
+            scope.base = strip_fragment(&join_uri(&scope.base, id));
+        }
+
+        if let Some(reference) = string_keyword(object, "$ref") {
+            self.validate_reference(instance, reference, &scope, errors);
+        }
+
+        self.validate_generic(instance, object, &scope, errors);
+        self.validate_combinators(instance, object, &scope, errors);
+
+        match instance {
+            JsonValue::Object(instance) => self.validate_object(instance, object, &scope, errors),
+            JsonValue::Array(instance) => self.validate_array(instance, object, &scope, errors),
+            JsonValue::String(instance) => self.validate_string(&instance.string, object, &scope, errors),
+            JsonValue::Number(instance) => validate_number(instance, object, &scope, errors),
+            _ => {}
+        }
+    }
+
+    fn validate_reference(&self, instance: &JsonValue, reference: &str, scope: &Scope, errors: &mut Vec<ValidationError>) {
+        let uri = join_uri(&scope.base, reference);
+        let Some((target, base)) = self.resolve_reference(&uri) else {
+            errors.push(scope.error("$ref", format!("Unresolvable reference {:?}", reference)));
+            return;
+        };
+        if scope.depth >= MAX_REF_DEPTH {
+            errors.push(scope.error("$ref", String::from("Maximum reference depth exceeded")));
+            return;
+        }
+
+        let scope = Scope {
+            base: String::from(base),
+            schema_path: pointer_push(&scope.schema_path, "$ref"),
+            depth: scope.depth + 1,
+            ..scope.clone()
+        };
+        self.validate_value(instance, target, &scope, errors);
+    }
+
+    fn validate_generic(&self, instance: &JsonValue, schema: &JsonObject, scope: &Scope, errors: &mut Vec<ValidationError>) {
+        if let Some(element) = schema.get("type") {
+            let allowed: Vec<&str> = match &element.value {
+                JsonValue::String(string) => vec![string.string.as_str()],
+                JsonValue::Array(array) => array.elements.iter().filter_map(|element| match &element.value {
+                    JsonValue::String(string) => Some(string.string.as_str()),
+                    _ => None,
+                }).collect(),
+                _ => vec![],
+            };
+            if !allowed.iter().any(|name| type_matches(instance, name)) {
+                errors.push(scope.error("type", format!("Expected {} but found {}", allowed.join(" or "), instance.type_name())));
+            }
+        }
+
+        if let Some(JsonValue::Array(allowed)) = schema.get("enum").map(|element| &element.value) {
+            if !allowed.elements.iter().any(|element| json_equal(instance, &element.value)) {
+                errors.push(scope.error("enum", format!("{} is not one of the allowed values", value_to_string(instance))));
+            }
+        }
+
+        if let Some(element) = schema.get("const") {
+            if !json_equal(instance, &element.value) {
+                errors.push(scope.error("const", format!("Expected {}", value_to_string(&element.value))));
+            }
+        }
+    }
+
+    fn validate_combinators(&self, instance: &JsonValue, schema: &JsonObject, scope: &Scope, errors: &mut Vec<ValidationError>) {
+        if let Some(JsonValue::Array(schemas)) = schema.get("allOf").map(|element| &element.value) {
+            for (index, subschema) in schemas.elements.iter().enumerate() {
+                let scope = scope.keyword("allOf").keyword(&index.to_string());
+                self.validate_value(instance, &subschema.value, &scope, errors);
+            }
+        }
+
+        if let Some(JsonValue::Array(schemas)) = schema.get("anyOf").map(|element| &element.value) {
+            let valid = schemas.elements.iter().enumerate().any(|(index, subschema)| {
+                let scope = scope.keyword("anyOf").keyword(&index.to_string());
+                self.check(instance, &subschema.value, &scope).is_empty()
+            });
+            if !valid {
+                errors.push(scope.error("anyOf", String::from("Value does not match any of the schemas")));
+            }
+        }
+
+        if let Some(JsonValue::Array(schemas)) = schema.get("oneOf").map(|element| &element.value) {
+            let valid = schemas.elements.iter().enumerate().filter(|(index, subschema)| {
+                let scope = scope.keyword("oneOf").keyword(&index.to_string());
+                self.check(instance, &subschema.value, &scope).is_empty()
+            }).count();
+            if valid != 1 {
+                errors.push(scope.error("oneOf", format!("Value matches {} of the schemas instead of exactly one", valid)));
+            }
+        }
+
+        if let Some(element) = schema.get("not") {
+            if self.check(instance, &element.value, &scope.keyword("not")).is_empty() {
+                errors.push(scope.error("not", String::from("Value must not match the schema")));
+            }
+        }
+
+        if let Some(element) = schema.get("if") {
+            if self.check(instance, &element.value, &scope.keyword("if")).is_empty() {
+                if let Some(then) = schema.get("then") {
+                    self.validate_value(instance, &then.value, &scope.keyword("then"), errors);
+                }
+            } else if let Some(otherwise) = schema.get("else") {
+                self.validate_value(instance, &otherwise.value, &scope.keyword("else"), errors);
+            }
+        }
+    }
+
+    fn validate_object(&self, instance: &JsonObject, schema: &JsonObject, scope: &Scope, errors: &mut Vec<ValidationError>) {
+        let properties = match schema.get("properties").map(|element| &element.value) {
+            Some(JsonValue::Object(properties)) => Some(properties),
+            _ => None,
+        };
+        let pattern_properties = match schema.get("patternProperties").map(|element| &element.value) {
+            Some(JsonValue::Object(patterns)) => Some(patterns),
+            _ => None,
+        };
+
+        for member in &instance.members {
+            let key = &member.string.string;
+            let value = &member.element.value;
+            let member_scope = scope.instance(key);
+            let mut evaluated = false;
+
+            if let Some(subschema) = properties.and_then(|properties| properties.get(key)) {
+                evaluated = true;
+                self.validate_value(value, &subschema.value, &member_scope.keyword("properties").keyword(key), errors);
+            }
+
+            for pattern in pattern_properties.iter().flat_map(|patterns| &patterns.members) {
+                let Some(regex) = self.patterns.get(&pattern.string.string) else {
+                    errors.push(member_scope.error("patternProperties", uncompiled(&pattern.string.string)));
+                    continue;
+                };
+                if regex.is_match(key) {
+                    evaluated = true;
+                    let pattern_scope = member_scope.keyword("patternProperties").keyword(&pattern.string.string);
+                    self.validate_value(value, &pattern.element.value, &pattern_scope, errors);
+                }
+            }
+
+            if !evaluated {
+                match schema.get("additionalProperties").map(|element| &element.value) {
+                    Some(JsonValue::False) => {
+                        errors.push(member_scope.error("additionalProperties", format!("Additional property {:?} is not allowed", key)));
+                    }
+                    Some(subschema) => self.validate_value(value, subschema, &member_scope.keyword("additionalProperties"), errors),
+                    None => {}
+                }
+            }
+
+            if let Some(subschema) = schema.get("propertyNames") {
+                let name = JsonValue::String(JsonString { string: key.clone() });
+                self.validate_value(&name, &subschema.value, &member_scope.keyword("propertyNames"), errors);
+            }
+        }
+
+        if let Some(JsonValue::Array(required)) = schema.get("required").map(|element| &element.value) {
+            for element in &required.elements {
+                if let JsonValue::String(key) = &element.value {
+                    if instance.get(&key.string).is_none() {
+                        errors.push(scope.error("required", format!("Missing required property {:?}", key.string)));
+                    }
+                }
+            }
+        }
+
+        if let Some(JsonValue::Object(dependencies)) = schema.get("dependentRequired").map(|element| &element.value) {
+            for dependency in &dependencies.members {
+                if instance.get(&dependency.string.string).is_none() {
+                    continue;
+                }
+                if let JsonValue::Array(required) = &dependency.element.value {
+                    for element in &required.elements {
+                        if let JsonValue::String(key) = &element.value {
+                            if instance.get(&key.string).is_none() {
+                                errors.push(scope.error("dependentRequired", format!(
+                                    "Property {:?} is required when {:?} is present", key.string, dependency.string.string
+                                )));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some(JsonValue::Object(dependencies)) = schema.get("dependentSchemas").map(|element| &element.value) {
+            for dependency in &dependencies.members {
+                if instance.get(&dependency.string.string).is_some() {
+                    let dependency_scope = scope.keyword("dependentSchemas").keyword(&dependency.string.string);
+                    self.validate_value(&JsonValue::Object(instance.clone()), &dependency.element.value, &dependency_scope, errors);
+                }
+            }
+        }
+
+        let count = instance.members.len();
+        if let Some(minimum) = usize_keyword(schema, "minProperties") {
+            if count < minimum {
+                errors.push(scope.error("minProperties", format!("Expected at least {} properties but found {}", minimum, count)));
+            }
+        }
+        if let Some(maximum) = usize_keyword(schema, "maxProperties") {
+            if count > maximum {
+                errors.push(scope.error("maxProperties", format!("Expected at most {} properties but found {}", maximum, count)));
+            }
+        }
+    }
+
+    fn validate_array(&self, instance: &JsonArray, schema: &JsonObject, scope: &Scope, errors: &mut Vec<ValidationError>) {
+        let mut prefix_length = 0;
+        let mut rest_keyword = "items";
+        let prefix = match (schema.get("prefixItems"), schema.get("items")) {
+            (Some(element), _) => Some(("prefixItems", &element.value)),
+            // Earlier drafts spell positional items as an `items` array.
+            (None, Some(element)) if matches!(element.value, JsonValue::Array(_)) => {
+                rest_keyword = "additionalItems";
+                Some(("items", &element.value))
+            }
+            _ => None,
+        };
+
+        if let Some((keyword, JsonValue::Array(schemas))) = prefix {
+            prefix_length = schemas.elements.len();
+            for (index, (element, subschema)) in instance.elements.iter().zip(&schemas.elements).enumerate() {
+                let index = index.to_string();
+                let element_scope = scope.instance(&index).keyword(keyword).keyword(&index);
+                self.validate_value(&element.value, &subschema.value, &element_scope, errors);
+            }
+        }
+
+        if let Some(subschema) = schema.get(rest_keyword) {
+            for (index, element) in instance.elements.iter().enumerate().skip(prefix_length) {
+                let element_scope = scope.instance(&index.to_string()).keyword(rest_keyword);
+                self.validate_value(&element.value, &subschema.value, &element_scope, errors);
+            }
+        }
+
+        if let Some(subschema) = schema.get("contains") {
+            let matches = instance.elements.iter().enumerate().filter(|(index, element)| {
+                let element_scope = scope.instance(&index.to_string()).keyword("contains");
+                self.check(&element.value, &subschema.value, &element_scope).is_empty()
+            }).count();
+            let minimum = usize_keyword(schema, "minContains").unwrap_or(1);
+            if matches < minimum {
+                let keyword = if schema.get("minContains").is_some() { "minContains" } else { "contains" };
+                errors.push(scope.error(keyword, format!("Expected at least {} matching items but found {}", minimum, matches)));
+            }
+            if let Some(maximum) = usize_keyword(schema, "maxContains") {
+                if matches > maximum {
+                    errors.push(scope.error("maxContains", format!("Expected at most {} matching items but found {}", maximum, matches)));
+                }
+            }
+        }
+
+        let count = instance.elements.len();
+        if let Some(minimum) = usize_keyword(schema, "minItems") {
+            if count < minimum {
+                errors.push(scope.error("minItems", format!("Expected at least {} items but found {}", minimum, count)));
+            }
+        }
+        if let Some(maximum) = usize_keyword(schema, "maxItems") {
+            if count > maximum {
+                errors.push(scope.error("maxItems", format!("Expected at most {} items but found {}", maximum, count)));
+            }
+        }
+
+        if let Some(JsonValue::True) = schema.get("uniqueItems").map(|element| &element.value) {
+            for (index, element) in instance.elements.iter().enumerate() {
+                if instance.elements[..index].iter().any(|other| json_equal(&element.value, &other.value)) {
+                    errors.push(scope.error("uniqueItems", format!("Item {} is a duplicate", index)));
+                }
+            }
+        }
+    }
+
+    fn validate_string(&self, instance: &str, schema: &JsonObject, scope: &Scope, errors: &mut Vec<ValidationError>) {
+        let length = instance.chars().count();
+        if let Some(minimum) = usize_keyword(schema, "minLength") {
+            if length < minimum {
+                errors.push(scope.error("minLength", format!("Expected at least {} characters but found {}", minimum, length)));
+            }
+        }
+        if let Some(maximum) = usize_keyword(schema, "maxLength") {
+            if length > maximum {
+                errors.push(scope.error("maxLength", format!("Expected at most {} characters but found {}", maximum, length)));
+            }
+        }
+        if let Some(pattern) = string_keyword(schema, "pattern") {
+            match self.patterns.get(pattern) {
+                Some(regex) if regex.is_match(instance) => {}
+                Some(_) => errors.push(scope.error("pattern", format!("{:?} does not match pattern {:?}", instance, pattern))),
+                None => errors.push(scope.error("pattern", uncompiled(pattern))),
+            }
+        }
+    }
+}
+
+// Every pattern the validator can reach is compiled up front, so this only
+// shows for schemas reached some way compiling did not foresee.
+fn uncompiled(pattern: &str) -> String {
+    format!("Pattern {:?} was not compiled with the schema", pattern)
+}
+
+// Numbers compare by exact value, so integers past 2^53 and long decimals
+// are not rounded to doubles first.
+fn validate_number(instance: &JsonNumber, schema: &JsonObject, scope: &Scope, errors: &mut Vec<ValidationError>) {
+    let text = number_text(instance);
+    if let Some(minimum) = number_keyword(schema, "minimum") {
+        if instance.compare(minimum) == Some(Ordering::Less) {
+            errors.push(scope.error("minimum", format!("{} is less than the minimum of {}", text, number_text(minimum))));
+        }
+    }
+    if let Some(maximum) = number_keyword(schema, "maximum") {
+        if instance.compare(maximum) == Some(Ordering::Greater) {
+            errors.push(scope.error("maximum", format!("{} is greater than the maximum of {}", text, number_text(maximum))));
+        }
+    }
+    if let Some(minimum) = number_keyword(schema, "exclusiveMinimum") {
+        if matches!(instance.compare(minimum), Some(Ordering::Less | Ordering::Equal)) {
+            errors.push(scope.error("exclusiveMinimum", format!("{} is not greater than {}", text, number_text(minimum))));
+        }
+    }
+    if let Some(maximum) = number_keyword(schema, "exclusiveMaximum") {
+        if matches!(instance.compare(maximum), Some(Ordering::Greater | Ordering::Equal)) {
+            errors.push(scope.error("exclusiveMaximum", format!("{} is not less than {}", text, number_text(maximum))));
+        }
+    }
+    if let Some(divisor) = number_keyword(schema, "multipleOf") {
+        let multiple = is_multiple(instance, divisor).unwrap_or_else(|| {
+            let (instance, divisor) = (instance.as_f64(), divisor.as_f64());
+            let quotient = instance / divisor;
+            !(divisor > 0.0 && quotient.is_finite() && (quotient - quotient.round()).abs() > 1e-9)
+        });
+        if !multiple {
+            errors.push(scope.error("multipleOf", format!("{} is not a multiple of {}", text, number_text(divisor))));
+        }
+    }
+}
+
+fn number_text(number: &JsonNumber) -> String {
+    value_to_string(&JsonValue::Number(number.clone()))
+}
+
+// Whether the instance is an integer times the divisor, worked out on the
+// decimal digits. `None` when the divisor has too many digits for that, or
+// either number has no exact value, and doubles have to do.
+fn is_multiple(instance: &JsonNumber, divisor: &JsonNumber) -> Option<bool> {
+    let (instance, divisor) = (instance.decimal_parts()?, divisor.decimal_parts()?);
+    if divisor.negative || divisor.digits.is_empty() {
+        return None;
+    }
+    let modulus = divisor.digits.parse::<u64>().ok()? as u128;
+    if instance.digits.is_empty() {
+        return Some(true);
+    }
+
+    // With the digits as integers I and D, the instance is I × 10^a and the
+    // divisor D × 10^b. Neither ends in a zero, so for a < b the instance
+    // would need I to be a multiple of 10, and otherwise the question is
+    // whether D divides I × 10^(a - b).
+    let scale = |parts: &DecimalParts| parts.point - parts.digits.len() as i64;
+    let shift = scale(&instance) - scale(&divisor);
+    if shift < 0 {
+        return Some(false);
+    }
+    let remainder = instance.digits.bytes().fold(0, |remainder, digit| (remainder * 10 + (digit - b'0') as u128) % modulus);
+    let (mut power, mut base, mut exponent) = (1 % modulus, 10 % modulus, shift);
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            power = power * base % modulus;
+        }
+        base = base * base % modulus;
+        exponent >>= 1;
+    }
+    Some(remainder * power % modulus == 0)
+}
+
+fn type_matches(instance: &JsonValue, name: &str) -> bool {
+    match (instance, name) {
+        (JsonValue::Number(number), "integer") => number.decimal_parts().is_some_and(|parts| parts.is_integer()),
+        _ => instance.type_name() == name,
+    }
+}
+
+fn json_equal(left: &JsonValue, right: &JsonValue) -> bool {
+    match (left, right) {
+        (JsonValue::Number(left), JsonValue::Number(right)) => left.compare(right) == Some(Ordering::Equal),
+        (JsonValue::Object(left), JsonValue::Object(right)) => {
+            left.members.len() == right.members.len() && left.members.iter().all(|member| {
+                right.get(&member.string.string).is_some_and(|element| json_equal(&member.element.value, &element.value))
+            })
+        }
+        (JsonValue::Array(left), JsonValue::Array(right)) => {
+            left.elements.len() == right.elements.len()
+                && left.elements.iter().zip(&right.elements).all(|(left, right)| json_equal(&left.value, &right.value))
+        }
+        _ => left == right,
+    }
+}
+
+fn pointer_push(path: &str, token: &str) -> String {
+    format!("{}/{}", path, token.replace('~', "~0").replace('/', "~1"))
+}
+
+fn strip_fragment(uri: &str) -> String {
+    match uri.split_once('#') {
+        Some((document, _)) => String::from(document),
+        None => String::from(uri),
+    }
+}
+
+fn join_uri(base: &str, reference: &str) -> String {
+    if reference.starts_with('#') {
+        format!("{}{}", strip_fragment(base), reference)
+    } else if reference.contains("://") || reference.starts_with("urn:") {
+        String::from(reference)
+    } else if reference.starts_with('/') {
+        match base.find("://") {
+            Some(scheme_end) => {
+                let authority_end = base[scheme_end + 3..].find('/').map_or(base.len(), |index| scheme_end + 3 + index);
+                format!("{}{}", &base[..authority_end], reference)
+            }
+            None => String::from(reference),
+        }
+    } else {
+        let base = strip_fragment(base);
+        match base.rfind('/') {
+            Some(index) => format!("{}{}", &base[..=index], reference),
+            None => String::from(reference),
+        }
+    }
+}
+
+fn schema_keyword<'a>(object: &'a JsonObject, keyword: &str) -> Option<&'a JsonValue> {
+    object.get(keyword).map(|element| &element.value)
+}
+
+fn string_keyword<'a>(object: &'a JsonObject, keyword: &str) -> Option<&'a str> {
+    match object.get(keyword).map(|element| &element.value) {
+        Some(JsonValue::String(string)) => Some(string.string.as_str()),
+        _ => None,
+    }
+}
+
+fn number_keyword<'a>(object: &'a JsonObject, keyword: &str) -> Option<&'a JsonNumber> {
+    match object.get(keyword).map(|element| &element.value) {
+        Some(JsonValue::Number(number)) => Some(number),
+        _ => None,
+    }
+}
+
+fn usize_keyword(object: &JsonObject, keyword: &str) -> Option<usize> {
+    number_keyword(object, keyword).map(JsonNumber::as_f64).filter(|value| *value >= 0.0).map(|value| value as usize)
+}
@@ -0,0 +1,434 @@
+use std::io::Error;
+
+// A small matcher for the ECMA-262 subset that schemas use in `pattern` and
+// `patternProperties`: literals, `.`, classes, `\d\w\s` escapes, anchors,
+// groups, alternation and greedy or lazy quantifiers. Patterns compile to an
+// NFA that is run over all its states at once, so matching takes time linear
+// in the text whatever the pattern, and untrusted schemas cannot stall it.
+// Anything outside the subset, such as a backreference, fails to compile
+// rather than being read as something it does not mean.
+
+// Counted repeats are unrolled, so `(a{1000}){1000}` would be a million states.
+const MAX_INSTRUCTIONS: usize = 100_000;
+
+#[derive(Debug, Clone)]
+enum Node {
+    Char(char),
+    Any,
+    Class(Vec<ClassItem>, bool),
+    Start,
+    End,
+    WordBoundary(bool),
+    Group(Vec<Vec<Node>>),
+    Repeat(Box<Node>, usize, Option<usize>),
+}
+
+#[derive(Debug, Clone)]
+enum ClassItem {
+    Range(char, char),
+    Digit(bool),
+    Word(bool),
+    Space(bool),
+}
+
+#[derive(Debug, Clone)]
+enum Instruction {
+    Char(char),
+    Any,
+    Class(Vec<ClassItem>, bool),
+    Start,
+    End,
+    WordBoundary(bool),
+    Split(usize, usize),
+    Jump(usize),
+    Match,
+}
+
+#[derive(Debug, Clone)]
+pub struct Regex {
+    program: Vec<Instruction>,
+}
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Regex, Error> {
+        let chars: Vec<char> = pattern.chars().collect();
+        let mut position = 0;
+        let alternatives = parse_alternatives(&chars, &mut position)?;
+        if position < chars.len() {
+            return Err(Error::new(std::io::ErrorKind::InvalidData, "Unbalanced parenthesis in pattern"));
+        }
+        let mut program = vec![];
+        compile(&Node::Group(alternatives), &mut program)?;
+        program.push(Instruction::Match);
+        Ok(Regex { program })
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        let chars: Vec<char> = text.chars().collect();
+        // The position each instruction was last added at, so a state is
+        // only followed once per position and empty loops end.
+        let mut added = vec![usize::MAX; self.program.len()];
+        let mut threads = vec![];
+        for position in 0..=chars.len() {
+            // A thread starting at every position makes the search unanchored.
+            if self.add(&mut threads, 0, &chars, position, &mut added) {
+                return true;
+            }
+            let mut next = vec![];
+            for pc in threads {
+                let matched = match (&self.program[pc], chars.get(position)) {
+                    (Instruction::Char(expected), Some(char)) => char == expected,
+                    (Instruction::Any, Some(char)) => !is_line_terminator(*char),
+                    (Instruction::Class(items, negated), Some(&char)) => class_matches(items, char) != *negated,
+                    _ => false,
+                };
+                if matched && self.add(&mut next, pc + 1, &chars, position + 1, &mut added) {
+                    return true;
+                }
+            }
+            threads = next;
+        }
+        false
+    }
+
+    // Follows the instructions that consume nothing from `pc`, adding the
+    // ones that consume a character to `threads`. Returns whether a match
+    // was reached.
+    fn add(&self, threads: &mut Vec<usize>, pc: usize, chars: &[char], position: usize, added: &mut [usize]) -> bool {
+        let mut stack = vec![pc];
+        while let Some(pc) = stack.pop() {
+            if added[pc] == position {
+                continue;
+            }
+            added[pc] = position;
+            match &self.program[pc] {
+                Instruction::Match => return true,
+                Instruction::Jump(target) => stack.push(*target),
+                Instruction::Split(first, second) => {
+                    stack.push(*second);
+                    stack.push(*first);
+                }
+                Instruction::Start if position == 0 => stack.push(pc + 1),
+                Instruction::End if position == chars.len() => stack.push(pc + 1),
+                Instruction::WordBoundary(expected) => {
+                    let before = position > 0 && is_word(chars[position - 1]);
+                    let after = position < chars.len() && is_word(chars[position]);
+                    if (before != after) == *expected {
+                        stack.push(pc + 1);
+                    }
+                }
+                Instruction::Char(_) | Instruction::Any | Instruction::Class(..) => threads.push(pc),
+                Instruction::Start | Instruction::End => {}
+            }
+        }
+        false
+    }
+}
+
+fn compile(node: &Node, program: &mut Vec<Instruction>) -> Result<(), Error> {
+    if program.len() > MAX_INSTRUCTIONS {
+        return Err(Error::new(std::io::ErrorKind::InvalidData, "Pattern is too large"));
+    }
+    match node {
+        Node::Char(char) => program.push(Instruction::Char(*char)),
+        Node::Any => program.push(Instruction::Any),
+        Node::Class(items, negated) => program.push(Instruction::Class(items.clone(), *negated)),
+        Node::Start => program.push(Instruction::Start),
+        Node::End => program.push(Instruction::End),
+        Node::WordBoundary(expected) => program.push(Instruction::WordBoundary(*expected)),
+        Node::Group(alternatives) => {
+            // Each alternative but the last is a split to it or the next one,
+            // and each ends in a jump past the rest, patched once known.
+            let mut jumps = vec![];
+            for (index, sequence) in alternatives.iter().enumerate() {
+                let split = program.len();
+                if index + 1 < alternatives.len() {
+                    program.push(Instruction::Split(split + 1, 0));
+                }
+                for node in sequence {
+                    compile(node, program)?;
+                }
+                if index + 1 < alternatives.len() {
+                    jumps.push(program.len());
+                    program.push(Instruction::Jump(0));
+                    program[split] = Instruction::Split(split + 1, program.len());
+                }
+            }
+            for jump in jumps {
+                program[jump] = Instruction::Jump(program.len());
+            }
+        }
+        Node::Repeat(inner, min, max) => {
+            if *min > MAX_INSTRUCTIONS || max.is_some_and(|max| max > MAX_INSTRUCTIONS) {
+                return Err(Error::new(std::io::ErrorKind::InvalidData, "Pattern is too large"));
+            }
+            for _ in 0..*min {
+                compile(inner, program)?;
+            }
+            match max {
+                None => {
+                    let split = program.len();
+                    program.push(Instruction::Split(split + 1, 0));
+                    compile(inner, program)?;
+                    program.push(Instruction::Jump(split));
+                    program[split] = Instruction::Split(split + 1, program.len());
+                }
+                Some(max) => {
+                    let mut splits = vec![];
+                    for _ in *min..*max {
+                        splits.push(program.len());
+                        program.push(Instruction::Split(0, 0));
+                        compile(inner, program)?;
+                    }
+                    for split in splits {
+                        program[split] = Instruction::Split(split + 1, program.len());
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn parse_alternatives(chars: &[char], position: &mut usize) -> Result<Vec<Vec<Node>>, Error> {
+    let mut alternatives = vec![parse_sequence(chars, position)?];
+    while *position < chars.len() && chars[*position] == '|' {
+        *position += 1;
+        alternatives.push(parse_sequence(chars, position)?);
+    }
+    Ok(alternatives)
+}
+
+fn parse_sequence(chars: &[char], position: &mut usize) -> Result<Vec<Node>, Error> {
+    let mut nodes = vec![];
+    while *position < chars.len() && chars[*position] != '|' && chars[*position] != ')' {
+        let atom = parse_atom(chars, position)?;
+        nodes.push(parse_quantifier(atom, chars, position)?);
+    }
+    Ok(nodes)
+}
+
+fn parse_atom(chars: &[char], position: &mut usize) -> Result<Node, Error> {
+    let char = chars[*position];
+    *position += 1;
+    match char {
+        '.' => Ok(Node::Any),
+        '^' => Ok(Node::Start),
+        '$' => Ok(Node::End),
+        '(' => {
+            if chars[*position..].starts_with(&['?', ':']) {
+                *position += 2;
+            } else if chars[*position..].starts_with(&['?']) {
+                return Err(Error::new(std::io::ErrorKind::InvalidData, "Unsupported group in pattern"));
+            }
+            let alternatives = parse_alternatives(chars, position)?;
+            if *position < chars.len() && chars[*position] == ')' {
+                *position += 1;
+                Ok(Node::Group(alternatives))
+            } else {
+                Err(Error::new(std::io::ErrorKind::InvalidData, "Unbalanced parenthesis in pattern"))
+            }
+        }
+        '[' => parse_class(chars, position),
+        '\\' => parse_escape(chars, position),
+        '*' | '+' | '?' => Err(Error::new(std::io::ErrorKind::InvalidData, "Nothing to repeat in pattern")),
+        _ => Ok(Node::Char(char)),
+    }
+}
+
+fn parse_escape(chars: &[char], position: &mut usize) -> Result<Node, Error> {
+    let Some(&char) = chars.get(*position) else {
+        return Err(Error::new(std::io::ErrorKind::InvalidData, "Trailing backslash in pattern"));
+    };
+    *position += 1;
+    match char {
+        'b' => Ok(Node::WordBoundary(true)),
+        'B' => Ok(Node::WordBoundary(false)),
+        _ => match escape_class_item(char) {
+            Some(item) => Ok(Node::Class(vec![item], false)),
+            None => Ok(Node::Char(escape_char(chars, position, char)?)),
+        },
+    }
+}
+
+fn escape_class_item(char: char) -> Option<ClassItem> {
+    match char {
+        'd' => Some(ClassItem::Digit(true)),
+        'D' => Some(ClassItem::Digit(false)),
+        'w' => Some(ClassItem::Word(true)),
+        'W' => Some(ClassItem::Word(false)),
+        's' => Some(ClassItem::Space(true)),
+        'S' => Some(ClassItem::Space(false)),
+        _ => None,
+    }
+}
+
+// Escapes of other letters and digits mean something this matcher does not
+// do, like the backreference `\1` or the property `\p{L}`, so they are
+// rejected instead of read as the character.
+fn escape_char(chars: &[char], position: &mut usize, char: char) -> Result<char, Error> {
+    match char {
+        '1'..='9' => Err(unsupported(&format!("backreference \\{}", char))),
+        '0' if chars.get(*position).is_some_and(char::is_ascii_digit) => Err(unsupported("octal escape")),
+        'n' => Ok('\n'),
+        'r' => Ok('\r'),
+        't' => Ok('\t'),
+        'f' => Ok('\u{000C}'),
+        'v' => Ok('\u{000B}'),
+        '0' => Ok('\u{0000}'),
+        'u' => {
+            let hex: String = chars.iter().skip(*position).take(4).collect();
+            let code = u32::from_str_radix(&hex, 16).ok().filter(|_| hex.len() == 4).and_then(char::from_u32);
+            match code {
+                Some(code) => {
+                    *position += 4;
+                    Ok(code)
+                }
+                None => Err(Error::new(std::io::ErrorKind::InvalidData, "Invalid unicode escape in pattern")),
+            }
+        }
+        'x' => {
+            let hex: String = chars.iter().skip(*position).take(2).collect();
+            match u8::from_str_radix(&hex, 16).ok().filter(|_| hex.len() == 2 && hex.bytes().all(|byte| byte.is_ascii_hexdigit())) {
+                Some(code) => {
+                    *position += 2;
+                    Ok(char::from(code))
+                }
+                None => Err(Error::new(std::io::ErrorKind::InvalidData, "Invalid hex escape in pattern")),
+            }
+        }
+        _ if char.is_ascii_alphanumeric() => Err(unsupported(&format!("escape \\{}", char))),
+        _ => Ok(char),
+    }
+}
+
+fn parse_class(chars: &[char], position: &mut usize) -> Result<Node, Error> {
+    let mut items = vec![];
+    let mut negated = false;
+    if chars.get(*position) == Some(&'^') {
+        negated = true;
+        *position += 1;
+    }
+
+    loop {
+        let Some(&char) = chars.get(*position) else {
+            return Err(Error::new(std::io::ErrorKind::InvalidData, "Unterminated character class in pattern"));
+        };
+        *position += 1;
+        let start = match char {
+            ']' => return Ok(Node::Class(items, negated)),
+            '\\' => {
+                let Some(&escaped) = chars.get(*position) else {
+                    return Err(Error::new(std::io::ErrorKind::InvalidData, "Trailing backslash in pattern"));
+                };
+                *position += 1;
+                if let Some(item) = escape_class_item(escaped) {
+                    if is_range(chars, *position) {
+                        return Err(unsupported("class escape in a range"));
+                    }
+                    items.push(item);
+                    continue;
+                }
+                // A backspace, as `\b` is only a word boundary outside classes.
+                if escaped == 'b' {
+                    '\u{8}'
+                } else {
+                    escape_char(chars, position, escaped)?
+                }
+            }
+            _ => char,
+        };
+
+        if is_range(chars, *position) {
+            *position += 1;
+            let mut end = chars[*position];
+            *position += 1;
+            if end == '\\' {
+                let Some(&escaped) = chars.get(*position) else {
+                    return Err(Error::new(std::io::ErrorKind::InvalidData, "Trailing backslash in pattern"));
+                };
+                *position += 1;
+                if escape_class_item(escaped).is_some() {
+                    return Err(unsupported("class escape in a range"));
+                }
+                end = if escaped == 'b' { '\u{8}' } else { escape_char(chars, position, escaped)? };
+            }
+            if end < start {
+                return Err(Error::new(std::io::ErrorKind::InvalidData, "Invalid range in character class"));
+            }
+            items.push(ClassItem::Range(start, end));
+        } else {
+            items.push(ClassItem::Range(start, start));
+        }
+    }
+}
+
+// Whether a `-` at `position` joins the class member before it to one after.
+fn is_range(chars: &[char], position: usize) -> bool {
+    chars.get(position) == Some(&'-') && chars.get(position + 1).is_some_and(|&end| end != ']')
+}
+
+fn parse_quantifier(atom: Node, chars: &[char], position: &mut usize) -> Result<Node, Error> {
+    let (min, max) = match chars.get(*position) {
+        Some('*') => (0, None),
+        Some('+') => (1, None),
+        Some('?') => (0, Some(1)),
+        Some('{') => match parse_braces(chars, *position) {
+            Some((min, max, close)) => {
+                *position = close;
+                (min, max)
+            }
+            None => return Ok(atom),
+        },
+        _ => return Ok(atom),
+    };
+    *position += 1;
+    // Lazy quantifiers match the same strings as greedy ones for a yes/no search.
+    if chars.get(*position) == Some(&'?') {
+        *position += 1;
+    }
+    if let Some(max) = max {
+        if max < min {
+            return Err(Error::new(std::io::ErrorKind::InvalidData, "Invalid quantifier bounds in pattern"));
+        }
+    }
+    Ok(Node::Repeat(Box::new(atom), min, max))
+}
+
+// Parses `{n}`, `{n,}` or `{n,m}` starting at `open` and returns the bounds
+// with the index of the closing brace. Anything else is a literal brace, as
+// ECMA-262 allows.
+fn parse_braces(chars: &[char], open: usize) -> Option<(usize, Option<usize>, usize)> {
+    let close = chars[open..].iter().position(|&char| char == '}')? + open;
+    let body: String = chars[open + 1..close].iter().collect();
+    let (min, max) = match body.split_once(',') {
+        Some((min, "")) => (min.parse().ok()?, None),
+        Some((min, max)) => (min.parse().ok()?, Some(max.parse().ok()?)),
+        None => {
+            let exact = body.parse().ok()?;
+            (exact, Some(exact))
+        }
+    };
+    Some((min, max, close))
+}
+
+fn class_matches(items: &[ClassItem], char: char) -> bool {
+    items.iter().any(|item| match item {
+        ClassItem::Range(start, end) => (*start..=*end).contains(&char),
+        ClassItem::Digit(expected) => char.is_ascii_digit() == *expected,
+        ClassItem::Word(expected) => is_word(char) == *expected,
+        ClassItem::Space(expected) => char.is_whitespace() == *expected,
+    })
+}
+
+fn is_line_terminator(char: char) -> bool {
+    matches!(char, '\n' | '\r' | '\u{2028}' | '\u{2029}')
+}
+
+fn unsupported(what: &str) -> Error {
+    Error::new(std::io::ErrorKind::InvalidData, format!("Pattern unsupported: {}", what))
+}
+
+fn is_word(char: char) -> bool {
+    char.is_ascii_alphanumeric() || char == '_'
+}
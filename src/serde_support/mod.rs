@@ -0,0 +1,693 @@
+use crate::data::*;
+use crate::to_json::ToJson;
+use serde::de::{self, Deserialize, DeserializeOwned, IntoDeserializer, Visitor};
+use serde::ser::{self, Serialize};
+use serde::forward_to_deserialize_any;
+use std::fmt;
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct SerdeError {
+    pub message: String,
+}
+
+impl fmt::Display for SerdeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for SerdeError {}
+
+impl de::Error for SerdeError {
+    fn custom<T: fmt::Display>(message: T) -> SerdeError {
+        SerdeError { message: message.to_string() }
+    }
+}
+
+impl ser::Error for SerdeError {
+    fn custom<T: fmt::Display>(message: T) -> SerdeError {
+        SerdeError { message: message.to_string() }
+    }
+}
+
+impl From<SerdeError> for std::io::Error {
+    fn from(err: SerdeError) -> std::io::Error {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, err.message)
+    }
+}
+
+impl From<std::io::Error> for SerdeError {
+    fn from(err: std::io::Error) -> SerdeError {
+        SerdeError { message: err.to_string() }
+    }
+}
+
+// Decimals cross serde as a struct of this name with one field of the same
+// name holding their text, as serde_json's `arbitrary_precision` does, so the
+// serializer and deserializer here keep every digit. Other formats see the
+// struct rather than a rounded float.
+const DECIMAL: &str = "$cc_json_parser::private::Decimal";
+
+pub fn from_str<T: DeserializeOwned>(string: &str) -> Result<T, SerdeError> {
+    let mut tokens = crate::lex::lex(&mut &*string)?;
+    let json_data = crate::parse::parse(&mut tokens)?;
+    from_data(&json_data)
+}
+
+pub fn from_data<'a, T: Deserialize<'a>>(data: &'a JsonData) -> Result<T, SerdeError> {
+    T::deserialize(Deserializer::new(&data.element.value))
+}
+
+pub fn to_value<T: Serialize + ?Sized>(value: &T) -> Result<JsonValue, SerdeError> {
+    value.serialize(Serializer)
+}
+
+pub fn to_string<T: Serialize + ?Sized>(value: &T) -> Result<String, SerdeError> {
+    Ok(crate::write::value_to_string(&to_value(value)?))
+}
+
+impl Serialize for JsonValue {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use ser::{SerializeMap, SerializeSeq};
+
+        match self {
+            JsonValue::Object(object) => {
+                let mut map = serializer.serialize_map(Some(object.members.len()))?;
+                for member in &object.members {
+                    map.serialize_entry(&member.string.string, &member.element.value)?;
+                }
+                map.end()
+            }
+            JsonValue::Array(array) => {
+                let mut seq = serializer.serialize_seq(Some(array.elements.len()))?;
+                for element in &array.elements {
+                    seq.serialize_element(&element.value)?;
+                }
+                seq.end()
+            }
+            JsonValue::String(string) => serializer.serialize_str(&string.string),
+            JsonValue::Number(number) => {
+                match number {
+                    JsonNumber::Integer(integer) => serializer.serialize_i64(*integer),
+                    JsonNumber::Unsigned(unsigned) => serializer.serialize_u64(*unsigned),
+                    JsonNumber::Float(float) => serializer.serialize_f64(*float),
+                    JsonNumber::Decimal(text) => {
+                        use ser::SerializeStruct;
+
+                        let mut decimal = serializer.serialize_struct(DECIMAL, 1)?;
+                        decimal.serialize_field(DECIMAL, text)?;
+                        decimal.end()
+                    }
+                }
+            }
+            JsonValue::True => serializer.serialize_bool(true),
+            JsonValue::False => serializer.serialize_bool(false),
+            JsonValue::Null => serializer.serialize_unit(),
+        }
+    }
+}
+
+impl Serialize for JsonData {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.element.value.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for JsonValue {
+    fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<JsonValue, D::Error> {
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+impl<'de> Deserialize<'de> for JsonData {
+    fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<JsonData, D::Error> {
+        let value = JsonValue::deserialize(deserializer)?;
+        Ok(JsonData { element: JsonElement { value } })
+    }
+}
+
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = JsonValue;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("any JSON value")
+    }
+
+    fn visit_bool<E>(self, value: bool) -> Result<JsonValue, E> {
+        Ok(value.to_json())
+    }
+
+    fn visit_i64<E>(self, value: i64) -> Result<JsonValue, E> {
+        Ok(value.to_json())
+    }
+
+    fn visit_u64<E>(self, value: u64) -> Result<JsonValue, E> {
+        Ok(value.to_json())
+    }
+
+    fn visit_f64<E>(self, value: f64) -> Result<JsonValue, E> {
+        Ok(value.to_json())
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<JsonValue, E> {
+        Ok(value.to_json())
+    }
+
+    fn visit_unit<E>(self) -> Result<JsonValue, E> {
+        Ok(JsonValue::Null)
+    }
+
+    fn visit_none<E>(self) -> Result<JsonValue, E> {
+        Ok(JsonValue::Null)
+    }
+
+    fn visit_some<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<JsonValue, D::Error> {
+        JsonValue::deserialize(deserializer)
+    }
+
+    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<JsonValue, A::Error> {
+        let mut elements = vec![];
+        while let Some(value) = seq.next_element()? {
+            elements.push(JsonElement { value });
+        }
+        Ok(JsonValue::Array(JsonArray { elements }))
+    }
+
+    fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<JsonValue, A::Error> {
+        let mut members = vec![];
+        while let Some(key) = map.next_key::<String>()? {
+            if members.is_empty() && key == DECIMAL {
+                return decimal(map.next_value()?).map_err(de::Error::custom);
+            }
+            members.push(crate::to_json::member(key, map.next_value()?));
+        }
+        Ok(JsonValue::Object(JsonObject { members }))
+    }
+}
+
+// Drives serde `Deserialize` implementations from a tree produced by `parse`.
+pub struct Deserializer<'a> {
+    value: &'a JsonValue,
+}
+
+impl<'a> Deserializer<'a> {
+    pub fn new(value: &'a JsonValue) -> Deserializer<'a> {
+        Deserializer { value }
+    }
+}
+
+// A number type asked for by name takes a decimal as an integer when its text
+// is one, and otherwise as the nearest float, which integer types refuse.
+macro_rules! deserialize_number {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+                match self.value {
+                    JsonValue::Number(JsonNumber::Decimal(text)) => match (text.parse::<i128>(), text.parse::<u128>()) {
+                        (Ok(integer), _) => visitor.visit_i128(integer),
+                        (_, Ok(unsigned)) => visitor.visit_u128(unsigned),
+                        _ => visitor.visit_f64(text.parse().unwrap_or(f64::NAN)),
+                    },
+                    _ => self.deserialize_any(visitor),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Deserializer<'de> {
+    type Error = SerdeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.value {
+            JsonValue::Object(object) => visitor.visit_map(MapAccess { members: object.members.iter(), value: None }),
+            JsonValue::Array(array) => visitor.visit_seq(SeqAccess { elements: array.elements.iter() }),
+            JsonValue::String(string) => visitor.visit_borrowed_str(&string.string),
+            JsonValue::Number(number) => {
+                match number {
+                    JsonNumber::Integer(integer) => visitor.visit_i64(*integer),
+                    JsonNumber::Unsigned(unsigned) => visitor.visit_u64(*unsigned),
+                    JsonNumber::Float(float) => visitor.visit_f64(*float),
+                    JsonNumber::Decimal(text) => visitor.visit_map(DecimalAccess { text: Some(text) }),
+                }
+            }
+            JsonValue::True => visitor.visit_bool(true),
+            JsonValue::False => visitor.visit_bool(false),
+            JsonValue::Null => visitor.visit_unit(),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.value {
+            JsonValue::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, SerdeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        match self.value {
+            JsonValue::String(string) => visitor.visit_enum(IntoDeserializer::<SerdeError>::into_deserializer(string.string.as_str())),
+            JsonValue::Object(object) if object.members.len() == 1 => {
+                let member = &object.members[0];
+                visitor.visit_enum(EnumAccess { variant: &member.string.string, value: &member.element.value })
+            }
+            _ => Err(de::Error::custom(format!("Expected enum but found {}", self.value.type_name()))),
+        }
+    }
+
+    deserialize_number! {
+        deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64 deserialize_i128
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64 deserialize_u128
+        deserialize_f32 deserialize_f64
+    }
+
+    forward_to_deserialize_any! {
+        bool char str string bytes byte_buf unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+// Presents a decimal as the one-member map its `Serialize` writes.
+struct DecimalAccess<'a> {
+    text: Option<&'a str>,
+}
+
+impl<'de> de::MapAccess<'de> for DecimalAccess<'de> {
+    type Error = SerdeError;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, SerdeError> {
+        match self.text {
+            Some(_) => seed.deserialize(de::value::StrDeserializer::<SerdeError>::new(DECIMAL)).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, SerdeError> {
+        match self.text.take() {
+            Some(text) => seed.deserialize(de::value::BorrowedStrDeserializer::<SerdeError>::new(text)),
+            None => Err(de::Error::custom("Value requested before key")),
+        }
+    }
+}
+
+// Checks the text of a decimal that arrived through serde before it goes into
+// the tree, where the writer would print it as it is.
+fn decimal(text: String) -> Result<JsonValue, SerdeError> {
+    let tokens = crate::lex::lex_bytes(text.as_bytes())?;
+    match tokens.front() {
+        Some(Token::Number(..)) if tokens.len() == 1 => Ok(JsonValue::Number(JsonNumber::Decimal(text))),
+        _ => Err(de::Error::custom(format!("Invalid decimal {:?}", text))),
+    }
+}
+
+struct SeqAccess<'a> {
+    elements: std::slice::Iter<'a, JsonElement>,
+}
+
+impl<'de> de::SeqAccess<'de> for SeqAccess<'de> {
+    type Error = SerdeError;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, SerdeError> {
+        match self.elements.next() {
+            Some(element) => seed.deserialize(Deserializer::new(&element.value)).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.elements.len())
+    }
+}
+
+struct MapAccess<'a> {
+    members: std::slice::Iter<'a, JsonMember>,
+    value: Option<&'a JsonValue>,
+}
+
+impl<'de> de::MapAccess<'de> for MapAccess<'de> {
+    type Error = SerdeError;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, SerdeError> {
+        match self.members.next() {
+            Some(member) => {
+                self.value = Some(&member.element.value);
+                seed.deserialize(de::value::StrDeserializer::<SerdeError>::new(&member.string.string)).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, SerdeError> {
+        match self.value.take() {
+            Some(value) => seed.deserialize(Deserializer::new(value)),
+            None => Err(de::Error::custom("Value requested before key")),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.members.len())
+    }
+}
+
+struct EnumAccess<'a> {
+    variant: &'a str,
+    value: &'a JsonValue,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumAccess<'de> {
+    type Error = SerdeError;
+    type Variant = Deserializer<'de>;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Deserializer<'de>), SerdeError> {
+        let variant = seed.deserialize(de::value::StrDeserializer::<SerdeError>::new(self.variant))?;
+        Ok((variant, Deserializer::new(self.value)))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for Deserializer<'de> {
+    type Error = SerdeError;
+
+    fn unit_variant(self) -> Result<(), SerdeError> {
+        Deserialize::deserialize(self)
+    }
+
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, SerdeError> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, SerdeError> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value, SerdeError> {
+        de::Deserializer::deserialize_map(self, visitor)
+    }
+}
+
+// Serializes into the crate's tree, which `to_string` then prints with the writer.
+pub struct Serializer;
+
+impl ser::Serializer for Serializer {
+    type Ok = JsonValue;
+    type Error = SerdeError;
+    type SerializeSeq = SerializeArray;
+    type SerializeTuple = SerializeArray;
+    type SerializeTupleStruct = SerializeArray;
+    type SerializeTupleVariant = SerializeArray;
+    type SerializeMap = SerializeObject;
+    type SerializeStruct = SerializeObject;
+    type SerializeStructVariant = SerializeObject;
+
+    fn serialize_bool(self, value: bool) -> Result<JsonValue, SerdeError> {
+        Ok(value.to_json())
+    }
+
+    fn serialize_i8(self, value: i8) -> Result<JsonValue, SerdeError> {
+        Ok(value.to_json())
+    }
+
+    fn serialize_i16(self, value: i16) -> Result<JsonValue, SerdeError> {
+        Ok(value.to_json())
+    }
+
+    fn serialize_i32(self, value: i32) -> Result<JsonValue, SerdeError> {
+        Ok(value.to_json())
+    }
+
+    fn serialize_i64(self, value: i64) -> Result<JsonValue, SerdeError> {
+        Ok(value.to_json())
+    }
+
+    fn serialize_u8(self, value: u8) -> Result<JsonValue, SerdeError> {
+        Ok(value.to_json())
+    }
+
+    fn serialize_u16(self, value: u16) -> Result<JsonValue, SerdeError> {
+        Ok(value.to_json())
+    }
+
+    fn serialize_u32(self, value: u32) -> Result<JsonValue, SerdeError> {
+        Ok(value.to_json())
+    }
+
+    fn serialize_u64(self, value: u64) -> Result<JsonValue, SerdeError> {
+        Ok(value.to_json())
+    }
+
+    fn serialize_f32(self, value: f32) -> Result<JsonValue, SerdeError> {
+        Ok(value.to_json())
+    }
+
+    fn serialize_f64(self, value: f64) -> Result<JsonValue, SerdeError> {
+        Ok(value.to_json())
+    }
+
+    fn serialize_char(self, value: char) -> Result<JsonValue, SerdeError> {
+        Ok(value.to_json())
+    }
+
+    fn serialize_str(self, value: &str) -> Result<JsonValue, SerdeError> {
+        Ok(value.to_json())
+    }
+
+    fn serialize_bytes(self, value: &[u8]) -> Result<JsonValue, SerdeError> {
+        Ok(value.to_json())
+    }
+
+    fn serialize_none(self) -> Result<JsonValue, SerdeError> {
+        Ok(JsonValue::Null)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<JsonValue, SerdeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<JsonValue, SerdeError> {
+        Ok(JsonValue::Null)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<JsonValue, SerdeError> {
+        Ok(JsonValue::Null)
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, _index: u32, variant: &'static str) -> Result<JsonValue, SerdeError> {
+        Ok(variant.to_json())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T) -> Result<JsonValue, SerdeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<JsonValue, SerdeError> {
+        Ok(crate::to_json::object(vec![(String::from(variant), to_value(value)?)]))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeArray, SerdeError> {
+        Ok(SerializeArray { variant: None, elements: Vec::with_capacity(len.unwrap_or(0)) })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SerializeArray, SerdeError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<SerializeArray, SerdeError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeArray, SerdeError> {
+        Ok(SerializeArray { variant: Some(variant), elements: Vec::with_capacity(len) })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<SerializeObject, SerdeError> {
+        Ok(SerializeObject { variant: None, members: Vec::with_capacity(len.unwrap_or(0)), key: None })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<SerializeObject, SerdeError> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeObject, SerdeError> {
+        Ok(SerializeObject { variant: Some(variant), members: Vec::with_capacity(len), key: None })
+    }
+}
+
+pub struct SerializeArray {
+    variant: Option<&'static str>,
+    elements: Vec<JsonElement>,
+}
+
+impl SerializeArray {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.elements.push(JsonElement { value: to_value(value)? });
+        Ok(())
+    }
+
+    fn finish(self) -> Result<JsonValue, SerdeError> {
+        let array = JsonValue::Array(JsonArray { elements: self.elements });
+        Ok(match self.variant {
+            Some(variant) => crate::to_json::object(vec![(String::from(variant), array)]),
+            None => array,
+        })
+    }
+}
+
+impl ser::SerializeSeq for SerializeArray {
+    type Ok = JsonValue;
+    type Error = SerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<JsonValue, SerdeError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for SerializeArray {
+    type Ok = JsonValue;
+    type Error = SerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<JsonValue, SerdeError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeArray {
+    type Ok = JsonValue;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<JsonValue, SerdeError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeArray {
+    type Ok = JsonValue;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<JsonValue, SerdeError> {
+        self.finish()
+    }
+}
+
+pub struct SerializeObject {
+    variant: Option<&'static str>,
+    members: Vec<JsonMember>,
+    key: Option<String>,
+}
+
+impl SerializeObject {
+    fn push<T: Serialize + ?Sized>(&mut self, key: String, value: &T) -> Result<(), SerdeError> {
+        self.members.push(crate::to_json::member(key, to_value(value)?));
+        Ok(())
+    }
+
+    fn finish(self) -> Result<JsonValue, SerdeError> {
+        let object = JsonValue::Object(JsonObject { members: self.members });
+        Ok(match self.variant {
+            Some(variant) => crate::to_json::object(vec![(String::from(variant), object)]),
+            None => object,
+        })
+    }
+}
+
+impl ser::SerializeMap for SerializeObject {
+    type Ok = JsonValue;
+    type Error = SerdeError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), SerdeError> {
+        // Object keys are always strings, so scalar keys use their JSON text.
+        self.key = Some(match to_value(key)? {
+            JsonValue::String(string) => string.string,
+            JsonValue::Number(number) => crate::write::value_to_string(&JsonValue::Number(number)),
+            JsonValue::True => String::from("true"),
+            JsonValue::False => String::from("false"),
+            other => return Err(ser::Error::custom(format!("Object keys must be strings, found {}", other.type_name()))),
+        });
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        match self.key.take() {
+            Some(key) => self.push(key, value),
+            None => Err(ser::Error::custom("Value serialized before key")),
+        }
+    }
+
+    fn end(self) -> Result<JsonValue, SerdeError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for SerializeObject {
+    type Ok = JsonValue;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), SerdeError> {
+        self.push(String::from(key), value)
+    }
+
+    fn end(mut self) -> Result<JsonValue, SerdeError> {
+        if self.variant.is_none() && self.members.len() == 1 && self.members[0].string.string == DECIMAL {
+            if let JsonValue::String(text) = self.members.remove(0).element.value {
+                return decimal(text.string);
+            }
+            return Err(ser::Error::custom("Invalid decimal"));
+        }
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeObject {
+    type Ok = JsonValue;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), SerdeError> {
+        self.push(String::from(key), value)
+    }
+
+    fn end(self) -> Result<JsonValue, SerdeError> {
+        self.finish()
+    }
+}
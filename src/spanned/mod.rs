@@ -0,0 +1,312 @@
+use crate::data::{self, JsonNumber, NumberMode, Span};
+use crate::parse::{number_from_lexeme, Builder, ParseOptions, Recovering};
+use std::borrow::Cow;
+use std::io::Error;
+
+// A tree that remembers where each part came from, for callers that report
+// problems found after parsing. Parsing costs an extra line table, so the plain
+// tree in `data` stays the default and this one is asked for explicitly.
+
+// Lines and columns count from 1. Columns count characters, not bytes.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct Position {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct SourceSpan {
+    pub start: Position,
+    pub end: Position,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct JsonData {
+    pub element: JsonElement,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct JsonElement {
+    pub value: JsonValue,
+    pub span: SourceSpan,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct JsonObject {
+    pub members: Vec<JsonMember>,
+}
+
+// Spans from the start of the key to the end of the value.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct JsonMember {
+    pub string: JsonString,
+    pub element: JsonElement,
+    pub span: SourceSpan,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct JsonArray {
+    pub elements: Vec<JsonElement>,
+}
+
+// The span includes the quotes.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct JsonString {
+    pub string: String,
+    pub span: SourceSpan,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum JsonValue {
+    Object(JsonObject),
+    Array(JsonArray),
+    String(JsonString),
+    Number(JsonNumber),
+    True,
+    False,
+    Null,
+    // Where the recovering parser in `recover` could not read a value.
+    Error,
+}
+
+// Turns byte offsets into lines and columns.
+pub struct Lines<'a> {
+    text: &'a [u8],
+    starts: Vec<usize>,
+    // The characters and UTF-16 units before each block of `BLOCK` bytes, so
+    // a column is counted from the nearest block rather than from the start
+    // of a line that may be the whole document.
+    blocks: Vec<(usize, usize)>,
+}
+
+const BLOCK: usize = 256;
+
+// The characters and UTF-16 units a byte starts. Continuation bytes start
+// neither, and a four-byte character takes two UTF-16 units.
+fn counts(byte: u8) -> (usize, usize) {
+    match byte {
+        0x80..=0xBF => (0, 0),
+        0xF0..=0xFF => (1, 2),
+        _ => (1, 1),
+    }
+}
+
+impl<'a> Lines<'a> {
+    pub fn new(text: &'a str) -> Lines<'a> {
+        Lines::from_bytes(text.as_bytes())
+    }
+
+    // For input that may not be valid UTF-8, where the parsers report the
+    // invalid bytes as errors.
+    pub fn from_bytes(text: &'a [u8]) -> Lines<'a> {
+        let mut starts = vec![0];
+        starts.extend(text.iter().enumerate().filter(|&(_, &byte)| byte == b'\n').map(|(offset, _)| offset + 1));
+
+        let mut blocks = vec![];
+        let (mut chars, mut units) = (0, 0);
+        for block in text.chunks(BLOCK) {
+            blocks.push((chars, units));
+            for &byte in block {
+                let (char, unit) = counts(byte);
+                chars += char;
+                units += unit;
+            }
+        }
+        blocks.push((chars, units));
+        Lines { text, starts, blocks }
+    }
+
+    // The characters and UTF-16 units before an offset.
+    fn before(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.min(self.text.len());
+        let block = offset / BLOCK;
+        let (mut chars, mut units) = self.blocks[block];
+        for &byte in &self.text[block * BLOCK..offset] {
+            let (char, unit) = counts(byte);
+            chars += char;
+            units += unit;
+        }
+        (chars, units)
+    }
+
+    // The zero-based line of an offset, with the character and UTF-16 unit
+    // counts of its start and of the offset.
+    fn locate(&self, offset: usize) -> (usize, (usize, usize), (usize, usize)) {
+        let line = self.starts.partition_point(|&start| start <= offset) - 1;
+        (line, self.before(self.starts[line]), self.before(offset))
+    }
+
+    pub fn position(&self, offset: usize) -> Position {
+        let (line, (start, _), (end, _)) = self.locate(offset);
+        Position { offset, line: line + 1, column: end - start + 1 }
+    }
+
+    // The zero-based line and UTF-16 column of an offset, which is how the
+    // Language Server Protocol counts positions.
+    pub fn utf16_position(&self, offset: usize) -> (usize, usize) {
+        let (line, (_, start), (_, end)) = self.locate(offset);
+        (line, end - start)
+    }
+
+    // The offset of a zero-based line and UTF-16 column. Positions past the end
+    // of a line or of the text are clamped to it.
+    pub fn utf16_offset(&self, line: usize, column: usize) -> usize {
+        let Some(&start) = self.starts.get(line) else {
+            return self.text.len();
+        };
+        // Invalid bytes count as the replacement characters they would be
+        // shown as.
+        let chars = self.text[start..].utf8_chunks().flat_map(|chunk| {
+            let invalid = (!chunk.invalid().is_empty()).then_some((char::REPLACEMENT_CHARACTER, chunk.invalid().len()));
+            chunk.valid().chars().map(|char| (char, char.len_utf8())).chain(invalid)
+        });
+        let mut offset = start;
+        let mut units = 0;
+        for (char, length) in chars {
+            if units >= column || char == '\n' {
+                break;
+            }
+            units += char.len_utf16();
+            offset += length;
+        }
+        offset
+    }
+
+    pub fn span(&self, span: Span) -> SourceSpan {
+        SourceSpan { start: self.position(span.start), end: self.position(span.end) }
+    }
+}
+
+pub fn parse(string: &str) -> Result<JsonData, Error> {
+    parse_with_options(string, &ParseOptions::default())
+}
+
+pub fn parse_with_options(string: &str, options: &ParseOptions) -> Result<JsonData, Error> {
+    let tokens = crate::lex::lex_spanned(string.as_bytes())?;
+    let tokens = tokens.into_iter().map(|(token, span)| (Ok(token), span));
+    let element = crate::parse::parse_tokens(tokens, &mut Tree::new(string), options)?;
+    Ok(JsonData {element})
+}
+
+// Builds this tree for the parsers in `parse`. The recovering parser in
+// `recover` uses it too, for `JsonValue::Error` nodes.
+pub struct Tree<'a> {
+    pub lines: Lines<'a>,
+}
+
+impl<'a> Tree<'a> {
+    pub fn new(text: &'a str) -> Tree<'a> {
+        Tree { lines: Lines::new(text) }
+    }
+
+    fn spanned(&self, value: JsonValue, span: Span) -> JsonElement {
+        JsonElement { value, span: self.lines.span(span) }
+    }
+}
+
+impl<'a> Builder<'a> for Tree<'_> {
+    type Value = JsonElement;
+    type Key = JsonString;
+    type Member = JsonMember;
+    type Element = JsonElement;
+    type Token = Span;
+
+    fn token(&mut self, span: Span) -> Span {
+        span
+    }
+
+    fn string(&mut self, string: Cow<'a, str>, span: Span) -> JsonElement {
+        let string = self.key(string, span);
+        JsonElement { span: string.span, value: JsonValue::String(string) }
+    }
+
+    fn number(&mut self, lexeme: &'a str, span: Span, mode: NumberMode) -> Result<JsonElement, Error> {
+        Ok(self.spanned(JsonValue::Number(number_from_lexeme(lexeme, span, mode)?), span))
+    }
+
+    fn boolean(&mut self, value: bool, span: Span) -> JsonElement {
+        self.spanned(if value { JsonValue::True } else { JsonValue::False }, span)
+    }
+
+    fn null(&mut self, span: Span) -> JsonElement {
+        self.spanned(JsonValue::Null, span)
+    }
+
+    fn key(&mut self, string: Cow<'a, str>, span: Span) -> JsonString {
+        JsonString { string: string.into_owned(), span: self.lines.span(span) }
+    }
+
+    fn member(&mut self, string: JsonString, _colon: Span, element: JsonElement, _comma: Option<Span>) -> JsonMember {
+        let span = SourceSpan { start: string.span.start, end: element.span.end };
+        JsonMember {string, element, span}
+    }
+
+    fn element(&mut self, element: JsonElement, _comma: Option<Span>) -> JsonElement {
+        element
+    }
+
+    fn object(&mut self, open: Span, members: Vec<JsonMember>, close: Span) -> JsonElement {
+        self.spanned(JsonValue::Object(JsonObject {members}), Span { start: open.start, end: close.end })
+    }
+
+    fn array(&mut self, open: Span, elements: Vec<JsonElement>, close: Span) -> JsonElement {
+        self.spanned(JsonValue::Array(JsonArray {elements}), Span { start: open.start, end: close.end })
+    }
+}
+
+impl<'a> Recovering<'a> for Tree<'_> {
+    fn invalid(&mut self, span: Span) -> JsonElement {
+        self.spanned(JsonValue::Error, span)
+    }
+}
+
+impl JsonObject {
+    pub fn get(&self, key: &str) -> Option<&JsonElement> {
+        self.members.iter().find(|member| member.string.string == key).map(|member| &member.element)
+    }
+
+    pub fn member(&self, key: &str) -> Option<&JsonMember> {
+        self.members.iter().find(|member| member.string.string == key)
+    }
+}
+
+// Plain trees have no error nodes, so a tree from `recover` that holds any
+// gives `None` rather than a value that was never in the document.
+impl JsonData {
+    pub fn without_spans(self) -> Option<data::JsonData> {
+        Some(data::JsonData {
+            element: self.element.without_spans()?,
+        })
+    }
+}
+
+impl JsonElement {
+    pub fn without_spans(self) -> Option<data::JsonElement> {
+        Some(data::JsonElement {
+            value: self.value.without_spans()?,
+        })
+    }
+}
+
+impl JsonValue {
+    pub fn without_spans(self) -> Option<data::JsonValue> {
+        Some(match self {
+            JsonValue::Object(object) => data::JsonValue::Object(data::JsonObject {
+                members: object.members.into_iter().map(|member| Some(data::JsonMember {
+                    string: data::JsonString { string: member.string.string },
+                    element: member.element.without_spans()?,
+                })).collect::<Option<_>>()?,
+            }),
+            JsonValue::Array(array) => data::JsonValue::Array(data::JsonArray {
+                elements: array.elements.into_iter().map(JsonElement::without_spans).collect::<Option<_>>()?,
+            }),
+            JsonValue::String(string) => data::JsonValue::String(data::JsonString { string: string.string }),
+            JsonValue::Number(number) => data::JsonValue::Number(number),
+            JsonValue::True => data::JsonValue::True,
+            JsonValue::False => data::JsonValue::False,
+            JsonValue::Null => data::JsonValue::Null,
+            JsonValue::Error => return None,
+        })
+    }
+}
@@ -0,0 +1,235 @@
+use crate::data::*;
+use std::collections::{BTreeMap, HashMap};
+
+pub use cc_json_parser_derive::ToJson;
+
+pub trait ToJson {
+    fn to_json(&self) -> JsonValue;
+}
+
+// Values that are always written as an object. The content of a newtype
+// variant in an internally tagged enum must be one, as the tag goes among its
+// members; anything else fails to compile rather than being written some
+// other way.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` is not always written as a JSON object",
+    note = "a newtype variant of an internally tagged enum must hold a struct with named fields or a map"
+)]
+pub trait ToJsonObject {
+    fn to_json_object(&self) -> JsonObject;
+}
+
+pub fn to_json<T: ToJson + ?Sized>(value: &T) -> JsonData {
+    JsonData {
+        element: JsonElement { value: value.to_json() },
+    }
+}
+
+pub fn object(members: Vec<(String, JsonValue)>) -> JsonValue {
+    JsonValue::Object(JsonObject {
+        members: members.into_iter().map(|(key, value)| member(key, value)).collect(),
+    })
+}
+
+pub fn member(key: String, value: JsonValue) -> JsonMember {
+    JsonMember {
+        string: JsonString { string: key },
+        element: JsonElement { value },
+    }
+}
+
+// Appends the members of a flattened field. Values that are not objects have
+// no members to lift, so they are kept under the field's own key.
+pub fn flatten_into(members: &mut Vec<JsonMember>, key: &str, value: JsonValue) {
+    match value {
+        JsonValue::Object(object) => members.extend(object.members),
+        value => members.push(member(String::from(key), value)),
+    }
+}
+
+// Builds an internally tagged value by putting the tag in front of the content's
+// members.
+pub fn internally_tagged(tag: &str, name: &str, content: JsonObject) -> JsonValue {
+    let mut members = vec![member(String::from(tag), string(name))];
+    members.extend(content.members);
+    JsonValue::Object(JsonObject { members })
+}
+
+pub fn string(string: &str) -> JsonValue {
+    JsonValue::String(JsonString { string: String::from(string) })
+}
+
+fn float(float: f64) -> JsonValue {
+    if float.is_finite() {
+        JsonValue::Number(JsonNumber::Float(float))
+    } else {
+        JsonValue::Null
+    }
+}
+
+impl ToJson for JsonValue {
+    fn to_json(&self) -> JsonValue {
+        self.clone()
+    }
+}
+
+impl ToJson for JsonData {
+    fn to_json(&self) -> JsonValue {
+        self.element.value.clone()
+    }
+}
+
+impl ToJson for bool {
+    fn to_json(&self) -> JsonValue {
+        if *self { JsonValue::True } else { JsonValue::False }
+    }
+}
+
+impl ToJson for str {
+    fn to_json(&self) -> JsonValue {
+        string(self)
+    }
+}
+
+impl ToJson for String {
+    fn to_json(&self) -> JsonValue {
+        string(self)
+    }
+}
+
+impl ToJson for char {
+    fn to_json(&self) -> JsonValue {
+        string(&self.to_string())
+    }
+}
+
+macro_rules! impl_to_json_integer {
+    ($($type:ty),*) => {
+        $(
+            impl ToJson for $type {
+                fn to_json(&self) -> JsonValue {
+                    match i64::try_from(*self) {
+                        Ok(value) => JsonValue::Number(JsonNumber::Integer(value)),
+                        Err(_) => JsonValue::Number(JsonNumber::Unsigned(*self as u64)),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_to_json_integer!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+impl ToJson for f64 {
+    fn to_json(&self) -> JsonValue {
+        float(*self)
+    }
+}
+
+impl ToJson for f32 {
+    fn to_json(&self) -> JsonValue {
+        // Go through the shortest decimal form so 0.1f32 stays 0.1.
+        float(self.to_string().parse().unwrap())
+    }
+}
+
+impl<T: ToJson + ?Sized> ToJson for &T {
+    fn to_json(&self) -> JsonValue {
+        (**self).to_json()
+    }
+}
+
+impl<T: ToJson + ?Sized> ToJson for Box<T> {
+    fn to_json(&self) -> JsonValue {
+        (**self).to_json()
+    }
+}
+
+impl<T: ToJson> ToJson for Option<T> {
+    fn to_json(&self) -> JsonValue {
+        match self {
+            Some(value) => value.to_json(),
+            None => JsonValue::Null,
+        }
+    }
+}
+
+impl<T: ToJson> ToJson for [T] {
+    fn to_json(&self) -> JsonValue {
+        JsonValue::Array(JsonArray {
+            elements: self.iter().map(|value| JsonElement { value: value.to_json() }).collect(),
+        })
+    }
+}
+
+impl<T: ToJson> ToJson for Vec<T> {
+    fn to_json(&self) -> JsonValue {
+        self.as_slice().to_json()
+    }
+}
+
+impl<T: ToJson> ToJson for BTreeMap<String, T> {
+    fn to_json(&self) -> JsonValue {
+        JsonValue::Object(self.to_json_object())
+    }
+}
+
+impl<T: ToJson> ToJsonObject for BTreeMap<String, T> {
+    fn to_json_object(&self) -> JsonObject {
+        JsonObject {
+            members: self.iter().map(|(key, value)| member(key.clone(), value.to_json())).collect(),
+        }
+    }
+}
+
+impl<T: ToJson> ToJson for HashMap<String, T> {
+    fn to_json(&self) -> JsonValue {
+        JsonValue::Object(self.to_json_object())
+    }
+}
+
+impl<T: ToJson> ToJsonObject for HashMap<String, T> {
+    fn to_json_object(&self) -> JsonObject {
+        // Sort the keys so the same map always produces the same document.
+        let mut members: Vec<JsonMember> = self.iter().map(|(key, value)| member(key.clone(), value.to_json())).collect();
+        members.sort_by(|left, right| left.string.string.cmp(&right.string.string));
+        JsonObject { members }
+    }
+}
+
+impl ToJsonObject for JsonObject {
+    fn to_json_object(&self) -> JsonObject {
+        self.clone()
+    }
+}
+
+impl<T: ToJsonObject + ?Sized> ToJsonObject for &T {
+    fn to_json_object(&self) -> JsonObject {
+        (**self).to_json_object()
+    }
+}
+
+impl<T: ToJsonObject + ?Sized> ToJsonObject for Box<T> {
+    fn to_json_object(&self) -> JsonObject {
+        (**self).to_json_object()
+    }
+}
+
+macro_rules! impl_to_json_tuple {
+    ($($name:ident $index:tt),*) => {
+        impl<$($name: ToJson),*> ToJson for ($($name,)*) {
+            fn to_json(&self) -> JsonValue {
+                JsonValue::Array(JsonArray {
+                    elements: vec![$(JsonElement { value: self.$index.to_json() }),*],
+                })
+            }
+        }
+    };
+}
+
+impl_to_json_tuple!(A 0);
+impl_to_json_tuple!(A 0, B 1);
+impl_to_json_tuple!(A 0, B 1, C 2);
+impl_to_json_tuple!(A 0, B 1, C 2, D 3);
+impl_to_json_tuple!(A 0, B 1, C 2, D 3, E 4);
+impl_to_json_tuple!(A 0, B 1, C 2, D 3, E 4, F 5);
@@ -0,0 +1,775 @@
+use crate::data::*;
+use std::collections::HashSet;
+use std::io::Error;
+
+// Writes TOML 1.0. Objects become tables and arrays of objects become arrays
+// of tables; everything else is written inline. TOML cannot hold every JSON
+// document, so writing fails with the path of the first value it cannot
+// represent: a top level that is not an object, a null, an integer beyond 64
+// bits, a number beyond a double, an array that mixes types, or a key given
+// twice in one object.
+
+pub fn to_toml(data: &JsonData) -> Result<String, Error> {
+    let JsonValue::Object(object) = &data.element.value else {
+        return Err(unrepresentable("a top level that is not an object", ""));
+    };
+    let mut out = String::new();
+    write_table(object, &mut vec![], &mut out)?;
+    Ok(out)
+}
+
+fn unrepresentable(what: &str, path: &str) -> Error {
+    let message = if path.is_empty() {
+        format!("TOML cannot represent {}", what)
+    } else {
+        format!("TOML cannot represent {} at {}", what, path)
+    };
+    Error::new(std::io::ErrorKind::InvalidData, message)
+}
+
+// Writes the plain keys of a table, then its tables and arrays of tables,
+// which have to come after them. `path` holds the keys of the table's header.
+fn write_table(object: &JsonObject, path: &mut Vec<String>, out: &mut String) -> Result<(), Error> {
+    check_keys(object, &path.join("."))?;
+    for member in &object.members {
+        if !is_table(&member.element.value) && !is_table_array(&member.element.value) {
+            write_key(&member.string.string, out);
+            out.push_str(" = ");
+            path.push(key(&member.string.string));
+            write_inline(&member.element.value, &path.join("."), out)?;
+            path.pop();
+            out.push('\n');
+        }
+    }
+
+    for member in &object.members {
+        path.push(key(&member.string.string));
+        match &member.element.value {
+            JsonValue::Object(table) => {
+                push_header(&format!("[{}]", path.join(".")), out);
+                write_table(table, path, out)?;
+            }
+            JsonValue::Array(array) if is_table_array(&member.element.value) => {
+                for element in &array.elements {
+                    if let JsonValue::Object(table) = &element.value {
+                        push_header(&format!("[[{}]]", path.join(".")), out);
+                        write_table(table, path, out)?;
+                    }
+                }
+            }
+            _ => {}
+        }
+        path.pop();
+    }
+    Ok(())
+}
+
+// Headers are set off by a blank line, except at the very start.
+fn push_header(header: &str, out: &mut String) {
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    out.push_str(header);
+    out.push('\n');
+}
+
+// A key may only be given once per table.
+fn check_keys(object: &JsonObject, path: &str) -> Result<(), Error> {
+    let mut keys = HashSet::new();
+    for member in &object.members {
+        if !keys.insert(member.string.string.as_str()) {
+            let what = format!("the repeated key {:?}", member.string.string);
+            return Err(unrepresentable(&what, path));
+        }
+    }
+    Ok(())
+}
+
+fn is_table(value: &JsonValue) -> bool {
+    matches!(value, JsonValue::Object(_))
+}
+
+fn is_table_array(value: &JsonValue) -> bool {
+    match value {
+        JsonValue::Array(array) => {
+            !array.elements.is_empty() && array.elements.iter().all(|element| is_table(&element.value))
+        }
+        _ => false,
+    }
+}
+
+fn write_inline(value: &JsonValue, path: &str, out: &mut String) -> Result<(), Error> {
+    match value {
+        JsonValue::Object(object) => {
+            check_keys(object, path)?;
+            out.push('{');
+            for (index, member) in object.members.iter().enumerate() {
+                out.push_str(if index > 0 { ", " } else { " " });
+                write_key(&member.string.string, out);
+                out.push_str(" = ");
+                write_inline(&member.element.value, &format!("{}.{}", path, key(&member.string.string)), out)?;
+            }
+            out.push_str(if object.members.is_empty() { "}" } else { " }" });
+        }
+        JsonValue::Array(array) => {
+            // TOML 1.0 allows mixed arrays, but 0.5 does not and many readers
+            // still follow it, so arrays keep to its rule. Nulls are reported
+            // on their own below.
+            let mut types = array.elements.iter().map(|element| &element.value)
+                .filter(|value| **value != JsonValue::Null).map(array_type);
+            if let Some(first) = types.next() {
+                if let Some(other) = types.find(|other| *other != first) {
+                    let what = format!("an array of both {} and {}", first, other);
+                    return Err(unrepresentable(&what, path));
+                }
+            }
+            out.push('[');
+            for (index, element) in array.elements.iter().enumerate() {
+                if index > 0 {
+                    out.push_str(", ");
+                }
+                write_inline(&element.value, &format!("{}[{}]", path, index), out)?;
+            }
+            out.push(']');
+        }
+        JsonValue::String(string) => write_string(&string.string, out),
+        JsonValue::Number(number) => write_number(number, path, out)?,
+        JsonValue::True => out.push_str("true"),
+        JsonValue::False => out.push_str("false"),
+        JsonValue::Null => return Err(unrepresentable("null", path)),
+    }
+    Ok(())
+}
+
+// The types of TOML 0.5 arrays. Integers and floats differ, but arrays are
+// one type whatever they hold, and so are inline tables.
+fn array_type(value: &JsonValue) -> &'static str {
+    match value {
+        JsonValue::Object(_) => "tables",
+        JsonValue::Array(_) => "arrays",
+        JsonValue::String(_) => "strings",
+        JsonValue::Number(JsonNumber::Integer(_) | JsonNumber::Unsigned(_)) => "integers",
+        JsonValue::Number(JsonNumber::Decimal(text)) if !text.contains(['.', 'e', 'E']) => "integers",
+        JsonValue::Number(_) => "floats",
+        JsonValue::True | JsonValue::False => "booleans",
+        JsonValue::Null => "nulls",
+    }
+}
+
+fn write_number(number: &JsonNumber, path: &str, out: &mut String) -> Result<(), Error> {
+    match number {
+        JsonNumber::Integer(integer) => out.push_str(&integer.to_string()),
+        JsonNumber::Unsigned(unsigned) if i64::try_from(*unsigned).is_ok() => out.push_str(&unsigned.to_string()),
+        JsonNumber::Float(float) if float.is_nan() => out.push_str("nan"),
+        JsonNumber::Float(float) if float.is_infinite() => out.push_str(if *float > 0.0 { "inf" } else { "-inf" }),
+        // `{:?}` always writes a fraction or an exponent, so it stays a float.
+        JsonNumber::Float(float) => out.push_str(&format!("{:?}", float)),
+        // JSON's float syntax is a subset of TOML's, but TOML floats are
+        // doubles, so text beyond their range cannot be written.
+        JsonNumber::Decimal(text) if text.contains(['.', 'e', 'E']) => {
+            if !fits_double(text) {
+                return Err(unrepresentable("a number beyond a double", path));
+            }
+            out.push_str(text);
+        }
+        JsonNumber::Decimal(text) if text.parse::<i64>().is_ok() => out.push_str(text),
+        _ => return Err(unrepresentable("an integer beyond 64 bits", path)),
+    }
+    Ok(())
+}
+
+// Whether float text is neither too large for a double nor too small to be
+// told from zero.
+fn fits_double(text: &str) -> bool {
+    let zero = text.split(['e', 'E']).next().unwrap_or(text).bytes().all(|byte| matches!(byte, b'0' | b'.' | b'-' | b'+'));
+    text.parse::<f64>().is_ok_and(|float| float.is_finite() && (float != 0.0 || zero))
+}
+
+// The key as it appears in a dotted key or table header.
+fn key(string: &str) -> String {
+    let mut out = String::new();
+    write_key(string, &mut out);
+    out
+}
+
+fn write_key(string: &str, out: &mut String) {
+    if !string.is_empty() && string.bytes().all(|byte| byte.is_ascii_alphanumeric() || byte == b'_' || byte == b'-') {
+        out.push_str(string);
+    } else {
+        write_string(string, out);
+    }
+}
+
+pub fn write_string(string: &str, out: &mut String) {
+    out.push('"');
+    for char in string.chars() {
+        match char {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\u{8}' => out.push_str("\\b"),
+            '\u{c}' => out.push_str("\\f"),
+            '\u{0000}'..='\u{001F}' | '\u{7f}' => out.push_str(&format!("\\u{:04X}", char as u32)),
+            _ => out.push(char),
+        }
+    }
+    out.push('"');
+}
+
+// Reads TOML 1.0 into the JSON tree. Tables become objects in the order
+// their keys first appear, and dates and times, which JSON has no type for,
+// become strings as they were written.
+
+// A value as it is being built. Tables stay open to later headers and dotted
+// keys until the document ends; everything else is finished when read.
+enum Item {
+    Value(JsonValue),
+    Table(Table),
+    Tables(Vec<Table>),
+}
+
+#[derive(PartialEq, Eq, Clone, Copy)]
+enum Defined {
+    // Only as part of a longer header, so a header of its own may follow.
+    Implicitly,
+    ByHeader,
+    ByDottedKeys,
+}
+
+struct Table {
+    entries: Vec<(String, Item)>,
+    defined: Defined,
+}
+
+impl Table {
+    fn new(defined: Defined) -> Table {
+        Table { entries: vec![], defined }
+    }
+
+    fn get_mut(&mut self, key: &str) -> Option<&mut Item> {
+        self.entries.iter_mut().find(|(name, _)| name == key).map(|(_, item)| item)
+    }
+
+    fn into_value(self) -> JsonValue {
+        let members = self.entries.into_iter().map(|(string, item)| {
+            let value = match item {
+                Item::Value(value) => value,
+                Item::Table(table) => table.into_value(),
+                Item::Tables(tables) => JsonValue::Array(JsonArray {
+                    elements: tables.into_iter().map(|table| JsonElement { value: table.into_value() }).collect(),
+                }),
+            };
+            JsonMember { string: JsonString {string}, element: JsonElement {value} }
+        });
+        JsonValue::Object(JsonObject { members: members.collect() })
+    }
+}
+
+struct Reader<'a> {
+    text: &'a str,
+    offset: usize,
+    depth: usize,
+}
+
+pub fn parse(string: &str) -> Result<JsonData, Error> {
+    let mut reader = Reader { text: string.strip_prefix('\u{feff}').unwrap_or(string), offset: 0, depth: 0 };
+    let mut root = Table::new(Defined::ByHeader);
+    let mut current: Vec<String> = vec![];
+
+    loop {
+        reader.skip_space();
+        match reader.peek() {
+            None => break,
+            Some('\n' | '\r' | '#') => {}
+            Some('[') => {
+                reader.offset += 1;
+                let array = reader.eat('[');
+                reader.skip_space();
+                let keys = reader.parse_key()?;
+                reader.skip_space();
+                if !reader.eat(']') || (array && !reader.eat(']')) {
+                    return Err(reader.error("Expected ']' after a table header"));
+                }
+                define_table(&mut root, &keys, array).map_err(|message| reader.error(&message))?;
+                current = keys;
+            }
+            Some(_) => {
+                let table = table_at(&mut root, &current);
+                reader.parse_entry(table)?;
+            }
+        }
+        reader.end_line()?;
+    }
+    Ok(JsonData::from(root.into_value()))
+}
+
+// The table a header names, following arrays of tables to their last table.
+fn table_at<'a>(root: &'a mut Table, keys: &[String]) -> &'a mut Table {
+    let mut table = root;
+    for key in keys {
+        table = match table.entries.iter_mut().find(|(name, _)| name == key) {
+            Some((_, Item::Table(child))) => child,
+            Some((_, Item::Tables(tables))) if !tables.is_empty() => {
+                let last = tables.len() - 1;
+                &mut tables[last]
+            }
+            // `define_table` made every table along the path.
+            _ => unreachable!("table_at follows defined tables"),
+        };
+    }
+    table
+}
+
+fn define_table(root: &mut Table, keys: &[String], array: bool) -> Result<(), String> {
+    let (last, parents) = keys.split_last().ok_or("Empty table header")?;
+    let mut table = root;
+    for key in parents {
+        if table.get_mut(key).is_none() {
+            table.entries.push((key.clone(), Item::Table(Table::new(Defined::Implicitly))));
+        }
+        table = match table.get_mut(key) {
+            Some(Item::Table(child)) => child,
+            Some(Item::Tables(tables)) if !tables.is_empty() => {
+                let last = tables.len() - 1;
+                &mut tables[last]
+            }
+            _ => return Err(format!("Key {:?} is not a table", key)),
+        };
+    }
+
+    match (table.get_mut(last), array) {
+        (None, false) => table.entries.push((last.clone(), Item::Table(Table::new(Defined::ByHeader)))),
+        (None, true) => table.entries.push((last.clone(), Item::Tables(vec![Table::new(Defined::ByHeader)]))),
+        (Some(Item::Table(table)), false) if table.defined == Defined::Implicitly => table.defined = Defined::ByHeader,
+        (Some(Item::Tables(tables)), true) => tables.push(Table::new(Defined::ByHeader)),
+        (Some(Item::Table(_) | Item::Tables(_)), false) => return Err(format!("Table {:?} is defined twice", last)),
+        (Some(Item::Value(_)), false) => return Err(format!("Key {:?} is already defined as a non-table value", last)),
+        (Some(_), true) => return Err(format!("Key {:?} cannot be redefined as an array of tables", last)),
+    }
+    Ok(())
+}
+
+impl<'a> Reader<'a> {
+    fn peek(&self) -> Option<char> {
+        self.text[self.offset..].chars().next()
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.text[self.offset..]
+    }
+
+    fn eat(&mut self, char: char) -> bool {
+        let found = self.peek() == Some(char);
+        if found {
+            self.offset += char.len_utf8();
+        }
+        found
+    }
+
+    fn error(&self, message: &str) -> Error {
+        let line = self.text[..self.offset].matches('\n').count() + 1;
+        Error::new(std::io::ErrorKind::InvalidData, format!("TOML line {}: {}", line, message))
+    }
+
+    fn skip_space(&mut self) {
+        let rest = self.rest();
+        self.offset += rest.len() - rest.trim_start_matches([' ', '\t']).len();
+    }
+
+    // Skips a comment if there is one, then the line break or end of input.
+    fn end_line(&mut self) -> Result<(), Error> {
+        self.skip_space();
+        if self.eat('#') {
+            while let Some(char) = self.peek().filter(|&char| char != '\n' && char != '\r') {
+                if is_control(char) {
+                    return Err(self.error("Control character in a comment"));
+                }
+                self.offset += char.len_utf8();
+            }
+        }
+        if self.rest().starts_with("\r\n") {
+            self.offset += 2;
+        } else if !self.eat('\n') && self.peek().is_some() {
+            return Err(self.error("Expected the end of the line"));
+        }
+        Ok(())
+    }
+
+    // Skips whitespace, comments and line breaks, which arrays allow between
+    // their values.
+    fn skip_lines(&mut self) -> Result<(), Error> {
+        loop {
+            self.skip_space();
+            match self.peek() {
+                Some('\n' | '\r' | '#') => self.end_line()?,
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    // Reads a possibly dotted key.
+    fn parse_key(&mut self) -> Result<Vec<String>, Error> {
+        let mut keys = vec![];
+        loop {
+            let key = match self.peek() {
+                Some('"') => self.parse_basic_string()?,
+                Some('\'') => self.parse_literal_string()?,
+                _ => {
+                    let bare = self.rest().find(|char: char| !char.is_ascii_alphanumeric() && char != '_' && char != '-');
+                    let bare = &self.rest()[..bare.unwrap_or(self.rest().len())];
+                    if bare.is_empty() {
+                        return Err(self.error("Expected a key"));
+                    }
+                    self.offset += bare.len();
+                    String::from(bare)
+                }
+            };
+            keys.push(key);
+            self.skip_space();
+            if !self.eat('.') {
+                return Ok(keys);
+            }
+            self.skip_space();
+        }
+    }
+
+    // Reads `key = value` into a table.
+    fn parse_entry(&mut self, table: &mut Table) -> Result<(), Error> {
+        let keys = self.parse_key()?;
+        self.skip_space();
+        if !self.eat('=') {
+            return Err(self.error("Expected '=' after a key"));
+        }
+        self.skip_space();
+        let value = self.parse_value()?;
+
+        let Some((last, parents)) = keys.split_last() else {
+            return Err(self.error("Expected a key"));
+        };
+        let mut table = table;
+        for key in parents {
+            if table.get_mut(key).is_none() {
+                table.entries.push((key.clone(), Item::Table(Table::new(Defined::ByDottedKeys))));
+            }
+            table = match table.get_mut(key) {
+                Some(Item::Table(child)) if child.defined == Defined::ByDottedKeys => child,
+                _ => return Err(self.error(&format!("Key {:?} is already defined", key))),
+            };
+        }
+        if table.get_mut(last).is_some() {
+            return Err(self.error(&format!("Key {:?} is already defined", last)));
+        }
+        table.entries.push((last.clone(), Item::Value(value)));
+        Ok(())
+    }
+
+    fn parse_value(&mut self) -> Result<JsonValue, Error> {
+        let rest = self.rest();
+        match self.peek() {
+            Some('"') if rest.starts_with("\"\"\"") => Ok(string(self.parse_multiline_string('"')?)),
+            Some('\'') if rest.starts_with("'''") => Ok(string(self.parse_multiline_string('\'')?)),
+            Some('"') => Ok(string(self.parse_basic_string()?)),
+            Some('\'') => Ok(string(self.parse_literal_string()?)),
+            Some('[' | '{') if self.depth >= crate::parse::DEFAULT_MAX_DEPTH => Err(self.error("Nesting too deep")),
+            Some('[') => {
+                self.offset += 1;
+                self.depth += 1;
+                let mut elements = vec![];
+                loop {
+                    self.skip_lines()?;
+                    if self.eat(']') {
+                        break;
+                    }
+                    elements.push(JsonElement { value: self.parse_value()? });
+                    self.skip_lines()?;
+                    if self.eat(']') {
+                        break;
+                    }
+                    if !self.eat(',') {
+                        return Err(self.error("Expected ',' or ']' in an array"));
+                    }
+                }
+                self.depth -= 1;
+                Ok(JsonValue::Array(JsonArray {elements}))
+            }
+            Some('{') => {
+                self.offset += 1;
+                self.depth += 1;
+                let mut table = Table::new(Defined::ByDottedKeys);
+                self.skip_space();
+                if !self.eat('}') {
+                    loop {
+                        self.skip_space();
+                        self.parse_entry(&mut table)?;
+                        self.skip_space();
+                        if self.eat('}') {
+                            break;
+                        }
+                        if !self.eat(',') {
+                            return Err(self.error("Expected ',' or '}' in an inline table"));
+                        }
+                    }
+                }
+                self.depth -= 1;
+                Ok(table.into_value())
+            }
+            Some('t') if rest.starts_with("true") => {
+                self.offset += 4;
+                Ok(JsonValue::True)
+            }
+            Some('f') if rest.starts_with("false") => {
+                self.offset += 5;
+                Ok(JsonValue::False)
+            }
+            _ => self.parse_number_or_date(),
+        }
+    }
+
+    fn parse_number_or_date(&mut self) -> Result<JsonValue, Error> {
+        let rest = self.rest();
+        let mut end = rest.find(|char: char| !char.is_ascii_alphanumeric() && !"+-_.:".contains(char)).unwrap_or(rest.len());
+        // A space may stand between a date and a time.
+        if is_date(&rest[..end]) && rest[end..].starts_with(' ') && rest[end + 1..].starts_with(|char: char| char.is_ascii_digit()) {
+            end += 1 + rest[end + 1..].find(|char: char| !char.is_ascii_alphanumeric() && !"+-_.:".contains(char)).unwrap_or(rest.len() - end - 1);
+        }
+        let token = &rest[..end];
+        if token.is_empty() {
+            return Err(self.error("Expected a value"));
+        }
+        let value = if is_date_time(token) {
+            string(String::from(token))
+        } else {
+            match number(token) {
+                Ok(number) => JsonValue::Number(number),
+                Err(true) => return Err(self.error(&format!("Number out of range {:?}", token))),
+                Err(false) => return Err(self.error(&format!("Invalid value {:?}", token))),
+            }
+        };
+        self.offset += end;
+        Ok(value)
+    }
+
+    fn parse_basic_string(&mut self) -> Result<String, Error> {
+        self.offset += 1;
+        let mut string = String::new();
+        loop {
+            match self.peek() {
+                Some('"') => {
+                    self.offset += 1;
+                    return Ok(string);
+                }
+                Some('\\') => self.parse_escape(&mut string)?,
+                Some(char) if !is_control(char) => {
+                    string.push(char);
+                    self.offset += char.len_utf8();
+                }
+                _ => return Err(self.error("Unterminated string")),
+            }
+        }
+    }
+
+    fn parse_literal_string(&mut self) -> Result<String, Error> {
+        self.offset += 1;
+        let Some(end) = self.rest().find(['\'', '\n']) else {
+            return Err(self.error("Unterminated string"));
+        };
+        let string = &self.rest()[..end];
+        if self.rest()[end..].starts_with('\n') || string.chars().any(is_control) {
+            return Err(self.error("Unterminated string"));
+        }
+        let string = String::from(string);
+        self.offset += end + 1;
+        Ok(string)
+    }
+
+    // Reads a `"""` or `'''` string. A line break right after the opening
+    // quotes is dropped, and in basic strings a backslash at the end of a line
+    // joins it to the next non-blank text.
+    fn parse_multiline_string(&mut self, quote: char) -> Result<String, Error> {
+        self.offset += 3;
+        if self.rest().starts_with("\r\n") {
+            self.offset += 2;
+        } else {
+            self.eat('\n');
+        }
+
+        let mut string = String::new();
+        loop {
+            let Some(char) = self.peek() else {
+                return Err(self.error("Unterminated string"));
+            };
+            if char == quote {
+                // Up to two quotes may come right before the closing three.
+                let run = self.rest().len() - self.rest().trim_start_matches(quote).len();
+                if run >= 3 {
+                    if run > 5 {
+                        return Err(self.error("Too many quotes at the end of a string"));
+                    }
+                    string.extend(std::iter::repeat_n(quote, run - 3));
+                    self.offset += run;
+                    return Ok(string);
+                }
+                string.extend(std::iter::repeat_n(quote, run));
+                self.offset += run;
+            } else if char == '\\' && quote == '"' {
+                let after = self.rest()[1..].trim_start_matches([' ', '\t']);
+                if after.starts_with(['\n', '\r']) {
+                    self.offset = self.text.len() - after.trim_start_matches([' ', '\t', '\n', '\r']).len();
+                } else {
+                    self.parse_escape(&mut string)?;
+                }
+            } else if char == '\r' && self.rest().starts_with("\r\n") {
+                string.push('\n');
+                self.offset += 2;
+            } else if char == '\n' || !is_control(char) {
+                string.push(char);
+                self.offset += char.len_utf8();
+            } else {
+                return Err(self.error("Control character in a string"));
+            }
+        }
+    }
+
+    fn parse_escape(&mut self, string: &mut String) -> Result<(), Error> {
+        self.offset += 1;
+        let escape = self.peek().ok_or_else(|| self.error("Unterminated string"))?;
+        self.offset += escape.len_utf8();
+        let char = match escape {
+            'b' => '\u{8}',
+            't' => '\t',
+            'n' => '\n',
+            'f' => '\u{c}',
+            'r' => '\r',
+            '"' => '"',
+            '\\' => '\\',
+            'u' | 'U' => {
+                let length = if escape == 'u' { 4 } else { 8 };
+                let digits = self.rest().get(..length).unwrap_or_default();
+                let char = u32::from_str_radix(digits, 16).ok().filter(|_| digits.bytes().all(|byte| byte.is_ascii_hexdigit())).and_then(char::from_u32);
+                let char = char.ok_or_else(|| self.error(&format!("Invalid escape \\{}{}", escape, digits)))?;
+                self.offset += length;
+                char
+            }
+            _ => return Err(self.error(&format!("Invalid escape \\{}", escape))),
+        };
+        string.push(char);
+        Ok(())
+    }
+}
+
+// The characters TOML does not allow in comments and strings: ASCII control
+// characters other than tab.
+fn is_control(char: char) -> bool {
+    matches!(char, '\0'..='\u{8}' | '\n'..='\u{1f}' | '\u{7f}')
+}
+
+fn string(string: String) -> JsonValue {
+    JsonValue::String(JsonString {string})
+}
+
+// Digits with single underscores between them, as TOML numbers allow.
+fn digits(text: &str, radix: u32) -> Option<String> {
+    let valid = !text.is_empty()
+        && !text.starts_with('_')
+        && !text.ends_with('_')
+        && !text.contains("__")
+        && text.chars().all(|char| char == '_' || char.is_digit(radix));
+    valid.then(|| text.replace('_', ""))
+}
+
+// Reads an integer or float. `Err(true)` means the number is well formed but
+// beyond 64 bits.
+fn number(token: &str) -> Result<JsonNumber, bool> {
+    for (prefix, radix) in [("0x", 16), ("0o", 8), ("0b", 2)] {
+        if let Some(rest) = token.strip_prefix(prefix) {
+            let digits = digits(rest, radix).ok_or(false)?;
+            return i64::from_str_radix(&digits, radix).map(JsonNumber::Integer).map_err(|_| true);
+        }
+    }
+
+    let (sign, unsigned) = match token.strip_prefix(['+', '-']) {
+        Some(unsigned) => (&token[..1], unsigned),
+        None => ("", token),
+    };
+    match unsigned {
+        "inf" => return Ok(JsonNumber::Float(if sign == "-" { f64::NEG_INFINITY } else { f64::INFINITY })),
+        "nan" => return Ok(JsonNumber::Float(f64::NAN)),
+        _ => {}
+    }
+
+    let (mantissa, exponent) = unsigned.split_once(['e', 'E']).map_or((unsigned, None), |(mantissa, exponent)| (mantissa, Some(exponent)));
+    let (whole, fraction) = mantissa.split_once('.').map_or((mantissa, None), |(whole, fraction)| (whole, Some(fraction)));
+    let whole = digits(whole, 10).ok_or(false)?;
+    if whole.len() > 1 && whole.starts_with('0') {
+        return Err(false);
+    }
+    if fraction.is_none() && exponent.is_none() {
+        return format!("{}{}", sign, whole).parse().map(JsonNumber::Integer).map_err(|_| true);
+    }
+
+    let mut lexeme = format!("{}{}", sign, whole);
+    if let Some(fraction) = fraction {
+        lexeme.push('.');
+        lexeme.push_str(&digits(fraction, 10).ok_or(false)?);
+    }
+    if let Some(exponent) = exponent {
+        let (exponent_sign, exponent) = match exponent.strip_prefix(['+', '-']) {
+            Some(rest) => (&exponent[..1], rest),
+            None => ("", exponent),
+        };
+        lexeme.push('e');
+        lexeme.push_str(exponent_sign);
+        lexeme.push_str(&digits(exponent, 10).ok_or(false)?);
+    }
+    let float: f64 = lexeme.parse().map_err(|_| false)?;
+    if float.is_finite() { Ok(JsonNumber::Float(float)) } else { Err(true) }
+}
+
+fn is_date(text: &str) -> bool {
+    let bytes = text.as_bytes();
+    bytes.len() == 10
+        && bytes.iter().enumerate().all(|(index, byte)| if index == 4 || index == 7 { *byte == b'-' } else { byte.is_ascii_digit() })
+        && (1..=12).contains(&text[5..7].parse::<u8>().unwrap_or(0))
+        && (1..=31).contains(&text[8..10].parse::<u8>().unwrap_or(0))
+}
+
+fn is_time(text: &str) -> bool {
+    let bytes = text.as_bytes();
+    let (time, fraction) = text.split_at(bytes.len().min(8));
+    time.len() == 8
+        && time.bytes().enumerate().all(|(index, byte)| if index == 2 || index == 5 { byte == b':' } else { byte.is_ascii_digit() })
+        && time[..2].parse::<u8>().is_ok_and(|hour| hour < 24)
+        && time[3..5].parse::<u8>().is_ok_and(|minute| minute < 60)
+        && time[6..8].parse::<u8>().is_ok_and(|second| second <= 60)
+        && (fraction.is_empty() || fraction.strip_prefix('.').is_some_and(|digits| !digits.is_empty() && digits.bytes().all(|byte| byte.is_ascii_digit())))
+}
+
+// Offset and local date-times, local dates and local times.
+fn is_date_time(text: &str) -> bool {
+    if is_date(text) || is_time(text) {
+        return true;
+    }
+    let Some((_, time)) = text.split_at_checked(10).filter(|(date, _)| is_date(date)) else {
+        return false;
+    };
+    let Some(time) = time.strip_prefix(['T', 't', ' ']) else {
+        return false;
+    };
+    let time = time.strip_suffix(['Z', 'z']).unwrap_or(time);
+    let time = match time.rfind(['+', '-']) {
+        Some(sign) => {
+            let offset = &time[sign + 1..];
+            let valid = offset.len() == 5 && offset.as_bytes()[2] == b':'
+                && offset[..2].parse::<u8>().is_ok_and(|hour| hour < 24)
+                && offset[3..].parse::<u8>().is_ok_and(|minute| minute < 60);
+            if !valid {
+                return false;
+            }
+            &time[..sign]
+        }
+        None => time,
+    };
+    is_time(time)
+}
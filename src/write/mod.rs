@@ -0,0 +1,73 @@
+use crate::data::*;
+
+pub fn value_to_string(value: &JsonValue) -> String {
+    let mut result = String::new();
+    write_value(value, &mut result);
+    result
+}
+
+pub fn write_value(value: &JsonValue, out: &mut String) {
+    match value {
+        JsonValue::Object(object) => write_object(object, out),
+        JsonValue::Array(array) => write_array(array, out),
+        JsonValue::String(string) => write_string(&string.string, out),
+        JsonValue::Number(number) => write_number(number, out),
+        JsonValue::True => out.push_str("true"),
+        JsonValue::False => out.push_str("false"),
+        JsonValue::Null => out.push_str("null"),
+    }
+}
+
+fn write_object(object: &JsonObject, out: &mut String) {
+    out.push('{');
+    for (index, member) in object.members.iter().enumerate() {
+        if index > 0 {
+            out.push(',');
+        }
+        write_string(&member.string.string, out);
+        out.push(':');
+        write_value(&member.element.value, out);
+    }
+    out.push('}');
+}
+
+fn write_array(array: &JsonArray, out: &mut String) {
+    out.push('[');
+    for (index, element) in array.elements.iter().enumerate() {
+        if index > 0 {
+            out.push(',');
+        }
+        write_value(&element.value, out);
+    }
+    out.push(']');
+}
+
+pub fn write_string(string: &str, out: &mut String) {
+    out.push('"');
+    for char in string.chars() {
+        match char {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\u{8}' => out.push_str("\\b"),
+            '\u{c}' => out.push_str("\\f"),
+            '\u{0000}'..='\u{001F}' => out.push_str(&format!("\\u{:04x}", char as u32)),
+            _ => out.push(char),
+        }
+    }
+    out.push('"');
+}
+
+fn write_number(number: &JsonNumber, out: &mut String) {
+    match number {
+        JsonNumber::Integer(integer) => out.push_str(&integer.to_string()),
+        JsonNumber::Unsigned(unsigned) => out.push_str(&unsigned.to_string()),
+        // `{:?}` prints the shortest digits that round-trip, switching to an
+        // exponent for very large or small values, which is valid JSON as is.
+        JsonNumber::Float(float) if float.is_finite() => out.push_str(&format!("{:?}", float)),
+        JsonNumber::Float(_) => out.push_str("null"),
+        JsonNumber::Decimal(text) => out.push_str(text),
+    }
+}
@@ -0,0 +1,885 @@
+use crate::data::*;
+use std::collections::HashSet;
+use std::io::Error;
+
+// Writes YAML 1.2 in block style. Every JSON document has a YAML form, so this
+// cannot fail. Strings are left plain when a YAML reader would read them back
+// as the same string, under both the 1.2 core schema and the older 1.1 rules
+// that still turn `yes` and `off` into booleans, and double-quoted otherwise.
+
+pub fn to_yaml(data: &JsonData) -> String {
+    let mut out = String::new();
+    match &data.element.value {
+        value @ (JsonValue::Object(_) | JsonValue::Array(_)) if !is_empty(value) => write_block(value, 0, &mut out),
+        value => {
+            write_scalar(value, &mut out);
+            out.push('\n');
+        }
+    }
+    out
+}
+
+fn is_empty(value: &JsonValue) -> bool {
+    match value {
+        JsonValue::Object(object) => object.members.is_empty(),
+        JsonValue::Array(array) => array.elements.is_empty(),
+        _ => false,
+    }
+}
+
+// Writes a non-empty object or array, one entry per line, starting at the
+// current line with later lines indented by `indent`.
+fn write_block(value: &JsonValue, indent: usize, out: &mut String) {
+    match value {
+        JsonValue::Object(object) => {
+            for (index, member) in object.members.iter().enumerate() {
+                if index > 0 {
+                    push_indent(indent, out);
+                }
+                write_string(&member.string.string, out);
+                out.push(':');
+                write_entry(&member.element.value, indent, true, out);
+            }
+        }
+        JsonValue::Array(array) => {
+            for (index, element) in array.elements.iter().enumerate() {
+                if index > 0 {
+                    push_indent(indent, out);
+                }
+                out.push('-');
+                write_entry(&element.value, indent, false, out);
+            }
+        }
+        value => {
+            write_scalar(value, out);
+            out.push('\n');
+        }
+    }
+}
+
+// Writes what follows a `key:` or `-`. Collections under a key start on the
+// next line; under a dash they start on the same one, so a list of objects
+// reads `- name: value`.
+fn write_entry(value: &JsonValue, indent: usize, keyed: bool, out: &mut String) {
+    match value {
+        JsonValue::Object(_) if !is_empty(value) && keyed => {
+            out.push('\n');
+            push_indent(indent + 2, out);
+            write_block(value, indent + 2, out);
+        }
+        // A list under a key may sit at the key's own indentation.
+        JsonValue::Array(_) if !is_empty(value) && keyed => {
+            out.push('\n');
+            push_indent(indent, out);
+            write_block(value, indent, out);
+        }
+        JsonValue::Object(_) | JsonValue::Array(_) if !is_empty(value) => {
+            out.push(' ');
+            write_block(value, indent + 2, out);
+        }
+        value => {
+            out.push(' ');
+            write_scalar(value, out);
+            out.push('\n');
+        }
+    }
+}
+
+fn push_indent(indent: usize, out: &mut String) {
+    out.extend(std::iter::repeat_n(' ', indent));
+}
+
+fn write_scalar(value: &JsonValue, out: &mut String) {
+    match value {
+        JsonValue::Object(_) => out.push_str("{}"),
+        JsonValue::Array(_) => out.push_str("[]"),
+        JsonValue::String(string) => write_string(&string.string, out),
+        JsonValue::Number(number) => write_number(number, out),
+        JsonValue::True => out.push_str("true"),
+        JsonValue::False => out.push_str("false"),
+        JsonValue::Null => out.push_str("null"),
+    }
+}
+
+fn write_number(number: &JsonNumber, out: &mut String) {
+    match number {
+        JsonNumber::Float(float) if float.is_nan() => out.push_str(".nan"),
+        JsonNumber::Float(float) if float.is_infinite() => out.push_str(if *float > 0.0 { ".inf" } else { "-.inf" }),
+        JsonNumber::Integer(_) | JsonNumber::Unsigned(_) => crate::write::write_value(&JsonValue::Number(number.clone()), out),
+        JsonNumber::Float(float) => write_float(&format!("{:?}", float), out),
+        JsonNumber::Decimal(text) if text.contains(['.', 'e', 'E']) => write_float(text, out),
+        JsonNumber::Decimal(text) => out.push_str(text),
+    }
+}
+
+// YAML 1.1 only reads a float with a point in it and a sign on the exponent,
+// so `1e5` is written `1.0e+5`, which 1.2 reads the same way.
+fn write_float(text: &str, out: &mut String) {
+    let (mantissa, exponent) = text.split_once(['e', 'E']).map_or((text, None), |(mantissa, exponent)| (mantissa, Some(exponent)));
+    out.push_str(mantissa);
+    if !mantissa.contains('.') {
+        out.push_str(".0");
+    }
+    if let Some(exponent) = exponent {
+        out.push('e');
+        if !exponent.starts_with(['+', '-']) {
+            out.push('+');
+        }
+        out.push_str(exponent);
+    }
+}
+
+pub fn write_string(string: &str, out: &mut String) {
+    if is_plain(string) {
+        out.push_str(string);
+        return;
+    }
+
+    out.push('"');
+    for char in string.chars() {
+        match char {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            char if !is_printable(char) => out.push_str(&format!("\\u{:04x}", char as u32)),
+            _ => out.push(char),
+        }
+    }
+    out.push('"');
+}
+
+// Words that some YAML reader takes for something other than a string.
+const RESERVED: [&str; 12] = ["null", "~", "true", "false", "yes", "no", "on", "off", "y", "n", "<<", "="];
+
+fn is_plain(string: &str) -> bool {
+    let Some(first) = string.chars().next() else {
+        return false;
+    };
+    let lower = string.to_lowercase();
+    // Indicators, and anything that could start a number, like `.5` or `-1`.
+    !"-?:,[]{}#&*!|>'\"%@`.+ ".contains(first)
+        && !first.is_ascii_digit()
+        && !string.ends_with([' ', ':'])
+        && !string.contains(": ")
+        && !string.contains(" #")
+        && !RESERVED.contains(&lower.as_str())
+        && string.chars().all(|char| !char.is_control() && is_printable(char))
+}
+
+// The characters YAML allows unescaped, less the ones YAML 1.1 reads as line
+// breaks: NEL and the Unicode line and paragraph separators. A byte order
+// mark is only allowed escaped.
+fn is_printable(char: char) -> bool {
+    matches!(char, '\t' | '\n' | '\r' | ' '..='~' | '\u{a0}'..='\u{2027}' | '\u{202a}'..='\u{d7ff}' | '\u{e000}'..='\u{fefe}' | '\u{ff00}'..='\u{fffd}' | '\u{10000}'..)
+}
+
+// Reads a practical subset of YAML 1.2 into the JSON tree: one document of
+// block mappings and sequences, flow collections, plain, quoted and block
+// scalars, and comments. Plain scalars resolve by the core schema, so `true`,
+// `null`, `0x1f` and `.inf` are not strings. Anchors, aliases, tags, complex
+// keys and further documents are reported instead of being misread.
+
+struct Line<'a> {
+    number: usize,
+    indent: usize,
+    text: &'a str,
+}
+
+struct Reader<'a> {
+    lines: Vec<Line<'a>>,
+    index: usize,
+}
+
+fn error(line: usize, message: impl std::fmt::Display) -> Error {
+    Error::new(std::io::ErrorKind::InvalidData, format!("YAML line {}: {}", line, message))
+}
+
+pub fn parse(string: &str) -> Result<JsonData, Error> {
+    let string = string.strip_prefix('\u{feff}').unwrap_or(string);
+    let lines = string.lines().enumerate().map(|(index, line)| {
+        let text = line.trim_start_matches(' ');
+        Line { number: index + 1, indent: line.len() - text.len(), text }
+    });
+    let mut reader = Reader { lines: lines.collect(), index: 0 };
+
+    while let Some(line) = reader.peek() {
+        if line.indent == 0 && line.text.starts_with("%YAML") {
+            reader.index += 1;
+        } else if line.indent == 0 && line.text.starts_with('%') {
+            return Err(error(line.number, "YAML directives other than %YAML are not supported"));
+        } else {
+            break;
+        }
+    }
+    if let Some(marker) = reader.peek().and_then(|line| document_marker(line, "---")) {
+        // Content may follow the marker on the same line.
+        let rest = marker.trim_start_matches([' ', '\t']);
+        if rest.is_empty() || rest.starts_with('#') {
+            reader.index += 1;
+        } else {
+            let line = &mut reader.lines[reader.index];
+            line.indent = line.text.len() - rest.len();
+            line.text = rest;
+        }
+    }
+
+    let value = match reader.peek() {
+        Some(line) if document_marker(line, "...").is_some() => JsonValue::Null,
+        Some(_) => reader.parse_node(0, 0)?,
+        None => JsonValue::Null,
+    };
+
+    if let Some(line) = reader.peek() {
+        if document_marker(line, "---").is_some() {
+            return Err(error(line.number, "Multiple YAML documents are not supported"));
+        }
+        if document_marker(line, "...").is_none() {
+            return Err(error(line.number, "Bad indentation"));
+        }
+        reader.index += 1;
+        if let Some(line) = reader.peek() {
+            return Err(error(line.number, "Multiple YAML documents are not supported"));
+        }
+    }
+    Ok(JsonData::from(value))
+}
+
+// The rest of the line after a `---` or `...` marker, if the line is one.
+fn document_marker<'a>(line: &Line<'a>, marker: &str) -> Option<&'a str> {
+    let rest = line.text.strip_prefix(marker).filter(|_| line.indent == 0)?;
+    (rest.is_empty() || rest.starts_with([' ', '\t'])).then_some(rest)
+}
+
+fn is_marker(line: &Line) -> bool {
+    document_marker(line, "---").is_some() || document_marker(line, "...").is_some()
+}
+
+fn is_item(text: &str) -> bool {
+    text == "-" || text.starts_with("- ") || text.starts_with("-\t")
+}
+
+fn is_blank(text: &str) -> bool {
+    let text = text.trim_start_matches([' ', '\t']);
+    text.is_empty() || text.starts_with('#')
+}
+
+impl<'a> Reader<'a> {
+    // The next line with content, skipping blank lines and comments.
+    fn peek(&mut self) -> Option<&Line<'a>> {
+        while self.lines.get(self.index).is_some_and(|line| is_blank(line.text)) {
+            self.index += 1;
+        }
+        self.lines.get(self.index)
+    }
+
+    // Reads the node starting at the next line, which is indented by at least
+    // `min`.
+    fn parse_node(&mut self, min: usize, depth: usize) -> Result<JsonValue, Error> {
+        let Some(line) = self.peek() else {
+            return Ok(JsonValue::Null);
+        };
+        let (number, indent, text) = (line.number, line.indent, line.text);
+        if depth > crate::parse::DEFAULT_MAX_DEPTH {
+            return Err(error(number, "Nesting too deep"));
+        }
+        if text.starts_with('\t') {
+            return Err(error(number, "Tabs are not allowed in indentation"));
+        }
+
+        if is_item(text) {
+            self.parse_sequence(indent, depth)
+        } else if split_entry(text, number)?.is_some() {
+            self.parse_mapping(indent, depth)
+        } else {
+            self.index += 1;
+            self.parse_inline(text, number, min, depth)
+        }
+    }
+
+    fn parse_sequence(&mut self, indent: usize, depth: usize) -> Result<JsonValue, Error> {
+        let mut elements = vec![];
+        while let Some(line) = self.peek() {
+            let (number, text) = (line.number, line.text);
+            if line.indent < indent || (line.indent == indent && !is_item(text)) {
+                break;
+            }
+            if line.indent > indent {
+                return Err(error(number, "Bad indentation"));
+            }
+
+            // An item can start a nested mapping or sequence on its own line,
+            // as in `- name: value` or `- - value`, indented past the dash.
+            let rest = text[1..].trim_start_matches([' ', '\t']);
+            let value = if !is_blank(rest) && (is_item(rest) || split_entry(rest, number)?.is_some()) {
+                let line = &mut self.lines[self.index];
+                line.indent += text.len() - rest.len();
+                line.text = rest;
+                self.parse_node(indent + 1, depth + 1)?
+            } else {
+                self.index += 1;
+                self.parse_value(rest, number, indent, false, depth + 1)?
+            };
+            elements.push(JsonElement { value });
+        }
+        Ok(JsonValue::Array(JsonArray {elements}))
+    }
+
+    fn parse_mapping(&mut self, indent: usize, depth: usize) -> Result<JsonValue, Error> {
+        let mut members: Vec<JsonMember> = vec![];
+        let mut keys = HashSet::new();
+        while let Some(line) = self.peek() {
+            let (number, text) = (line.number, line.text);
+            if line.indent < indent {
+                break;
+            }
+            if line.indent > indent {
+                return Err(error(number, "Bad indentation"));
+            }
+            let Some((key, rest)) = split_entry(text, number)? else {
+                if is_item(text) || is_marker(line) {
+                    break;
+                }
+                if text.starts_with('\t') {
+                    return Err(error(number, "Tabs are not allowed in indentation"));
+                }
+                return Err(error(number, "Expected a mapping key"));
+            };
+            if !keys.insert(key.clone()) {
+                return Err(error(number, format!("Duplicate key {:?}", key)));
+            }
+
+            self.index += 1;
+            let value = self.parse_value(rest, number, indent, true, depth + 1)?;
+            members.push(JsonMember { string: JsonString { string: key }, element: JsonElement { value } });
+        }
+        Ok(JsonValue::Object(JsonObject {members}))
+    }
+
+    // Reads what follows a `key:` or `-` at `indent`, which may be on the same
+    // line or below it. A sequence under a key may sit at the key's own
+    // indentation.
+    fn parse_value(&mut self, rest: &'a str, number: usize, indent: usize, keyed: bool, depth: usize) -> Result<JsonValue, Error> {
+        let rest = rest.trim_start_matches([' ', '\t']);
+        if !is_blank(rest) {
+            return self.parse_inline(rest, number, indent + 1, depth);
+        }
+        match self.peek() {
+            Some(line) if line.indent > indent => self.parse_node(indent + 1, depth),
+            Some(line) if line.indent == indent && keyed && is_item(line.text) => self.parse_sequence(indent, depth),
+            _ => Ok(JsonValue::Null),
+        }
+    }
+
+    // Reads a scalar or flow collection that starts on a line already taken,
+    // along with any lines it continues onto.
+    fn parse_inline(&mut self, text: &'a str, number: usize, min: usize, depth: usize) -> Result<JsonValue, Error> {
+        match text.chars().next() {
+            Some('[' | '{') => {
+                let mut joined = String::from(text);
+                while !flow_closed(&joined) {
+                    let Some(line) = self.lines.get(self.index) else {
+                        return Err(error(number, "Unclosed flow collection"));
+                    };
+                    joined.push('\n');
+                    joined.push_str(line.text);
+                    self.index += 1;
+                }
+                let mut flow = Flow { text: &joined, offset: 0, number, depth };
+                let value = flow.parse_value()?;
+                flow.skip_space();
+                if flow.offset < joined.len() {
+                    return Err(error(number, "Unexpected content after a flow collection"));
+                }
+                Ok(value)
+            }
+            Some(quote @ ('"' | '\'')) => {
+                let mut joined = String::from(text);
+                let (string, end) = loop {
+                    if let Some(result) = quoted(&joined, quote, number)? {
+                        break result;
+                    }
+                    let Some(line) = self.lines.get(self.index) else {
+                        return Err(error(number, "Unterminated string"));
+                    };
+                    joined.push('\n');
+                    joined.push_str(line.text);
+                    self.index += 1;
+                };
+                if !is_blank(&joined[end..]) {
+                    return Err(error(number, "Unexpected content after a string"));
+                }
+                Ok(JsonValue::String(JsonString {string}))
+            }
+            Some(indicator @ ('|' | '>')) => self.parse_block_scalar(&text[1..], indicator == '>', number, min),
+            Some(indicator) if unsupported(indicator).is_some() => {
+                Err(error(number, format!("YAML {} are not supported", unsupported(indicator).unwrap_or_default())))
+            }
+            _ => {
+                // A plain scalar after a key cannot hold a key of its own, as
+                // in `a: b: c`.
+                if split_entry(text, number)?.is_some() {
+                    return Err(error(number, "Unexpected ':' in a plain scalar"));
+                }
+                // Plain scalars continue onto more indented lines, joined by
+                // spaces, until a blank line or a comment.
+                let mut plain = String::from(strip_comment(text).trim_end_matches([' ', '\t']));
+                let commented = strip_comment(text).len() < text.len();
+                while let Some(line) = self.lines.get(self.index) {
+                    if commented || is_blank(line.text) || line.indent < min || is_marker(line) {
+                        break;
+                    }
+                    if is_item(line.text) || split_entry(line.text, line.number)?.is_some() {
+                        return Err(error(line.number, "Bad indentation"));
+                    }
+                    plain.push(' ');
+                    plain.push_str(strip_comment(line.text).trim_end_matches([' ', '\t']));
+                    self.index += 1;
+                }
+                Ok(resolve(&plain))
+            }
+        }
+    }
+
+    // Reads a `|` literal or `>` folded scalar. `header` is the rest of the
+    // indicator's line, which may hold chomping and indentation indicators.
+    fn parse_block_scalar(&mut self, header: &str, folded: bool, number: usize, min: usize) -> Result<JsonValue, Error> {
+        let mut chomp = None;
+        let mut explicit = None;
+        let mut rest = header;
+        while let Some(char) = rest.chars().next() {
+            match char {
+                '-' | '+' if chomp.is_none() => chomp = Some(char),
+                '1'..='9' if explicit.is_none() => explicit = char.to_digit(10),
+                _ => break,
+            }
+            rest = &rest[1..];
+        }
+        if !is_blank(rest) || rest.starts_with('#') {
+            return Err(error(number, "Invalid block scalar header"));
+        }
+
+        let content_indent = match explicit {
+            Some(digits) => min.saturating_sub(1) + digits as usize,
+            None => {
+                let first = self.lines[self.index..].iter().find(|line| !line.text.trim_start_matches([' ', '\t']).is_empty());
+                first.map_or(min, |line| line.indent.max(min))
+            }
+        };
+
+        let mut lines = vec![];
+        while let Some(line) = self.lines.get(self.index) {
+            if line.text.trim_start_matches([' ', '\t']).is_empty() {
+                lines.push(String::new());
+            } else if line.indent >= content_indent {
+                lines.push(format!("{}{}", " ".repeat(line.indent - content_indent), line.text));
+            } else {
+                break;
+            }
+            self.index += 1;
+        }
+
+        let trailing = lines.iter().rev().take_while(|line| line.is_empty()).count();
+        lines.truncate(lines.len() - trailing);
+        let mut string = if folded { fold(&lines) } else { lines.join("\n") };
+        match chomp {
+            Some('-') => {}
+            Some('+') => string.push_str(&"\n".repeat(trailing + usize::from(!lines.is_empty()))),
+            _ if !lines.is_empty() => string.push('\n'),
+            _ => {}
+        }
+        Ok(JsonValue::String(JsonString {string}))
+    }
+}
+
+// Joins the lines of a folded scalar: a single line break between two lines
+// of text becomes a space, except around more indented lines, which keep
+// their breaks.
+fn fold(lines: &[String]) -> String {
+    let mut out = String::new();
+    let mut previous: Option<&str> = None;
+    let mut empty = 0;
+    for line in lines {
+        if line.is_empty() {
+            empty += 1;
+            continue;
+        }
+        let indented = |line: &str| line.starts_with([' ', '\t']);
+        match previous {
+            Some(previous) if !indented(previous) && !indented(line) && empty == 0 => out.push(' '),
+            Some(previous) if !indented(previous) && !indented(line) => out.push_str(&"\n".repeat(empty)),
+            Some(_) => out.push_str(&"\n".repeat(empty + 1)),
+            None => out.push_str(&"\n".repeat(empty)),
+        }
+        out.push_str(line);
+        previous = Some(line);
+        empty = 0;
+    }
+    out
+}
+
+fn unsupported(indicator: char) -> Option<&'static str> {
+    match indicator {
+        '&' => Some("anchors"),
+        '*' => Some("aliases"),
+        '!' => Some("tags"),
+        '?' => Some("complex keys"),
+        '@' | '`' | '%' => Some("reserved indicators"),
+        _ => None,
+    }
+}
+
+// Splits `key: value` into its key and the text after the colon, or returns
+// `None` when the line is not a mapping entry.
+fn split_entry(text: &str, number: usize) -> Result<Option<(String, &str)>, Error> {
+    if let Some(quote @ ('"' | '\'')) = text.chars().next() {
+        let Some((key, end)) = quoted(text, quote, number)? else {
+            return Ok(None);
+        };
+        let rest = text[end..].trim_start_matches([' ', '\t']);
+        return Ok(rest.strip_prefix(':').filter(|rest| rest.is_empty() || rest.starts_with([' ', '\t'])).map(|rest| (key, rest)));
+    }
+    if let Some(indicator) = text.chars().next().and_then(unsupported) {
+        if !text.starts_with(['%', '@', '`']) {
+            return Err(error(number, format!("YAML {} are not supported", indicator)));
+        }
+    }
+    if text.starts_with(['[', '{', '#', '|', '>']) || is_item(text) {
+        return Ok(None);
+    }
+
+    let mut chars = text.char_indices().peekable();
+    let mut previous = ' ';
+    while let Some((index, char)) = chars.next() {
+        if char == '#' && (previous == ' ' || previous == '\t') {
+            return Ok(None);
+        }
+        if char == ':' && chars.peek().is_none_or(|&(_, next)| next == ' ' || next == '\t') {
+            return Ok(Some((String::from(text[..index].trim_end_matches([' ', '\t'])), &text[index + 1..])));
+        }
+        previous = char;
+    }
+    Ok(None)
+}
+
+// Cuts a trailing comment from a plain scalar.
+fn strip_comment(text: &str) -> &str {
+    let mut previous = ' ';
+    for (index, char) in text.char_indices() {
+        if char == '#' && (previous == ' ' || previous == '\t') {
+            return &text[..index];
+        }
+        previous = char;
+    }
+    text
+}
+
+// Reads a quoted scalar at the start of `text`, returning the string and the
+// offset past its closing quote, or `None` if it does not close. Line breaks
+// fold the way they do in YAML: one becomes a space, and each further blank
+// line a line feed.
+fn quoted(text: &str, quote: char, number: usize) -> Result<Option<(String, usize)>, Error> {
+    let mut string = String::new();
+    let mut chars = text.char_indices().skip(1).peekable();
+    while let Some((index, char)) = chars.next() {
+        match char {
+            '\'' if quote == '\'' && chars.peek().is_some_and(|&(_, next)| next == '\'') => {
+                chars.next();
+                string.push('\'');
+            }
+            char if char == quote => return Ok(Some((string, index + 1))),
+            '\\' if quote == '"' => {
+                let Some((_, escape)) = chars.next() else {
+                    return Ok(None);
+                };
+                let hex = match escape {
+                    'x' => 2,
+                    'u' => 4,
+                    'U' => 8,
+                    _ => 0,
+                };
+                if hex > 0 {
+                    let digits: String = chars.by_ref().take(hex).map(|(_, char)| char).collect();
+                    let char = u32::from_str_radix(&digits, 16).ok().filter(|_| digits.len() == hex).and_then(char::from_u32);
+                    string.push(char.ok_or_else(|| error(number, format!("Invalid escape \\{}{}", escape, digits)))?);
+                    continue;
+                }
+                string.push(match escape {
+                    '0' => '\0',
+                    'a' => '\u{7}',
+                    'b' => '\u{8}',
+                    't' | '\t' => '\t',
+                    'n' => '\n',
+                    'v' => '\u{b}',
+                    'f' => '\u{c}',
+                    'r' => '\r',
+                    'e' => '\u{1b}',
+                    ' ' | '"' | '/' | '\\' => escape,
+                    'N' => '\u{85}',
+                    '_' => '\u{a0}',
+                    'L' => '\u{2028}',
+                    'P' => '\u{2029}',
+                    // An escaped line break joins the lines with nothing
+                    // between them.
+                    '\n' => {
+                        while chars.next_if(|&(_, next)| next == ' ' || next == '\t').is_some() {}
+                        continue;
+                    }
+                    _ => return Err(error(number, format!("Invalid escape \\{}", escape))),
+                });
+            }
+            '\n' => {
+                let trimmed = string.trim_end_matches([' ', '\t']).len();
+                string.truncate(trimmed);
+                let mut breaks = 0;
+                while let Some(&(_, next)) = chars.peek() {
+                    match next {
+                        '\n' => breaks += 1,
+                        ' ' | '\t' => {}
+                        _ => break,
+                    }
+                    chars.next();
+                }
+                string.push_str(&if breaks == 0 { String::from(" ") } else { "\n".repeat(breaks) });
+            }
+            char => string.push(char),
+        }
+    }
+    Ok(None)
+}
+
+// Whether the brackets of a flow collection balance, ignoring any inside
+// quotes. Errors in the collection are left for `Flow` to report.
+fn flow_closed(text: &str) -> bool {
+    let mut open = 0;
+    let mut quote = None;
+    let mut previous = ' ';
+    let mut chars = text.chars().peekable();
+    while let Some(char) = chars.next() {
+        match (quote, char) {
+            (Some('"'), '\\') => {
+                chars.next();
+            }
+            (Some('\''), '\'') if chars.peek() == Some(&'\'') => {
+                chars.next();
+            }
+            (Some(current), char) if char == current => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') if " \t\n[{,:".contains(previous) => quote = Some(char),
+            (None, '#') if " \t\n".contains(previous) => {
+                while chars.next_if(|&next| next != '\n').is_some() {}
+            }
+            (None, '[' | '{') => open += 1,
+            (None, ']' | '}') => {
+                open -= 1;
+                if open == 0 {
+                    return true;
+                }
+            }
+            _ => {}
+        }
+        previous = char;
+    }
+    false
+}
+
+// Reads a flow collection, `[a, b]` or `{a: 1}`, once all of its lines are
+// joined.
+struct Flow<'a> {
+    text: &'a str,
+    offset: usize,
+    number: usize,
+    depth: usize,
+}
+
+impl Flow<'_> {
+    fn peek(&self) -> Option<char> {
+        self.text[self.offset..].chars().next()
+    }
+
+    fn error(&self, message: &str) -> Error {
+        error(self.number + self.text[..self.offset].matches('\n').count(), message)
+    }
+
+    fn skip_space(&mut self) {
+        loop {
+            let rest = &self.text[self.offset..];
+            let trimmed = rest.trim_start_matches([' ', '\t', '\n']);
+            self.offset += rest.len() - trimmed.len();
+            if trimmed.starts_with('#') && (self.offset == 0 || self.text[..self.offset].ends_with([' ', '\t', '\n'])) {
+                self.offset += trimmed.find('\n').unwrap_or(trimmed.len());
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<JsonValue, Error> {
+        self.skip_space();
+        match self.peek() {
+            Some('[' | '{') if self.depth > crate::parse::DEFAULT_MAX_DEPTH => Err(self.error("Nesting too deep")),
+            Some('[') => {
+                self.offset += 1;
+                self.depth += 1;
+                let mut elements = vec![];
+                loop {
+                    self.skip_space();
+                    if self.peek() == Some(']') {
+                        break;
+                    }
+                    elements.push(JsonElement { value: self.parse_value()? });
+                    if !self.separator(']')? {
+                        break;
+                    }
+                }
+                self.offset += 1;
+                self.depth -= 1;
+                Ok(JsonValue::Array(JsonArray {elements}))
+            }
+            Some('{') => {
+                self.offset += 1;
+                self.depth += 1;
+                let mut members: Vec<JsonMember> = vec![];
+                let mut keys = HashSet::new();
+                loop {
+                    self.skip_space();
+                    if self.peek() == Some('}') {
+                        break;
+                    }
+                    let key = match self.parse_scalar(true)? {
+                        JsonValue::String(string) => string.string,
+                        _ => return Err(self.error("Expected a mapping key")),
+                    };
+                    if !keys.insert(key.clone()) {
+                        return Err(self.error(&format!("Duplicate key {:?}", key)));
+                    }
+                    self.skip_space();
+                    let value = if self.peek() == Some(':') {
+                        self.offset += 1;
+                        self.skip_space();
+                        if matches!(self.peek(), Some(',' | '}')) { JsonValue::Null } else { self.parse_value()? }
+                    } else {
+                        JsonValue::Null
+                    };
+                    members.push(JsonMember { string: JsonString { string: key }, element: JsonElement { value } });
+                    if !self.separator('}')? {
+                        break;
+                    }
+                }
+                self.offset += 1;
+                self.depth -= 1;
+                Ok(JsonValue::Object(JsonObject {members}))
+            }
+            _ => self.parse_scalar(false),
+        }
+    }
+
+    // Reads the comma after an entry. Returns false at the closing bracket.
+    fn separator(&mut self, close: char) -> Result<bool, Error> {
+        self.skip_space();
+        match self.peek() {
+            Some(',') => {
+                self.offset += 1;
+                Ok(true)
+            }
+            Some(char) if char == close => Ok(false),
+            _ => Err(self.error(&format!("Expected ',' or '{}'", close))),
+        }
+    }
+
+    // Reads a quoted or plain scalar. A key is always a string.
+    fn parse_scalar(&mut self, key: bool) -> Result<JsonValue, Error> {
+        let rest = &self.text[self.offset..];
+        match self.peek() {
+            Some(quote @ ('"' | '\'')) => {
+                let Some((string, end)) = quoted(rest, quote, self.number)? else {
+                    return Err(self.error("Unterminated string"));
+                };
+                self.offset += end;
+                Ok(JsonValue::String(JsonString {string}))
+            }
+            Some(indicator) if unsupported(indicator).is_some() => {
+                Err(self.error(&format!("YAML {} are not supported", unsupported(indicator).unwrap_or_default())))
+            }
+            _ => {
+                let mut end = rest.len();
+                let mut chars = rest.char_indices().peekable();
+                while let Some((index, char)) = chars.next() {
+                    let next = chars.peek().map(|&(_, next)| next);
+                    if matches!(char, ',' | '[' | ']' | '{' | '}' | '\n')
+                        || (char == ':' && next.is_none_or(|next| " \t\n,]}".contains(next)))
+                        || (char == '#' && index > 0 && rest[..index].ends_with([' ', '\t']))
+                    {
+                        end = index;
+                        break;
+                    }
+                }
+                let plain = rest[..end].trim_end_matches([' ', '\t']);
+                if plain.is_empty() {
+                    return Err(self.error("Expected a value"));
+                }
+                self.offset += end;
+                Ok(if key { JsonValue::String(JsonString { string: String::from(plain) }) } else { resolve(plain) })
+            }
+        }
+    }
+}
+
+// Resolves a plain scalar by the YAML 1.2 core schema.
+fn resolve(plain: &str) -> JsonValue {
+    match plain {
+        "" | "~" | "null" | "Null" | "NULL" => return JsonValue::Null,
+        "true" | "True" | "TRUE" => return JsonValue::True,
+        "false" | "False" | "FALSE" => return JsonValue::False,
+        ".nan" | ".NaN" | ".NAN" => return JsonValue::Number(JsonNumber::Float(f64::NAN)),
+        _ => {}
+    }
+
+    let (negative, unsigned) = match plain.strip_prefix('-') {
+        Some(unsigned) => (true, unsigned),
+        None => (false, plain.strip_prefix('+').unwrap_or(plain)),
+    };
+    if matches!(unsigned, ".inf" | ".Inf" | ".INF") {
+        return JsonValue::Number(JsonNumber::Float(if negative { f64::NEG_INFINITY } else { f64::INFINITY }));
+    }
+    for (prefix, radix) in [("0x", 16), ("0o", 8)] {
+        // `from_str_radix` takes a sign, which YAML does not allow here.
+        if let Some(digits) = plain.strip_prefix(prefix).filter(|digits| digits.chars().all(|char| char.is_digit(radix))) {
+            if let Ok(unsigned) = u64::from_str_radix(digits, radix) {
+                let number = i64::try_from(unsigned).map_or(JsonNumber::Unsigned(unsigned), JsonNumber::Integer);
+                return JsonValue::Number(number);
+            }
+        }
+    }
+
+    // Core schema floats may leave out digits on either side of the point;
+    // JSON needs both, so fill them in before reading the number.
+    let (mantissa, exponent) = unsigned.split_once(['e', 'E']).map_or((unsigned, None), |(mantissa, exponent)| (mantissa, Some(exponent)));
+    let (whole, fraction) = mantissa.split_once('.').map_or((mantissa, None), |(whole, fraction)| (whole, Some(fraction)));
+    let digits = |text: &str| text.bytes().all(|byte| byte.is_ascii_digit());
+    let valid = digits(whole)
+        && fraction.is_none_or(digits)
+        && (!whole.is_empty() || fraction.is_some_and(|fraction| !fraction.is_empty()))
+        && exponent.is_none_or(|exponent| {
+            let exponent = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+            !exponent.is_empty() && digits(exponent)
+        });
+    if !valid {
+        return JsonValue::String(JsonString { string: String::from(plain) });
+    }
+    let mut lexeme = String::from(if negative { "-" } else { "" });
+    lexeme.push_str(if whole.is_empty() { "0" } else { whole });
+    if let Some(fraction) = fraction {
+        lexeme.push('.');
+        lexeme.push_str(if fraction.is_empty() { "0" } else { fraction });
+    }
+    if let Some(exponent) = exponent {
+        lexeme.push('e');
+        lexeme.push_str(exponent);
+    }
+    match crate::parse::number_from_lexeme(&lexeme, Span { start: 0, end: 0 }, NumberMode::Exact) {
+        Ok(number) => JsonValue::Number(number),
+        Err(_) => JsonValue::String(JsonString { string: String::from(plain) }),
+    }
+}
@@ -0,0 +1,41 @@
+// The derives expand to paths in `cc_json_parser`, so they are tested from a
+// crate of their own, as any user of the library would call them.
+
+use cc_json_parser::data::JsonValue;
+use cc_json_parser::from_json::{from_json, FromJson};
+use cc_json_parser::json;
+use cc_json_parser::to_json::{to_json, ToJson};
+
+#[derive(FromJson, ToJson, PartialEq, Debug)]
+struct Server<T> {
+    host: String,
+    #[json(rename = "listen-port")]
+    port: u16,
+    #[json(default)]
+    tags: Vec<String>,
+    extra: T,
+}
+
+#[derive(ToJson)]
+#[json(tag = "type", content = "value")]
+enum Event {
+    Started,
+    Moved { x: i32 },
+}
+
+#[test]
+fn test_derive_outside_the_crate() {
+    let server = Server { host: String::from("a"), port: 80, tags: vec![], extra: true };
+    let json_data = to_json(&server);
+    assert_eq!(json_data.element.value, json!({"host": "a", "listen-port": 80, "tags": [], "extra": true}));
+    assert_eq!(from_json::<Server<bool>>(&json_data).unwrap(), server);
+
+    let parsed: Server<Option<f64>> = from_json(&json!({"host": "b", "listen-port": 8080, "extra": 1.5}).into()).unwrap();
+    assert_eq!(parsed, Server { host: String::from("b"), port: 8080, tags: vec![], extra: Some(1.5) });
+    let err = from_json::<Server<bool>>(&json!({"host": "b", "listen-port": -1, "extra": true}).into()).unwrap_err();
+    assert_eq!(err.path, "/listen-port");
+
+    let events: Vec<JsonValue> = [Event::Started, Event::Moved { x: -2 }].iter().map(ToJson::to_json).collect();
+    assert_eq!(events, vec![json!({"type": "Started"}), json!({"type": "Moved", "value": {"x": -2}})]);
+    assert_eq!(json!(server), to_json(&server).element.value);
+}